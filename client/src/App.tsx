@@ -1,4 +1,4 @@
-import { useEffect, useState } from "react";
+import { useCallback, useEffect, useRef, useState } from "react";
 import useWebSocket, { ReadyState } from "react-use-websocket";
 import { Moon, Sun, MessageCircle, MapPin, Search, X } from "lucide-react";
 import { Header } from "./components/Header";
@@ -8,10 +8,22 @@ import PolicyDialog from "./components/PolicyDialog";
 import { CityStats } from "./components/CityStats";
 import { useChatStore } from "./store/useChatStore";
 import { getDeviceId } from "./lib/utils";
-import { apiGet, apiPost, WS_BASE_URL } from "./lib/api";
-import { type Message, type MessageType } from "./types";
+import { getBrowserFingerprint } from "./lib/fingerprint";
+import {
+  apiGet,
+  apiPost,
+  decodeSocketFrame,
+  getSessionToken,
+  supportsWsDeflate,
+  WS_BASE_URL,
+  WS_DEFLATE_PROTOCOL,
+} from "./lib/api";
+import { type ListingDetails, type Message, type MessageType } from "./types";
 import stateAndCityData from "./data/stateandcity.json";
 
+// Application close codes sent by the server (4000 shutdown, 4001 slow client)
+const WS_CLOSE_POLICY_VIOLATION = 4002;
+
 // WebSocket URL with dynamic protocol conversion
 const getWsUrl = () => {
   const ws = WS_BASE_URL || "ws://localhost:3001";
@@ -19,6 +31,12 @@ const getWsUrl = () => {
 };
 const WS_URL = getWsUrl();
 
+// How long a post sent over the socket waits for the server's answer
+const SOCKET_POST_TIMEOUT_MS = 15000;
+
+// Must match the server's PRIVACY_POLICY_VERSION; bumping it asks everyone to accept again
+const POLICY_VERSION = import.meta.env.VITE_PRIVACY_POLICY_VERSION || "1";
+
 interface BackendMessage {
   id: string;
   browser_id?: string;
@@ -29,11 +47,15 @@ interface BackendMessage {
   type?: MessageType;
   timestamp: number | string;
   phone?: string;
+  expires_at?: number;
+  details?: ListingDetails;
 }
 
 function App() {
-  const { addMessage, clearMessages, setCooldown } = useChatStore();
+  const { addMessage, updateMessage, removeMessage, clearMessages, setCooldown } = useChatStore();
   const [postError, setPostError] = useState<string | null>(null);
+  // Issued with a fixable rejection; lets the corrected post skip the cooldown
+  const correctionTokenRef = useRef<string | null>(null);
   const [darkMode, setDarkMode] = useState(false);
   const [city, setCity] = useState<string>("");
   const [state, setState] = useState<string>("Detecting...");
@@ -46,7 +68,7 @@ function App() {
   const [stateSearch, setStateSearch] = useState("");
   const [isLoadingMessages, setIsLoadingMessages] = useState(false);
   const [policyAccepted, setPolicyAccepted] = useState(() => {
-    return localStorage.getItem("policyAccepted") === "true";
+    return localStorage.getItem("policyVersion") === POLICY_VERSION;
   });
   const [dailyStats, setDailyStats] = useState<{
     unique_visitors: number;
@@ -57,13 +79,42 @@ function App() {
   // Handler for accepting policy
   const handleAcceptPolicy = () => {
     setPolicyAccepted(true);
-    localStorage.setItem("policyAccepted", "true");
+    localStorage.setItem("policyVersion", POLICY_VERSION);
   };
 
-  const { lastMessage, readyState } = useWebSocket(WS_URL, {
-    shouldReconnect: () => true,
+  // stream_id of the last listing received, so a reconnect replays what was missed
+  const lastStreamIdRef = useRef<string | null>(null);
+  // Bumped when the server can't replay everything we missed, to refetch the feed
+  const [resyncCount, setResyncCount] = useState(0);
+
+  // Answers to posts sent over the socket, by correlation id
+  const pendingPostsRef = useRef(new Map<string, (frame: any) => void>());
+
+  // Authenticated connections also receive activity on the user's own listings
+  // The fingerprint stands in for the header a socket can't send, so posts can go over it
+  const getSocketUrl = useCallback(async () => {
+    const sessionToken = await getSessionToken();
+    const params = new URLSearchParams();
+    if (sessionToken) params.set("session", sessionToken);
+    params.set("fingerprint", await getBrowserFingerprint());
+    if (lastStreamIdRef.current) params.set("last_id", lastStreamIdRef.current);
+    const query = params.toString();
+    return query ? `${WS_URL}?${query}` : WS_URL;
+  }, []);
+
+  // Set when a server going down asks us to wait before reconnecting
+  const reconnectAfterRef = useRef<number | null>(null);
+
+  const { lastMessage, readyState, sendJsonMessage } = useWebSocket(getSocketUrl, {
+    // 4002: closed for sending invalid frames - reconnecting would just repeat it
+    shouldReconnect: (closeEvent) => closeEvent.code !== WS_CLOSE_POLICY_VIOLATION,
     reconnectAttempts: 10,
-    reconnectInterval: 3000,
+    reconnectInterval: () => {
+      const hint = reconnectAfterRef.current;
+      reconnectAfterRef.current = null;
+      return hint ?? 3000;
+    },
+    protocols: supportsWsDeflate ? WS_DEFLATE_PROTOCOL : undefined,
   });
 
   const theme = darkMode
@@ -182,6 +233,24 @@ function App() {
     }
   };
 
+  // Without browser geolocation, start the city picker from the server's GeoIP guess
+  const prefillFromGeoIp = async () => {
+    try {
+      const geo = await apiGet<{ country?: string; region?: string; city?: string }>("/api/geo");
+      const cities = geo.region
+        ? (stateAndCityData as Record<string, string[]>)[geo.region] || []
+        : [];
+      if (geo.region && cities.length > 0) {
+        setState(geo.region);
+        setAvailableCities(cities);
+        setLocationDenied(false);
+        setShowCitySearch(true);
+      }
+    } catch (e) {
+      // Keep the location prompt
+    }
+  };
+
   const requestLocation = () => {
     if ("geolocation" in navigator) {
       // setIsLoadingLocation(true); // removed
@@ -228,6 +297,7 @@ function App() {
           setLocationDenied(true);
           // setIsLoadingLocation(false); // removed
           setState("Location Denied");
+          prefillFromGeoIp();
         },
         {
           enableHighAccuracy: false,
@@ -238,6 +308,7 @@ function App() {
     } else {
       setState("Not Supported");
       setLocationDenied(true);
+      prefillFromGeoIp();
       // setIsLoadingLocation(false); // removed
     }
   };
@@ -270,6 +341,10 @@ function App() {
                   ? new Date(msg.timestamp * 1000).toISOString()
                   : msg.timestamp,
               phone: msg.phone,
+              expires_at: msg.expires_at
+                ? new Date(msg.expires_at * 1000).toISOString()
+                : undefined,
+              details: msg.details,
             };
             addMessage(adaptedMessage);
           });
@@ -282,13 +357,67 @@ function App() {
     };
 
     fetchInitialMessages();
-  }, [city, locationDenied, showCitySearch, addMessage, clearMessages]);
+  }, [city, locationDenied, showCitySearch, addMessage, clearMessages, resyncCount]);
+
+  // Ask the server for this city's listings only; sent again after every reconnect
+  useEffect(() => {
+    if (readyState !== ReadyState.OPEN || !city) return;
+    sendJsonMessage({ type: "subscribe", city });
+  }, [readyState, city, sendJsonMessage]);
 
   // Handle incoming messages from WebSocket - only add if from same city
   useEffect(() => {
-    if (lastMessage !== null) {
+    if (lastMessage === null) {
+      return;
+    }
+
+    const handleFrame = async () => {
       try {
-        const data = JSON.parse(lastMessage.data);
+        const data = JSON.parse(await decodeSocketFrame(lastMessage.data));
+
+        if (data.type === "shutdown" && typeof data.reconnect_after_ms === "number") {
+          reconnectAfterRef.current = data.reconnect_after_ms;
+          return;
+        }
+
+        // Missed too much while disconnected to replay: reload the feed
+        if (data.type === "resync_required") {
+          setResyncCount((count) => count + 1);
+          return;
+        }
+
+        // The server's answer to a post sent over the socket
+        const pendingPost = pendingPostsRef.current.get(data.id);
+        if (pendingPost && ["posted", "rejected", "error"].includes(data.type)) {
+          pendingPostsRef.current.delete(data.id);
+          pendingPost(data);
+          return;
+        }
+
+        if (typeof data.stream_id === "string") {
+          lastStreamIdRef.current = data.stream_id;
+        }
+
+        // A poster edited a listing: replace its text and details in place
+        if (data.type === "message_updated" && data.message) {
+          const edited: BackendMessage = data.message;
+          updateMessage(edited.id, {
+            content: edited.message || edited.content || "",
+            details: edited.details,
+          });
+          return;
+        }
+
+        // A listing was retracted by moderation or deleted: drop it from the feed
+        if (data.type === "message_deleted" && data.message_id) {
+          removeMessage(data.message_id);
+          return;
+        }
+
+        // Command acks and private activity events aren't listings
+        if (data.message_type === undefined && typeof data.type === "string") {
+          return;
+        }
         // Adapter for Rust backend format to Frontend format
         // Rust sends: { id, browser_id, message, message_type, timestamp (number), location? }
         // Frontend expects: { id, device_id, content, type, timestamp (string), phone? }
@@ -316,14 +445,37 @@ function App() {
               ? new Date(data.timestamp * 1000).toISOString()
               : data.timestamp,
           phone: data.phone,
+          expires_at: data.expires_at
+            ? new Date(data.expires_at * 1000).toISOString()
+            : undefined,
+          details: data.details,
         };
 
         addMessage(adaptedMessage);
       } catch (e) {
         // Silently handle error
       }
-    }
-  }, [lastMessage, addMessage, city]);
+    };
+
+    handleFrame();
+  }, [lastMessage, addMessage, updateMessage, removeMessage, city]);
+
+  // Post over the open socket, skipping an HTTP round trip; null when it isn't open
+  const postOverSocket = (payload: object): Promise<any> | null => {
+    if (readyState !== ReadyState.OPEN) return null;
+    const id = `post-${Date.now()}-${Math.random().toString(36).slice(2)}`;
+    return new Promise((resolve, reject) => {
+      const timer = setTimeout(() => {
+        pendingPostsRef.current.delete(id);
+        reject(new Error("Failed to send message"));
+      }, SOCKET_POST_TIMEOUT_MS);
+      pendingPostsRef.current.set(id, (frame) => {
+        clearTimeout(timer);
+        resolve(frame);
+      });
+      sendJsonMessage({ type: "post", id, ...payload });
+    });
+  };
 
   const handleSendMessage = async (
     content: string,
@@ -360,10 +512,26 @@ function App() {
       phone: phone || undefined,
       location: city, // Send user's location
       website: "", // Honeypot field - leave empty for legitimate users
+      correction_token: correctionTokenRef.current || undefined,
+      // Phone numbers are only stored with consent to the current privacy policy
+      consent: phone && policyAccepted ? POLICY_VERSION : undefined,
     };
 
     try {
-      await apiPost("/messages", payload);
+      const pending = postOverSocket(payload);
+      const reply = pending ? await pending : null;
+      if (reply?.type === "error") {
+        throw new Error(reply.message);
+      }
+      // Rejections carry the same body as the HTTP error; a stale session is
+      // left to the HTTP path, which starts a new one and retries
+      if (reply?.type === "rejected" && reply.code !== "session_required") {
+        throw new Error(`${reply.error || "Failed to send message"} ${JSON.stringify(reply)}`);
+      }
+      if (reply?.type !== "posted") {
+        await apiPost("/messages", payload);
+      }
+      correctionTokenRef.current = null;
     } catch (e) {
       // Handle error
       void e;
@@ -378,6 +546,20 @@ function App() {
         if (jsonMatch) {
           const errorData = JSON.parse(jsonMatch[0]);
 
+          // The server's policy moved on: ask for consent again before resubmitting
+          if (errorData.policy_version) {
+            localStorage.removeItem("policyVersion");
+            setPolicyAccepted(false);
+          }
+
+          // Fixable mistake: the corrected post can go out straight away
+          if (errorData.correction_token) {
+            correctionTokenRef.current = errorData.correction_token;
+            setCooldown(0);
+            setPostError(errorData.reason || errorData.error);
+            return;
+          }
+
           if (errorData.retry_after_seconds !== undefined) {
             // Update cooldown based on backend response
             setCooldown(errorData.retry_after_seconds);
@@ -106,6 +106,9 @@ export const MessageItem = ({ message, theme }: MessageItemProps) => {
           >
             <Clock className="w-2.5 h-2.5" />
             {tryFormatDate(message.timestamp)}
+            {message.expires_at && (
+              <span>· expires {tryFormatDate(message.expires_at)}</span>
+            )}
           </div>
         </div>
 
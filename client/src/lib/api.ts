@@ -18,30 +18,127 @@ export const WS_BASE_URL = (() => {
   return backendUrl;
 })();
 
+// Subprotocol asking the server for compressed frames; only offered when the
+// browser can inflate them
+export const WS_DEFLATE_PROTOCOL = "krib.deflate";
+export const supportsWsDeflate = typeof DecompressionStream !== "undefined";
+
+// Compressed frames arrive as binary raw-DEFLATE data, everything else as text
+export async function decodeSocketFrame(data: string | Blob): Promise<string> {
+  if (typeof data === "string") {
+    return data;
+  }
+  const inflated = data
+    .stream()
+    .pipeThrough(new DecompressionStream("deflate-raw"));
+  return new Response(inflated).text();
+}
+
+interface SessionTokens {
+  access_token: string;
+  refresh_token: string;
+  expires_in: number;
+}
+
+interface StoredSession {
+  accessToken: string;
+  refreshToken: string;
+  expiresAt: number;
+}
+
+const SESSION_STORAGE_KEY = "krib_session";
+// Refresh slightly before the access token actually expires
+const SESSION_EXPIRY_MARGIN_MS = 30_000;
+
+function loadSession(): StoredSession | null {
+  try {
+    const raw = localStorage.getItem(SESSION_STORAGE_KEY);
+    return raw ? (JSON.parse(raw) as StoredSession) : null;
+  } catch {
+    return null;
+  }
+}
+
+function storeSession(tokens: SessionTokens | null): StoredSession | null {
+  if (!tokens) {
+    localStorage.removeItem(SESSION_STORAGE_KEY);
+    return null;
+  }
+
+  const session = {
+    accessToken: tokens.access_token,
+    refreshToken: tokens.refresh_token,
+    expiresAt: Date.now() + tokens.expires_in * 1000,
+  };
+  localStorage.setItem(SESSION_STORAGE_KEY, JSON.stringify(session));
+  return session;
+}
+
 /**
- * Get headers with browser fingerprint for API requests
+ * Get a valid anonymous session access token, creating or refreshing the
+ * session as needed. Posting requires a session; returns null if one can't be obtained
+ */
+export async function getSessionToken(): Promise<string | null> {
+  let session = loadSession();
+  if (session && session.expiresAt - SESSION_EXPIRY_MARGIN_MS > Date.now()) {
+    return session.accessToken;
+  }
+
+  if (session) {
+    const response = await fetch(`${API_BASE_URL}/api/session/refresh`, {
+      method: "POST",
+      headers: { "Content-Type": "application/json" },
+      body: JSON.stringify({ refresh_token: session.refreshToken }),
+    }).catch(() => null);
+    session = storeSession(response?.ok ? await response.json() : null);
+    if (session) {
+      return session.accessToken;
+    }
+  }
+
+  const fingerprint = await getBrowserFingerprint();
+  const response = await fetch(`${API_BASE_URL}/api/session`, {
+    method: "POST",
+    headers: { "X-Browser-Fingerprint": fingerprint },
+  }).catch(() => null);
+  session = storeSession(response?.ok ? await response.json() : null);
+  return session?.accessToken ?? null;
+}
+
+/**
+ * Get headers with browser fingerprint and session token for API requests
  */
 async function getHeaders(): Promise<HeadersInit> {
   const fingerprint = await getBrowserFingerprint();
+  const sessionToken = await getSessionToken();
 
   return {
     "Content-Type": "application/json",
     "X-Browser-Fingerprint": fingerprint,
+    ...(sessionToken ? { "X-Session-Token": sessionToken } : {}),
   };
 }
 
 /**
- * POST request with fingerprint header
+ * POST request with fingerprint and session headers
  */
 export async function apiPost<T>(endpoint: string, body: any): Promise<T> {
-  const headers = await getHeaders();
-
-  const response = await fetch(`${API_BASE_URL}${endpoint}`, {
+  let response = await fetch(`${API_BASE_URL}${endpoint}`, {
     method: "POST",
-    headers,
+    headers: await getHeaders(),
     body: JSON.stringify(body),
   });
 
+  // The session was revoked or belongs to a different IP; start a new one and retry once
+  if (response.status === 401) {
+    storeSession(null);
+    response = await fetch(`${API_BASE_URL}${endpoint}`, {
+      method: "POST",
+      headers: await getHeaders(),
+      body: JSON.stringify(body),
+    });
+  }
+
   if (!response.ok) {
     const errorData = await response.json().catch(() => ({}));
 
@@ -87,15 +184,24 @@ export async function apiGet<T>(endpoint: string): Promise<T> {
   return response.json();
 }
 
+export type ReportReason = "spam" | "scam" | "abusive" | "wrong_category";
+
 /**
  * Report a message
  */
 export async function reportMessage(
   messageId: string,
-  reportedBrowserId: string
-): Promise<{ success: boolean; message: string; reports_on_ip: number }> {
+  reportedBrowserId: string,
+  reason?: ReportReason
+): Promise<{
+  success: boolean;
+  message: string;
+  reports_on_ip: number;
+  already_reported?: boolean;
+}> {
   return apiPost("/api/report", {
     message_id: messageId,
     reported_browser_id: reportedBrowserId,
+    reason,
   });
 }
@@ -8,6 +8,8 @@ interface ChatState {
 
   setTab: (tab: MessageType) => void;
   addMessage: (msg: Message) => void;
+  updateMessage: (id: string, changes: Partial<Message>) => void;
+  removeMessage: (id: string) => void;
   clearMessages: () => void;
   markPostSent: () => void;
   setCooldown: (seconds: number) => void;
@@ -27,6 +29,18 @@ export const useChatStore = create<ChatState>((set) => ({
       return { messages: [...state.messages, msg] };
     }),
 
+  // Apply a poster's edit to a listing already shown; unknown ids are ignored
+  updateMessage: (id, changes) =>
+    set((state) => ({
+      messages: state.messages.map((m) => (m.id === id ? { ...m, ...changes } : m)),
+    })),
+
+  // Drop a listing the server retracted or deleted
+  removeMessage: (id) =>
+    set((state) => ({
+      messages: state.messages.filter((m) => m.id !== id),
+    })),
+
   clearMessages: () => set({ messages: [] }),
 
   markPostSent: () => set({ lastPostTime: Date.now() }),
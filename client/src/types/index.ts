@@ -1,5 +1,16 @@
 export type MessageType = "offered" | "requested";
 
+export type Furnishing = "furnished" | "semi_furnished" | "unfurnished";
+
+// Optional structured fields of a listing, validated by the server
+export interface ListingDetails {
+  rent?: number;
+  deposit?: number;
+  bhk?: number;
+  furnished?: Furnishing;
+  locality?: string;
+}
+
 export interface Message {
   id: string;
   type: MessageType;
@@ -7,6 +18,9 @@ export interface Message {
   phone?: string;
   timestamp: string;
   device_id: string;
+  // ISO time the listing expires; moves forward when the poster renews it
+  expires_at?: string;
+  details?: ListingDetails;
 }
 
 // Payload for sending to server
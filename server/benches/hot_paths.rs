@@ -0,0 +1,95 @@
+//! Cost of the checks every post goes through: `cargo bench --features bench --bench hot_paths`
+//! Rate limiter benches need a Redis to talk to and only run with `BENCH_REDIS_URL`
+//! set; point it at a throwaway instance, the benches write `ratelimit:*` keys
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use kirb_server::redis_client::RedisClient;
+use kirb_server::security::language::Language;
+use kirb_server::security::rate_limiter::{RateLimitConfig, RateLimitType};
+use kirb_server::security::{ContentFilter, ModerationService, RateLimiter};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Representative posts: a clean listing, one with profanity, one that reads like
+/// spam and one at the length limit
+const LISTINGS: &[(&str, &str)] = &[
+    ("clean", "Furnished 2BHK flat for rent near Baner, 10 minutes from the IT park. Rent 18000, deposit 50000, family or working professionals preferred."),
+    ("profane", "Room available in Koramangala, landlord is a total bastard about visitors but rent is only 9000 per month"),
+    ("spammy", "ROOM ROOM ROOM!!! Cheapest PG in Pune, call now, visit www.best-pg-deals.example for more rooms and offers"),
+    ("long", "Spacious 3BHK apartment on the fourth floor with lift and power backup, close to the metro station, two balconies, modular kitchen, covered parking for one car, gym and swimming pool in the society, looking for three working professionals to share, rent split equally, maintenance included, available from the first of next month, no brokerage, vegetarians preferred by the owner who lives in the same building, call or message for a visit on weekends, photos on request, please mention your office location and expected move-in date so we can plan visits, thanks"),
+];
+
+fn moderation(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("tokio runtime");
+    // No OpenAI key: only the local checks, which is what every post pays for
+    let service = ModerationService::new(None);
+
+    let mut group = c.benchmark_group("moderation");
+    for (name, text) in LISTINGS {
+        let language = Language::detect(text);
+        group.bench_with_input(BenchmarkId::new("moderate_message", name), text, |b, text| {
+            b.to_async(&runtime)
+                .iter(|| service.moderate_message(black_box(text), Some("Pune"), language, None));
+        });
+        group.bench_with_input(BenchmarkId::new("toxicity_score", name), text, |b, text| {
+            b.iter(|| service.toxicity_score(black_box(text), language));
+        });
+        group.bench_with_input(BenchmarkId::new("detect_language", name), text, |b, text| {
+            b.iter(|| Language::detect(black_box(text)));
+        });
+    }
+    group.finish();
+}
+
+fn content_filter(c: &mut Criterion) {
+    let filter = ContentFilter::new();
+
+    let mut group = c.benchmark_group("content_filter");
+    for (name, text) in LISTINGS {
+        group.bench_with_input(BenchmarkId::new("check_message", name), text, |b, text| {
+            b.iter(|| filter.check_message(black_box(text)));
+        });
+        group.bench_with_input(BenchmarkId::new("is_suspicious_pattern", name), text, |b, text| {
+            b.iter(|| filter.is_suspicious_pattern(black_box(text)));
+        });
+    }
+    group.bench_function("validate_phone", |b| {
+        b.iter(|| filter.validate_phone(black_box(Some("+91 98765 43210"))));
+    });
+    group.finish();
+}
+
+fn rate_limiter(c: &mut Criterion) {
+    let Ok(redis_url) = std::env::var("BENCH_REDIS_URL") else {
+        eprintln!("skipping rate limiter benches: BENCH_REDIS_URL is not set");
+        return;
+    };
+    let runtime = tokio::runtime::Runtime::new().expect("tokio runtime");
+    let limiter = runtime.block_on(async {
+        let redis = RedisClient::new(&redis_url).await.expect("connect to BENCH_REDIS_URL");
+        RateLimiter::new(redis, RateLimitConfig::default())
+    });
+
+    let mut group = c.benchmark_group("rate_limiter");
+    // A fresh key per iteration is the common case: most posters are well under their limit
+    let next_key = AtomicU64::new(0);
+    group.bench_function("check_rate_limit/fresh_key", |b| {
+        b.to_async(&runtime).iter(|| {
+            let key = format!("bench:{}", next_key.fetch_add(1, Ordering::Relaxed));
+            let limiter = limiter.clone();
+            async move { limiter.check_rate_limit(&key, RateLimitType::BurstProtection).await }
+        });
+    });
+    // One key hammered past its limit, so the window stays full
+    group.bench_function("check_rate_limit/limited_key", |b| {
+        b.to_async(&runtime)
+            .iter(|| limiter.check_rate_limit("bench:limited", RateLimitType::BurstProtection));
+    });
+    group.bench_function("check_rate_limit_status", |b| {
+        b.to_async(&runtime)
+            .iter(|| limiter.check_rate_limit_status("bench:limited", RateLimitType::PostMessage));
+    });
+    group.finish();
+}
+
+criterion_group!(benches, moderation, content_filter, rate_limiter);
+criterion_main!(benches);
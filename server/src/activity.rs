@@ -0,0 +1,110 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::models::WsServerEvent;
+use crate::state::AppState;
+use crate::keys;
+
+/// How long a listing's poster is spared repeat "under review" notices
+const UNDER_REVIEW_NOTICE_SECONDS: u64 = 86400; // 24 hours
+
+/// Which activity notices a poster receives
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NotificationPreferences {
+    /// "Someone viewed your contact" notices; on unless opted out
+    pub contact_reveals: bool,
+}
+
+pub async fn preferences(state: &AppState, composite_key: &str) -> Result<NotificationPreferences> {
+    let opted_out = state.redis
+        .sismember(keys::REVEAL_NOTICE_OPT_OUT, composite_key)
+        .await
+        .map_err(|e| anyhow!("Failed to load notification preferences: {}", e))?;
+    Ok(NotificationPreferences { contact_reveals: !opted_out })
+}
+
+pub async fn set_preferences(state: &AppState, composite_key: &str, preferences: &NotificationPreferences) -> Result<()> {
+    let result = if preferences.contact_reveals {
+        state.redis.srem(keys::REVEAL_NOTICE_OPT_OUT, composite_key).await
+    } else {
+        state.redis.sadd(keys::REVEAL_NOTICE_OPT_OUT, composite_key).await
+    };
+    result
+        .map(|_| ())
+        .map_err(|e| anyhow!("Failed to save notification preferences: {}", e))
+}
+
+/// Send an event about a listing to its poster's private channel
+/// Returns false if the listing's owner isn't known
+pub async fn notify_owner(state: &AppState, message_id: &str, event: &WsServerEvent) -> Result<bool> {
+    let Some(owner) = state.listing_stats.owner(message_id).await? else {
+        return Ok(false);
+    };
+
+    state.broadcast.publish_to_actor(&owner, &serde_json::to_string(event)?).await?;
+    metrics::counter!("activity_events_total", 1, "kind" => event.kind());
+    Ok(true)
+}
+
+/// Tell the poster their listing is under review, at most once a day per listing
+/// so the notice doesn't leak how many reports it's receiving
+async fn notify_under_review(state: &AppState, message_id: &str) -> Result<bool> {
+    let claimed = state.redis
+        .set_nx_ex(&keys::under_review_notice(message_id), "1", UNDER_REVIEW_NOTICE_SECONDS)
+        .await?;
+    if !claimed {
+        return Ok(false);
+    }
+
+    let event = WsServerEvent::UnderReview { message_id: message_id.to_string() };
+    notify_owner(state, message_id, &event).await
+}
+
+/// Tell the poster someone revealed their listing's contact, unless they opted out
+/// Surfaces scraping to the people being scraped
+async fn notify_contact_revealed(state: &AppState, message_id: &str, city: Option<String>) -> Result<bool> {
+    let Some(owner) = state.listing_stats.owner(message_id).await? else {
+        return Ok(false);
+    };
+    if !preferences(state, &owner).await?.contact_reveals {
+        return Ok(false);
+    }
+
+    let event = WsServerEvent::ContactRequested { message_id: message_id.to_string(), city };
+    state.broadcast.publish_to_actor(&owner, &serde_json::to_string(&event)?).await?;
+    metrics::counter!("activity_events_total", 1, "kind" => event.kind());
+    Ok(true)
+}
+
+/// Fire-and-forget notification off the request path
+pub fn spawn_notify_owner(state: &AppState, message_id: &str, event: WsServerEvent) {
+    let state = state.clone();
+    let message_id = message_id.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = notify_owner(&state, &message_id, &event).await {
+            tracing::error!("Failed to notify listing owner: {}", e);
+        }
+    });
+}
+
+/// Fire-and-forget contact reveal notice off the request path
+pub fn spawn_notify_contact_revealed(state: &AppState, message_id: &str, city: Option<String>) {
+    let state = state.clone();
+    let message_id = message_id.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = notify_contact_revealed(&state, &message_id, city).await {
+            tracing::error!("Failed to notify listing owner: {}", e);
+        }
+    });
+}
+
+/// Fire-and-forget "under review" notice off the request path
+pub fn spawn_notify_under_review(state: &AppState, message_id: &str) {
+    let state = state.clone();
+    let message_id = message_id.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = notify_under_review(&state, &message_id).await {
+            tracing::error!("Failed to notify listing owner: {}", e);
+        }
+    });
+}
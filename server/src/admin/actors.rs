@@ -0,0 +1,230 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashSet;
+
+use crate::keys;
+use crate::models::ChatMessage;
+use crate::security::audit::{AuditEvent, AuditEventKind};
+use crate::state::AppState;
+
+/// Audit events searched for the actor by default, newest first
+const DEFAULT_AUDIT_SCAN: usize = 5000;
+/// The audit stream is capped at this length anyway
+const MAX_AUDIT_SCAN: usize = 100_000;
+
+#[derive(Deserialize)]
+pub(super) struct TimelineQuery {
+    /// How many recent audit events to search
+    scan: Option<usize>,
+}
+
+/// One thing an actor did, or that was done to them
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TimelineEntry {
+    Posted {
+        timestamp: u64,
+        message_id: String,
+        /// Whether the listing is still stored
+        live: bool,
+    },
+    Revealed {
+        timestamp: u64,
+        message_id: String,
+        poster: String,
+        city: Option<String>,
+    },
+    ReportFiled {
+        timestamp: u64,
+        message_id: String,
+    },
+    ReportReceived {
+        timestamp: u64,
+        message_id: String,
+        reporter: String,
+    },
+    /// Any other audit event naming the actor, their fingerprint or their listings
+    Audit {
+        timestamp: u64,
+        event: AuditEvent,
+    },
+}
+
+impl TimelineEntry {
+    fn timestamp(&self) -> u64 {
+        match self {
+            TimelineEntry::Posted { timestamp, .. }
+            | TimelineEntry::Revealed { timestamp, .. }
+            | TimelineEntry::ReportFiled { timestamp, .. }
+            | TimelineEntry::ReportReceived { timestamp, .. }
+            | TimelineEntry::Audit { timestamp, .. } => *timestamp,
+        }
+    }
+}
+
+/// Who an actor is across the keys their activity is stored under
+struct Subject<'a> {
+    composite_key: &'a str,
+    /// Their listings, for reports and actions against those
+    message_ids: HashSet<String>,
+    /// `reported:<fingerprint>` keys of the fingerprints they posted with
+    reported_keys: HashSet<String>,
+}
+
+impl Subject<'_> {
+    /// Place an audit event on the actor's timeline, if it concerns them
+    fn classify(&self, event: AuditEvent) -> Option<TimelineEntry> {
+        let timestamp = event.timestamp;
+        if event.kind == AuditEventKind::ReportSubmitted {
+            if event.actor == self.composite_key {
+                return Some(TimelineEntry::ReportFiled { timestamp, message_id: event.target });
+            }
+            if self.message_ids.contains(&event.target) {
+                return Some(TimelineEntry::ReportReceived {
+                    timestamp,
+                    message_id: event.target,
+                    reporter: event.actor,
+                });
+            }
+            return None;
+        }
+
+        let involved = event.actor == self.composite_key
+            || event.target == self.composite_key
+            || self.message_ids.contains(&event.target)
+            || self.reported_keys.contains(&event.target);
+        involved.then_some(TimelineEntry::Audit { timestamp, event })
+    }
+}
+
+/// GET /admin/actors/:key - everything one composite key did, for investigating complaints
+/// Listings (30 days), reveals, violations and enforcement come from their Redis
+/// structures; reports and ban history from the most recent `?scan=` audit events
+pub(super) async fn actor_timeline(
+    State(state): State<AppState>,
+    Path(composite_key): Path<String>,
+    Query(query): Query<TimelineQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let internal_error = |e: anyhow::Error| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to assemble actor timeline"})),
+        )
+    };
+    let scan = query.scan.unwrap_or(DEFAULT_AUDIT_SCAN).clamp(1, MAX_AUDIT_SCAN);
+
+    let listings = state.poster_limits.listings(&composite_key).await.map_err(internal_error)?;
+    let mut timeline = Vec::new();
+    let mut stored = Vec::new();
+    let mut fingerprints = HashSet::new();
+    for (message_id, posted_at) in &listings {
+        let message = state.get_message_by_id(message_id).await;
+        if let Some(message) = &message {
+            fingerprints.insert(message.browser_id.clone());
+        }
+        timeline.push(TimelineEntry::Posted {
+            timestamp: *posted_at,
+            message_id: message_id.clone(),
+            live: message.is_some(),
+        });
+        if let Some(message) = message {
+            stored.push(ChatMessage { phone: None, ..message });
+        }
+    }
+
+    // Reports against a poster are counted by the fingerprint they posted with
+    let mut reports_received = Vec::new();
+    for fingerprint in &fingerprints {
+        let reported_key = keys::reported(fingerprint);
+        let reports = state.redis
+            .scard(&keys::fingerprint_reporters(fingerprint))
+            .await
+            .map_err(|e| internal_error(e.into()))?;
+        let shadowban = state.shadowban_manager.get_shadowban(&reported_key).await.map_err(internal_error)?;
+        reports_received.push(json!({
+            "fingerprint": fingerprint,
+            "reports": reports,
+            "shadowban": shadowban,
+        }));
+    }
+
+    let reveals = state.reveal_graph.reveals(&composite_key).await.map_err(internal_error)?;
+    let reveals_revoked = state.reveal_graph.is_revoked(&composite_key).await.map_err(internal_error)?;
+    timeline.extend(reveals.into_iter().map(|(message_id, edge)| TimelineEntry::Revealed {
+        timestamp: edge.timestamp,
+        message_id,
+        poster: edge.poster,
+        city: edge.city,
+    }));
+
+    let subject = Subject {
+        composite_key: &composite_key,
+        message_ids: listings.iter().map(|(id, _)| id.clone()).collect(),
+        reported_keys: fingerprints.iter().map(|fingerprint| keys::reported(fingerprint)).collect(),
+    };
+    let events = state.audit_log.recent(scan).await.map_err(internal_error)?;
+    let audit_scanned = events.len();
+    timeline.extend(events.into_iter().filter_map(|event| subject.classify(event)));
+    timeline.sort_by_key(|entry| std::cmp::Reverse(entry.timestamp()));
+
+    let violations = state.shadowban_manager.get_violations(&composite_key).await.map_err(internal_error)?;
+    let soft_violations = state.shadowban_manager.get_soft_violations(&composite_key).await.map_err(internal_error)?;
+    let shadowban = state.shadowban_manager.get_shadowban(&composite_key).await.map_err(internal_error)?;
+    let cooldown = state.ip_reputation.get_cooldown(&composite_key).await.map_err(internal_error)?;
+
+    Ok(Json(json!({
+        "composite_key": composite_key,
+        "listings": stored,
+        "violations": { "hard": violations, "soft": soft_violations },
+        "reports_received": reports_received,
+        "reveals_revoked": reveals_revoked,
+        "enforcement": { "shadowban": shadowban, "cooldown": cooldown },
+        "timeline": timeline,
+        "audit_scanned": audit_scanned,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subject() -> Subject<'static> {
+        Subject {
+            composite_key: "actor",
+            message_ids: HashSet::from(["m1".to_string()]),
+            reported_keys: HashSet::from([keys::reported("fp")]),
+        }
+    }
+
+    #[test]
+    fn test_reports_are_split_by_direction() {
+        let subject = subject();
+        let filed = AuditEvent::new(AuditEventKind::ReportSubmitted, "actor", "m9", "Reported by user");
+        assert!(matches!(subject.classify(filed), Some(TimelineEntry::ReportFiled { message_id, .. }) if message_id == "m9"));
+
+        let received = AuditEvent::new(AuditEventKind::ReportSubmitted, "someone", "m1", "Reported by user");
+        assert!(matches!(subject.classify(received), Some(TimelineEntry::ReportReceived { reporter, .. }) if reporter == "someone"));
+
+        let unrelated = AuditEvent::new(AuditEventKind::ReportSubmitted, "someone", "m9", "Reported by user");
+        assert!(subject.classify(unrelated).is_none());
+    }
+
+    #[test]
+    fn test_enforcement_on_fingerprint_or_key_is_included() {
+        let subject = subject();
+        let by_reports = AuditEvent::new(AuditEventKind::Shadowbanned, "system", &keys::reported("fp"), "3 reports");
+        assert!(matches!(subject.classify(by_reports), Some(TimelineEntry::Audit { .. })));
+
+        let direct = AuditEvent::new(AuditEventKind::RateLimitReset, "admin@krib.in", "actor", "false positive");
+        assert!(matches!(subject.classify(direct), Some(TimelineEntry::Audit { .. })));
+
+        let other = AuditEvent::new(AuditEventKind::Shadowbanned, "system", "someone", "spam");
+        assert!(subject.classify(other).is_none());
+    }
+}
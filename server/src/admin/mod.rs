@@ -0,0 +1,1265 @@
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+    Extension, Json, Router,
+};
+use anyhow::{Context, Result};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use serde::Deserialize;
+use serde_json::json;
+use ipnet::IpNet;
+use std::{env, fs::File, io::BufReader, net::{IpAddr, SocketAddr}, sync::Arc};
+
+use crate::security::audit::{AuditEvent, AuditEventKind};
+use crate::security::hotspots::HOTSPOT_WINDOW_SECONDS;
+use crate::security::review_queue::ReviewItem;
+use crate::models::ChatMessage;
+use crate::pins::PinOutcome;
+use crate::moderation_dataset::{ReportAction, MAX_EXPORT_DAYS};
+use crate::redis_usage::{self, RedisUsage};
+use crate::stats::LifetimeTotals;
+use crate::slo::SloReport;
+use crate::cities::CityDirectory;
+use crate::security::city_policy::{CityModerationPolicy, Strictness};
+use crate::security::city_surge::CitySurge;
+use crate::config::{self, ConfigSummary};
+use crate::security::rate_limiter::{self, RateLimitType};
+use crate::security::enforcement::EnforcementRecord;
+use crate::security::TokenSigner;
+use crate::state::{AppState, MESSAGE_TTL};
+
+mod actors;
+pub mod oidc;
+mod stream;
+
+pub use oidc::{AdminIdentity, AdminRole, OidcConfig};
+
+const DEFAULT_ADMIN_TLS_ADDR: &str = "0.0.0.0:3443";
+const DEFAULT_AUDIT_LIMIT: usize = 100;
+const MAX_AUDIT_LIMIT: usize = 1000;
+const DEFAULT_HOTSPOT_LIMIT: usize = 10;
+const MAX_HOTSPOT_LIMIT: usize = 64;
+const DEFAULT_BLOCK_SECONDS: u64 = 86400; // 24 hours
+const MAX_BLOCK_SECONDS: u64 = 31536000; // 1 year
+/// Broadest networks that may be blocked in one go
+const MIN_IPV4_BLOCK_PREFIX: u8 = 16;
+const MIN_IPV6_BLOCK_PREFIX: u8 = 32;
+
+/// Mutual-TLS settings for the dedicated admin listener
+#[derive(Clone, Debug)]
+pub struct AdminTlsConfig {
+    pub listen_addr: String,
+    pub cert_path: String,
+    pub key_path: String,
+    /// CA bundle used to verify client certificates
+    pub client_ca_path: String,
+}
+
+/// Admin API configuration
+/// The admin API is disabled unless ADMIN_TOKEN or the OIDC_* login variables
+/// are set. When the ADMIN_TLS_* variables are also set it is served only on a
+/// separate listener that requires a client certificate signed by ADMIN_TLS_CLIENT_CA.
+#[derive(Clone)]
+pub struct AdminConfig {
+    pub token: Option<String>,
+    pub tls: Option<AdminTlsConfig>,
+    pub oidc: Option<OidcConfig>,
+    /// Signs short-lived moderator session tokens issued after OIDC login
+    pub session_signer: TokenSigner,
+}
+
+impl AdminConfig {
+    pub fn new(server_secret: &str) -> Self {
+        Self {
+            token: None,
+            tls: None,
+            oidc: None,
+            session_signer: TokenSigner::new(server_secret, "admin-session"),
+        }
+    }
+
+    pub fn from_env(server_secret: &str) -> Self {
+        let token = env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty());
+
+        let tls = match (
+            env::var("ADMIN_TLS_CERT"),
+            env::var("ADMIN_TLS_KEY"),
+            env::var("ADMIN_TLS_CLIENT_CA"),
+        ) {
+            (Ok(cert_path), Ok(key_path), Ok(client_ca_path)) => Some(AdminTlsConfig {
+                listen_addr: env::var("ADMIN_TLS_ADDR")
+                    .unwrap_or_else(|_| DEFAULT_ADMIN_TLS_ADDR.to_string()),
+                cert_path,
+                key_path,
+                client_ca_path,
+            }),
+            _ => None,
+        };
+
+        Self {
+            token,
+            tls,
+            oidc: OidcConfig::from_env(),
+            ..Self::new(server_secret)
+        }
+    }
+
+    /// Whether any admin authentication method is configured
+    pub fn enabled(&self) -> bool {
+        self.token.is_some() || self.oidc.is_some()
+    }
+
+    /// Whether the admin API should be mounted on the public listener
+    pub fn serves_on_public_listener(&self) -> bool {
+        self.enabled() && self.tls.is_none()
+    }
+}
+
+/// Admin routes (not yet bound to state)
+/// Login routes are public; everything else sits behind the admin auth middleware
+pub fn admin_routes(state: &AppState) -> Router<AppState> {
+    let protected = Router::new()
+        .route("/admin/audit", get(list_audit_events))
+        .route("/admin/auth/me", get(current_identity))
+        .route("/admin/hotspots", get(list_hotspots))
+        .route("/admin/redis/usage", get(redis_usage))
+        .route("/admin/slo", get(slo_report))
+        .route("/admin/totals", get(lifetime_totals))
+        .route("/admin/reveals/flagged", get(list_flagged_revealers))
+        .route("/admin/reveals/:composite_key/restore", post(restore_reveals))
+        .route("/admin/pins/:city", get(list_pins).post(pin_listing))
+        .route("/admin/pins/:city/:message_id", delete(unpin_listing))
+        .route("/admin/cities/waitlist", get(list_waitlisted_cities))
+        .route(
+            "/admin/cities/registry",
+            get(get_city_registry).put(set_city_registry).delete(clear_city_registry),
+        )
+        .route("/admin/cities/surges", get(list_city_surges))
+        .route("/admin/cities/:city/surge", delete(clear_city_surge))
+        .route("/admin/cities/:city/waitlist", post(waitlist_city))
+        .route("/admin/cities/:city/launch", post(launch_city))
+        .route("/admin/moderation/queue", get(list_review_queue))
+        .route("/admin/moderation/queue/:id/approve", post(approve_review_item))
+        .route("/admin/moderation/queue/:id/remove", post(remove_review_item))
+        .route("/admin/moderation/export", get(export_moderation_dataset))
+        .route("/admin/ws", get(stream::moderation_stream))
+        .route("/admin/blocks", get(list_ip_blocks).post(block_ip))
+        .route("/admin/blocks/:target", delete(unblock_ip))
+        .route("/admin/enforcement/:composite_key", get(get_enforcement))
+        .route("/admin/actors/:composite_key", get(actors::actor_timeline))
+        .route(
+            "/admin/posters/:composite_key/verified",
+            put(verify_poster).delete(unverify_poster),
+        )
+        .route(
+            "/admin/ratelimits/:composite_key",
+            get(inspect_rate_limits).delete(reset_rate_limits),
+        )
+        .route(
+            "/admin/moderation/cities/:city",
+            get(get_city_policy).put(set_city_policy).delete(clear_city_policy),
+        )
+        .route("/admin/config/reload", post(reload_config))
+        .route_layer(middleware::from_fn_with_state(state.clone(), admin_auth_middleware));
+
+    Router::new()
+        .route("/admin/auth/login", get(oidc::login))
+        .route("/admin/auth/callback", get(oidc::callback))
+        .merge(protected)
+}
+
+/// Require `Authorization: Bearer <token>` on every admin request
+/// Accepts the static ADMIN_TOKEN (admin role) or a signed moderator session,
+/// and exposes the caller as an `AdminIdentity` request extension
+/// Browsers can't set headers on a WebSocket upgrade, so /admin/ws also takes `?token=`
+async fn admin_auth_middleware(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    if !state.admin.enabled() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let query_token = (req.uri().path() == "/admin/ws")
+        .then(|| req.uri().query())
+        .flatten()
+        .and_then(|query| {
+            query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("token="))
+                .map(str::to_string)
+        });
+    let provided = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .or(query_token.as_deref());
+
+    let identity = provided.and_then(|token| match state.admin.token.as_deref() {
+        Some(expected) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => Some(AdminIdentity {
+            subject: "admin-token".to_string(),
+            role: AdminRole::Admin,
+        }),
+        _ => oidc::verify_session(&state, token),
+    });
+
+    match identity {
+        Some(identity) => {
+            req.extensions_mut().insert(identity);
+            next.run(req).await
+        }
+        None => (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({"error": "Admin authentication required"})),
+        ).into_response(),
+    }
+}
+
+/// Compare two byte strings without short-circuiting on the first difference
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Describe the authenticated admin caller
+async fn current_identity(Extension(identity): Extension<AdminIdentity>) -> Json<serde_json::Value> {
+    Json(json!({
+        "subject": identity.subject,
+        "role": identity.role,
+    }))
+}
+
+#[derive(Deserialize)]
+struct AuditQuery {
+    limit: Option<usize>,
+}
+
+/// List the most recent audit events
+async fn list_audit_events(
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let limit = query.limit.unwrap_or(DEFAULT_AUDIT_LIMIT).clamp(1, MAX_AUDIT_LIMIT);
+
+    let events = state.audit_log.recent(limit).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to read audit events"})),
+        )
+    })?;
+
+    Ok(Json(json!({ "events": events })))
+}
+
+#[derive(Deserialize)]
+struct HotspotQuery {
+    limit: Option<usize>,
+}
+
+/// Most active composite keys and IPs on this instance
+async fn list_hotspots(
+    State(state): State<AppState>,
+    Query(query): Query<HotspotQuery>,
+) -> Json<serde_json::Value> {
+    let limit = query.limit.unwrap_or(DEFAULT_HOTSPOT_LIMIT).clamp(1, MAX_HOTSPOT_LIMIT);
+
+    Json(json!({
+        "window_seconds": HOTSPOT_WINDOW_SECONDS,
+        "current": state.hotspots.current(limit),
+        "previous": state.hotspots.previous(limit),
+    }))
+}
+
+/// Redis memory and key counts by prefix
+async fn redis_usage(
+    State(state): State<AppState>,
+) -> Result<Json<RedisUsage>, (StatusCode, Json<serde_json::Value>)> {
+    redis_usage::collect(&state.redis).await.map(Json).map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to read Redis usage"})),
+        )
+    })
+}
+
+/// Rolling error rate, per-route p95 latency and dependency health against SLO targets
+async fn slo_report(State(state): State<AppState>) -> Json<SloReport> {
+    Json(state.slo.report())
+}
+
+/// Lifetime message and reveal totals across the cluster
+/// Served here rather than as metrics, which are per instance
+async fn lifetime_totals(
+    State(state): State<AppState>,
+) -> Result<Json<LifetimeTotals>, (StatusCode, Json<serde_json::Value>)> {
+    state.stats.lifetime_totals().await.map(Json).map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to read lifetime totals"})),
+        )
+    })
+}
+
+/// Actors whose reveal ability was revoked, pending review
+async fn list_flagged_revealers(
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let limit = query.limit.unwrap_or(DEFAULT_AUDIT_LIMIT).clamp(1, MAX_AUDIT_LIMIT);
+
+    let flagged = state.reveal_graph.flagged(limit).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to read flagged revealers"})),
+        )
+    })?;
+
+    Ok(Json(json!({ "flagged": flagged })))
+}
+
+/// Restore reveal ability for a reviewed actor
+async fn restore_reveals(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Path(composite_key): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    state.reveal_graph.restore(&composite_key).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to restore reveal ability"})),
+        )
+    })?;
+
+    let event = AuditEvent::new(
+        AuditEventKind::RevealRestored,
+        &identity.subject,
+        &composite_key,
+        "Reviewed by moderator",
+    );
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Active shadowban and posting cooldown for a composite key, with their metadata
+async fn get_enforcement(
+    State(state): State<AppState>,
+    Path(composite_key): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let internal_error = |e: anyhow::Error| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to read enforcement state"})),
+        )
+    };
+
+    let shadowban = state.shadowban_manager.get_shadowban(&composite_key).await.map_err(internal_error)?;
+    let shadowban_ttl = state.shadowban_manager.get_shadowban_ttl(&composite_key).await.map_err(internal_error)?;
+    let cooldown = state.ip_reputation.get_cooldown(&composite_key).await.map_err(internal_error)?;
+    let cooldown_remaining = state.ip_reputation.check_cooldown(&composite_key).await.map_err(internal_error)?;
+
+    Ok(Json(json!({
+        "composite_key": composite_key,
+        "shadowban": shadowban.map(|record| json!({ "record": record, "ttl": shadowban_ttl })),
+        "cooldown": cooldown.map(|record| json!({ "record": record, "ttl": cooldown_remaining.unwrap_or(0) })),
+    })))
+}
+
+/// Current rate limit windows for a composite key (or IP, for IP-keyed limits)
+async fn inspect_rate_limits(
+    State(state): State<AppState>,
+    Path(composite_key): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let windows = state.rate_limiter.inspect(&composite_key).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to read rate limits"})),
+        )
+    })?;
+
+    Ok(Json(json!({
+        "key": composite_key,
+        "windows": windows,
+    })))
+}
+
+#[derive(Deserialize)]
+struct RateLimitResetQuery {
+    /// Reset only this limit type (all types when absent)
+    #[serde(rename = "type")]
+    limit_type: Option<RateLimitType>,
+    reason: Option<String>,
+}
+
+/// Clear a key's rate limit windows, e.g. after a false positive
+async fn reset_rate_limits(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Path(composite_key): Path<String>,
+    Query(query): Query<RateLimitResetQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let cleared = state.rate_limiter
+        .reset(&composite_key, query.limit_type)
+        .await
+        .map_err(|e| {
+            tracing::error!("{}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": "Failed to reset rate limits"})),
+            )
+        })?;
+
+    let event = AuditEvent::new(
+        AuditEventKind::RateLimitReset,
+        &identity.subject,
+        &composite_key,
+        query.reason.as_deref().unwrap_or("Reset by moderator"),
+    )
+    .with_details(json!({ "cleared": cleared }));
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+
+    Ok(Json(json!({
+        "key": composite_key,
+        "cleared": cleared,
+    })))
+}
+
+/// Parse a block target: a single IP address or a CIDR network
+fn parse_block_target(target: &str) -> Option<IpNet> {
+    target
+        .parse::<IpNet>()
+        .ok()
+        .or_else(|| target.parse::<IpAddr>().ok().map(IpNet::from))
+        .map(|network| network.trunc())
+}
+
+/// How a block target is shown: a bare address for single hosts
+fn block_target_label(network: &IpNet) -> String {
+    if rate_limiter::is_single_host(network) {
+        network.addr().to_string()
+    } else {
+        network.to_string()
+    }
+}
+
+/// Active IP and network blocks with their reasons and remaining time
+async fn list_ip_blocks(
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let blocks = state.rate_limiter.list_blocks().await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to list blocks"})),
+        )
+    })?;
+
+    Ok(Json(json!({ "blocks": blocks })))
+}
+
+#[derive(Deserialize)]
+struct BlockRequest {
+    /// IP address or CIDR network
+    target: String,
+    duration_seconds: Option<u64>,
+    reason: String,
+}
+
+/// Manually block an IP address or network
+async fn block_ip(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Json(request): Json<BlockRequest>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let Some(network) = parse_block_target(request.target.trim()) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "Target must be an IP address or CIDR network"})),
+        ));
+    };
+    let min_prefix = match network {
+        IpNet::V4(_) => MIN_IPV4_BLOCK_PREFIX,
+        IpNet::V6(_) => MIN_IPV6_BLOCK_PREFIX,
+    };
+    if network.prefix_len() < min_prefix {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": format!("Networks broader than /{} can't be blocked", min_prefix)})),
+        ));
+    }
+    if request.reason.trim().is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "A reason is required"})),
+        ));
+    }
+    let duration = request.duration_seconds.unwrap_or(DEFAULT_BLOCK_SECONDS).clamp(1, MAX_BLOCK_SECONDS);
+
+    let record = EnforcementRecord::manual(&identity.subject, &request.reason);
+    let result = if rate_limiter::is_single_host(&network) {
+        state.rate_limiter.block_ip(&network.addr().to_string(), duration, record).await
+    } else {
+        state.rate_limiter.block_network(&network, duration, record).await
+    };
+    result.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to block"})),
+        )
+    })?;
+
+    let event = AuditEvent::new(
+        AuditEventKind::IpBlocked,
+        &identity.subject,
+        &block_target_label(&network),
+        &request.reason,
+    )
+    .with_details(json!({ "duration_seconds": duration }));
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lift a block; CIDR targets must be percent-encoded (`10.0.0.0%2F24`)
+async fn unblock_ip(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Path(target): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let Some(network) = parse_block_target(target.trim()) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "Target must be an IP address or CIDR network"})),
+        ));
+    };
+
+    let removed = state.rate_limiter.unblock(&network).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to unblock"})),
+        )
+    })?;
+    if !removed {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "No active block for that target"})),
+        ));
+    }
+
+    let event = AuditEvent::new(
+        AuditEventKind::IpUnblocked,
+        &identity.subject,
+        &block_target_label(&network),
+        "Unblocked by moderator",
+    );
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Exempt a poster from the active listing cap
+async fn verify_poster(
+    state: State<AppState>,
+    identity: Extension<AdminIdentity>,
+    composite_key: Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    set_poster_verified(state, identity, composite_key, true).await
+}
+
+/// Put a poster back under the active listing cap
+async fn unverify_poster(
+    state: State<AppState>,
+    identity: Extension<AdminIdentity>,
+    composite_key: Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    set_poster_verified(state, identity, composite_key, false).await
+}
+
+async fn set_poster_verified(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Path(composite_key): Path<String>,
+    verified: bool,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let changed = state.poster_limits.set_verified(&composite_key, verified).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to update poster verification"})),
+        )
+    })?;
+
+    if changed {
+        let (kind, reason) = if verified {
+            (AuditEventKind::PosterVerified, "Verified by moderator")
+        } else {
+            (AuditEventKind::PosterUnverified, "Verification removed by moderator")
+        };
+        let event = AuditEvent::new(kind, &identity.subject, &composite_key, reason);
+        if let Err(e) = state.audit_log.record(event).await {
+            tracing::error!("{}", e);
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Listings currently pinned in a city
+async fn list_pins(
+    State(state): State<AppState>,
+    Path(city): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let pinned = state.pinned_messages(&city).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to load pinned listings"})),
+        )
+    })?;
+
+    Ok(Json(json!({
+        "city": city,
+        "max_pinned": state.pins.max_per_city(),
+        "pinned": pinned,
+    })))
+}
+
+#[derive(Deserialize)]
+struct PinRequest {
+    message_id: String,
+}
+
+/// Pin a verified listing to the top of its city's feed
+async fn pin_listing(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Path(city): Path<String>,
+    Json(request): Json<PinRequest>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let internal_error = |e: anyhow::Error| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to pin listing"})),
+        )
+    };
+
+    let Some(message) = state.get_message_by_id(&request.message_id).await else {
+        return Err((StatusCode::NOT_FOUND, Json(json!({"error": "Message not found"}))));
+    };
+    if message.location.as_deref() != Some(city.as_str()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "Listing is not in this city"})),
+        ));
+    }
+
+    // Drop pins whose listings have expired so they don't hold a slot
+    state.pinned_messages(&city).await.map_err(internal_error)?;
+
+    match state.pins.pin(&city, &message.id).await.map_err(internal_error)? {
+        PinOutcome::Pinned => {
+            let event = AuditEvent::new(AuditEventKind::ListingPinned, &identity.subject, &message.id, "Pinned listing")
+                .with_details(json!({ "city": city }));
+            if let Err(e) = state.audit_log.record(event).await {
+                tracing::error!("{}", e);
+            }
+            Ok(StatusCode::CREATED)
+        }
+        PinOutcome::AlreadyPinned => Ok(StatusCode::OK),
+        PinOutcome::CityFull => Err((
+            StatusCode::CONFLICT,
+            Json(json!({
+                "error": "Pinned listing limit reached for this city",
+                "max_pinned": state.pins.max_per_city(),
+            })),
+        )),
+    }
+}
+
+/// Unpin a listing
+async fn unpin_listing(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Path((city, message_id)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let removed = state.pins.unpin(&city, &message_id).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to unpin listing"})),
+        )
+    })?;
+
+    if !removed {
+        return Err((StatusCode::NOT_FOUND, Json(json!({"error": "Listing is not pinned"}))));
+    }
+
+    let event = AuditEvent::new(AuditEventKind::ListingUnpinned, &identity.subject, &message_id, "Unpinned listing")
+        .with_details(json!({ "city": city }));
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Cities with an active posting surge
+async fn list_city_surges(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<CitySurge>>, (StatusCode, Json<serde_json::Value>)> {
+    state.city_surges.active().await.map(Json).map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to list city surges"})),
+        )
+    })
+}
+
+/// End a city's posting surge, restoring its normal moderation and cooldowns
+async fn clear_city_surge(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Path(city): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let cleared = state.city_surges.clear(&city).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to clear city surge"})),
+        )
+    })?;
+
+    if !cleared {
+        return Err((StatusCode::NOT_FOUND, Json(json!({"error": "City has no active surge"}))));
+    }
+
+    let event = AuditEvent::new(AuditEventKind::CitySurgeCleared, &identity.subject, &city, "Surge cleared by moderator");
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Cities currently on the launch waitlist with their counters
+async fn list_waitlisted_cities(
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let internal_error = |e: anyhow::Error| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to list waitlisted cities"})),
+        )
+    };
+
+    let mut cities = Vec::new();
+    for city in state.cities.waitlisted().await.map_err(internal_error)? {
+        let (interested, queued_listings) = state.cities.interest(&city).await.map_err(internal_error)?;
+        cities.push(json!({
+            "city": city,
+            "interested": interested,
+            "queued_listings": queued_listings,
+        }));
+    }
+
+    Ok(Json(json!({ "cities": cities })))
+}
+
+/// Put a city on the waitlist so new posts are queued until launch
+async fn waitlist_city(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Path(city): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    state.cities.add_to_waitlist(&city).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to waitlist city"})),
+        )
+    })?;
+
+    let event = AuditEvent::new(AuditEventKind::CityWaitlisted, &identity.subject, &city, "City put on waitlist");
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Flip a city live and publish the posts queued while it was waitlisted
+async fn launch_city(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Path(city): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let backlog = state.cities.launch(&city).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to launch city"})),
+        )
+    })?;
+
+    // Queued posts go live as of the launch so they get a full listing lifetime
+    let launched_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let mut published = 0;
+    for post in backlog {
+        let message = ChatMessage {
+            timestamp: launched_at,
+            expires_at: Some(launched_at + MESSAGE_TTL),
+            ..post.message
+        };
+        if let Err(e) = state.add_message(message.clone()).await {
+            tracing::error!("Failed to publish queued post {}: {}", message.id, e);
+            continue;
+        }
+        if let Err(e) = state.listing_stats.record_owner(&message.id, &post.composite_key).await {
+            tracing::error!("{}", e);
+        }
+        if let Err(e) = state.poster_limits.track(&post.composite_key, &message.id, message.timestamp).await {
+            tracing::error!("{}", e);
+        }
+        published += 1;
+    }
+
+    tracing::info!(city = %city, published, "city launched");
+    let event = AuditEvent::new(AuditEventKind::CityLaunched, &identity.subject, &city, "City launched")
+        .with_details(json!({ "published": published }));
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+
+    Ok(Json(json!({ "city": city, "published": published })))
+}
+
+/// Items awaiting moderator review, newest first
+async fn list_review_queue(
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let limit = query.limit.unwrap_or(DEFAULT_AUDIT_LIMIT).clamp(1, MAX_AUDIT_LIMIT);
+
+    let items = state.review_queue.list(limit).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to read review queue"})),
+        )
+    })?;
+
+    Ok(Json(json!({ "items": items })))
+}
+
+/// Take a decided item off the review queue, or 404 if it's no longer there
+async fn take_review_item(
+    state: &AppState,
+    id: &str,
+) -> Result<ReviewItem, (StatusCode, Json<serde_json::Value>)> {
+    match state.review_queue.take(id).await {
+        Ok(Some(item)) => Ok(item),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Review item not found"})),
+        )),
+        Err(e) => {
+            tracing::error!("{}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": "Failed to read review queue"})),
+            ))
+        }
+    }
+}
+
+/// Keep a queued item's message: drop it from the queue and stop further
+/// report-driven auto-actions on the message
+async fn approve_review_item(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let item = take_review_item(&state, &id).await?;
+
+    if let Some(message_id) = &item.message_id {
+        if let Err(e) = state.report_guard.freeze(message_id).await {
+            tracing::error!("{}", e);
+        }
+        if let Err(e) = state.moderation_dataset.record_report(message_id, ReportAction::Approved).await {
+            tracing::error!("{}", e);
+        }
+    }
+
+    let event = AuditEvent::new(
+        AuditEventKind::ReviewApproved,
+        &identity.subject,
+        item.message_id.as_deref().unwrap_or(&item.composite_key),
+        "Kept after review",
+    )
+    .with_details(json!(item));
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+
+    Ok(Json(json!({ "id": item.id, "action": "approved" })))
+}
+
+/// Remove a queued item's message from the feed and drop the item
+async fn remove_review_item(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let item = take_review_item(&state, &id).await?;
+
+    if let Some(message_id) = &item.message_id {
+        if let Err(e) = state.delete_message(message_id).await {
+            tracing::error!("{}", e);
+            // Put it back so the decision can be retried
+            if let Err(e) = state.review_queue.enqueue(&item).await {
+                tracing::error!("{}", e);
+            }
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": "Failed to remove message"})),
+            ));
+        }
+        if let Err(e) = state.moderation_dataset.record_report(message_id, ReportAction::RemovedByModerator).await {
+            tracing::error!("{}", e);
+        }
+    }
+
+    let event = AuditEvent::new(
+        AuditEventKind::ReviewRemoved,
+        &identity.subject,
+        item.message_id.as_deref().unwrap_or(&item.composite_key),
+        "Removed after review",
+    )
+    .with_details(json!(item));
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+
+    Ok(Json(json!({ "id": item.id, "action": "removed" })))
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    /// Inclusive start date, YYYY-MM-DD
+    from: chrono::NaiveDate,
+    /// Inclusive end date, YYYY-MM-DD
+    to: chrono::NaiveDate,
+}
+
+/// Anonymized moderation outcomes with their later report outcomes, as NDJSON
+async fn export_moderation_dataset(
+    State(state): State<AppState>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let days = (query.to - query.from).num_days() + 1;
+    if !(1..=MAX_EXPORT_DAYS).contains(&days) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": format!("Date range must cover 1 to {} days", MAX_EXPORT_DAYS)})),
+        ));
+    }
+
+    let rows = state.moderation_dataset.export(query.from, query.to).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to export moderation dataset"})),
+        )
+    })?;
+
+    let mut body = String::new();
+    for row in &rows {
+        body.push_str(&serde_json::to_string(row).unwrap_or_default());
+        body.push('\n');
+    }
+
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], body).into_response())
+}
+
+/// Effective moderation policy for a city and where it comes from
+async fn get_city_policy(
+    State(state): State<AppState>,
+    Path(city): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let config = state.config.current();
+    let policies = config.moderation.city_policies();
+    let admin_override = policies.get_override(&city).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to load city moderation policy"})),
+        )
+    })?;
+    let configured = policies.configured(&city).cloned();
+    let mut effective = admin_override.clone().or_else(|| configured.clone()).unwrap_or_default();
+    let surge_active = state.city_surges.is_active(&city).await.unwrap_or(false);
+    if surge_active {
+        effective.strictness = Strictness::Strict;
+    }
+
+    Ok(Json(json!({
+        "city": city,
+        "effective": effective,
+        "surge_active": surge_active,
+        "override": admin_override,
+        "configured": configured,
+    })))
+}
+
+/// Override a city's moderation strictness, wordlists and relevance threshold
+async fn set_city_policy(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Path(city): Path<String>,
+    Json(policy): Json<CityModerationPolicy>,
+) -> Result<Json<CityModerationPolicy>, (StatusCode, Json<serde_json::Value>)> {
+    if policy.relevance_threshold.is_some_and(|t| !(0.0..=1.0).contains(&t)) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "relevance_threshold must be between 0 and 1"})),
+        ));
+    }
+
+    state.config.current().moderation.city_policies().set_override(&city, &policy).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to store city moderation policy"})),
+        )
+    })?;
+
+    let event = AuditEvent::new(AuditEventKind::CityPolicyUpdated, &identity.subject, &city, "Moderation policy overridden")
+        .with_details(json!(policy));
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+
+    Ok(Json(policy))
+}
+
+/// Remove a city's override so the configured defaults apply again
+async fn clear_city_policy(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Path(city): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    state.config.current().moderation.city_policies().clear_override(&city).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to clear city moderation policy"})),
+        )
+    })?;
+
+    let event = AuditEvent::new(AuditEventKind::CityPolicyUpdated, &identity.subject, &city, "Moderation policy override cleared");
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The city registry in use and whether an admin replaced the built-in one
+async fn get_city_registry(
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let admin_override = state.city_registry.get_override().await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to load city registry"})),
+        )
+    })?;
+
+    Ok(Json(json!({
+        "overridden": admin_override.is_some(),
+        "effective": admin_override.unwrap_or_default(),
+    })))
+}
+
+/// Replace the registered cities and their aliases
+async fn set_city_registry(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Json(directory): Json<CityDirectory>,
+) -> Result<Json<CityDirectory>, (StatusCode, Json<serde_json::Value>)> {
+    if let Err(error) = directory.validate() {
+        return Err((StatusCode::BAD_REQUEST, Json(json!({"error": error}))));
+    }
+
+    state.city_registry.set_override(&directory).await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to store city registry"})),
+        )
+    })?;
+
+    let event = AuditEvent::new(AuditEventKind::CityRegistryUpdated, &identity.subject, "cities", "City registry replaced")
+        .with_details(json!(directory));
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+
+    Ok(Json(directory))
+}
+
+/// Go back to the built-in cities and aliases
+async fn clear_city_registry(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    state.city_registry.clear_override().await.map_err(|e| {
+        tracing::error!("{}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to clear city registry"})),
+        )
+    })?;
+
+    let event = AuditEvent::new(AuditEventKind::CityRegistryUpdated, &identity.subject, "cities", "City registry reset to defaults");
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Re-read rate limits, masking and escalation rules without restarting
+async fn reload_config(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+) -> Json<ConfigSummary> {
+    Json(config::reload(&state, &identity.subject, "admin").await)
+}
+
+/// Build a rustls server config that requires a client certificate
+fn build_mtls_config(tls: &AdminTlsConfig) -> Result<ServerConfig> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(
+        File::open(&tls.cert_path).context("Failed to open ADMIN_TLS_CERT")?,
+    ))
+    .collect::<Result<Vec<_>, _>>()
+    .context("Failed to parse ADMIN_TLS_CERT")?;
+
+    let key = rustls_pemfile::private_key(&mut BufReader::new(
+        File::open(&tls.key_path).context("Failed to open ADMIN_TLS_KEY")?,
+    ))
+    .context("Failed to parse ADMIN_TLS_KEY")?
+    .context("ADMIN_TLS_KEY contains no private key")?;
+
+    let mut client_roots = RootCertStore::empty();
+    for ca in rustls_pemfile::certs(&mut BufReader::new(
+        File::open(&tls.client_ca_path).context("Failed to open ADMIN_TLS_CLIENT_CA")?,
+    )) {
+        client_roots
+            .add(ca.context("Failed to parse ADMIN_TLS_CLIENT_CA")?)
+            .context("Invalid client CA certificate")?;
+    }
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(client_roots), provider.clone())
+        .build()
+        .context("Failed to build client certificate verifier")?;
+
+    let mut config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .context("Failed to select TLS protocol versions")?
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(certs, key)
+        .context("Invalid admin TLS certificate/key pair")?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(config)
+}
+
+/// Serve the admin API on its own mutual-TLS listener
+pub async fn serve_mtls(state: AppState, tls: AdminTlsConfig) -> Result<()> {
+    let config = build_mtls_config(&tls)?;
+    let rustls_config = axum_server::tls_rustls::RustlsConfig::from_config(Arc::new(config));
+    let addr: SocketAddr = tls.listen_addr.parse().context("Invalid ADMIN_TLS_ADDR")?;
+
+    let app = admin_routes(&state).with_state(state);
+
+    tracing::info!(addr = %addr, "admin API (mutual TLS) listening");
+    axum_server::bind_rustls(addr, rustls_config)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .context("Admin TLS listener failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret-token", b"secret-token"));
+        assert!(!constant_time_eq(b"secret-token", b"secret-tokex"));
+        assert!(!constant_time_eq(b"secret", b"secret-token"));
+    }
+
+    #[test]
+    fn test_parse_block_target() {
+        let host = parse_block_target("203.0.113.9").unwrap();
+        assert_eq!(block_target_label(&host), "203.0.113.9");
+
+        // Host bits are dropped so the stored network matches on unblock
+        let network = parse_block_target("203.0.113.9/24").unwrap();
+        assert_eq!(block_target_label(&network), "203.0.113.0/24");
+
+        assert!(parse_block_target("not-an-ip").is_none());
+    }
+
+    #[test]
+    fn test_admin_served_publicly_only_without_tls() {
+        let mut config = AdminConfig::new("secret");
+        assert!(!config.serves_on_public_listener());
+
+        config.token = Some("token".to_string());
+        assert!(config.serves_on_public_listener());
+
+        config.tls = Some(AdminTlsConfig {
+            listen_addr: DEFAULT_ADMIN_TLS_ADDR.to_string(),
+            cert_path: "cert.pem".to_string(),
+            key_path: "key.pem".to_string(),
+            client_ca_path: "ca.pem".to_string(),
+        });
+        assert!(!config.serves_on_public_listener());
+    }
+}
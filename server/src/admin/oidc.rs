@@ -0,0 +1,388 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Redirect,
+    Json,
+};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::env;
+
+use crate::security::audit::{AuditEvent, AuditEventKind};
+use crate::state::AppState;
+use crate::keys;
+
+/// How long an OAuth `state` value stays valid between login and callback
+const LOGIN_STATE_TTL_SECONDS: u64 = 600;
+/// Lifetime of an admin session token
+pub const SESSION_TTL_SECONDS: u64 = 3600;
+
+/// Roles that can be granted to an admin identity (ordered by privilege)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AdminRole {
+    Moderator,
+    Admin,
+}
+
+impl AdminRole {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "moderator" => Some(AdminRole::Moderator),
+            "admin" => Some(AdminRole::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// Authenticated caller of the admin API (inserted into request extensions)
+#[derive(Debug, Clone)]
+pub struct AdminIdentity {
+    pub subject: String,
+    pub role: AdminRole,
+}
+
+/// Payload of a signed admin session token
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminSession {
+    pub sub: String,
+    pub role: AdminRole,
+    pub exp: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OidcProvider {
+    Google,
+    GitHub,
+}
+
+impl OidcProvider {
+    fn authorize_url(&self) -> &'static str {
+        match self {
+            OidcProvider::Google => "https://accounts.google.com/o/oauth2/v2/auth",
+            OidcProvider::GitHub => "https://github.com/login/oauth/authorize",
+        }
+    }
+
+    fn token_url(&self) -> &'static str {
+        match self {
+            OidcProvider::Google => "https://oauth2.googleapis.com/token",
+            OidcProvider::GitHub => "https://github.com/login/oauth/access_token",
+        }
+    }
+
+    fn scopes(&self) -> &'static str {
+        match self {
+            OidcProvider::Google => "openid email",
+            OidcProvider::GitHub => "read:user user:email read:org",
+        }
+    }
+}
+
+/// Maps verified emails and groups to admin roles
+/// Format: comma-separated `principal=role` entries where principal is an email,
+/// `group:<name>` (GitHub org / Google Workspace domain) - e.g.
+/// `alice@example.com=admin,group:krib-mods=moderator`
+#[derive(Debug, Clone, Default)]
+pub struct RoleAllowlist {
+    entries: Vec<(String, AdminRole)>,
+}
+
+impl RoleAllowlist {
+    pub fn parse(spec: &str) -> Self {
+        let entries = spec
+            .split(',')
+            .filter_map(|entry| {
+                let (principal, role) = entry.split_once('=')?;
+                let principal = principal.trim().to_ascii_lowercase();
+                if principal.is_empty() {
+                    return None;
+                }
+                Some((principal, AdminRole::parse(role)?))
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// Highest role granted to an email or any of its groups
+    pub fn resolve(&self, email: &str, groups: &[String]) -> Option<AdminRole> {
+        let email = email.to_ascii_lowercase();
+        let groups: Vec<String> = groups
+            .iter()
+            .map(|g| format!("group:{}", g.to_ascii_lowercase()))
+            .collect();
+
+        self.entries
+            .iter()
+            .filter(|(principal, _)| *principal == email || groups.contains(principal))
+            .map(|(_, role)| *role)
+            .max()
+    }
+}
+
+/// OAuth/OIDC login settings for moderators
+#[derive(Clone, Debug)]
+pub struct OidcConfig {
+    pub provider: OidcProvider,
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_url: String,
+    pub allowlist: RoleAllowlist,
+    http_client: reqwest::Client,
+}
+
+impl OidcConfig {
+    /// Load from OIDC_* environment variables; None unless all are set
+    pub fn from_env() -> Option<Self> {
+        let provider = match env::var("OIDC_PROVIDER").ok()?.to_ascii_lowercase().as_str() {
+            "google" => OidcProvider::Google,
+            "github" => OidcProvider::GitHub,
+            other => {
+                tracing::warn!(provider = other, "unknown OIDC_PROVIDER, moderator login disabled");
+                return None;
+            }
+        };
+
+        Some(Self {
+            provider,
+            client_id: env::var("OIDC_CLIENT_ID").ok()?,
+            client_secret: env::var("OIDC_CLIENT_SECRET").ok()?,
+            redirect_url: env::var("OIDC_REDIRECT_URL").ok()?,
+            allowlist: RoleAllowlist::parse(&env::var("ADMIN_ALLOWLIST").unwrap_or_default()),
+            http_client: reqwest::Client::new(),
+        })
+    }
+
+    /// Exchange an authorization code for an access token
+    async fn exchange_code(&self, code: &str) -> Result<String> {
+        #[derive(Deserialize)]
+        struct TokenResponse {
+            access_token: String,
+        }
+
+        let response: TokenResponse = self.http_client
+            .post(self.provider.token_url())
+            .header("Accept", "application/json")
+            .form(&[
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", self.client_secret.as_str()),
+                ("code", code),
+                ("redirect_uri", self.redirect_url.as_str()),
+                ("grant_type", "authorization_code"),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .context("Unexpected token response")?;
+
+        Ok(response.access_token)
+    }
+
+    /// Fetch the verified email and group memberships for an access token
+    async fn fetch_identity(&self, access_token: &str) -> Result<(String, Vec<String>)> {
+        match self.provider {
+            OidcProvider::Google => {
+                #[derive(Deserialize)]
+                struct UserInfo {
+                    email: String,
+                    #[serde(default)]
+                    email_verified: bool,
+                    /// Google Workspace domain, used as the group
+                    hd: Option<String>,
+                }
+
+                let info: UserInfo = self.http_client
+                    .get("https://openidconnect.googleapis.com/v1/userinfo")
+                    .bearer_auth(access_token)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+
+                if !info.email_verified {
+                    return Err(anyhow!("Google account email is not verified"));
+                }
+                Ok((info.email, info.hd.into_iter().collect()))
+            }
+            OidcProvider::GitHub => {
+                #[derive(Deserialize)]
+                struct Email {
+                    email: String,
+                    primary: bool,
+                    verified: bool,
+                }
+                #[derive(Deserialize)]
+                struct Org {
+                    login: String,
+                }
+
+                let emails: Vec<Email> = self.github_get("https://api.github.com/user/emails", access_token).await?;
+                let email = emails
+                    .into_iter()
+                    .find(|e| e.primary && e.verified)
+                    .map(|e| e.email)
+                    .ok_or_else(|| anyhow!("GitHub account has no verified primary email"))?;
+
+                let orgs: Vec<Org> = self.github_get("https://api.github.com/user/orgs", access_token).await?;
+                Ok((email, orgs.into_iter().map(|o| o.login).collect()))
+            }
+        }
+    }
+
+    async fn github_get<T: serde::de::DeserializeOwned>(&self, url: &str, access_token: &str) -> Result<T> {
+        Ok(self.http_client
+            .get(url)
+            .bearer_auth(access_token)
+            .header("User-Agent", "krib-admin")
+            .header("Accept", "application/vnd.github+json")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Start the login flow by redirecting to the identity provider
+pub async fn login(
+    State(state): State<AppState>,
+) -> Result<Redirect, (StatusCode, Json<serde_json::Value>)> {
+    let Some(oidc) = state.admin.oidc.as_ref() else {
+        return Err((StatusCode::NOT_FOUND, Json(json!({"error": "Moderator login is not configured"}))));
+    };
+
+    let login_state = uuid::Uuid::new_v4().simple().to_string();
+    if let Err(e) = state.redis
+        .set_ex(&keys::oidc_login_state(&login_state), "1", LOGIN_STATE_TTL_SECONDS)
+        .await
+    {
+        tracing::error!("Failed to store OIDC login state: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": "Failed to start login"}))));
+    }
+
+    let url = reqwest::Url::parse_with_params(
+        oidc.provider.authorize_url(),
+        &[
+            ("client_id", oidc.client_id.as_str()),
+            ("redirect_uri", oidc.redirect_url.as_str()),
+            ("response_type", "code"),
+            ("scope", oidc.provider.scopes()),
+            ("state", login_state.as_str()),
+        ],
+    )
+    .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": "Invalid provider URL"}))))?;
+
+    Ok(Redirect::to(url.as_str()))
+}
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    code: String,
+    state: String,
+}
+
+/// Complete the login flow and issue a short-lived admin session token
+pub async fn callback(
+    State(state): State<AppState>,
+    Query(query): Query<CallbackQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let Some(oidc) = state.admin.oidc.as_ref() else {
+        return Err((StatusCode::NOT_FOUND, Json(json!({"error": "Moderator login is not configured"}))));
+    };
+
+    // The state value is single-use: it must exist and is deleted on first use
+    let state_key = keys::oidc_login_state(&query.state);
+    let state_valid = state.redis.exists(&state_key).await.unwrap_or(false);
+    let _ = state.redis.del(&state_key).await;
+    if !state_valid {
+        return Err((StatusCode::BAD_REQUEST, Json(json!({"error": "Invalid or expired login state"}))));
+    }
+
+    let identity = async {
+        let access_token = oidc.exchange_code(&query.code).await?;
+        oidc.fetch_identity(&access_token).await
+    }
+    .await;
+
+    let (email, groups) = identity.map_err(|e| {
+        tracing::error!("OIDC login failed: {:#}", e);
+        (StatusCode::BAD_GATEWAY, Json(json!({"error": "Identity provider login failed"})))
+    })?;
+
+    let Some(role) = oidc.allowlist.resolve(&email, &groups) else {
+        record_login(&state, &email, "Login denied - not in allowlist", None).await;
+        return Err((StatusCode::FORBIDDEN, Json(json!({"error": "Account is not authorized for moderation"}))));
+    };
+
+    let session = AdminSession {
+        sub: email.clone(),
+        role,
+        exp: now() + SESSION_TTL_SECONDS,
+    };
+    let token = state.admin.session_signer.sign(&session);
+
+    record_login(&state, &email, "Login succeeded", Some(role)).await;
+
+    Ok(Json(json!({
+        "token": token,
+        "subject": session.sub,
+        "role": session.role,
+        "expires_at": session.exp,
+    })))
+}
+
+async fn record_login(state: &AppState, email: &str, reason: &str, role: Option<AdminRole>) {
+    let event = AuditEvent::new(AuditEventKind::AdminLogin, email, "admin_api", reason)
+        .with_details(json!({ "role": role }));
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+}
+
+/// Verify a session token and return the identity it grants
+pub fn verify_session(state: &AppState, token: &str) -> Option<AdminIdentity> {
+    let session: AdminSession = state.admin.session_signer.verify(token)?;
+    if session.exp < now() {
+        return None;
+    }
+    Some(AdminIdentity {
+        subject: session.sub,
+        role: session.role,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowlist_resolves_highest_role() {
+        let allowlist = RoleAllowlist::parse(
+            "Alice@Example.com=admin, group:krib-mods=moderator, bob@example.com=moderator, bad-entry, x=unknown",
+        );
+
+        assert_eq!(allowlist.resolve("alice@example.com", &[]), Some(AdminRole::Admin));
+        assert_eq!(allowlist.resolve("bob@example.com", &[]), Some(AdminRole::Moderator));
+        assert_eq!(
+            allowlist.resolve("carol@example.com", &["Krib-Mods".to_string()]),
+            Some(AdminRole::Moderator)
+        );
+        assert_eq!(
+            allowlist.resolve("alice@example.com", &["krib-mods".to_string()]),
+            Some(AdminRole::Admin)
+        );
+        assert_eq!(allowlist.resolve("mallory@example.com", &[]), None);
+        assert_eq!(allowlist.resolve("x", &[]), None);
+    }
+}
@@ -0,0 +1,103 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    response::Response,
+};
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use std::collections::HashSet;
+
+use crate::security::audit::AuditEventKind;
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+pub(super) struct StreamQuery {
+    /// Comma-separated audit kinds to forward (all when absent)
+    kinds: Option<String>,
+}
+
+/// Live moderation feed: audit events pushed to the dashboard as they are recorded
+/// `?kinds=message_blocked,report_submitted` narrows the feed
+pub(super) async fn moderation_stream(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Query(query): Query<StreamQuery>,
+) -> Response {
+    let kinds = query.kinds.map(|kinds| parse_kinds(&kinds));
+    ws.on_upgrade(move |socket| forward_audit_events(socket, state, kinds))
+}
+
+/// Unknown kind names are ignored rather than rejected
+fn parse_kinds(kinds: &str) -> HashSet<AuditEventKind> {
+    kinds
+        .split(',')
+        .filter_map(|kind| serde_json::from_value(serde_json::Value::String(kind.trim().to_string())).ok())
+        .collect()
+}
+
+async fn forward_audit_events(socket: WebSocket, state: AppState, kinds: Option<HashSet<AuditEventKind>>) {
+    let mut tail = match state.audit_log.tail().await {
+        Ok(tail) => tail,
+        Err(e) => {
+            tracing::error!("{}", e);
+            return;
+        }
+    };
+
+    let (mut sender, mut receiver) = socket.split();
+    metrics::increment_gauge!("admin_stream_connections", 1.0);
+
+    // Watch for the dashboard going away; other inbound frames are ignored
+    // The tail read is only ever interrupted by a close, so it is never cut off mid-reply
+    let mut closed = tokio::spawn(async move {
+        while let Some(Ok(msg)) = receiver.next().await {
+            if let Message::Close(_) = msg {
+                break;
+            }
+        }
+    });
+
+    'feed: loop {
+        tokio::select! {
+            batch = tail.next_batch() => {
+                let events = match batch {
+                    Ok(events) => events,
+                    Err(e) => {
+                        tracing::error!("{}", e);
+                        break;
+                    }
+                };
+                for event in events {
+                    if kinds.as_ref().is_some_and(|kinds| !kinds.contains(&event.kind)) {
+                        continue;
+                    }
+                    let Ok(json) = serde_json::to_string(&event) else {
+                        continue;
+                    };
+                    if sender.send(Message::Text(json)).await.is_err() {
+                        break 'feed;
+                    }
+                }
+            }
+            _ = &mut closed => break,
+        }
+    }
+
+    closed.abort();
+    metrics::decrement_gauge!("admin_stream_connections", 1.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_kinds_skips_unknown() {
+        let kinds = parse_kinds("message_blocked, report_submitted,nonsense");
+        assert_eq!(kinds.len(), 2);
+        assert!(kinds.contains(&AuditEventKind::MessageBlocked));
+        assert!(kinds.contains(&AuditEventKind::ReportSubmitted));
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::Result;
+
+use crate::models::{Availability, ChatMessage, WsServerEvent};
+use crate::state::{AppState, MESSAGE_TTL};
+use crate::keys;
+
+/// How long before expiry the poster is asked whether a listing is still available
+pub const PROMPT_LEAD_SECONDS: u64 = 21600; // 6 hours
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Prompt posters of listings that expire within PROMPT_LEAD_SECONDS
+/// Each listing is prompted at most once per expiry cycle; silence lets it expire
+/// Returns the number of prompts sent
+pub async fn prompt_expiring_listings(state: &AppState) -> Result<usize> {
+    let now = now();
+
+    // Anything posted recently enough can't be near expiry yet - renewed
+    // listings keep their original score, so TTL is the real test below
+    let candidates = state
+        .message_ids_posted_before((now + PROMPT_LEAD_SECONDS).saturating_sub(MESSAGE_TTL))
+        .await?;
+
+    let mut prompted = 0;
+    for message_id in candidates {
+        let Some(expires_in) = state.message_ttl(&message_id).await? else {
+            continue;
+        };
+        if expires_in > PROMPT_LEAD_SECONDS {
+            continue;
+        }
+
+        let Some(owner) = state.listing_stats.owner(&message_id).await? else {
+            continue;
+        };
+
+        // Claim the prompt for this cycle so other instances don't repeat it
+        if !state.redis.set_nx_ex(&keys::listing_prompted(&message_id), "1", PROMPT_LEAD_SECONDS).await? {
+            continue;
+        }
+
+        let Some(mut message) = state.get_message_by_id(&message_id).await else {
+            continue;
+        };
+        message.availability = Some(Availability::Pending { prompted_at: now });
+        state.update_message(&message).await?;
+
+        let event = serde_json::to_string(&WsServerEvent::AvailabilityPrompt {
+            message_id: message_id.clone(),
+            expires_in,
+        })?;
+        state.broadcast.publish_to_actor(&owner, &event).await?;
+        prompted += 1;
+    }
+
+    Ok(prompted)
+}
+
+/// Confirm a listing is still available and renew it for another MESSAGE_TTL
+/// Returns None if the listing doesn't exist or wasn't posted by `composite_key`
+pub async fn confirm_listing(
+    state: &AppState,
+    message_id: &str,
+    composite_key: &str,
+) -> Result<Option<ChatMessage>> {
+    if !state.listing_stats.is_owner(message_id, composite_key).await? {
+        return Ok(None);
+    }
+    let Some(mut message) = state.get_message_by_id(message_id).await else {
+        return Ok(None);
+    };
+
+    message.availability = Some(Availability::Confirmed { confirmed_at: now() });
+    state.renew_message(&mut message).await?;
+    state.listing_stats.renew(message_id).await?;
+    let _ = state.redis.del(&keys::listing_prompted(message_id)).await;
+
+    metrics::counter!("listings_renewed_total", 1);
+    Ok(Some(message))
+}
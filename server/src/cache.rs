@@ -0,0 +1,200 @@
+use lru::LruCache;
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A stored value and when it was stored
+type Snapshot<T> = Option<(Instant, Arc<T>)>;
+
+/// A single shared value that goes stale after a maximum age
+/// For whole-result caches (like the feed) where entries can't be tracked individually
+pub struct SnapshotCache<T> {
+    max_age: Duration,
+    inner: Arc<Mutex<Snapshot<T>>>,
+}
+
+impl<T> Clone for SnapshotCache<T> {
+    fn clone(&self) -> Self {
+        Self {
+            max_age: self.max_age,
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> SnapshotCache<T> {
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            inner: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// The stored value if it is younger than the maximum age
+    pub fn get(&self) -> Option<Arc<T>> {
+        match &*self.inner.lock().unwrap() {
+            Some((stored_at, value)) if stored_at.elapsed() < self.max_age => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn store(&self, value: T) -> Arc<T> {
+        let value = Arc::new(value);
+        *self.inner.lock().unwrap() = Some((Instant::now(), value.clone()));
+        value
+    }
+
+    /// Drop the stored value so the next read reloads it
+    pub fn invalidate(&self) {
+        *self.inner.lock().unwrap() = None;
+    }
+}
+
+/// A value refreshed at most once per maximum age, however many callers ask
+/// Callers arriving during a refresh wait for it instead of starting their own
+/// (single flight), so polling costs one refresh per period
+pub struct RefreshCache<V> {
+    max_age: Duration,
+    slot: Arc<tokio::sync::Mutex<Option<(Instant, V)>>>,
+}
+
+impl<V> Clone for RefreshCache<V> {
+    fn clone(&self) -> Self {
+        Self {
+            max_age: self.max_age,
+            slot: self.slot.clone(),
+        }
+    }
+}
+
+impl<V: Clone> RefreshCache<V> {
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            slot: Arc::new(tokio::sync::Mutex::new(None)),
+        }
+    }
+
+    /// The cached value, or the result of `refresh` if it is missing or stale
+    pub async fn get_or_refresh<F, Fut>(&self, refresh: F) -> V
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = V>,
+    {
+        let mut slot = self.slot.lock().await;
+        if let Some((refreshed_at, value)) = &*slot {
+            if refreshed_at.elapsed() < self.max_age {
+                return value.clone();
+            }
+        }
+        let value = refresh().await;
+        *slot = Some((Instant::now(), value.clone()));
+        value
+    }
+}
+
+/// `RefreshCache`s by key, keeping at most `capacity` keys and evicting the
+/// least recently used
+pub struct KeyedRefreshCache<K: Hash + Eq, V> {
+    max_age: Duration,
+    entries: Arc<Mutex<LruCache<K, RefreshCache<V>>>>,
+}
+
+impl<K: Hash + Eq, V> Clone for KeyedRefreshCache<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            max_age: self.max_age,
+            entries: self.entries.clone(),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V: Clone> KeyedRefreshCache<K, V> {
+    pub fn new(capacity: usize, max_age: Duration) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            max_age,
+            entries: Arc::new(Mutex::new(LruCache::new(capacity))),
+        }
+    }
+
+    pub async fn get_or_refresh<F, Fut>(&self, key: K, refresh: F) -> V
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = V>,
+    {
+        let max_age = self.max_age;
+        let entry = self.entries
+            .lock()
+            .unwrap()
+            .get_or_insert_mut(key, || RefreshCache::new(max_age))
+            .clone();
+        entry.get_or_refresh(refresh).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_expires_and_invalidates() {
+        let snapshot = SnapshotCache::new(Duration::from_secs(60));
+        assert!(snapshot.get().is_none());
+
+        snapshot.store(vec![1, 2]);
+        assert_eq!(snapshot.get().as_deref(), Some(&vec![1, 2]));
+
+        snapshot.invalidate();
+        assert!(snapshot.get().is_none());
+
+        let stale = SnapshotCache::new(Duration::ZERO);
+        stale.store(1);
+        assert!(stale.get().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_cache_single_flight() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let cache = RefreshCache::new(Duration::from_secs(60));
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        let callers = (0..10).map(|_| {
+            let cache = cache.clone();
+            let refreshes = refreshes.clone();
+            tokio::spawn(async move {
+                cache
+                    .get_or_refresh(|| async {
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        refreshes.fetch_add(1, Ordering::SeqCst) + 1
+                    })
+                    .await
+            })
+        });
+        for caller in callers.collect::<Vec<_>>() {
+            assert_eq!(caller.await.unwrap(), 1);
+        }
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_keyed_refresh_cache_evicts_least_recently_used() {
+        let cache = KeyedRefreshCache::new(2, Duration::from_secs(60));
+        let refreshes = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let get = |key: &'static str| {
+            let refreshes = refreshes.clone();
+            cache.get_or_refresh(key, move || async move {
+                refreshes.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1
+            })
+        };
+
+        assert_eq!(get("a").await, 1);
+        assert_eq!(get("b").await, 2);
+        // Touch "a" so "b" becomes the eviction candidate
+        assert_eq!(get("a").await, 1);
+        assert_eq!(get("c").await, 3);
+        assert_eq!(get("a").await, 1);
+        assert_eq!(get("b").await, 4);
+    }
+}
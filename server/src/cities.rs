@@ -0,0 +1,343 @@
+use crate::cache::SnapshotCache;
+use crate::models::ChatMessage;
+use crate::redis_client::RedisClient;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::keys;
+
+/// Cities tracked in the city stats until an admin replaces the registry
+const DEFAULT_CITIES: [&str; 9] = [
+    "Bengaluru", "Hyderabad", "Pune", "Chennai", "Kolkata",
+    "Thiruvananthapuram", "Delhi", "Noida", "Gurgaon",
+];
+/// Other spellings of the default cities, folded into their registered name
+const DEFAULT_ALIASES: [(&str, &str); 6] = [
+    ("Bangalore", "Bengaluru"),
+    ("Gurugram", "Gurgaon"),
+    ("Trivandrum", "Thiruvananthapuram"),
+    ("Calcutta", "Kolkata"),
+    ("Madras", "Chennai"),
+    ("New Delhi", "Delhi"),
+];
+/// Metric label for every city outside the registry
+pub const OTHER_CITY_LABEL: &str = "other";
+/// How long an instance keeps using its copy of the registry before rereading it
+const REGISTRY_MAX_AGE: Duration = Duration::from_secs(30);
+
+/// Launch state of a city
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CityStatus {
+    Live,
+    /// Posts are accepted but queued until the city launches
+    Waitlist,
+}
+
+/// A post accepted while its city was on the waitlist
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueuedPost {
+    pub composite_key: String,
+    pub message: ChatMessage,
+}
+
+/// Controlled city launches
+/// Waitlisted cities come from WAITLIST_CITIES (comma separated) and from admin
+/// actions; launching a city moves it live and hands back its queued posts
+#[derive(Clone)]
+pub struct CityLaunches {
+    redis: RedisClient,
+    configured_waitlist: HashSet<String>,
+}
+
+impl CityLaunches {
+    pub fn new(redis: RedisClient) -> Self {
+        let configured_waitlist = std::env::var("WAITLIST_CITIES")
+            .unwrap_or_default()
+            .split(',')
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .collect();
+        Self { redis, configured_waitlist }
+    }
+
+    pub async fn status(&self, city: &str) -> Result<CityStatus> {
+        let launched = self.redis
+            .sismember(keys::CITIES_LAUNCHED, city)
+            .await
+            .map_err(|e| anyhow!("Failed to check city status: {}", e))?;
+        if launched {
+            return Ok(CityStatus::Live);
+        }
+
+        let waitlisted = self.configured_waitlist.contains(city)
+            || self.redis
+                .sismember(keys::CITY_WAITLIST, city)
+                .await
+                .map_err(|e| anyhow!("Failed to check city status: {}", e))?;
+
+        Ok(if waitlisted { CityStatus::Waitlist } else { CityStatus::Live })
+    }
+
+    /// All cities currently on the waitlist
+    pub async fn waitlisted(&self) -> Result<Vec<String>> {
+        let mut cities: HashSet<String> = self.redis
+            .smembers(keys::CITY_WAITLIST)
+            .await
+            .map_err(|e| anyhow!("Failed to list waitlisted cities: {}", e))?
+            .into_iter()
+            .collect();
+        cities.extend(self.configured_waitlist.iter().cloned());
+
+        let mut waitlisted = Vec::new();
+        for city in cities {
+            if self.status(&city).await? == CityStatus::Waitlist {
+                waitlisted.push(city);
+            }
+        }
+        waitlisted.sort();
+        Ok(waitlisted)
+    }
+
+    /// Put a city on the waitlist
+    pub async fn add_to_waitlist(&self, city: &str) -> Result<()> {
+        self.redis
+            .srem(keys::CITIES_LAUNCHED, city)
+            .await
+            .map_err(|e| anyhow!("Failed to waitlist city: {}", e))?;
+        self.redis
+            .sadd(keys::CITY_WAITLIST, city)
+            .await
+            .map_err(|e| anyhow!("Failed to waitlist city: {}", e))?;
+        Ok(())
+    }
+
+    /// Queue a post for a waitlisted city
+    pub async fn queue_post(&self, city: &str, post: &QueuedPost) -> Result<()> {
+        let json = serde_json::to_string(post)?;
+        self.redis
+            .lpush(&keys::city_backlog(city), &json)
+            .await
+            .map_err(|e| anyhow!("Failed to queue post: {}", e))
+    }
+
+    /// Register a visitor's interest in a waitlisted city
+    pub async fn register_interest(&self, city: &str, composite_key: &str) -> Result<()> {
+        self.redis
+            .sadd(&keys::city_interest(city), composite_key)
+            .await
+            .map_err(|e| anyhow!("Failed to register interest: {}", e))?;
+        Ok(())
+    }
+
+    /// (interested users, queued posts) for a city
+    pub async fn interest(&self, city: &str) -> Result<(u64, u64)> {
+        let interested = self.redis
+            .scard(&keys::city_interest(city))
+            .await
+            .map_err(|e| anyhow!("Failed to count interest: {}", e))?;
+        let queued = self.redis
+            .llen(&keys::city_backlog(city))
+            .await
+            .map_err(|e| anyhow!("Failed to count queued posts: {}", e))?;
+        Ok((interested.max(0) as u64, queued.max(0) as u64))
+    }
+
+    /// Flip a city live and drain its backlog (oldest post first)
+    pub async fn launch(&self, city: &str) -> Result<Vec<QueuedPost>> {
+        self.redis
+            .sadd(keys::CITIES_LAUNCHED, city)
+            .await
+            .map_err(|e| anyhow!("Failed to launch city: {}", e))?;
+        self.redis
+            .srem(keys::CITY_WAITLIST, city)
+            .await
+            .map_err(|e| anyhow!("Failed to launch city: {}", e))?;
+
+        let key = keys::city_backlog(city);
+        let entries = self.redis
+            .lrange(&key, 0, -1)
+            .await
+            .map_err(|e| anyhow!("Failed to read city backlog: {}", e))?;
+        self.redis
+            .del(&key)
+            .await
+            .map_err(|e| anyhow!("Failed to clear city backlog: {}", e))?;
+        let _ = self.redis.del(&keys::city_interest(city)).await;
+
+        // LPUSH stores newest first
+        Ok(entries
+            .iter()
+            .rev()
+            .filter_map(|json| serde_json::from_str(json).ok())
+            .collect())
+    }
+}
+
+/// Known cities and the other names they go by
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CityDirectory {
+    pub cities: Vec<String>,
+    /// Alternative name -> registered city
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl Default for CityDirectory {
+    fn default() -> Self {
+        Self {
+            cities: DEFAULT_CITIES.iter().map(|c| c.to_string()).collect(),
+            aliases: DEFAULT_ALIASES.iter().map(|(alias, city)| (alias.to_string(), city.to_string())).collect(),
+        }
+    }
+}
+
+impl CityDirectory {
+    /// The registered spelling of a city, resolving aliases and ignoring case
+    /// Unknown cities are kept as given, minus surrounding whitespace
+    pub fn normalize(&self, city: &str) -> String {
+        let city = city.trim();
+        if let Some(known) = self.cities.iter().find(|c| c.eq_ignore_ascii_case(city)) {
+            return known.clone();
+        }
+        if let Some((_, known)) = self.aliases.iter().find(|(alias, _)| alias.eq_ignore_ascii_case(city)) {
+            return known.clone();
+        }
+        city.to_string()
+    }
+
+    /// The registered name to label a city's metrics with, or `OTHER_CITY_LABEL`
+    /// Posters type any city they like, so unknown ones can't each get a series
+    pub fn metric_label(&self, city: &str) -> String {
+        let city = self.normalize(city);
+        if self.cities.contains(&city) {
+            city
+        } else {
+            OTHER_CITY_LABEL.to_string()
+        }
+    }
+
+    /// Whether a city, under any of its names, is in the registry
+    pub fn is_registered(&self, city: &str) -> bool {
+        self.cities.contains(&self.normalize(city))
+    }
+
+    /// Every alias must point at a registered city and no name may be listed twice
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        let mut seen = HashSet::new();
+        for name in self.cities.iter().chain(self.aliases.keys()) {
+            if name.trim().is_empty() || name.trim() != name {
+                return Err(format!("Invalid city name {:?}", name));
+            }
+            if !seen.insert(name.to_lowercase()) {
+                return Err(format!("{} is listed more than once", name));
+            }
+        }
+        if let Some((alias, city)) = self.aliases.iter().find(|(_, city)| !self.cities.contains(city)) {
+            return Err(format!("Alias {} points at unregistered city {}", alias, city));
+        }
+        Ok(())
+    }
+}
+
+/// The cities Krib knows about, so one city isn't split across spellings
+/// ("Bangalore" and "Bengaluru" listings end up in the same feed)
+/// Admins replace the built-in directory via /admin/cities/registry; each
+/// instance rereads it at most every `REGISTRY_MAX_AGE`
+#[derive(Clone)]
+pub struct CityRegistry {
+    redis: RedisClient,
+    snapshot: SnapshotCache<CityDirectory>,
+}
+
+impl CityRegistry {
+    pub fn new(redis: RedisClient) -> Self {
+        Self { redis, snapshot: SnapshotCache::new(REGISTRY_MAX_AGE) }
+    }
+
+    /// The admin-managed directory, or the built-in one if none was set
+    pub async fn directory(&self) -> Arc<CityDirectory> {
+        if let Some(directory) = self.snapshot.get() {
+            return directory;
+        }
+        match self.get_override().await {
+            Ok(directory) => self.snapshot.store(directory.unwrap_or_default()),
+            Err(e) => {
+                // Not cached, so the next request retries Redis
+                tracing::error!("{}", e);
+                Arc::new(CityDirectory::default())
+            }
+        }
+    }
+
+    pub async fn normalize(&self, city: &str) -> String {
+        self.directory().await.normalize(city)
+    }
+
+    /// The directory set by an admin, if any
+    pub async fn get_override(&self) -> Result<Option<CityDirectory>> {
+        let json = self.redis
+            .get(keys::CITY_REGISTRY)
+            .await
+            .map_err(|e| anyhow!("Failed to load city registry: {}", e))?;
+        Ok(json.and_then(|json| serde_json::from_str(&json).ok()))
+    }
+
+    pub async fn set_override(&self, directory: &CityDirectory) -> Result<()> {
+        let json = serde_json::to_string(directory)?;
+        self.redis
+            .set(keys::CITY_REGISTRY, &json)
+            .await
+            .map_err(|e| anyhow!("Failed to store city registry: {}", e))?;
+        self.snapshot.invalidate();
+        Ok(())
+    }
+
+    /// Go back to the built-in directory
+    pub async fn clear_override(&self) -> Result<()> {
+        self.redis
+            .del(keys::CITY_REGISTRY)
+            .await
+            .map_err(|e| anyhow!("Failed to clear city registry: {}", e))?;
+        self.snapshot.invalidate();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aliases_and_case_fold_into_registered_name() {
+        let directory = CityDirectory::default();
+        assert_eq!(directory.normalize("Bangalore"), "Bengaluru");
+        assert_eq!(directory.normalize(" bengaluru "), "Bengaluru");
+        assert_eq!(directory.normalize("GURUGRAM"), "Gurgaon");
+        assert_eq!(directory.normalize("Mysuru"), "Mysuru");
+        assert!(directory.validate().is_ok());
+    }
+
+    #[test]
+    fn test_unregistered_cities_share_one_metric_label() {
+        let directory = CityDirectory::default();
+        assert_eq!(directory.metric_label("bangalore"), "Bengaluru");
+        assert_eq!(directory.metric_label("Mysuru"), OTHER_CITY_LABEL);
+        assert_eq!(directory.metric_label("x".repeat(64).as_str()), OTHER_CITY_LABEL);
+        assert!(directory.is_registered("Gurugram"));
+        assert!(!directory.is_registered("Mysuru"));
+    }
+
+    #[test]
+    fn test_validate_rejects_dangling_and_duplicate_names() {
+        let mut directory = CityDirectory::default();
+        directory.aliases.insert("Bombay".to_string(), "Mumbai".to_string());
+        assert!(directory.validate().is_err());
+
+        let mut directory = CityDirectory::default();
+        directory.aliases.insert("pune".to_string(), "Pune".to_string());
+        assert!(directory.validate().is_err());
+    }
+}
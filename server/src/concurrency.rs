@@ -0,0 +1,142 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::security::middleware::SecurityContext;
+use crate::security::trust_tier::{TierShares, TrustTier};
+
+/// Caps how many requests of one route group run at once
+/// Requests over the ceiling wait up to the queue timeout for a slot and are
+/// then shed with a 503, so slow handlers (OpenAI-backed posting) can't pile up
+/// and starve cheap reads. Lower trust tiers are shed first: once a tier's share
+/// of the ceiling is in use its requests are rejected without queueing.
+/// Exports `concurrency_in_flight`, `concurrency_limit`, `concurrency_queue_seconds`
+/// and `concurrency_rejected_total` labelled by group (rejections also by tier)
+#[derive(Clone)]
+pub struct ConcurrencyLimit {
+    group: &'static str,
+    max_in_flight: usize,
+    queue_timeout: Duration,
+    permits: Arc<Semaphore>,
+    shares: TierShares,
+}
+
+impl ConcurrencyLimit {
+    pub fn new(group: &'static str, max_in_flight: usize, queue_timeout: Duration) -> Self {
+        let max_in_flight = max_in_flight.max(1);
+        metrics::gauge!("concurrency_limit", max_in_flight as f64, "group" => group);
+        metrics::gauge!("concurrency_in_flight", 0.0, "group" => group);
+
+        Self {
+            group,
+            max_in_flight,
+            queue_timeout,
+            permits: Arc::new(Semaphore::new(max_in_flight)),
+            shares: TierShares::default(),
+        }
+    }
+
+    pub fn with_shares(mut self, shares: TierShares) -> Self {
+        self.shares = shares;
+        self
+    }
+
+    /// Read `CONCURRENCY_<GROUP>_MAX` and `CONCURRENCY_<GROUP>_QUEUE_MS`, falling back to defaults
+    pub fn from_env(group: &'static str, default_max: usize, default_queue_ms: u64) -> Self {
+        let var = |suffix: &str| {
+            std::env::var(format!("CONCURRENCY_{}_{}", group.to_ascii_uppercase(), suffix)).ok()
+        };
+        let max_in_flight = var("MAX").and_then(|v| v.parse().ok()).unwrap_or(default_max);
+        let queue_ms = var("QUEUE_MS").and_then(|v| v.parse().ok()).unwrap_or(default_queue_ms);
+        Self::new(group, max_in_flight, Duration::from_millis(queue_ms))
+            .with_shares(TierShares::from_env())
+    }
+
+    fn in_flight(&self) -> usize {
+        self.max_in_flight - self.permits.available_permits()
+    }
+
+    /// Whether a tier still has room under its share of the ceiling
+    fn admits(&self, tier: TrustTier) -> bool {
+        self.in_flight() < self.shares.ceiling(tier, self.max_in_flight)
+    }
+
+    fn report_in_flight(&self) {
+        metrics::gauge!("concurrency_in_flight", self.in_flight() as f64, "group" => self.group);
+    }
+
+    /// A slot for work from `tier`, waiting up to the queue timeout for one
+    /// `None` when the tier's share is used up or no slot freed in time
+    pub async fn acquire(&self, tier: TrustTier) -> Option<ConcurrencyPermit> {
+        // Lower tiers don't queue behind trusted traffic - they're turned away once their share is used
+        if tier < TrustTier::Session && !self.admits(tier) {
+            self.reject(tier);
+            return None;
+        }
+
+        let queued_at = Instant::now();
+        let permit = match tokio::time::timeout(self.queue_timeout, self.permits.clone().acquire_owned()).await {
+            Ok(Ok(permit)) => permit,
+            // Timed out waiting (the semaphore is never closed)
+            _ => {
+                self.reject(tier);
+                return None;
+            }
+        };
+        metrics::histogram!("concurrency_queue_seconds", queued_at.elapsed().as_secs_f64(), "group" => self.group);
+        self.report_in_flight();
+        Some(ConcurrencyPermit { limit: self.clone(), permit: Some(permit) })
+    }
+
+    fn reject(&self, tier: TrustTier) {
+        metrics::counter!("concurrency_rejected_total", 1, "group" => self.group, "tier" => tier.as_str());
+    }
+}
+
+/// A slot under a `ConcurrencyLimit`, given back when dropped
+pub struct ConcurrencyPermit {
+    limit: ConcurrencyLimit,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        drop(self.permit.take());
+        self.limit.report_in_flight();
+    }
+}
+
+/// Route layer enforcing a group's concurrency limit
+pub async fn concurrency_limit_middleware(
+    State(limit): State<ConcurrencyLimit>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let tier = req
+        .extensions()
+        .get::<SecurityContext>()
+        .map_or(TrustTier::Unverified, TrustTier::of);
+
+    let Some(permit) = limit.acquire(tier).await else {
+        return busy();
+    };
+    let response = next.run(req).await;
+    drop(permit);
+    response
+}
+
+fn busy() -> Response {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::RETRY_AFTER, "1")],
+        Json(json!({"error": "Server busy, please retry shortly"})),
+    ).into_response()
+}
@@ -0,0 +1,146 @@
+use arc_swap::ArcSwap;
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::poster_limits::PosterLimits;
+use crate::security::audit::{AuditEvent, AuditEventKind};
+use crate::security::escalation::EscalationPolicy;
+use crate::security::moderation::ModerationService;
+use crate::security::{ContentFilter, GovernorRateLimiter};
+use crate::state::AppState;
+
+const DEFAULT_IP_REQUESTS_PER_MINUTE: u32 = 50;
+
+/// Settings that can change without a restart
+/// A reload swaps in a whole new snapshot, so a request that took one with
+/// `ConfigHandle::current` never sees half of a reload
+pub struct RuntimeConfig {
+    /// Moderation with the current profanity masking and wordlists applied
+    pub moderation: ModerationService,
+    /// Scam links from the same wordlists as moderation
+    pub content_filter: ContentFilter,
+    pub escalation: EscalationPolicy,
+    /// Per-IP request quota from `IP_RATE_LIMIT_PER_MINUTE`
+    pub governor: GovernorRateLimiter,
+    /// Live listings per poster from `MAX_ACTIVE_LISTINGS_PER_POSTER`
+    pub max_active_listings: usize,
+    pub loaded_at: u64,
+}
+
+/// What a reload put in force
+#[derive(Debug, Serialize)]
+pub struct ConfigSummary {
+    pub escalation_rules: usize,
+    pub mild_profanity: &'static str,
+    pub severe_profanity: &'static str,
+    /// Whether the moderation provider is asked before ("inline") or after ("deferred") publishing
+    pub moderation_provider_mode: &'static str,
+    pub wordlist_entries: usize,
+    pub max_active_listings: usize,
+    pub ip_requests_per_minute: u32,
+    pub loaded_at: u64,
+}
+
+impl RuntimeConfig {
+    fn build(base_moderation: &ModerationService, previous: Option<&RuntimeConfig>) -> Self {
+        let ip_requests_per_minute = std::env::var("IP_RATE_LIMIT_PER_MINUTE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_IP_REQUESTS_PER_MINUTE)
+            .max(1);
+        // Per-IP buckets survive a reload unless the quota itself changed
+        let governor = match previous {
+            Some(previous) if previous.governor.per_minute() == ip_requests_per_minute => previous.governor.clone(),
+            _ => GovernorRateLimiter::with_quota(ip_requests_per_minute),
+        };
+
+        let moderation = base_moderation.reload();
+        Self {
+            content_filter: ContentFilter::with_wordlists(moderation.wordlists()),
+            moderation,
+            escalation: EscalationPolicy::from_env(),
+            governor,
+            max_active_listings: PosterLimits::max_active_from_env(),
+            loaded_at: chrono::Utc::now().timestamp() as u64,
+        }
+    }
+
+    pub fn summary(&self) -> ConfigSummary {
+        let masking = self.moderation.masking();
+        ConfigSummary {
+            escalation_rules: self.escalation.rules().len(),
+            mild_profanity: masking.mild.as_str(),
+            severe_profanity: masking.severe.as_str(),
+            moderation_provider_mode: self.moderation.provider_mode().as_str(),
+            wordlist_entries: self.moderation.wordlists().len(),
+            max_active_listings: self.max_active_listings,
+            ip_requests_per_minute: self.governor.per_minute(),
+            loaded_at: self.loaded_at,
+        }
+    }
+}
+
+/// Shared, atomically swappable handle on the runtime config
+#[derive(Clone)]
+pub struct ConfigHandle {
+    current: Arc<ArcSwap<RuntimeConfig>>,
+    /// Moderation before the reloadable settings are applied
+    base_moderation: ModerationService,
+}
+
+impl ConfigHandle {
+    pub fn new(base_moderation: ModerationService) -> Self {
+        let config = RuntimeConfig::build(&base_moderation, None);
+        Self {
+            current: Arc::new(ArcSwap::from_pointee(config)),
+            base_moderation,
+        }
+    }
+
+    /// The snapshot in force; take it once per request
+    pub fn current(&self) -> Arc<RuntimeConfig> {
+        self.current.load_full()
+    }
+
+    /// Re-read `.env` and the environment and swap the result in
+    /// The listener and open WebSockets are untouched
+    fn reload(&self) -> ConfigSummary {
+        dotenvy::dotenv_override().ok();
+        let previous = self.current();
+        let config = RuntimeConfig::build(&self.base_moderation, Some(&previous));
+        let summary = config.summary();
+        self.current.store(Arc::new(config));
+        summary
+    }
+}
+
+/// Reload the runtime config and audit who asked for it
+pub async fn reload(state: &AppState, actor: &str, trigger: &'static str) -> ConfigSummary {
+    let summary = state.config.reload();
+    metrics::counter!("config_reloads_total", 1, "trigger" => trigger);
+    tracing::info!(trigger, summary = ?summary, "configuration reloaded");
+
+    let event = AuditEvent::new(AuditEventKind::ConfigReloaded, actor, "config", &format!("Reloaded via {}", trigger))
+        .with_details(serde_json::json!(summary));
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+    summary
+}
+
+/// Reload on every SIGHUP for the life of the process
+#[cfg(unix)]
+pub async fn reload_on_sighup(state: AppState) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            tracing::error!("Failed to install SIGHUP handler: {}", e);
+            return;
+        }
+    };
+    while hangups.recv().await.is_some() {
+        reload(&state, "system", "sighup").await;
+    }
+}
@@ -0,0 +1,67 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::redis_client::RedisClient;
+use crate::keys;
+
+const DEFAULT_POLICY_VERSION: &str = "1";
+/// Consent is asked for again after a year even if the policy hasn't changed
+const CONSENT_TTL_SECONDS: u64 = 31536000; // 365 days
+
+/// A poster's acceptance of a privacy policy version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsentRecord {
+    pub policy_version: String,
+    pub accepted_at: i64,
+}
+
+/// Tracks which privacy policy version each composite key accepted
+/// Phone numbers are only stored for posters who accepted the current
+/// `PRIVACY_POLICY_VERSION`; bumping it asks everyone again
+#[derive(Clone)]
+pub struct ConsentStore {
+    redis: RedisClient,
+    pub policy_version: String,
+}
+
+impl ConsentStore {
+    pub fn from_env(redis: RedisClient) -> Self {
+        Self {
+            redis,
+            policy_version: std::env::var("PRIVACY_POLICY_VERSION")
+                .unwrap_or_else(|_| DEFAULT_POLICY_VERSION.to_string()),
+        }
+    }
+
+    /// Whether a version sent by a client is the one in force
+    pub fn is_current(&self, version: &str) -> bool {
+        version == self.policy_version
+    }
+
+    /// Persist acceptance of the current policy version
+    pub async fn record(&self, composite_key: &str) -> Result<()> {
+        let record = ConsentRecord {
+            policy_version: self.policy_version.clone(),
+            accepted_at: chrono::Utc::now().timestamp(),
+        };
+        self.redis
+            .set_ex(&keys::consent(composite_key), &serde_json::to_string(&record)?, CONSENT_TTL_SECONDS)
+            .await
+            .map_err(|e| anyhow!("Failed to record consent: {}", e))
+    }
+
+    pub async fn get(&self, composite_key: &str) -> Result<Option<ConsentRecord>> {
+        let json = self.redis
+            .get(&keys::consent(composite_key))
+            .await
+            .map_err(|e| anyhow!("Failed to load consent: {}", e))?;
+        Ok(json.and_then(|json| serde_json::from_str(&json).ok()))
+    }
+
+    /// Whether the poster accepted the policy version currently in force
+    pub async fn has_current(&self, composite_key: &str) -> Result<bool> {
+        Ok(self.get(composite_key)
+            .await?
+            .is_some_and(|record| self.is_current(&record.policy_version)))
+    }
+}
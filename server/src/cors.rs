@@ -0,0 +1,122 @@
+use axum::http::{header, request::Parts, HeaderName, HeaderValue, Method};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::handlers::NEXT_CURSOR_HEADER;
+use crate::security::rate_limiter::{RATE_LIMIT_LIMIT_HEADER, RATE_LIMIT_REMAINING_HEADER};
+
+/// Which browser origins may call the API, by the Host the request was sent to
+/// White-label city sites get their API on their own domain and only their own
+/// site may use it; every other host falls back to `ALLOWED_ORIGIN`
+#[derive(Debug, Clone, Default)]
+pub struct OriginPolicy {
+    default_origins: Vec<String>,
+    /// Lowercased host (no port) to the origins allowed on it
+    tenants: HashMap<String, Vec<String>>,
+}
+
+impl OriginPolicy {
+    /// `ALLOWED_ORIGIN` (comma-separated) plus `TENANT_ORIGINS`, a JSON object
+    /// mapping each tenant API host to its allowed origins
+    pub fn from_env(allowed_origin: &str) -> Self {
+        let tenants = match std::env::var("TENANT_ORIGINS") {
+            Ok(json) => serde_json::from_str::<HashMap<String, Vec<String>>>(&json).unwrap_or_else(|e| {
+                tracing::warn!(error = %e, "invalid TENANT_ORIGINS, ignoring");
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        Self::new(allowed_origin.split(',').map(str::to_string).collect(), tenants)
+    }
+
+    fn new(default_origins: Vec<String>, tenants: HashMap<String, Vec<String>>) -> Self {
+        let normalize = |origins: Vec<String>| {
+            origins
+                .into_iter()
+                .map(|origin| origin.trim().trim_end_matches('/').to_string())
+                .filter(|origin| !origin.is_empty())
+                .collect::<Vec<_>>()
+        };
+        Self {
+            default_origins: normalize(default_origins),
+            tenants: tenants
+                .into_iter()
+                .map(|(host, origins)| (host.to_ascii_lowercase(), normalize(origins)))
+                .collect(),
+        }
+    }
+
+    /// Whether `origin` may make cross-origin requests to `host`
+    pub fn allows(&self, host: Option<&str>, origin: &str) -> bool {
+        let host = host.map(|host| host.split(':').next().unwrap_or(host).to_ascii_lowercase());
+        let origins = host
+            .and_then(|host| self.tenants.get(&host))
+            .unwrap_or(&self.default_origins);
+        origins.iter().any(|allowed| allowed == origin)
+    }
+
+    /// Every origin allowed anywhere, for the startup log
+    pub fn describe(&self) -> String {
+        let mut described = self.default_origins.join(", ");
+        for (host, origins) in &self.tenants {
+            described.push_str(&format!("; {} -> {}", host, origins.join(", ")));
+        }
+        described
+    }
+}
+
+/// CORS layer enforcing the origin policy for the request's Host
+pub fn layer(policy: OriginPolicy) -> CorsLayer {
+    let policy = Arc::new(policy);
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::predicate(move |origin: &HeaderValue, parts: &Parts| {
+            let host = parts.headers.get(header::HOST).and_then(|host| host.to_str().ok());
+            origin.to_str().is_ok_and(|origin| policy.allows(host, origin))
+        }))
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            HeaderName::from_static("x-browser-fingerprint"),
+            HeaderName::from_static("x-session-token"),
+        ])
+        .expose_headers([
+            HeaderName::from_static(NEXT_CURSOR_HEADER),
+            HeaderName::from_static(RATE_LIMIT_LIMIT_HEADER),
+            HeaderName::from_static(RATE_LIMIT_REMAINING_HEADER),
+            header::RETRY_AFTER,
+        ])
+        .max_age(Duration::from_secs(3600))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tenant_hosts_are_bound_to_their_origins() {
+        let policy = OriginPolicy::new(
+            vec!["https://krib.in".to_string()],
+            HashMap::from([(
+                "API.kribpune.in".to_string(),
+                vec!["https://kribpune.in/".to_string(), "https://www.kribpune.in".to_string()],
+            )]),
+        );
+
+        assert!(policy.allows(Some("api.krib.in"), "https://krib.in"));
+        assert!(policy.allows(None, "https://krib.in"));
+        assert!(policy.allows(Some("api.kribpune.in:443"), "https://kribpune.in"));
+        assert!(policy.allows(Some("api.kribpune.in"), "https://www.kribpune.in"));
+        // A tenant's API only serves its own site, and its site only its own API
+        assert!(!policy.allows(Some("api.kribpune.in"), "https://krib.in"));
+        assert!(!policy.allows(Some("api.krib.in"), "https://kribpune.in"));
+    }
+}
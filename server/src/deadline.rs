@@ -0,0 +1,130 @@
+//! Per-request deadlines
+//! `enforce` gives every HTTP request a `Deadline`, both as a request extension
+//! (for code that decides what to skip, like `PostingService` and moderation) and
+//! as a task-local that `RedisClient` checks on every command, so work for a
+//! request that has already timed out fails fast instead of piling up behind it
+//! Spawned tasks don't inherit the task-local: background work is never cut short
+
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Default for `REQUEST_TIMEOUT_SECONDS`
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+tokio::task_local! {
+    static CURRENT: Deadline;
+}
+
+/// The point by which a request's work must be done
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline {
+    at: Instant,
+}
+
+/// Work abandoned because its request ran out of time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadlineExceeded;
+
+impl std::fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Request deadline exceeded")
+    }
+}
+
+impl std::error::Error for DeadlineExceeded {}
+
+impl Deadline {
+    pub fn after(timeout: Duration) -> Self {
+        Self { at: Instant::now() + timeout }
+    }
+
+    /// The deadline of the request this task is serving, if any
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(|deadline| *deadline).ok()
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// Run `future`, giving up when the deadline passes
+    pub async fn run<F: Future>(self, future: F) -> Result<F::Output, DeadlineExceeded> {
+        tokio::time::timeout_at(self.at, future)
+            .await
+            .map_err(|_| DeadlineExceeded)
+    }
+
+    /// Make this the current deadline while `future` runs
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        CURRENT.scope(self, future).await
+    }
+}
+
+/// Overall time allowed per request, from `REQUEST_TIMEOUT_SECONDS`
+pub fn request_timeout_from_env() -> Duration {
+    std::env::var("REQUEST_TIMEOUT_SECONDS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_REQUEST_TIMEOUT)
+}
+
+/// Attach a deadline to the request and answer 408 once it passes
+/// Dropping the handler on timeout cancels whatever it was still awaiting
+pub async fn enforce(State(timeout): State<Duration>, mut req: Request, next: Next) -> Response {
+    let deadline = Deadline::after(timeout);
+    req.extensions_mut().insert(deadline);
+
+    match deadline.run(deadline.scope(next.run(req))).await {
+        Ok(response) => response,
+        Err(DeadlineExceeded) => {
+            metrics::counter!("requests_timed_out_total", 1);
+            (
+                StatusCode::REQUEST_TIMEOUT,
+                Json(json!({"error": "Request timed out"})),
+            ).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_work_past_the_deadline_is_abandoned() {
+        let deadline = Deadline::after(Duration::from_millis(50));
+        assert_eq!(deadline.run(async { 1 }).await, Ok(1));
+
+        let slow = tokio::time::sleep(Duration::from_secs(5));
+        assert_eq!(deadline.run(slow).await, Err(DeadlineExceeded));
+        assert!(deadline.is_expired());
+    }
+
+    #[tokio::test]
+    async fn test_current_is_scoped_to_the_request_task() {
+        assert!(Deadline::current().is_none());
+        let deadline = Deadline::after(Duration::from_secs(5));
+        deadline
+            .scope(async move {
+                assert_eq!(Deadline::current(), Some(deadline));
+                // Spawned work outlives the request and isn't bound by its deadline
+                let spawned = tokio::spawn(async { Deadline::current() }).await.unwrap();
+                assert!(spawned.is_none());
+            })
+            .await;
+    }
+}
@@ -0,0 +1,117 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Dependency names as reported by /health
+pub const REDIS: &str = "redis";
+pub const EVENT_BUS: &str = "event_bus";
+pub const MODERATION_PROVIDER: &str = "moderation_provider";
+pub const TRANSLATION_PROVIDER: &str = "translation_provider";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyStatus {
+    Up,
+    Down,
+    /// Configured but not called since startup
+    Idle,
+    /// Not configured on this instance
+    Disabled,
+}
+
+/// One dependency's entry in the health report
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyReport {
+    pub name: &'static str,
+    pub status: DependencyStatus,
+    /// Whether the instance is unhealthy while this dependency is down
+    pub critical: bool,
+    /// Latency of the last call or check
+    pub latency_ms: Option<u64>,
+    pub checked_at: Option<u64>,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Outcome {
+    ok: bool,
+    latency_ms: u64,
+    at: u64,
+    last_error: Option<(String, u64)>,
+}
+
+/// Last outcome of every call to, or check of, each external dependency
+/// Providers record as they're used, so /health reports on them without
+/// making calls of its own
+#[derive(Clone, Default)]
+pub struct DependencyTracker {
+    outcomes: Arc<Mutex<HashMap<&'static str, Outcome>>>,
+}
+
+impl DependencyTracker {
+    pub fn record(&self, name: &'static str, latency: Duration, result: Result<(), String>) {
+        let at = now_secs();
+        let mut outcomes = self.outcomes.lock().unwrap();
+        let outcome = outcomes.entry(name).or_default();
+        outcome.ok = result.is_ok();
+        outcome.latency_ms = latency.as_millis() as u64;
+        outcome.at = at;
+        if let Err(error) = result {
+            outcome.last_error = Some((error, at));
+        }
+    }
+
+    pub fn report(&self, name: &'static str, configured: bool, critical: bool) -> DependencyReport {
+        let outcome = self.outcomes.lock().unwrap().get(name).cloned();
+        let status = match (&outcome, configured) {
+            (_, false) => DependencyStatus::Disabled,
+            (None, true) => DependencyStatus::Idle,
+            (Some(outcome), true) if outcome.ok => DependencyStatus::Up,
+            (Some(_), true) => DependencyStatus::Down,
+        };
+        let (last_error, last_error_at) = outcome
+            .as_ref()
+            .and_then(|outcome| outcome.last_error.clone())
+            .unzip();
+        DependencyReport {
+            name,
+            status,
+            critical,
+            latency_ms: outcome.as_ref().map(|outcome| outcome.latency_ms),
+            checked_at: outcome.as_ref().map(|outcome| outcome.at),
+            last_error,
+            last_error_at,
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_follows_last_outcome_and_keeps_last_error() {
+        let tracker = DependencyTracker::default();
+        assert_eq!(tracker.report(REDIS, true, true).status, DependencyStatus::Idle);
+        assert_eq!(tracker.report(MODERATION_PROVIDER, false, false).status, DependencyStatus::Disabled);
+
+        tracker.record(REDIS, Duration::from_millis(40), Err("connection refused".to_string()));
+        let report = tracker.report(REDIS, true, true);
+        assert_eq!(report.status, DependencyStatus::Down);
+        assert_eq!(report.latency_ms, Some(40));
+
+        tracker.record(REDIS, Duration::from_millis(2), Ok(()));
+        let report = tracker.report(REDIS, true, true);
+        assert_eq!(report.status, DependencyStatus::Up);
+        assert_eq!(report.last_error.as_deref(), Some("connection refused"));
+    }
+}
@@ -0,0 +1,462 @@
+//! One broadcast-stream reader and one pub/sub subscription per instance, shared
+//! by every WebSocket on it
+//! Listings are read from the broadcast stream and per-actor events arrive over
+//! pub/sub; the relay drops duplicates, strips phone numbers and serializes each
+//! listing once, then hands the frames to sockets over an in-process broadcast channel
+
+use futures::StreamExt;
+use redis::{Client, RedisError};
+use serde_json::json;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::keys;
+use crate::models::{ChatMessage, ListingEvent};
+use crate::redis_client::RedisClient;
+use crate::scaling::{self, PubSubHeartbeat};
+
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(500);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+/// Frames buffered for sockets; one further behind than this is a slow client
+const FANOUT_CAPACITY: usize = 1024;
+/// Broadcasts remembered for duplicate suppression
+const RECENT_IDS_CAPACITY: usize = 256;
+/// How long one blocking read of the broadcast stream waits for new entries
+const STREAM_BLOCK: Duration = Duration::from_secs(5);
+/// Entries fetched per read of the broadcast stream
+const STREAM_READ_BATCH: usize = 100;
+/// Most listings replayed to a resuming client; further behind, it refetches
+const MAX_REPLAY_ENTRIES: usize = 2000;
+
+/// Field of a broadcast-stream entry holding the broadcast payload
+pub const STREAM_PAYLOAD_FIELD: &str = "payload";
+
+/// ID of a broadcast-stream entry (`<ms>-<seq>`), ordered the way Redis orders entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StreamPosition {
+    ms: u64,
+    seq: u64,
+}
+
+impl StreamPosition {
+    pub fn parse(id: &str) -> Option<Self> {
+        let (ms, seq) = id.split_once('-')?;
+        Some(Self {
+            ms: ms.parse().ok()?,
+            seq: seq.parse().ok()?,
+        })
+    }
+
+    /// The smallest possible ID after this one, for reading strictly past it
+    fn next(self) -> Self {
+        match self.seq.checked_add(1) {
+            Some(seq) => Self { ms: self.ms, seq },
+            None => Self { ms: self.ms + 1, seq: 0 },
+        }
+    }
+}
+
+impl fmt::Display for StreamPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+/// One broadcast relayed to this instance's sockets
+#[derive(Debug)]
+pub enum FanoutFrame {
+    /// A listing, already without its phone number, tagged with its `stream_id`
+    Listing { position: StreamPosition, city: Option<String>, json: String },
+    /// A complete server event frame for every connection of one composite key
+    Actor { composite_key: String, payload: String },
+}
+
+/// What a client resuming from an earlier stream entry missed
+#[derive(Debug)]
+pub enum Replay {
+    /// Every listing since, oldest first; `position` is the last entry read
+    Missed { frames: Vec<FanoutFrame>, position: StreamPosition },
+    /// Entries were trimmed away or there were too many to replay
+    Gap,
+}
+
+/// ID of the newest entry of a stream, so a reader starts after it
+/// (`0-0` while the stream is empty)
+pub async fn stream_tail(redis: &RedisClient, stream: &str) -> Result<String, RedisError> {
+    let newest = redis.xrevrange(stream, 1).await?;
+    Ok(newest.into_iter().next().map(|(id, _)| id).unwrap_or_else(|| "0-0".to_string()))
+}
+
+/// Listings in the broadcast stream after `last_id`, for a reconnecting client
+pub async fn replay(redis: &RedisClient, last_id: StreamPosition) -> Result<Replay, RedisError> {
+    // The client's last entry trimmed away means others after it may be gone too
+    let oldest = redis.xrange(keys::BROADCAST_STREAM, "-", 1).await?;
+    let trimmed = oldest
+        .first()
+        .and_then(|(id, _)| StreamPosition::parse(id))
+        .is_some_and(|oldest| oldest > last_id);
+    if trimmed {
+        return Ok(Replay::Gap);
+    }
+
+    let mut recent = RecentIds::new();
+    let mut frames = Vec::new();
+    let mut position = last_id;
+    loop {
+        let entries = redis
+            .xrange(keys::BROADCAST_STREAM, &position.next().to_string(), STREAM_READ_BATCH)
+            .await?;
+        let caught_up = entries.len() < STREAM_READ_BATCH;
+        for (id, fields) in entries {
+            let Some(entry_position) = StreamPosition::parse(&id) else {
+                continue;
+            };
+            position = entry_position;
+            if let Some(frame) = Fanout::listing_frame(position, &fields, &mut recent) {
+                frames.push(frame);
+            }
+        }
+
+        if caught_up {
+            return Ok(Replay::Missed { frames, position });
+        }
+        // Only listings count: anything else in the stream isn't replayed
+        if frames.len() >= MAX_REPLAY_ENTRIES {
+            return Ok(Replay::Gap);
+        }
+    }
+}
+
+/// Sender side of the in-process fan-out
+#[derive(Clone)]
+pub struct Fanout {
+    sender: broadcast::Sender<Arc<FanoutFrame>>,
+}
+
+impl Fanout {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(FANOUT_CAPACITY);
+        Self { sender }
+    }
+
+    /// Frames relayed from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<FanoutFrame>> {
+        self.sender.subscribe()
+    }
+
+    /// Relay the broadcast stream and actor events forever, reconnecting with backoff
+    pub async fn run(self, redis: RedisClient) {
+        let client = redis.get_client();
+        tokio::join!(self.run_listings(redis), self.run_actor_events(client));
+    }
+
+    /// Read the broadcast stream forever
+    async fn run_listings(&self, redis: RedisClient) {
+        let mut backoff = Backoff::new();
+        // Kept across reconnects so an outbox relay or retry can't replay a message
+        let mut recent = RecentIds::new();
+        // Also kept, so a reconnect picks up where the last read left off
+        let mut last_id = None;
+
+        loop {
+            if let Err(e) = self.read_listings(&redis, &mut last_id, &mut backoff, &mut recent).await {
+                tracing::error!("Failed to read the broadcast stream: {}", e);
+            }
+            metrics::counter!("websocket_stream_reconnects_total", 1);
+            tokio::time::sleep(backoff.next_delay()).await;
+        }
+    }
+
+    /// Read and relay listings until a read fails
+    async fn read_listings(
+        &self,
+        redis: &RedisClient,
+        last_id: &mut Option<String>,
+        backoff: &mut Backoff,
+        recent: &mut RecentIds,
+    ) -> Result<(), RedisError> {
+        let mut reader = redis.stream_reader().await?;
+        if last_id.is_none() {
+            *last_id = Some(stream_tail(redis, keys::BROADCAST_STREAM).await?);
+        }
+        backoff.reset();
+
+        loop {
+            let after = last_id.clone().unwrap_or_default();
+            let entries = reader.read(keys::BROADCAST_STREAM, &after, STREAM_BLOCK, STREAM_READ_BATCH).await?;
+            for (id, fields) in entries {
+                let position = StreamPosition::parse(&id);
+                *last_id = Some(id);
+                let Some(position) = position else {
+                    continue;
+                };
+                if let Some(frame) = Self::listing_frame(position, &fields, recent) {
+                    // No receivers just means no sockets are open on this instance
+                    let _ = self.sender.send(Arc::new(frame));
+                }
+            }
+        }
+    }
+
+    /// Relay per-actor pub/sub events forever, resubscribing with backoff whenever it drops
+    async fn run_actor_events(&self, client: Client) {
+        let mut backoff = Backoff::new();
+
+        loop {
+            match self.relay_actor_events(&client, &mut backoff).await {
+                Ok(()) => tracing::error!("Redis pub/sub subscription lost for WebSockets, reconnecting"),
+                Err(e) => tracing::error!("Failed to subscribe to Redis channels: {}", e),
+            }
+            metrics::counter!("websocket_pubsub_reconnects_total", 1);
+            tokio::time::sleep(backoff.next_delay()).await;
+        }
+    }
+
+    /// Subscribe and relay until the subscription ends
+    async fn relay_actor_events(&self, client: &Client, backoff: &mut Backoff) -> redis::RedisResult<()> {
+        let mut pubsub = client.get_async_connection().await?.into_pubsub();
+        pubsub.psubscribe(format!("{}*", keys::ACTOR_CHANNEL_PREFIX)).await?;
+        backoff.reset();
+
+        let mut stream = pubsub.on_message();
+        while let Some(msg) = stream.next().await {
+            let payload: String = match msg.get_payload() {
+                Ok(p) => p,
+                Err(e) => {
+                    tracing::error!("Failed to get payload from Redis message: {}", e);
+                    continue;
+                }
+            };
+            if let Some(frame) = Self::actor_frame(msg.get_channel_name(), payload) {
+                let _ = self.sender.send(Arc::new(frame));
+            }
+        }
+        Ok(())
+    }
+
+    /// Actor-channel payloads are already complete server event frames
+    fn actor_frame(channel: &str, payload: String) -> Option<FanoutFrame> {
+        if !scaling::is_actor_channel(channel) {
+            return None;
+        }
+        let composite_key = channel[keys::ACTOR_CHANNEL_PREFIX.len()..].to_string();
+        Some(FanoutFrame::Actor { composite_key, payload })
+    }
+
+    /// What a broadcast-stream entry becomes for the sockets, if anything
+    fn listing_frame(
+        position: StreamPosition,
+        fields: &HashMap<String, String>,
+        recent: &mut RecentIds,
+    ) -> Option<FanoutFrame> {
+        let payload = fields.get(STREAM_PAYLOAD_FIELD)?;
+
+        // Heartbeats have their own stream now, but entries from before the split may remain
+        if PubSubHeartbeat::is_heartbeat(payload) {
+            return None;
+        }
+
+        // New listings are broadcast bare, changes to them as events naming the listing
+        // Phone numbers are stripped for privacy - only available via API
+        let (id, city, value) = match serde_json::from_str::<ChatMessage>(payload) {
+            Ok(message) => {
+                let message = ChatMessage { phone: None, ..message };
+                (message.id.clone(), message.location.clone(), serde_json::to_value(&message))
+            }
+            Err(e) => match serde_json::from_str::<ListingEvent>(payload) {
+                Ok(event) => {
+                    let event = event.without_phone();
+                    let id = event.message_id().to_string();
+                    let city = event.location().map(str::to_string);
+                    (id, city, serde_json::to_value(&event))
+                }
+                Err(_) => {
+                    tracing::error!("Failed to parse message from Redis: {}", e);
+                    return None;
+                }
+            },
+        };
+        if !recent.insert(&delivery_key(&id, payload)) {
+            metrics::counter!("websocket_duplicates_suppressed_total", 1);
+            return None;
+        }
+
+        match value {
+            Ok(mut value) => {
+                // Clients reconnect with the last one they saw to catch up on what they missed
+                value["stream_id"] = json!(position.to_string());
+                Some(FanoutFrame::Listing { position, city, json: value.to_string() })
+            }
+            Err(e) => {
+                tracing::error!("Failed to serialize message: {}", e);
+                None
+            }
+        }
+    }
+}
+
+impl Default for Fanout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Exponential backoff for stream and pub/sub reconnection attempts
+struct Backoff {
+    attempt: u32,
+}
+
+impl Backoff {
+    fn new() -> Self {
+        Self { attempt: 0 }
+    }
+
+    /// Delay before the next attempt: base * 2^attempt, capped at the max
+    fn next_delay(&mut self) -> Duration {
+        let delay = RECONNECT_BASE_DELAY
+            .saturating_mul(1u32 << self.attempt.min(16))
+            .min(RECONNECT_MAX_DELAY);
+        self.attempt += 1;
+        delay
+    }
+
+    fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+/// Deliveries most recently relayed
+/// A broadcast can land in the stream more than once (outbox relay, dead-letter
+/// retries); only the first copy is forwarded
+struct RecentIds {
+    order: VecDeque<String>,
+    seen: HashSet<String>,
+}
+
+impl RecentIds {
+    fn new() -> Self {
+        Self {
+            order: VecDeque::with_capacity(RECENT_IDS_CAPACITY),
+            seen: HashSet::with_capacity(RECENT_IDS_CAPACITY),
+        }
+    }
+
+    /// Remember a delivery; returns false if it was already sent recently
+    fn insert(&mut self, id: &str) -> bool {
+        if self.seen.contains(id) {
+            return false;
+        }
+        if self.order.len() == RECENT_IDS_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.order.push_back(id.to_string());
+        self.seen.insert(id.to_string());
+        true
+    }
+}
+
+/// Identity of one broadcast: edits and renewals re-broadcast the same message id
+/// with a new payload, and those must still reach the client
+fn delivery_key(id: &str, payload: &str) -> String {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    payload.hash(&mut hasher);
+    format!("{}:{:x}", id, hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        let mut backoff = Backoff::new();
+        assert_eq!(backoff.next_delay(), Duration::from_millis(500));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(2));
+
+        for _ in 0..20 {
+            assert!(backoff.next_delay() <= RECONNECT_MAX_DELAY);
+        }
+        assert_eq!(backoff.next_delay(), RECONNECT_MAX_DELAY);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_recent_ids_suppress_duplicates() {
+        let mut recent = RecentIds::new();
+        assert!(recent.insert("m1"));
+        assert!(!recent.insert("m1"));
+
+        // The oldest id is forgotten once the ring is full
+        for i in 0..RECENT_IDS_CAPACITY {
+            assert!(recent.insert(&format!("other-{}", i)));
+        }
+        assert!(recent.insert("m1"));
+    }
+
+    #[test]
+    fn test_delivery_key_distinguishes_edits() {
+        let original = r#"{"id":"m1","message":"2BHK in Baner"}"#;
+        let edited = r#"{"id":"m1","message":"2BHK in Baner, furnished"}"#;
+        assert_eq!(delivery_key("m1", original), delivery_key("m1", original));
+        assert_ne!(delivery_key("m1", original), delivery_key("m1", edited));
+    }
+
+    #[test]
+    fn test_stream_positions_order_like_redis() {
+        let position = StreamPosition::parse("1700000000000-5").unwrap();
+        assert_eq!(position.to_string(), "1700000000000-5");
+        assert!(StreamPosition::parse("1700000000001-0").unwrap() > position);
+        assert_eq!(position.next(), StreamPosition::parse("1700000000000-6").unwrap());
+        assert_eq!(
+            StreamPosition::parse(&format!("7-{}", u64::MAX)).unwrap().next(),
+            StreamPosition::parse("8-0").unwrap()
+        );
+        assert!(StreamPosition::parse("$").is_none());
+        assert!(StreamPosition::parse("12-x").is_none());
+    }
+
+    #[test]
+    fn test_frames_strip_phones_and_route_actor_events() {
+        let mut recent = RecentIds::new();
+        let listing = ChatMessage {
+            location: Some("Pune".to_string()),
+            ..ChatMessage::new(
+                "fp".to_string(),
+                "2BHK".to_string(),
+                crate::models::MessageType::Offered,
+                Some("9876543210".to_string()),
+                None,
+            )
+        };
+        let entry = |payload: String| HashMap::from([(STREAM_PAYLOAD_FIELD.to_string(), payload)]);
+        let position = StreamPosition::parse("1-0").unwrap();
+        let payload = serde_json::to_string(&listing).unwrap();
+
+        let frame = Fanout::listing_frame(position, &entry(payload.clone()), &mut recent);
+        assert!(matches!(&frame, Some(FanoutFrame::Listing { city: Some(city), json, .. })
+            if city == "Pune" && !json.contains("9876543210") && json.contains(r#""stream_id":"1-0""#)));
+        assert!(Fanout::listing_frame(position, &entry(payload), &mut recent).is_none());
+
+        let edit = serde_json::to_string(&ListingEvent::MessageUpdated { message: listing }).unwrap();
+        let frame = Fanout::listing_frame(position.next(), &entry(edit), &mut recent);
+        assert!(matches!(&frame, Some(FanoutFrame::Listing { json, .. })
+            if json.contains(r#""type":"message_updated""#) && !json.contains("9876543210")));
+
+        let retraction = ListingEvent::MessageDeleted { message_id: "m1".to_string(), location: Some("Pune".to_string()) };
+        let frame = Fanout::listing_frame(position.next().next(), &entry(serde_json::to_string(&retraction).unwrap()), &mut recent);
+        assert!(matches!(&frame, Some(FanoutFrame::Listing { city: Some(city), json, .. })
+            if city == "Pune" && json.contains(r#""type":"message_deleted""#)));
+
+        let event = Fanout::actor_frame(&keys::actor_channel("fp:1.2.3.4"), "{}".to_string());
+        assert!(matches!(event, Some(FanoutFrame::Actor { composite_key, .. }) if composite_key == "fp:1.2.3.4"));
+    }
+}
@@ -1,337 +1,510 @@
 use axum::{
     extract::{ws::WebSocketUpgrade, State, Path, Query},
-    http::StatusCode,
-    response::Response,
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
     Json, Extension,
 };
 use serde_json::json;
+use tracing::Instrument;
 use crate::{
-    models::{ChatMessage, PostMessageRequest, RateLimitError, ContentFilterError, ReportMessageRequest, ReportResponse},
+    models::{ApiError, ChatMessage, EditMessageRequest, LiteListing, MessageResponse, WsServerEvent, PostMessageRequest, RevealQuota, ReportMessageRequest, ReportResponse, RefreshSessionRequest},
     state::AppState,
     websocket::handle_websocket,
     security::middleware::SecurityContext,
     security::rate_limiter::RateLimitType,
+    security::ip_reputation::RiskLevel,
+    security::audit::{AuditEvent, AuditEventKind},
+    security::session::SessionTokens,
+    security::reveal_graph::RevealEdge,
+    security::review_queue::{ReviewItem, ReviewReason},
+    security::report_guard::ReportSource,
+    security::fingerprint::{self, UNKNOWN_FINGERPRINT},
+    security::enforcement::{EnforcementRecord, ReasonCode},
+    security::geoip::GeoLocation,
+    listing_stats::ListingStats,
+    availability,
+    activity,
+    load_shedding::SheddableWork,
+    ws_compression::DEFLATE_PROTOCOL,
+    moderation_dataset::ReportAction,
+    posting::PostingService,
+    recorder::Decisions,
+    cities::CityStatus,
+    deadline::Deadline,
+    keys,
+    translation::is_valid_language_code,
+    pagination::{take_page, CursorPosition, CursorSigner, Page, PageDirection, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE},
 };
 
+/// How long IPs caught by a bot trap stay blocked (24 hours)
+const BOT_TRAP_BLOCK_SECONDS: u64 = 86400;
+
+/// How long reports against a fingerprint (and who filed them) are remembered
+const REPORT_TTL_SECONDS: i64 = 604800; // 7 days
+
+/// Longest accepted search query, in bytes
+const MAX_SEARCH_QUERY_LENGTH: usize = 100;
+
+/// Response header carrying the opaque cursor for the next page of messages
+pub const NEXT_CURSOR_HEADER: &str = "x-next-cursor";
+
+/// Browsers can't set headers on a WebSocket upgrade, so the session token may
+/// also come as `?session=<access token>`; only an authenticated connection is
+/// subscribed to its poster's private activity channel
+/// `?last_id=<stream_id>` resumes after the last listing a reconnecting client saw
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
+    Extension(mut security_ctx): Extension<SecurityContext>,
+    Query(params): Query<HashMap<String, String>>,
 ) -> Response {
-    ws.on_upgrade(|socket| handle_websocket(socket, state))
-}
+    // Right after startup, upgrades are metered so a deploy's reconnect wave can't swamp us
+    if !state.ws_handover.admit() {
+        metrics::counter!("websocket_upgrades_throttled_total", 1);
+        return ApiError::Unavailable {
+            error: "Server is starting up, retry shortly",
+            retry_after: 1,
+        }.into_response();
+    }
 
-pub async fn post_message(
-    State(state): State<AppState>,
-    Extension(security_ctx): Extension<SecurityContext>,
-    Json(request): Json<PostMessageRequest>,
-) -> Result<Json<ChatMessage>, (StatusCode, Json<serde_json::Value>)> {
-    // Check honeypot field
-    let honeypot_result = state.content_filter.check_honeypot(request.website.as_deref());
-    if !honeypot_result.is_allowed {
-        // Hard block the composite key permanently
-        if let Err(e) = state.shadowban_manager.shadowban(
-            &security_ctx.composite_key,
-            Some("Honeypot triggered - bot detected"),
-            None, // Permanent
-        ).await {
-            eprintln!("Failed to shadowban honeypot violator: {}", e);
+    // Browsers can't set headers on an upgrade, so the fingerprint may come as `?fingerprint=`;
+    // it only matters for posting over the socket, where it must match the session's key
+    if security_ctx.fingerprint == UNKNOWN_FINGERPRINT {
+        if let Some(fingerprint) = params.get("fingerprint").filter(|fp| fingerprint::validate(fp).is_ok()) {
+            security_ctx.composite_key = state.key_generator.generate(&security_ctx.ip_address, fingerprint);
+            security_ctx.fingerprint = fingerprint.clone();
         }
-
-        return Err((
-            StatusCode::FORBIDDEN,
-            Json(json!(ContentFilterError::new(
-                honeypot_result.reason.unwrap_or_else(|| "Bot detected".to_string())
-            )))
-        ));
     }
 
-    // Check if user is shadowbanned
-    let is_shadowbanned = state.shadowban_manager
-        .is_shadowbanned(&security_ctx.composite_key)
-        .await
-        .unwrap_or(false);
-
-    // Also check if fingerprint is shadowbanned due to reports
-    let reported_key = format!("reported:{}", security_ctx.fingerprint);
-    let is_reported_shadowbanned = state.shadowban_manager
-        .is_shadowbanned(&reported_key)
-        .await
-        .unwrap_or(false);
-
-    let is_shadowbanned_total = is_shadowbanned || is_reported_shadowbanned;
+    let session = match (security_ctx.session.take(), params.get("session")) {
+        (Some(claims), _) => Some(claims),
+        (None, Some(token)) => state.sessions.authenticate(token).await.unwrap_or_else(|e| {
+            tracing::error!("Error checking session: {}", e);
+            None
+        }),
+        (None, None) => None,
+    };
+    let actor = session.as_ref().map(|claims| claims.key.clone());
+    security_ctx.session = session;
 
-    // Validate message length
-    if request.message.len() > 280 {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": "Message too long (max 280 characters)"}))
-        ));
-    }
+    // Clients that offer the deflate subprotocol get compressed frames
+    let ws = if state.ws_compression.enabled {
+        ws.protocols([DEFLATE_PROTOCOL])
+    } else {
+        ws
+    };
 
-    if request.message.trim().is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": "Message cannot be empty"}))
-        ));
+    // The upgraded socket outlives the request, so it gets a span of its own
+    let span = tracing::info_span!("websocket", composite_key = tracing::field::Empty);
+    if let Some(actor) = &actor {
+        span.record("composite_key", actor.as_str());
     }
+    let resume_from = params.get("last_id").cloned();
+    ws.on_upgrade(move |socket| handle_websocket(socket, state, security_ctx, resume_from).instrument(span))
+}
 
-    // Check content filters
-    let filter_result = state.content_filter.check_message(&request.message);
-    if !filter_result.is_allowed {
-        // Increment violation count
-        if let Ok(violations) = state.shadowban_manager
-            .increment_violations(&security_ctx.composite_key)
-            .await
-        {
-            // Auto-shadowban after 3 violations (24 hour ban)
-            let _ = state.shadowban_manager
-                .auto_shadowban_on_violations(&security_ctx.composite_key, 3, 86400)
-                .await;
-            
-            eprintln!("Content violation by {}: {} violations", security_ctx.composite_key, violations);
-        }
+pub async fn post_message(
+    State(state): State<AppState>,
+    Extension(security_ctx): Extension<SecurityContext>,
+    decisions: Option<Extension<Decisions>>,
+    deadline: Option<Extension<Deadline>>,
+    Json(request): Json<PostMessageRequest>,
+) -> Result<(HeaderMap, Json<ChatMessage>), ApiError> {
+    let service = PostingService::new(&state)
+        .recording(decisions.map(|Extension(decisions)| decisions))
+        .with_deadline(deadline.map(|Extension(deadline)| deadline));
+    let outcome = service.submit(request, &security_ctx).await?;
+    let headers = service.rate_limit().map(|result| result.headers()).unwrap_or_default();
+    Ok((headers, Json(outcome.into_message())))
+}
 
-        return Err((
-            StatusCode::FORBIDDEN,
-            Json(json!(ContentFilterError::new(
-                filter_result.reason.unwrap_or_else(|| "Content policy violation".to_string())
-            )))
-        ));
-    }
+/// Edit the caller's own listing shortly after posting it (see `PostingService::edit`)
+pub async fn edit_message(
+    Path(message_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(security_ctx): Extension<SecurityContext>,
+    decisions: Option<Extension<Decisions>>,
+    deadline: Option<Extension<Deadline>>,
+    Json(request): Json<EditMessageRequest>,
+) -> Result<Json<ChatMessage>, ApiError> {
+    let message = PostingService::new(&state)
+        .recording(decisions.map(|Extension(decisions)| decisions))
+        .with_deadline(deadline.map(|Extension(deadline)| deadline))
+        .edit(&message_id, request, &security_ctx)
+        .await?;
+    // Shown back to its poster, who may see their own number
+    Ok(Json(message))
+}
 
-    // Run comprehensive moderation checks (profanity, relevance, spam, OpenAI)
-    let moderation_result = state.moderation_service.moderate_message(&request.message).await;
-    if !moderation_result.is_allowed {
-        // Increment violation count for moderation violations
-        if let Ok(violations) = state.shadowban_manager
-            .increment_violations(&security_ctx.composite_key)
-            .await
-        {
-            // Auto-shadowban after 3 violations (24 hour ban)
-            let _ = state.shadowban_manager
-                .auto_shadowban_on_violations(&security_ctx.composite_key, 3, 86400)
-                .await;
-            
-            eprintln!("Moderation violation by {}: {} - {} violations", 
-                     security_ctx.composite_key, 
-                     moderation_result.reason.as_ref().unwrap_or(&"Unknown violation".to_string()),
-                     violations);
+/// Append what a report did to a post to the export dataset, off the response path
+fn spawn_record_report(state: &AppState, message_id: &str, action: ReportAction) {
+    let dataset = state.moderation_dataset.clone();
+    let message_id = message_id.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = dataset.record_report(&message_id, action).await {
+            tracing::error!("{}", e);
         }
+    });
+}
 
-        return Err((
-            StatusCode::FORBIDDEN,
-            Json(json!(ContentFilterError::new(
-                moderation_result.reason.unwrap_or_else(|| "Content policy violation".to_string())
-            )))
-        ));
-    }
+use std::collections::HashMap;
 
-    // Validate phone number format if provided
-    if !state.content_filter.validate_phone(request.phone.as_deref()) {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": "Invalid phone number format"}))
-        ));
-    }
+/// The live feed without contact numbers
+/// `?limit=` with `?before=`/`?after=` cursors pages through it, returning a `Page`
+/// whose `next_cursor` continues the same way; `?fields=lite` returns `LiteListing`s
+pub async fn get_messages(
+    State(state): State<AppState>,
+    Extension(security_ctx): Extension<SecurityContext>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, ApiError> {
+    // "Bangalore" and "Bengaluru" are one feed (see `CityRegistry`)
+    let cities = state.city_registry.directory().await;
+    let location_filter = params.get("location").map(|city| cities.normalize(city));
 
-    // Check suspicious patterns
-    if state.content_filter.is_suspicious_pattern(&request.message) {
-        // Increment violations for suspicious patterns
-        let _ = state.shadowban_manager
-            .increment_violations(&security_ctx.composite_key)
-            .await;
-    }
+    // `?fields=lite` trims each listing to what a constrained client can render
+    let lite = match params.get("fields").map(String::as_str) {
+        None | Some("full") => false,
+        Some("lite") => true,
+        Some(_) => return Err(ApiError::BadRequest(
+            "Invalid fields, expected \"lite\" or \"full\"".to_string()
+        )),
+    };
 
-    // Check IP reputation risk level and apply cooldowns based on it
-    let ip_risk_level = state.ip_reputation
-        .get_ip_risk_level(&security_ctx.ip_address)
-        .await
-        .unwrap_or(crate::security::ip_reputation::RiskLevel::Level0);
+    // Pagination is opt-in: without `limit`/`before`/`after` the full feed is returned
+    // `cursor` is the original name of `before`, still accepted
+    let filter_hash = CursorSigner::filter_hash(&[location_filter.as_deref()]);
+    let verify_cursor = |cursor: &String| {
+        state.cursor_signer
+            .verify(cursor, &filter_hash)
+            .map_err(|e| ApiError::BadRequest(e.to_string()))
+    };
+    let before = params.get("before").or(params.get("cursor")).map(verify_cursor).transpose()?;
+    let after = params.get("after").map(verify_cursor).transpose()?;
+    let (cursor_position, direction) = match (before, after) {
+        (Some(_), Some(_)) => return Err(ApiError::BadRequest("Use either before or after, not both".to_string())),
+        (Some(position), None) => (Some(position), PageDirection::Older),
+        (None, Some(position)) => (Some(position), PageDirection::Newer),
+        (None, None) => (None, PageDirection::Older),
+    };
+    let page_size = match params.get("limit") {
+        Some(limit) => Some(
+            limit.parse::<usize>()
+                .map(|l| l.clamp(1, MAX_PAGE_SIZE))
+                .map_err(|_| ApiError::BadRequest("Invalid limit".to_string()))?
+        ),
+        None if cursor_position.is_some() => Some(DEFAULT_PAGE_SIZE),
+        None => None,
+    };
     
-    let visibility_mode = ip_risk_level.visibility_mode();
+    // Track unique daily visitors per city (not just page views)
+    // Counted where GeoIP places the visitor, falling back to the feed they asked for
+    // Buffered and flushed in the background so the feed never waits on stats writes
+    let visitor_city = security_ctx.geo
+        .as_ref()
+        .and_then(|geo| geo.city.as_deref())
+        .map(|city| cities.normalize(city))
+        .or_else(|| location_filter.clone());
+    if let Some(city) = &visitor_city {
+        state.stats.record_city_visitor(city, &security_ctx.fingerprint);
+    }
     
-    // Check if IP is in cooldown based on risk level
-    if let Ok(Some(remaining)) = state.ip_reputation
-        .check_cooldown(&security_ctx.composite_key)
+    let mut messages: Vec<ChatMessage> = state.get_messages()
         .await
-    {
-        // IP is in cooldown - return error with remaining seconds
-        return Err((
-            StatusCode::TOO_MANY_REQUESTS,
-            Json(json!(RateLimitError::new(remaining)))
-        ));
-    }
+        .into_iter()
+        .filter(|msg| {
+            // If location filter is provided, only include messages with matching location
+            // Listings posted before an alias was registered still match
+            if let Some(filter_location) = &location_filter {
+                msg.location.as_deref().is_some_and(|loc| cities.normalize(loc) == *filter_location)
+            } else {
+                true
+            }
+        })
+        .filter(|msg| {
+            // Only messages strictly beyond the cursor position, in the page's direction
+            cursor_position.as_ref().is_none_or(|pos| {
+                let key = (msg.timestamp, msg.id.as_str());
+                let cursor = (pos.timestamp, pos.id.as_str());
+                match direction {
+                    PageDirection::Older => key < cursor,
+                    PageDirection::Newer => key > cursor,
+                }
+            })
+        })
+        .map(|mut msg| {
+            msg.phone = None;
+            msg
+        })
+        .collect();
 
-    // Check rate limit for posting
-    let rate_limit_result = state.rate_limiter
-        .check_rate_limit(&security_ctx.composite_key, RateLimitType::PostMessage)
-        .await
-        .map_err(|e| {
-            eprintln!("Rate limit check error: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": "Failed to check rate limit"}))
-            )
-        })?;
+    // Pinned listings lead the city's first page and are left out of the rest
+    let pinned: Vec<ChatMessage> = match &location_filter {
+        Some(city) => state.pinned_messages(city).await.unwrap_or_else(|e| {
+            tracing::error!("Failed to load pinned listings: {}", e);
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
+    messages.retain(|msg| !pinned.iter().any(|p| p.id == msg.id));
 
-    if !rate_limit_result.allowed {
-        return Err((
-            StatusCode::TOO_MANY_REQUESTS,
-            Json(json!(RateLimitError::new(rate_limit_result.reset_at)))
-        ));
+    let mut headers = HeaderMap::new();
+    let mut next_cursor = None;
+    if let Some(page_size) = page_size {
+        messages.sort_by(|a, b| (a.timestamp, &a.id).cmp(&(b.timestamp, &b.id)));
+        let next = take_page(&mut messages, page_size, direction, |msg| CursorPosition {
+            timestamp: msg.timestamp,
+            id: msg.id.clone(),
+        });
+        // More remain that way - hand out a signed cursor for the next page
+        if let Some(position) = next {
+            let cursor = state.cursor_signer.issue(position, &filter_hash);
+            if let Ok(value) = HeaderValue::from_str(&cursor) {
+                headers.insert(NEXT_CURSOR_HEADER, value);
+            }
+            next_cursor = Some(cursor);
+        }
     }
-    
-    // Set cooldown for the composite key based on risk level
-    let cooldown_duration = ip_risk_level.cooldown_seconds();
-    if let Err(e) = state.ip_reputation
-        .set_cooldown(&security_ctx.composite_key, cooldown_duration)
-        .await
-    {
-        eprintln!("Failed to set IP reputation cooldown: {}", e);
+
+    if cursor_position.is_none() && !pinned.is_empty() {
+        let pinned = pinned.into_iter().map(|msg| ChatMessage { phone: None, ..msg });
+        messages.splice(0..0, pinned);
     }
 
-    let message = ChatMessage::new(
-        request.browser_id,
-        request.message,
-        request.message_type,
-        request.phone,
-        request.location,
-    );
+    // Count this viewer against every listing shown, off the response path
+    // View counts are the first thing dropped while Redis is slow
+    if !state.load_shedder.should_shed(SheddableWork::Stats) {
+        let shown: Vec<String> = messages.iter().map(|m| m.id.clone()).collect();
+        let listing_stats = state.listing_stats.clone();
+        let viewer = security_ctx.composite_key.clone();
+        tokio::spawn(async move {
+            if let Err(e) = listing_stats.record_views(&shown, &viewer).await {
+                tracing::error!("{}", e);
+            }
+        });
+    }
 
-    // Check IP reputation visibility restrictions
-    use crate::security::ip_reputation::VisibilityMode;
-    
-    // If shadowbanned or visibility is banned, pretend to succeed but don't broadcast
-    if is_shadowbanned_total || visibility_mode == VisibilityMode::Banned {
-        // Just return success without storing/broadcasting
-        return Ok(Json(message));
+    // Paginated responses come in a `Page` envelope, the full feed as a bare array
+    let response = match (lite, page_size.is_some()) {
+        (true, true) => Json(Page {
+            messages: messages.into_iter().map(LiteListing::from).collect::<Vec<_>>(),
+            next_cursor,
+        }).into_response(),
+        (true, false) => Json(messages.into_iter().map(LiteListing::from).collect::<Vec<_>>()).into_response(),
+        (false, true) => Json(Page { messages, next_cursor }).into_response(),
+        (false, false) => Json(messages).into_response(),
+    };
+    Ok((headers, response).into_response())
+}
+
+/// Listings whose text or locality contains every word of `?q=`, newest first,
+/// without contact numbers; `?city=` narrows to one city, `?limit=` caps the results
+pub async fn search_messages(
+    State(state): State<AppState>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let query = params.get("q").map(|q| q.trim()).unwrap_or_default();
+    if query.is_empty() || query.len() > MAX_SEARCH_QUERY_LENGTH {
+        return Err(ApiError::BadRequest(format!(
+            "q must be 1-{} characters", MAX_SEARCH_QUERY_LENGTH
+        )));
     }
+    let limit = match params.get("limit") {
+        Some(limit) => limit.parse::<usize>()
+            .map(|l| l.clamp(1, MAX_PAGE_SIZE))
+            .map_err(|_| ApiError::BadRequest("Invalid limit".to_string()))?,
+        None => DEFAULT_PAGE_SIZE,
+    };
+    let city = match params.get("city") {
+        Some(city) => Some(state.city_registry.normalize(city).await),
+        None => None,
+    };
 
-    // Normal flow: add message to Redis and broadcast via pub/sub
-    state.add_message(message.clone())
+    let results = state.search
+        .search(query, city.as_deref(), limit)
         .await
         .map_err(|e| {
-            eprintln!("Failed to add message: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": "Failed to post message"}))
-            )
+            tracing::error!("{}", e);
+            ApiError::Internal("Search failed")
         })?;
+    let results: Vec<ChatMessage> = results
+        .into_iter()
+        .map(|msg| ChatMessage { phone: None, ..msg })
+        .collect();
 
-    // Track message count (using Redis increment for today)
-    let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
-    let message_count_key = format!("stats:message_count:{}", today);
-    if let Err(e) = state.redis.incr(&message_count_key).await {
-        eprintln!("Failed to increment message count: {}", e);
-    }
-    // Set expiration to 7 days
-    let _ = state.redis.expire(&message_count_key, 604800).await;
-
-    Ok(Json(message))
+    Ok(Json(json!({
+        "query": query,
+        "count": results.len(),
+        "results": results,
+    })))
 }
 
-use std::collections::HashMap;
-
-pub async fn get_messages(
+/// A single listing without its contact number
+/// `?translate=<lang>` adds a translated rendering next to the original text
+pub async fn get_message(
     State(state): State<AppState>,
-    Extension(security_ctx): Extension<SecurityContext>,
+    Path(message_id): Path<String>,
     Query(params): Query<HashMap<String, String>>,
-) -> Json<Vec<ChatMessage>> {
-    let location_filter = params.get("location");
-    
-    // Track unique daily visitors per city (not just page views)
-    if let Some(city) = location_filter {
-        let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
-        
-        // Use a Redis set to track unique visitors per city per day
-        // Key format: stats:city_visitors:CITY:DATE
-        let city_visitors_key = format!("stats:city_visitors:{}:{}", city, today);
-        
-        // Add the fingerprint to the set (returns 1 if new, 0 if already exists)
-        match state.redis.sadd(&city_visitors_key, &security_ctx.fingerprint).await {
-            Ok(is_new) => {
-                // Only increment if this is a new visitor today
-                if is_new > 0 {
-                    let city_views_key = format!("stats:city_views:{}:{}", city, today);
-                    if let Err(e) = state.redis.incr(&city_views_key).await {
-                        eprintln!("Failed to increment city views for {}: {}", city, e);
-                    }
-                    // Set expiry to 7 days for both keys
-                    let _ = state.redis.expire(&city_views_key, 604800).await;
-                }
-                // Always set expiry on the visitors set
-                let _ = state.redis.expire(&city_visitors_key, 604800).await;
+) -> Result<Json<MessageResponse>, ApiError> {
+    let message = state.get_message_by_id(&message_id)
+        .await
+        .ok_or(ApiError::NotFound("Message not found"))?;
+    let message = ChatMessage { phone: None, ..message };
+
+    let translation = match params.get("translate") {
+        Some(target) => {
+            if !is_valid_language_code(target) {
+                return Err(ApiError::BadRequest("Invalid translation language".to_string()));
             }
-            Err(e) => {
-                eprintln!("Failed to track visitor for {}: {}", city, e);
+            if !state.translator.enabled() {
+                return Err(ApiError::NotImplemented("Translation is not available"));
             }
-        }
-    }
-    
-    let messages = state.get_messages()
-        .await
-        .into_iter()
-        .filter(|msg| {
-            // If location filter is provided, only include messages with matching location
-            if let Some(filter_location) = location_filter {
-                msg.location.as_ref().map_or(false, |loc| loc == filter_location)
+
+            // Nothing to do when the listing is already in the requested language
+            let already_in_target = message.language
+                .and_then(|l| l.iso_code())
+                .is_some_and(|code| code == target);
+            if already_in_target {
+                None
             } else {
-                true
+                let translation = state.translator
+                    .translate(&message.message, target)
+                    .await
+                    .map_err(|e| {
+                        tracing::error!("{}", e);
+                        ApiError::Upstream("Translation failed")
+                    })?;
+                Some(translation)
             }
-        })
-        .map(|mut msg| {
-            msg.phone = None;
-            msg
-        })
-        .collect();
-    Json(messages)
+        }
+        None => None,
+    };
+
+    Ok(Json(MessageResponse { message, translation }))
 }
 
 pub async fn get_contact(
     Path(message_id): Path<String>,
     State(state): State<AppState>,
     Extension(security_ctx): Extension<SecurityContext>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
-    // Check rate limit for contact reveal (5 per hour)
+) -> Result<(HeaderMap, Json<serde_json::Value>), ApiError> {
+    // Actors caught harvesting numbers lose reveal ability until reviewed
+    match state.reveal_graph.is_revoked(&security_ctx.composite_key).await {
+        Ok(true) => {
+            return Err(ApiError::Forbidden("Contact reveal is not available"));
+        }
+        Err(e) => tracing::error!("Error checking reveal revocation: {}", e),
+        _ => {}
+    }
+
+    // Check rate limit for contact reveal (5 per hour by default)
     let rate_limit_result = state.rate_limiter
         .check_rate_limit(&security_ctx.composite_key, RateLimitType::ContactReveal)
         .await
         .map_err(|e| {
-            eprintln!("Rate limit check error: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": "Failed to check rate limit"}))
-            )
+            tracing::error!("Rate limit check error: {}", e);
+            ApiError::Internal("Failed to check rate limit")
         })?;
 
     if !rate_limit_result.allowed {
-        return Err((
-            StatusCode::TOO_MANY_REQUESTS,
-            Json(json!(RateLimitError::new(rate_limit_result.reset_at)))
-        ));
+        return Err(ApiError::RateLimited {
+            result: rate_limit_result,
+            quota: Some(RevealQuota::new(0, rate_limit_result.limit, rate_limit_result.reset_at)),
+        });
     }
 
     match state.get_message_by_id(&message_id).await {
         Some(message) => {
             if let Some(phone) = message.phone {
-                // Update contact reveal metric
-                state.metrics.increment_contact_reveals().await;
+                // Add the edge to the reveal graph for harvesting analysis
+                let edge = RevealEdge {
+                    poster: message.browser_id.clone(),
+                    city: message.location.clone(),
+                    timestamp: std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                };
+                if let Err(e) = state.reveal_graph.record(&security_ctx.composite_key, &message.id, &edge).await {
+                    tracing::error!("{}", e);
+                }
+                if let Err(e) = state.stats.record_contact_reveal(&security_ctx.composite_key, &message.id).await {
+                    tracing::error!("{}", e);
+                }
+                activity::spawn_notify_contact_revealed(&state, &message.id, message.location.clone());
                 
-                Ok(Json(json!({ "phone": phone })))
+                let quota = reveal_quota(&state, &security_ctx.composite_key).await.ok();
+                Ok((rate_limit_result.headers(), Json(json!({ "phone": phone, "quota": quota }))))
             } else {
-                Err((
-                    StatusCode::NOT_FOUND,
-                    Json(json!({"error": "No contact information available"}))
-                ))
+                Err(ApiError::NotFound("No contact information available"))
             }
         }
-        None => Err((
-            StatusCode::NOT_FOUND,
-            Json(json!({"error": "Message not found"}))
-        ))
+        None => Err(ApiError::NotFound("Message not found"))
     }
 }
 
+pub async fn get_notification_preferences(
+    State(state): State<AppState>,
+    Extension(security_ctx): Extension<SecurityContext>,
+) -> Result<Json<activity::NotificationPreferences>, ApiError> {
+    activity::preferences(&state, &security_ctx.composite_key)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            tracing::error!("{}", e);
+            ApiError::Internal("Failed to load notification preferences")
+        })
+}
+
+/// Opt in or out of activity notices on the caller's listings
+pub async fn set_notification_preferences(
+    State(state): State<AppState>,
+    Extension(security_ctx): Extension<SecurityContext>,
+    Json(preferences): Json<activity::NotificationPreferences>,
+) -> Result<Json<activity::NotificationPreferences>, ApiError> {
+    activity::set_preferences(&state, &security_ctx.composite_key, &preferences)
+        .await
+        .map_err(|e| {
+            tracing::error!("{}", e);
+            ApiError::Internal("Failed to save notification preferences")
+        })?;
+    Ok(Json(preferences))
+}
+
+/// Remaining contact reveals, so clients can warn before the hourly limit is hit
+pub async fn get_reveal_quota(
+    State(state): State<AppState>,
+    Extension(security_ctx): Extension<SecurityContext>,
+) -> Result<Json<RevealQuota>, ApiError> {
+    reveal_quota(&state, &security_ctx.composite_key)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            tracing::error!("Rate limit check error: {}", e);
+            ApiError::Internal("Failed to check rate limit")
+        })
+}
+
+async fn reveal_quota(state: &AppState, composite_key: &str) -> anyhow::Result<RevealQuota> {
+    let status = state.rate_limiter
+        .check_rate_limit_status(composite_key, RateLimitType::ContactReveal)
+        .await?;
+    let limit = state.rate_limits.max_requests(RateLimitType::ContactReveal);
+    Ok(RevealQuota::new(status.remaining, limit, status.reset_at))
+}
+
+/// Where GeoIP places the caller, so clients can pre-fill their city picker
+/// The city is the registered spelling (see `CityRegistry`); fields are absent when unknown
+pub async fn get_geo(
+    State(state): State<AppState>,
+    Extension(security_ctx): Extension<SecurityContext>,
+) -> Json<GeoLocation> {
+    let mut geo = security_ctx.geo.unwrap_or_default();
+    if let Some(city) = &geo.city {
+        geo.city = Some(state.city_registry.normalize(city).await);
+    }
+    Json(geo)
+}
+
 pub async fn get_cooldown(
     State(state): State<AppState>,
     Extension(security_ctx): Extension<SecurityContext>,
@@ -353,11 +526,7 @@ pub async fn get_cooldown(
                     .duration_since(std::time::UNIX_EPOCH)
                     .unwrap()
                     .as_secs();
-                let seconds_remaining = if result.reset_at > now {
-                    result.reset_at - now
-                } else {
-                    0
-                };
+                let seconds_remaining = result.reset_at.saturating_sub(now);
                 Json(json!({
                     "can_post": false,
                     "remaining_seconds": seconds_remaining
@@ -373,36 +542,351 @@ pub async fn get_cooldown(
     }
 }
 
+/// View, reveal and reaction counts for a listing - only for its poster
+pub async fn get_listing_stats(
+    Path(message_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(security_ctx): Extension<SecurityContext>,
+) -> Result<Json<ListingStats>, ApiError> {
+    let is_owner = state.listing_stats
+        .is_owner(&message_id, &security_ctx.composite_key)
+        .await
+        .unwrap_or(false);
+
+    // Same response for "not yours" and "doesn't exist" so ids can't be probed
+    if !is_owner {
+        return Err(ApiError::NotFound("Message not found"));
+    }
+
+    state.listing_stats.get(&message_id).await
+        .map(Json)
+        .map_err(|e| {
+            tracing::error!("{}", e);
+            ApiError::Internal("Failed to load listing stats")
+        })
+}
+
+/// Confirm a listing is still available, renewing it (poster only)
+pub async fn confirm_availability(
+    Path(message_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(security_ctx): Extension<SecurityContext>,
+) -> Result<Json<ChatMessage>, ApiError> {
+    match availability::confirm_listing(&state, &message_id, &security_ctx.composite_key).await {
+        Ok(Some(message)) => Ok(Json(message)),
+        Ok(None) => Err(ApiError::NotFound("Message not found")),
+        Err(e) => {
+            tracing::error!("Failed to confirm listing: {}", e);
+            Err(ApiError::Internal("Failed to confirm listing"))
+        }
+    }
+}
+
+/// React to a listing (counted once per composite key, shown to the poster as a total)
+pub async fn react_to_message(
+    Path(message_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(security_ctx): Extension<SecurityContext>,
+) -> Result<StatusCode, ApiError> {
+    if state.get_message_by_id(&message_id).await.is_none() {
+        return Err(ApiError::NotFound("Message not found"));
+    }
+
+    state.listing_stats
+        .add_reaction(&message_id, &security_ctx.composite_key)
+        .await
+        .map(|added| {
+            if added {
+                activity::spawn_notify_owner(
+                    &state,
+                    &message_id,
+                    WsServerEvent::Reaction { message_id: message_id.clone() },
+                );
+            }
+            StatusCode::NO_CONTENT
+        })
+        .map_err(|e| {
+            tracing::error!("{}", e);
+            ApiError::Internal("Failed to record reaction")
+        })
+}
+
+/// Launch status of a city with its waitlist counters
+pub async fn get_city_waitlist(
+    Path(city): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let internal_error = |e: anyhow::Error| {
+        tracing::error!("{}", e);
+        ApiError::Internal("Failed to load city status")
+    };
+
+    let status = state.cities.status(&city).await.map_err(internal_error)?;
+    let (interested, queued_listings) = state.cities.interest(&city).await.map_err(internal_error)?;
+
+    Ok(Json(json!({
+        "city": city,
+        "status": status,
+        "interested": interested,
+        "queued_listings": queued_listings,
+    })))
+}
+
+/// Register interest in a city that hasn't launched yet
+pub async fn register_city_interest(
+    Path(city): Path<String>,
+    State(state): State<AppState>,
+    Extension(security_ctx): Extension<SecurityContext>,
+) -> Result<StatusCode, ApiError> {
+    match state.cities.status(&city).await {
+        Ok(CityStatus::Waitlist) => {}
+        Ok(CityStatus::Live) => {
+            return Err(ApiError::Conflict("City is already live"));
+        }
+        Err(e) => {
+            tracing::error!("{}", e);
+            return Err(ApiError::Internal("Failed to register interest"));
+        }
+    }
+
+    state.cities.register_interest(&city, &security_ctx.composite_key).await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(|e| {
+            tracing::error!("{}", e);
+            ApiError::Internal("Failed to register interest")
+        })
+}
+
+/// Start an anonymous session bound to the caller's composite key
+/// This is where a fingerprint gets registered, so each IP may only introduce
+/// a handful of new fingerprints per day
+pub async fn create_session(
+    State(state): State<AppState>,
+    Extension(security_ctx): Extension<SecurityContext>,
+) -> Result<Json<SessionTokens>, ApiError> {
+    if security_ctx.fingerprint == UNKNOWN_FINGERPRINT {
+        return Err(ApiError::BadRequest("A valid browser fingerprint is required".to_string()));
+    }
+
+    let known = state.fingerprints
+        .is_known(&security_ctx.ip_address, &security_ctx.fingerprint)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::error!("{}", e);
+            false
+        });
+    if !known {
+        let rate_limit_result = state.rate_limiter
+            .check_rate_limit(&security_ctx.ip_address, RateLimitType::NewFingerprint)
+            .await
+            .map_err(|e| {
+                tracing::error!("Rate limit check error: {}", e);
+                ApiError::Internal("Failed to check rate limit")
+            })?;
+
+        if !rate_limit_result.allowed {
+            metrics::counter!("new_fingerprints_throttled_total", 1);
+            return Err(ApiError::RateLimited { result: rate_limit_result, quota: None });
+        }
+
+        if let Err(e) = state.fingerprints
+            .register(&security_ctx.ip_address, &security_ctx.fingerprint)
+            .await
+        {
+            tracing::error!("{}", e);
+        }
+    }
+
+    state.sessions.create(&security_ctx.composite_key).await
+        .map(Json)
+        .map_err(|e| {
+            tracing::error!("{}", e);
+            ApiError::Internal("Failed to create session")
+        })
+}
+
+/// Exchange a refresh token for a fresh access token (the refresh token is rotated)
+pub async fn refresh_session(
+    State(state): State<AppState>,
+    Json(request): Json<RefreshSessionRequest>,
+) -> Result<Json<SessionTokens>, ApiError> {
+    match state.sessions.refresh(&request.refresh_token).await {
+        Ok(Some(tokens)) => Ok(Json(tokens)),
+        Ok(None) => Err(ApiError::Unauthorized("Session expired or revoked")),
+        Err(e) => {
+            tracing::error!("{}", e);
+            Err(ApiError::Internal("Failed to refresh session"))
+        }
+    }
+}
+
+/// Revoke the caller's current session
+pub async fn end_session(
+    State(state): State<AppState>,
+    Extension(security_ctx): Extension<SecurityContext>,
+) -> StatusCode {
+    let Some(session) = security_ctx.session else {
+        return StatusCode::NO_CONTENT;
+    };
+
+    match state.sessions.revoke(&session.sid, &session.key).await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(e) => {
+            tracing::error!("{}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Record who reported a message and freeze report-driven auto-actions on it
+/// when the reports come from suspiciously correlated sources
+/// Returns true if auto-actions are frozen for the message
+async fn screen_report(
+    state: &AppState,
+    security_ctx: &SecurityContext,
+    message_id: &str,
+    reported_browser_id: &str,
+) -> anyhow::Result<bool> {
+    if state.report_guard.is_frozen(message_id).await? {
+        return Ok(true);
+    }
+
+    let source = ReportSource::new(
+        &security_ctx.composite_key,
+        &security_ctx.ip_address,
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+    );
+    let verdict = state.report_guard.record(message_id, &source).await?;
+    if !verdict.is_correlated() {
+        return Ok(false);
+    }
+    if !state.report_guard.freeze(message_id).await? {
+        // Another request froze it first and already queued the review
+        return Ok(true);
+    }
+
+    tracing::info!(message_id, reasons = %verdict.reasons.join("; "), "froze report actions");
+    metrics::counter!("reports_brigade_frozen_total", 1);
+    spawn_record_report(state, message_id, ReportAction::Frozen);
+
+    let event = AuditEvent::new(
+        AuditEventKind::ReportActionsFrozen,
+        "system",
+        message_id,
+        &verdict.reasons.join("; "),
+    )
+    .with_details(json!(verdict));
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+
+    let item = ReviewItem::new(
+        &keys::reported(reported_browser_id),
+        Some(message_id),
+        ReviewReason::ReportBrigade { reasons: verdict.reasons.clone() },
+    )
+    .with_details(json!(verdict));
+    state.review_queue.enqueue(&item).await?;
+    Ok(true)
+}
+
+/// Put a reported message in front of moderators, once per message
+async fn queue_reported_message(
+    state: &AppState,
+    message: &ChatMessage,
+    request: &ReportMessageRequest,
+) -> anyhow::Result<()> {
+    if !state.report_guard.mark_queued(&message.id).await? {
+        return Ok(());
+    }
+    let item = ReviewItem::new(
+        &keys::reported(&request.reported_browser_id),
+        Some(&message.id),
+        ReviewReason::UserReports {
+            reported_browser_id: request.reported_browser_id.clone(),
+            reason: request.reason,
+        },
+    )
+    .with_details(json!({
+        "message": message.message,
+        "location": message.location,
+        "posted_at": message.timestamp,
+    }));
+    state.review_queue.enqueue(&item).await
+}
+
 pub async fn report_message(
     State(state): State<AppState>,
     Extension(security_ctx): Extension<SecurityContext>,
     Json(request): Json<ReportMessageRequest>,
-) -> Result<Json<ReportResponse>, (StatusCode, Json<serde_json::Value>)> {
+) -> Result<Json<ReportResponse>, ApiError> {
     // Verify the message exists
     let message = state.get_message_by_id(&request.message_id).await;
     if message.is_none() {
-        return Err((
-            StatusCode::NOT_FOUND,
-            Json(json!({"error": "Message not found"}))
-        ));
+        return Err(ApiError::NotFound("Message not found"));
     }
 
     let message = message.unwrap();
 
     // Verify the browser_id matches
     if message.browser_id != request.reported_browser_id {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": "Invalid browser ID"}))
-        ));
+        return Err(ApiError::BadRequest("Invalid browser ID".to_string()));
     }
 
     // Can't report your own messages
     if message.browser_id == security_ctx.fingerprint {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": "Cannot report your own message"}))
-        ));
+        return Err(ApiError::BadRequest("Cannot report your own message".to_string()));
+    }
+
+    // Reports get their own budget, per identity and per IP, so one scripted
+    // user can't nuke listings before report dedup catches up
+    for (key, limit_type) in [
+        (&security_ctx.composite_key, RateLimitType::ReportMessage),
+        (&security_ctx.ip_address, RateLimitType::ReportPerIp),
+    ] {
+        let rate_limit_result = state.rate_limiter
+            .check_rate_limit(key, limit_type)
+            .await
+            .map_err(|e| {
+                tracing::error!("Rate limit check error: {}", e);
+                ApiError::Internal("Failed to check rate limit")
+            })?;
+
+        if !rate_limit_result.allowed {
+            metrics::counter!("reports_throttled_total", 1);
+            return Err(ApiError::RateLimited { result: rate_limit_result, quota: None });
+        }
+    }
+
+    let report_key = keys::fingerprint_reporters(&request.reported_browser_id);
+
+    // Each reporter counts once per listing, however often they resubmit, so one
+    // person refreshing can't reach the report thresholds alone
+    let reporters_key = keys::report_reporters(&request.message_id);
+    let first_report = match state.redis
+        .pipeline()
+        .sadd(&reporters_key, &security_ctx.composite_key)
+        .expire(&reporters_key, REPORT_TTL_SECONDS).ignore()
+        .query::<(i64,)>()
+        .await
+    {
+        Ok((added,)) => added == 1,
+        Err(e) => {
+            tracing::error!("Failed to record reporter: {}", e);
+            return Err(ApiError::Internal("Failed to process report"));
+        }
+    };
+    if !first_report {
+        metrics::counter!("reports_duplicate_total", 1);
+        return Ok(Json(ReportResponse {
+            success: true,
+            message: "You already reported this listing".to_string(),
+            reports_on_ip: current_report_count(&state, &report_key).await,
+            already_reported: true,
+        }));
     }
 
     // Add the report to IP reputation system
@@ -415,43 +899,104 @@ pub async fn report_message(
         .add_report(&security_ctx.ip_address, &request.reported_browser_id)
         .await
         .unwrap_or(0);
-    
-    // For 3 reports on a fingerprint, shadowban that fingerprint
-    let report_key = format!("reports:fingerprint:{}", request.reported_browser_id);
-    let report_count = match state.redis.incr(&report_key).await {
-        Ok(count) => count,
+
+    let event = AuditEvent::new(
+        AuditEventKind::ReportSubmitted,
+        &security_ctx.composite_key,
+        &request.message_id,
+        "Reported by user",
+    )
+    .with_details(json!({
+        "reported_browser_id": request.reported_browser_id,
+        "reason": request.reason.map(|reason| reason.as_str()),
+    }));
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+
+    // Let the poster know their listing is being looked at
+    activity::spawn_notify_under_review(&state, &request.message_id);
+    spawn_record_report(&state, &request.message_id, ReportAction::Reported);
+    if let Some(reason) = request.reason {
+        metrics::counter!("reports_by_reason_total", 1, "reason" => reason.as_str());
+    }
+
+    // A brigaded message keeps accepting reports, but they no longer count
+    // toward auto-actions - a moderator decides from the review queue
+    let frozen = screen_report(&state, &security_ctx, &request.message_id, &request.reported_browser_id)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::error!("Failed to screen report: {}", e);
+            false
+        });
+    if frozen {
+        return Ok(Json(ReportResponse {
+            success: true,
+            message: "Report submitted successfully".to_string(),
+            reports_on_ip: current_report_count(&state, &report_key).await,
+            already_reported: false,
+        }));
+    }
+
+    if let Err(e) = queue_reported_message(&state, &message, &request).await {
+        tracing::error!("Failed to queue reported message: {}", e);
+    }
+
+    // Distinct reporters of a fingerprint shadowban it, and delete the listing, at
+    // the policy's fallback thresholds; one person reporting several of the same
+    // poster's listings counts once. Reports are forgiven 7 days after the last
+    let report_count = match state.redis
+        .pipeline()
+        .sadd(&report_key, &security_ctx.composite_key).ignore()
+        .expire(&report_key, REPORT_TTL_SECONDS).ignore()
+        .scard(&report_key)
+        .query::<(i64,)>()
+        .await
+    {
+        Ok((count,)) => count,
         Err(e) => {
-            eprintln!("Failed to increment report count: {}", e);
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": "Failed to process report"}))
-            ));
+            tracing::error!("Failed to record report: {}", e);
+            return Err(ApiError::Internal("Failed to process report"));
         }
     };
 
-    // Set expiration on reports (forgive after 7 days)
-    let _ = state.redis.expire(&report_key, 604800).await;
-
-    // If 5 or more reports, delete the message
-    if report_count >= 5 {
+    let policy = state.report_guard.policy;
+    if policy.deletes(report_count) {
         if let Err(e) = state.delete_message(&request.message_id).await {
-            eprintln!("Failed to delete reported message {}: {}", request.message_id, e);
+            tracing::error!("Failed to delete reported message {}: {}", request.message_id, e);
         } else {
-            eprintln!("Message {} deleted after {} reports", request.message_id, report_count);
+            tracing::info!(message_id = %request.message_id, report_count, "message deleted after reports");
+            spawn_record_report(&state, &request.message_id, ReportAction::Deleted);
         }
     }
 
-    // If 3 or more reports, shadowban the fingerprint permanently
-    if report_count >= 3 {
+    // The fingerprint is shadowbanned permanently
+    if policy.shadowbans(report_count) {
         // Create a composite key for the reported user (we use fingerprint as basis)
-        let reported_composite_key = format!("reported:{}", request.reported_browser_id);
+        let reported_composite_key = keys::reported(&request.reported_browser_id);
         
+        let reason = format!("Auto-shadowbanned after {} reports", report_count);
+        let record = EnforcementRecord::system(ReasonCode::UserReports, "handlers::report_message", &reason)
+            .with_message(&request.message_id);
         if let Err(e) = state.shadowban_manager.shadowban(
             &reported_composite_key,
-            Some(&format!("Auto-shadowbanned after {} reports", report_count)),
+            record,
             None, // Permanent shadowban
         ).await {
-            eprintln!("Failed to shadowban reported user: {}", e);
+            tracing::error!("Failed to shadowban reported user: {}", e);
+        } else {
+            spawn_record_report(&state, &request.message_id, ReportAction::PosterShadowbanned);
+        }
+
+        let event = AuditEvent::new(
+            AuditEventKind::Shadowbanned,
+            "system",
+            &reported_composite_key,
+            &reason,
+        )
+        .with_details(json!({ "message_id": request.message_id }));
+        if let Err(e) = state.audit_log.record(event).await {
+            tracing::error!("{}", e);
         }
     }
 
@@ -459,15 +1004,65 @@ pub async fn report_message(
         success: true,
         message: "Report submitted successfully".to_string(),
         reports_on_ip: report_count as usize,
+        already_reported: false,
     }))
 }
 
+/// Distinct reporters counted against a fingerprint so far
+async fn current_report_count(state: &AppState, report_key: &str) -> usize {
+    state.redis.scard(report_key).await.unwrap_or(0) as usize
+}
+
+/// Decoy endpoint handler for common vulnerability-scanner paths
+/// Escalates the caller's IP to the highest risk level, blocks it, and
+/// answers with a plain 404 so the trap is indistinguishable from a missing page
+pub async fn bot_trap(
+    State(state): State<AppState>,
+    Extension(security_ctx): Extension<SecurityContext>,
+    uri: axum::http::Uri,
+) -> StatusCode {
+    let path = uri.path().to_string();
+    tracing::warn!(ip = %security_ctx.ip_address, path = %path, "bot trap hit");
+    metrics::counter!("bot_trap_hits_total", 1, "path" => path.clone());
+
+    if let Err(e) = state.ip_reputation
+        .escalate_risk(&security_ctx.ip_address, RiskLevel::Level3, BOT_TRAP_BLOCK_SECONDS)
+        .await
+    {
+        tracing::error!("Failed to escalate IP risk level: {}", e);
+    }
+
+    if let Err(e) = state.rate_limiter.block_ip(
+        &security_ctx.ip_address,
+        BOT_TRAP_BLOCK_SECONDS,
+        EnforcementRecord::system(ReasonCode::BotTrap, "handlers::bot_trap", &format!("Requested {}", path)),
+    ).await {
+        tracing::error!("Failed to block IP: {}", e);
+    }
+
+    let event = AuditEvent::new(
+        AuditEventKind::BotTrap,
+        "system",
+        &security_ctx.ip_address,
+        "Requested decoy endpoint",
+    )
+    .with_details(json!({
+        "path": path,
+        "composite_key": security_ctx.composite_key,
+    }));
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+
+    StatusCode::NOT_FOUND
+}
+
 /// Health check endpoint for load balancer
 pub async fn health_check(
     State(state): State<AppState>,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
     // Use the scaling health check
-    let health = crate::scaling::HealthStatus::check(&state.redis, &state.metrics).await;
+    let health = crate::scaling::HealthStatus::check(&state).await;
     
     if health.healthy {
         Ok(Json(serde_json::to_value(health).unwrap()))
@@ -476,55 +1071,65 @@ pub async fn health_check(
     }
 }
 
+/// Liveness probe: answers as long as the process is up
+pub async fn health_live() -> Json<crate::scaling::LivenessStatus> {
+    Json(crate::scaling::LivenessStatus::check())
+}
+
+/// Readiness probe: Redis answers, the pub/sub heartbeat is arriving and the
+/// instance isn't draining for shutdown; 503 with the full status otherwise
+pub async fn health_ready(
+    State(state): State<AppState>,
+) -> (StatusCode, Json<crate::scaling::HealthStatus>) {
+    let health = crate::scaling::HealthStatus::check(&state).await;
+    let status = if health.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(health))
+}
+
 /// Track a unique visitor by IP address
 pub async fn track_visitor(
     State(state): State<AppState>,
     Extension(security_ctx): Extension<SecurityContext>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    // Get today's date in YYYY-MM-DD format (UTC)
-    let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
-    
-    // Track unique visitors by fingerprint (using a Redis set for today)
-    let unique_visitors_key = format!("stats:unique_visitors:{}", today);
-    if let Err(e) = state.redis.sadd(&unique_visitors_key, &security_ctx.fingerprint).await {
-        eprintln!("Failed to track visitor fingerprint: {}", e);
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
-    }
-    
-    // Set expiration to 7 days (to clean up old stats)
-    if let Err(e) = state.redis.expire(&unique_visitors_key, 604800).await {
-        eprintln!("Failed to set expiration on unique visitors: {}", e);
-    }
-    
-    Ok(Json(json!({
+) -> Json<serde_json::Value> {
+    // Track unique visitors by fingerprint (buffered, flushed to a Redis set for today)
+    state.stats.record_visitor(&security_ctx.fingerprint);
+
+    Json(json!({
         "success": true,
         "message": "Visitor tracked"
-    })))
+    }))
 }
 
 /// Get daily statistics (unique visitors and message count for the day)
 pub async fn get_daily_stats(
     State(state): State<AppState>,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
-    // Get today's date in YYYY-MM-DD format (UTC)
-    let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
-    
-    // Get unique visitors for today (by fingerprint)
-    let unique_visitors_key = format!("stats:unique_visitors:{}", today);
-    let unique_visitors = state.redis
-        .scard(&unique_visitors_key)
-        .await
-        .unwrap_or(0) as u64;
-    
-    // Get message count for today
-    let message_count_key = format!("stats:message_count:{}", today);
-    let message_count = state.redis
-        .get(&message_count_key)
-        .await
-        .ok()
-        .flatten()
-        .and_then(|s| s.parse::<u64>().ok())
-        .unwrap_or(0);
+    // Served from memory for a couple of seconds; concurrent misses share one refresh
+    let (unique_visitors, message_count) = state.daily_stats_cache
+        .get_or_refresh(|| async {
+            // Get today's date in YYYY-MM-DD format (UTC)
+            let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+
+            // Get unique visitors for today (by fingerprint)
+            let unique_visitors_key = keys::unique_visitors(&today);
+            let unique_visitors = state.redis
+                .scard(&unique_visitors_key)
+                .await
+                .unwrap_or(0) as u64;
+
+            // Get message count for today
+            let message_count_key = keys::message_count(&today);
+            let message_count = state.redis
+                .get(&message_count_key)
+                .await
+                .ok()
+                .flatten()
+                .and_then(|s| s.parse::<u64>().ok())
+                .unwrap_or(0);
+
+            (unique_visitors, message_count)
+        })
+        .await;
     
     Ok(Json(json!({
         "unique_visitors": unique_visitors,
@@ -535,40 +1140,51 @@ pub async fn get_daily_stats(
 /// Returns average daily views for major cities
 pub async fn get_city_stats(
     State(state): State<AppState>,
+    Extension(security_ctx): Extension<SecurityContext>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
     let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
     
-    // List of major cities to track
-    let major_cities_base = vec![
-        "Bengaluru", "Hyderabad", "Pune", "Chennai", "Kolkata",
-        "Thiruvananthapuram", "Delhi", "Noida", "Gurgaon",
-    ];
+    // Cities to track come from the city registry
+    let directory = state.city_registry.directory().await;
     
     let mut cities_to_fetch: Vec<String> = Vec::new();
     
-    // Add current_city if provided and not already in the list
-    if let Some(current_city) = params.get("current_city") {
-        if !major_cities_base.iter().any(|c| c.eq_ignore_ascii_case(current_city)) {
-            cities_to_fetch.push(current_city.clone());
+    // Add current_city (or the visitor's GeoIP city) if not already in the list
+    let current_city = params
+        .get("current_city")
+        .map(String::as_str)
+        .or_else(|| security_ctx.geo.as_ref().and_then(|geo| geo.city.as_deref()));
+    if let Some(current_city) = current_city {
+        let current_city = directory.normalize(current_city);
+        if !directory.cities.contains(&current_city) {
+            cities_to_fetch.push(current_city);
         }
     }
     
-    // Add major cities
-    cities_to_fetch.extend(major_cities_base.iter().map(|s| s.to_string()));
+    // Add registered cities
+    cities_to_fetch.extend(directory.cities.iter().cloned());
     
     let mut city_stats = Vec::new();
     
     for city in cities_to_fetch {
-        // Track views per city per day
-        let city_views_key = format!("stats:city_views:{}:{}", city, today);
-        let views: u64 = state.redis
-            .get(&city_views_key)
-            .await
-            .ok()
-            .flatten()
-            .and_then(|s| s.parse::<u64>().ok())
-            .unwrap_or(0);
+        // Track views per city per day, cached like the daily stats for registered
+        // cities only, so arbitrary `current_city` values can't evict them
+        let city_views_key = keys::city_views(&city, &today);
+        let read_views = || async {
+            state.redis
+                .get(&city_views_key)
+                .await
+                .ok()
+                .flatten()
+                .and_then(|s| s.parse::<u64>().ok())
+                .unwrap_or(0)
+        };
+        let views: u64 = if directory.cities.contains(&city) {
+            state.city_views_cache.get_or_refresh(city_views_key.clone(), read_views).await
+        } else {
+            read_views().await
+        };
         
         // Calculate daily average (for now, just today's count)
         // In future, can calculate average over last 7 days
@@ -0,0 +1,340 @@
+//! Every Redis key and pub/sub channel name the server uses
+//! Keys are `<namespace>:<parts>`; building them only through these constructors
+//! keeps formats in one place so two features can't collide on a key by accident
+//! (`redis_usage` reports memory by the namespace before the first `:`)
+
+// Listings
+
+/// Sorted set of live message ids, scored by posting time
+pub const MESSAGES: &str = "messages";
+/// Prefix of stored messages, for SCAN patterns
+pub const MESSAGE_PREFIX: &str = "message:";
+/// Composite keys exempt from the active listing cap
+pub const VERIFIED_POSTERS: &str = "posters:verified";
+
+pub fn message(id: &str) -> String {
+    format!("{}{}", MESSAGE_PREFIX, id)
+}
+
+pub fn listing_owner(message_id: &str) -> String {
+    format!("listing:{}:owner", message_id)
+}
+
+pub fn listing_views(message_id: &str) -> String {
+    format!("listing:{}:views", message_id)
+}
+
+pub fn listing_reveals(message_id: &str) -> String {
+    format!("listing:{}:reveals", message_id)
+}
+
+pub fn listing_reactions(message_id: &str) -> String {
+    format!("listing:{}:reactions", message_id)
+}
+
+/// Set once a listing's "still available?" prompt was sent
+pub fn listing_prompted(message_id: &str) -> String {
+    format!("listing:{}:prompted", message_id)
+}
+
+/// A poster's live listings
+pub fn active_listings(composite_key: &str) -> String {
+    format!("listings:active:{}", composite_key)
+}
+
+pub fn city_pins(city: &str) -> String {
+    format!("pins:city:{}", city)
+}
+
+/// Ids of listings containing a search term, scored by expiry time
+pub fn search_term(term: &str) -> String {
+    format!("search:term:{}", term)
+}
+
+/// Short-lived intersection of the term sets of one multi-term search
+pub fn search_query(id: &str) -> String {
+    format!("search:query:{}", id)
+}
+
+// Cities
+
+/// Cities put on the waitlist by admins at runtime
+pub const CITY_WAITLIST: &str = "cities:waitlist";
+/// Cities launched by admins (overrides `WAITLIST_CITIES` from the environment)
+pub const CITIES_LAUNCHED: &str = "cities:launched";
+/// Admin-managed city directory (JSON `CityDirectory`)
+pub const CITY_REGISTRY: &str = "cities:registry";
+/// Cities with recent posts, for surge detection
+pub const CITY_VELOCITY_CITIES: &str = "city:velocity:cities";
+
+/// Posts queued while a city is waitlisted
+pub fn city_backlog(city: &str) -> String {
+    format!("cities:backlog:{}", city)
+}
+
+pub fn city_interest(city: &str) -> String {
+    format!("cities:interest:{}", city)
+}
+
+/// Posts in a city during one minute
+pub fn city_velocity(city: &str, minute: u64) -> String {
+    format!("city:velocity:{}:{}", city, minute)
+}
+
+pub fn city_surge(city: &str) -> String {
+    format!("city:surge:{}", city)
+}
+
+// Broadcast
+
+/// Stream of broadcast listings every instance reads; reconnecting clients
+/// resume from the last entry they saw
+pub const BROADCAST_STREAM: &str = "broadcast:stream";
+/// Stream the pub/sub watchdog sends its heartbeats round, kept apart from
+/// `BROADCAST_STREAM` so they don't crowd listings out of a client's replay
+pub const BROADCAST_HEARTBEATS: &str = "broadcast:heartbeats";
+/// Prefix of per-actor channels carrying events for one composite key
+pub const ACTOR_CHANNEL_PREFIX: &str = "chat:actor:";
+/// Broadcasts written together with their message, scored by creation time
+pub const BROADCAST_OUTBOX: &str = "broadcast:outbox";
+/// Broadcasts that failed to publish, newest at the head
+pub const BROADCAST_DEAD_LETTER: &str = "broadcast:dead_letter";
+
+pub fn actor_channel(composite_key: &str) -> String {
+    format!("{}{}", ACTOR_CHANNEL_PREFIX, composite_key)
+}
+
+// Stats and metrics
+
+pub const METRICS_MESSAGES_SENT: &str = "metrics:messages_sent";
+pub const METRICS_CONTACT_REVEALS: &str = "metrics:contact_reveals";
+
+/// Fingerprints seen on a day (`YYYY-MM-DD`)
+pub fn unique_visitors(day: &str) -> String {
+    format!("stats:unique_visitors:{}", day)
+}
+
+pub fn message_count(day: &str) -> String {
+    format!("stats:message_count:{}", day)
+}
+
+pub fn city_visitors(city: &str, day: &str) -> String {
+    format!("stats:city_visitors:{}:{}", city, day)
+}
+
+pub fn city_views(city: &str, day: &str) -> String {
+    format!("stats:city_views:{}:{}", city, day)
+}
+
+/// Set once an event (`message:<id>`, `reveal:<viewer>:<id>`) was counted
+pub fn stats_event(event_id: &str) -> String {
+    format!("stats:event:{}", event_id)
+}
+
+// Abuse prevention
+
+/// Networks with an active block
+pub const BLOCKED_NETWORKS: &str = "blocked:cidrs";
+pub const BLOCKED_IP_PREFIX: &str = "blocked:ip:";
+pub const BLOCKED_CIDR_PREFIX: &str = "blocked:cidr:";
+
+pub fn blocked_ip(ip: &str) -> String {
+    format!("{}{}", BLOCKED_IP_PREFIX, ip)
+}
+
+pub fn blocked_cidr(network: &str) -> String {
+    format!("{}{}", BLOCKED_CIDR_PREFIX, network)
+}
+
+/// Sliding-window log for one limit (`ratelimit:<limit>`) and subject
+pub fn rate_limit(limit_prefix: &str, subject: &str) -> String {
+    format!("{}:{}", limit_prefix, subject)
+}
+
+pub fn shadowban(composite_key: &str) -> String {
+    format!("shadowban:{}", composite_key)
+}
+
+pub fn violations(composite_key: &str) -> String {
+    format!("violations:{}", composite_key)
+}
+
+pub fn soft_violations(composite_key: &str) -> String {
+    format!("soft_violations:{}", composite_key)
+}
+
+pub fn cooldown(composite_key: &str) -> String {
+    format!("cooldown:{}", composite_key)
+}
+
+pub fn burst(composite_key: &str) -> String {
+    format!("burst:{}", composite_key)
+}
+
+pub fn ip_reports(ip: &str) -> String {
+    format!("reports:ip:{}", ip)
+}
+
+pub fn ip_risk(ip: &str) -> String {
+    format!("risk:ip:{}", ip)
+}
+
+/// Composite keys that reported a browser fingerprint's listings, so each
+/// reporter counts once against a poster however many listings they report
+pub fn fingerprint_reporters(fingerprint: &str) -> String {
+    format!("reports:fingerprint:reporters:{}", fingerprint)
+}
+
+/// Rate-limit subject for reports received by a browser fingerprint
+pub fn reported(fingerprint: &str) -> String {
+    format!("reported:{}", fingerprint)
+}
+
+/// Fingerprints recently seen from an IP
+pub fn ip_fingerprints(ip: &str) -> String {
+    format!("fingerprints:ip:{}", ip)
+}
+
+/// Who reported a listing
+pub fn report_sources(message_id: &str) -> String {
+    format!("reports:sources:{}", message_id)
+}
+
+/// Composite keys that reported a listing, so each reporter counts once
+pub fn report_reporters(message_id: &str) -> String {
+    format!("reports:reporters:{}", message_id)
+}
+
+/// Set once a reported listing was placed in the review queue
+pub fn report_queued(message_id: &str) -> String {
+    format!("reports:queued:{}", message_id)
+}
+
+/// Set while a listing's reports are frozen for review
+pub fn report_frozen(message_id: &str) -> String {
+    format!("reports:frozen:{}", message_id)
+}
+
+/// Set while a composite key's solved captcha still covers its posts
+pub fn captcha_passed(composite_key: &str) -> String {
+    format!("captcha:passed:{}", composite_key)
+}
+
+pub fn correction_token(composite_key: &str) -> String {
+    format!("correction:{}", composite_key)
+}
+
+// Contact reveals
+
+pub const ACTIVE_REVEALERS: &str = "reveals:active";
+pub const FLAGGED_REVEALERS: &str = "reveals:flagged";
+
+pub fn reveals_by(composite_key: &str) -> String {
+    format!("reveals:by:{}", composite_key)
+}
+
+pub fn reveal_revoked(composite_key: &str) -> String {
+    format!("reveal:revoked:{}", composite_key)
+}
+
+// Moderation
+
+pub const REVIEW_QUEUE: &str = "moderation:queue";
+/// Published posts waiting for the moderation provider (see `moderation_queue`)
+pub const MODERATION_PENDING: &str = "moderation:pending";
+/// Instances that have claimed moderation jobs, checked for stale claims
+pub const MODERATION_WORKERS: &str = "moderation:workers";
+
+/// Jobs an instance has claimed from `MODERATION_PENDING` and not yet finished
+pub fn moderation_processing(instance_id: &str) -> String {
+    format!("moderation:processing:{}", instance_id)
+}
+
+/// Present while an instance's moderation workers are running
+pub fn moderation_worker_alive(instance_id: &str) -> String {
+    format!("moderation:worker_alive:{}", instance_id)
+}
+
+pub fn city_policy(city: &str) -> String {
+    format!("moderation:city_policy:{}", city)
+}
+
+/// A poster's recent messages, for cross-message context
+pub fn moderation_recent(composite_key: &str) -> String {
+    format!("moderation:recent:{}", composite_key)
+}
+
+pub fn moderation_context_flagged(composite_key: &str) -> String {
+    format!("moderation:context_flagged:{}", composite_key)
+}
+
+/// Moderation provider verdict by normalized text hash
+pub fn moderation_verdict(text_hash: &str) -> String {
+    format!("moderation:verdict:{}", text_hash)
+}
+
+/// Moderation outcomes exported for a day (`YYYY-MM-DD`)
+pub fn moderation_dataset(day: &str) -> String {
+    format!("moderation:dataset:{}", day)
+}
+
+pub fn moderation_dataset_reports(day: &str) -> String {
+    format!("moderation:dataset:reports:{}", day)
+}
+
+/// Translated text by target language and source text hash
+pub fn translation(target: &str, text_hash: &str) -> String {
+    format!("translation:{}:{}", target, text_hash)
+}
+
+// Sessions, consent and notifications
+
+/// Sessions by expiry time
+pub const SESSION_EXPIRY_INDEX: &str = "sessions:expiry";
+pub const REVEAL_NOTICE_OPT_OUT: &str = "activity:reveal_notices:opted_out";
+
+pub fn session(sid: &str) -> String {
+    format!("session:{}", sid)
+}
+
+/// A composite key's sessions
+pub fn sessions_by_key(composite_key: &str) -> String {
+    format!("sessions:key:{}", composite_key)
+}
+
+pub fn consent(composite_key: &str) -> String {
+    format!("consent:{}", composite_key)
+}
+
+/// Set once a poster was told a listing is under review
+pub fn under_review_notice(message_id: &str) -> String {
+    format!("activity:under_review:{}", message_id)
+}
+
+// Streams and admin
+
+pub const AUDIT_STREAM: &str = "audit:events";
+pub const RECORDER_STREAM: &str = "recorder:requests";
+
+pub fn oidc_login_state(state: &str) -> String {
+    format!("oidc:state:{}", state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Keys already live in Redis; changing a format orphans the data under it
+    #[test]
+    fn test_key_formats_are_stable() {
+        assert_eq!(message("abc"), "message:abc");
+        assert_eq!(listing_views("abc"), "listing:abc:views");
+        assert_eq!(actor_channel("fp:1.2.3.4"), "chat:actor:fp:1.2.3.4");
+        assert_eq!(city_views("Pune", "2024-01-02"), "stats:city_views:Pune:2024-01-02");
+        assert_eq!(stats_event("message:abc"), "stats:event:message:abc");
+        assert_eq!(rate_limit("ratelimit:post", "fp:1.2.3.4"), "ratelimit:post:fp:1.2.3.4");
+        assert_eq!(blocked_cidr("10.0.0.0/8"), "blocked:cidr:10.0.0.0/8");
+        assert_eq!(moderation_dataset_reports("2024-01-02"), "moderation:dataset:reports:2024-01-02");
+        assert_eq!(moderation_processing("abc"), "moderation:processing:abc");
+    }
+}
@@ -0,0 +1,46 @@
+//! Krib API server; the binary in `main.rs` wires these modules together
+//! Built as a library too so benchmarks can drive the hot paths directly
+
+pub mod models;
+pub mod state;
+pub mod handlers;
+pub mod websocket;
+pub mod routes;
+pub mod redis_client;
+pub mod security;
+pub mod scaling;
+pub mod scheduler;
+pub mod pagination;
+pub mod admin;
+pub mod cache;
+pub mod listing_stats;
+pub mod availability;
+pub mod pins;
+pub mod cities;
+pub mod translation;
+pub mod redis_usage;
+pub mod stats;
+pub mod activity;
+pub mod moderation_dataset;
+pub mod concurrency;
+pub mod load_shedding;
+pub mod ws_backpressure;
+pub mod ws_compression;
+pub mod ws_handover;
+pub mod poster_limits;
+pub mod metrics_export;
+pub mod logging;
+pub mod slo;
+pub mod posting;
+pub mod recorder;
+pub mod consent;
+pub mod dependencies;
+pub mod config;
+pub mod keys;
+pub mod cors;
+pub mod deadline;
+pub mod warmup;
+pub mod permalink;
+pub mod fanout;
+pub mod search;
+pub mod moderation_queue;
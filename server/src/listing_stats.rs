@@ -0,0 +1,110 @@
+use crate::redis_client::RedisClient;
+use crate::state::MESSAGE_TTL;
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use crate::keys;
+
+/// Aggregate interest in a single listing (never who showed it)
+#[derive(Debug, Serialize)]
+pub struct ListingStats {
+    pub message_id: String,
+    /// Approximate number of distinct viewers the listing was shown to
+    pub views: u64,
+    pub reveals: u64,
+    pub reactions: u64,
+}
+
+/// Per-listing view, reveal and reaction counters
+/// All keys share the message TTL so stats disappear with the listing
+#[derive(Clone)]
+pub struct ListingStatsTracker {
+    redis: RedisClient,
+}
+
+impl ListingStatsTracker {
+    pub fn new(redis: RedisClient) -> Self {
+        Self { redis }
+    }
+
+    /// Remember which composite key posted a listing
+    pub async fn record_owner(&self, message_id: &str, composite_key: &str) -> Result<()> {
+        self.redis
+            .set_ex(&keys::listing_owner(message_id), composite_key, MESSAGE_TTL)
+            .await
+            .map_err(|e| anyhow!("Failed to record listing owner: {}", e))
+    }
+
+    /// Composite key that posted the listing, if known
+    pub async fn owner(&self, message_id: &str) -> Result<Option<String>> {
+        self.redis
+            .get(&keys::listing_owner(message_id))
+            .await
+            .map_err(|e| anyhow!("Failed to load listing owner: {}", e))
+    }
+
+    /// Whether a composite key posted the listing
+    pub async fn is_owner(&self, message_id: &str, composite_key: &str) -> Result<bool> {
+        Ok(self.owner(message_id).await?.as_deref() == Some(composite_key))
+    }
+
+    /// Extend the owner and stats keys along with a renewed listing
+    pub async fn renew(&self, message_id: &str) -> Result<()> {
+        for key in [
+            keys::listing_owner(message_id),
+            keys::listing_views(message_id),
+            keys::listing_reveals(message_id),
+            keys::listing_reactions(message_id),
+        ] {
+            self.redis
+                .expire(&key, MESSAGE_TTL as i64)
+                .await
+                .map_err(|e| anyhow!("Failed to renew listing stats: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Count a viewer for every listing they were shown
+    pub async fn record_views(&self, message_ids: &[String], viewer: &str) -> Result<()> {
+        let keys: Vec<String> = message_ids.iter().map(|id| keys::listing_views(id)).collect();
+        self.redis
+            .pfadd_batch(&keys, viewer, MESSAGE_TTL as i64)
+            .await
+            .map_err(|e| anyhow!("Failed to record listing views: {}", e))
+    }
+
+    /// Add a reaction; each composite key counts once per listing
+    /// Returns false if this key had already reacted
+    pub async fn add_reaction(&self, message_id: &str, composite_key: &str) -> Result<bool> {
+        let key = keys::listing_reactions(message_id);
+        let added = self.redis
+            .sadd(&key, composite_key)
+            .await
+            .map_err(|e| anyhow!("Failed to record reaction: {}", e))?;
+        let _ = self.redis.expire(&key, MESSAGE_TTL as i64).await;
+        Ok(added > 0)
+    }
+
+    pub async fn get(&self, message_id: &str) -> Result<ListingStats> {
+        let views = self.redis
+            .pfcount(&keys::listing_views(message_id))
+            .await
+            .map_err(|e| anyhow!("Failed to read listing views: {}", e))?;
+        let reveals = self.redis
+            .get(&keys::listing_reveals(message_id))
+            .await
+            .map_err(|e| anyhow!("Failed to read listing reveals: {}", e))?
+            .and_then(|r| r.parse().ok())
+            .unwrap_or(0);
+        let reactions = self.redis
+            .scard(&keys::listing_reactions(message_id))
+            .await
+            .map_err(|e| anyhow!("Failed to read listing reactions: {}", e))?;
+
+        Ok(ListingStats {
+            message_id: message_id.to_string(),
+            views: views.max(0) as u64,
+            reveals,
+            reactions: reactions.max(0) as u64,
+        })
+    }
+}
@@ -0,0 +1,235 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Redis command latencies kept for the rolling estimate
+const LATENCY_SAMPLES: usize = 512;
+/// Fewer samples than this are too noisy to act on
+const MIN_SAMPLES: usize = 20;
+/// A level is only left once p99 drops below this fraction of its threshold,
+/// so latency hovering around a threshold doesn't flap between levels
+const RECOVERY_FACTOR: f64 = 0.8;
+const DEFAULT_DEGRADED_MS: u64 = 50;
+const DEFAULT_CRITICAL_MS: u64 = 200;
+
+/// Rolling window of the most recent Redis command latencies
+#[derive(Default)]
+pub struct LatencyWindow {
+    samples: Mutex<VecDeque<Duration>>,
+}
+
+impl LatencyWindow {
+    pub fn record(&self, latency: Duration) {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() == LATENCY_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(latency);
+    }
+
+    /// 99th percentile of the window, None until enough samples are in
+    pub fn p99(&self) -> Option<Duration> {
+        let mut sorted: Vec<Duration> = self.samples.lock().unwrap().iter().copied().collect();
+        if sorted.len() < MIN_SAMPLES {
+            return None;
+        }
+        sorted.sort_unstable();
+        let index = (sorted.len() * 99).div_ceil(100) - 1;
+        Some(sorted[index])
+    }
+}
+
+/// How degraded Redis currently looks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadLevel {
+    Normal = 0,
+    /// Deferrable analytics writes are shed
+    Degraded = 1,
+    /// Burst profiling is shed as well; only posting and reading keep their Redis budget
+    Critical = 2,
+}
+
+impl LoadLevel {
+    fn from_u8(level: u8) -> Self {
+        match level {
+            0 => LoadLevel::Normal,
+            1 => LoadLevel::Degraded,
+            _ => LoadLevel::Critical,
+        }
+    }
+}
+
+/// Low-priority work that may be skipped while Redis is slow, cheapest to lose first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SheddableWork {
+    /// Listing view counts and visitor stats flushes
+    Stats,
+    /// Per-request endpoint-hopping bot detection
+    BurstProfiling,
+}
+
+impl SheddableWork {
+    fn shed_at(&self) -> LoadLevel {
+        match self {
+            SheddableWork::Stats => LoadLevel::Degraded,
+            SheddableWork::BurstProfiling => LoadLevel::Critical,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            SheddableWork::Stats => "stats",
+            SheddableWork::BurstProfiling => "burst_profiling",
+        }
+    }
+}
+
+/// Snapshot reported by /health
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadStatus {
+    pub level: LoadLevel,
+    pub redis_p99_ms: Option<f64>,
+}
+
+/// Decides when to shed low-priority work from Redis' rolling p99 latency
+/// Thresholds come from `REDIS_DEGRADED_P99_MS` and `REDIS_CRITICAL_P99_MS`
+#[derive(Clone)]
+pub struct LoadShedder {
+    latency: Arc<LatencyWindow>,
+    degraded: Duration,
+    critical: Duration,
+    level: Arc<AtomicU8>,
+}
+
+impl LoadShedder {
+    pub fn new(latency: Arc<LatencyWindow>, degraded: Duration, critical: Duration) -> Self {
+        Self {
+            latency,
+            degraded,
+            critical,
+            level: Arc::new(AtomicU8::new(LoadLevel::Normal as u8)),
+        }
+    }
+
+    pub fn from_env(latency: Arc<LatencyWindow>) -> Self {
+        let threshold = |var: &str, default: u64| {
+            Duration::from_millis(
+                std::env::var(var).ok().and_then(|v| v.parse().ok()).unwrap_or(default),
+            )
+        };
+        Self::new(
+            latency,
+            threshold("REDIS_DEGRADED_P99_MS", DEFAULT_DEGRADED_MS),
+            threshold("REDIS_CRITICAL_P99_MS", DEFAULT_CRITICAL_MS),
+        )
+    }
+
+    pub fn level(&self) -> LoadLevel {
+        LoadLevel::from_u8(self.level.load(Ordering::Relaxed))
+    }
+
+    /// Whether to skip a piece of work right now; counts what was shed
+    pub fn should_shed(&self, work: SheddableWork) -> bool {
+        let shed = self.level() >= work.shed_at();
+        if shed {
+            metrics::counter!("load_shed_total", 1, "work" => work.as_str());
+        }
+        shed
+    }
+
+    /// Re-evaluate the level from the latest latency window
+    /// Returns the new level if it changed
+    pub fn update(&self) -> Option<LoadLevel> {
+        let p99 = self.latency.p99();
+        if let Some(p99) = p99 {
+            metrics::gauge!("redis_latency_p99_seconds", p99.as_secs_f64());
+        }
+
+        let current = self.level();
+        let next = next_level(current, p99, self.degraded, self.critical);
+        metrics::gauge!("load_shed_level", next as u8 as f64);
+        if next == current {
+            return None;
+        }
+        self.level.store(next as u8, Ordering::Relaxed);
+        Some(next)
+    }
+
+    pub fn status(&self) -> LoadStatus {
+        LoadStatus {
+            level: self.level(),
+            redis_p99_ms: self.latency.p99().map(|p99| p99.as_secs_f64() * 1000.0),
+        }
+    }
+}
+
+/// Level for a p99 reading; stepping down requires clearing the recovery margin
+fn next_level(current: LoadLevel, p99: Option<Duration>, degraded: Duration, critical: Duration) -> LoadLevel {
+    let Some(p99) = p99 else {
+        return current;
+    };
+
+    let raw = if p99 >= critical {
+        LoadLevel::Critical
+    } else if p99 >= degraded {
+        LoadLevel::Degraded
+    } else {
+        LoadLevel::Normal
+    };
+    if raw >= current {
+        return raw;
+    }
+
+    // Only step down once latency is comfortably below the current level's threshold
+    let threshold = match current {
+        LoadLevel::Critical => critical,
+        _ => degraded,
+    };
+    if p99.as_secs_f64() < threshold.as_secs_f64() * RECOVERY_FACTOR {
+        raw
+    } else {
+        current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEGRADED: Duration = Duration::from_millis(50);
+    const CRITICAL: Duration = Duration::from_millis(200);
+
+    fn level(current: LoadLevel, p99_ms: u64) -> LoadLevel {
+        next_level(current, Some(Duration::from_millis(p99_ms)), DEGRADED, CRITICAL)
+    }
+
+    #[test]
+    fn test_p99_needs_samples() {
+        let window = LatencyWindow::default();
+        for _ in 0..MIN_SAMPLES - 1 {
+            window.record(Duration::from_millis(1));
+        }
+        assert_eq!(window.p99(), None);
+
+        for ms in 0..100 {
+            window.record(Duration::from_millis(ms));
+        }
+        assert!(window.p99().unwrap() >= Duration::from_millis(97));
+    }
+
+    #[test]
+    fn test_levels_with_hysteresis() {
+        assert_eq!(level(LoadLevel::Normal, 10), LoadLevel::Normal);
+        assert_eq!(level(LoadLevel::Normal, 60), LoadLevel::Degraded);
+        assert_eq!(level(LoadLevel::Normal, 250), LoadLevel::Critical);
+        // 180ms is under the critical threshold but not below its recovery margin
+        assert_eq!(level(LoadLevel::Critical, 180), LoadLevel::Critical);
+        assert_eq!(level(LoadLevel::Critical, 150), LoadLevel::Degraded);
+        assert_eq!(level(LoadLevel::Degraded, 45), LoadLevel::Degraded);
+        assert_eq!(level(LoadLevel::Degraded, 30), LoadLevel::Normal);
+        assert_eq!(next_level(LoadLevel::Degraded, None, DEGRADED, CRITICAL), LoadLevel::Degraded);
+    }
+}
@@ -0,0 +1,113 @@
+use axum::{extract::Request, middleware::Next, response::Response};
+use std::env;
+use tracing::{Instrument, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{
+    fmt::{self, writer::MakeWriter},
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+    EnvFilter, Layer,
+};
+
+const DEFAULT_LOG_FILE_PREFIX: &str = "kirb-server.log";
+const DEFAULT_RETENTION_FILES: usize = 7;
+
+/// Flushes buffered log lines on drop; hold it for the life of the process
+pub struct LogGuards(#[allow(dead_code)] Vec<WorkerGuard>);
+
+/// Install the global subscriber: stdout always, plus a rotating file when `LOG_DIR` is set
+/// Both writers are non-blocking, so a slow disk or terminal never stalls a request.
+/// Config: `RUST_LOG` (default `info`), `LOG_FORMAT` (`text` or `json`, default `text`),
+/// `LOG_DIR`, `LOG_FILE_PREFIX`, `LOG_ROTATION` (`minutely`, `hourly`, `daily`,
+/// `never`; default `daily`) and `LOG_RETENTION_FILES` (rotated files kept, default 7)
+pub fn init() -> LogGuards {
+    let mut guards = Vec::new();
+    let json = env::var("LOG_FORMAT").is_ok_and(|format| format.eq_ignore_ascii_case("json"));
+
+    let (stdout, guard) = tracing_appender::non_blocking(std::io::stdout());
+    guards.push(guard);
+    let stdout_layer = format_layer(stdout, json, true);
+
+    let mut file_error = None;
+    let file_layer = env::var("LOG_DIR").ok().filter(|dir| !dir.is_empty()).and_then(|dir| {
+        match file_appender(&dir) {
+            Ok(appender) => {
+                let (writer, guard) = tracing_appender::non_blocking(appender);
+                guards.push(guard);
+                Some(format_layer(writer, json, false))
+            }
+            Err(e) => {
+                file_error = Some(format!("Failed to open log directory {}: {}", dir, e));
+                None
+            }
+        }
+    });
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .with(stdout_layer)
+        .with(file_layer)
+        .init();
+
+    if let Some(e) = file_error {
+        tracing::error!(error = %e, "logging to stdout only");
+    }
+    LogGuards(guards)
+}
+
+/// One output in the configured format
+/// JSON lines carry the fields of every enclosing span, so each event can be
+/// aggregated by request path or composite key
+fn format_layer<S, W>(writer: W, json: bool, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = fmt::layer().with_target(false).with_writer(writer);
+    if json {
+        layer.json().with_current_span(false).with_span_list(true).boxed()
+    } else {
+        layer.with_ansi(ansi).boxed()
+    }
+}
+
+/// Run each request inside a span with its method and path
+/// `security_middleware` fills in the composite key once it's known
+pub async fn request_span(req: Request, next: Next) -> Response {
+    let span = tracing::info_span!(
+        "request",
+        method = %req.method(),
+        path = %req.uri().path(),
+        composite_key = tracing::field::Empty,
+    );
+    async move {
+        let started = std::time::Instant::now();
+        let response = next.run(req).await;
+        tracing::debug!(status = response.status().as_u16(), elapsed_ms = started.elapsed().as_millis() as u64, "request finished");
+        response
+    }
+    .instrument(span)
+    .await
+}
+
+fn file_appender(dir: &str) -> Result<RollingFileAppender, tracing_appender::rolling::InitError> {
+    let rotation = match env::var("LOG_ROTATION").unwrap_or_default().to_ascii_lowercase().as_str() {
+        "minutely" => Rotation::MINUTELY,
+        "hourly" => Rotation::HOURLY,
+        "never" => Rotation::NEVER,
+        _ => Rotation::DAILY,
+    };
+    let retention = env::var("LOG_RETENTION_FILES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_RETENTION_FILES)
+        .max(1);
+
+    RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(env::var("LOG_FILE_PREFIX").unwrap_or_else(|_| DEFAULT_LOG_FILE_PREFIX.to_string()))
+        .max_log_files(retention)
+        .build(dir)
+}
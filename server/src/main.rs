@@ -1,23 +1,20 @@
-mod models;
-mod state;
-mod handlers;
-mod websocket;
-mod routes;
-mod redis_client;
-mod security;
-mod scaling;
-
-use tower_http::cors::CorsLayer;
-use tower_http::timeout::TimeoutLayer;
+use kirb_server::{state, routes, scheduler, admin, ws_handover, metrics_export, logging, recorder, config, cors, deadline, warmup};
 use dotenvy::dotenv;
 use std::env;
 use std::time::Duration;
+use ws_handover::WsHandover;
+
+/// How long shutdown waits for WebSocket clients to be sent away
+const WS_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
     // Load environment variables from .env file
     dotenv().ok();
 
+    // Keep the guards alive so buffered lines are flushed on shutdown
+    let _log_guards = logging::init();
+
     let redis_url = env::var("REDIS_URL")
         .expect("REDIS_URL must be set in .env file");
     
@@ -28,9 +25,14 @@ async fn main() -> Result<(), anyhow::Error> {
     let allowed_origin = env::var("ALLOWED_ORIGIN")
         .expect("ALLOWED_ORIGIN must be set in .env file (e.g., https://yourdomain.com)");
 
-    println!("🔐 Initializing security systems...");
+    tracing::info!("initializing security systems");
     let state = state::AppState::new(&redis_url, server_secret).await?;
-    println!("✅ Security systems initialized");
+    tracing::info!("security systems initialized");
+
+    // `kirb-server replay` re-runs recorded requests instead of serving
+    if let Some(args) = recorder::ReplayArgs::from_args() {
+        return recorder::replay(&state, args).await;
+    }
     
     // Initialize Prometheus metrics exporter
     let builder = metrics_exporter_prometheus::PrometheusBuilder::new();
@@ -40,43 +42,55 @@ async fn main() -> Result<(), anyhow::Error> {
     
     // Initialize custom metrics
     metrics::gauge!("active_websocket_connections", 0.0);
-    metrics::counter!("messages_per_second", 0);
-    metrics::counter!("contact_reveals_total", 0);
+    metrics::gauge!("messages_per_second", 0.0);
+    metrics::gauge!("pubsub_healthy", 1.0);
     
-    println!("📊 Metrics initialized");
+    // Push/StatsD exporters for deployments where /metrics can't be scraped
+    metrics_export::spawn_exporters(prometheus_handle.clone());
+
+    tracing::info!("metrics initialized");
+
+    scheduler::spawn_background_jobs(state.clone());
+
+    // Index, feed cache and pub/sub are readied before the listener is bound
+    warmup::run(&state).await;
+
+    // `kill -HUP` reloads rate limits, masking, wordlists and escalation rules in place
+    #[cfg(unix)]
+    tokio::spawn(config::reload_on_sighup(state.clone()));
+
+    // Serve the admin API on a separate client-certificate listener if configured
+    if let (Some(tls), true) = (state.admin.tls.clone(), state.admin.enabled()) {
+        let admin_state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = admin::serve_mtls(admin_state, tls).await {
+                tracing::error!(error = format!("{:#}", e), "admin API listener stopped");
+            }
+        });
+    }
     
-    // Configure CORS to only allow the specific production domain
-    let cors = CorsLayer::new()
-        .allow_origin(
-            allowed_origin.parse::<axum::http::HeaderValue>()
-                .expect("Invalid ALLOWED_ORIGIN value")
-        )
-        .allow_methods([
-            axum::http::Method::GET,
-            axum::http::Method::POST,
-            axum::http::Method::OPTIONS,
-        ])
-        .allow_headers([
-            axum::http::header::CONTENT_TYPE,
-            axum::http::header::AUTHORIZATION,
-            axum::http::HeaderName::from_static("x-browser-fingerprint"),
-        ])
-        .max_age(Duration::from_secs(3600));
+    // Only the production site (or a tenant's own site, on its API host) may call the API
+    let origin_policy = cors::OriginPolicy::from_env(&allowed_origin);
+    let allowed_origins = origin_policy.describe();
+    let cors = cors::layer(origin_policy);
     
+    let stats = state.stats.clone();
+    let ws_handover = state.ws_handover.clone();
     let app = routes::create_router(state)
         .route("/metrics", axum::routing::get(move || async move {
             prometheus_handle.render()
         }))
-        .layer(TimeoutLayer::new(Duration::from_secs(30))) // 30 second timeout
+        .layer(axum::middleware::from_fn_with_state(deadline::request_timeout_from_env(), deadline::enforce))
+        .layer(axum::middleware::from_fn(logging::request_span))
         .layer(cors);
 
     let port = env::var("PORT").unwrap_or_else(|_| "3001".to_string());
     let addr = format!("0.0.0.0:{}", port);
     
-    println!("🚀 Server running on http://0.0.0.0:{}", port);
-    println!("📊 Metrics available at http://0.0.0.0:{}/metrics", port);
-    println!("🏥 Health check available at http://0.0.0.0:{}/health", port);
-    println!("🌐 CORS enabled for: {}", allowed_origin);
+    tracing::info!(port = %port, "server running");
+    tracing::info!(path = "/metrics", "metrics available");
+    tracing::info!(path = "/health", probes = "/health/live, /health/ready", "health check available");
+    tracing::info!(origins = %allowed_origins, "CORS enabled");
     
     let listener = tokio::net::TcpListener::bind(&addr).await?;
     
@@ -87,19 +101,27 @@ async fn main() -> Result<(), anyhow::Error> {
     );
     
     // Setup graceful shutdown
-    let graceful = server.with_graceful_shutdown(shutdown_signal());
+    let graceful = server.with_graceful_shutdown(shutdown_signal(ws_handover.clone()));
     
-    println!("✅ Server ready for connections (graceful shutdown enabled)");
+    tracing::info!("server ready for connections (graceful shutdown enabled)");
     
     graceful.await?;
+
+    // Upgraded sockets aren't tracked by graceful shutdown; give them time to send their hints
+    ws_handover.drain(WS_DRAIN_TIMEOUT).await;
+
+    // Don't lose the last few seconds of buffered visitor stats
+    if let Err(e) = stats.flush().await {
+        tracing::error!("{}", e);
+    }
     
-    println!("👋 Server shutdown complete");
+    tracing::info!("server shutdown complete");
     
     Ok(())
 }
 
-/// Waits for shutdown signal (CTRL+C or SIGTERM)
-async fn shutdown_signal() {
+/// Waits for shutdown signal (CTRL+C or SIGTERM), then tells WebSocket clients to move on
+async fn shutdown_signal(ws_handover: WsHandover) {
     use tokio::signal;
     
     let ctrl_c = async {
@@ -121,10 +143,12 @@ async fn shutdown_signal() {
 
     tokio::select! {
         _ = ctrl_c => {
-            println!("\n🛑 Received shutdown signal (CTRL+C), shutting down gracefully...");
+            tracing::info!(signal = "SIGINT", "shutting down gracefully");
         },
         _ = terminate => {
-            println!("\n🛑 Received SIGTERM signal, shutting down gracefully...");
+            tracing::info!(signal = "SIGTERM", "shutting down gracefully");
         },
     }
+
+    ws_handover.begin_shutdown();
 }
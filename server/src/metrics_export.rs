@@ -0,0 +1,251 @@
+use metrics_exporter_prometheus::PrometheusHandle;
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+const DEFAULT_PUSH_INTERVAL_SECONDS: u64 = 15;
+const DEFAULT_PUSH_JOB: &str = "kirb-server";
+const DEFAULT_STATSD_INTERVAL_SECONDS: u64 = 10;
+/// Keep datagrams under a typical MTU
+const STATSD_MAX_PACKET_BYTES: usize = 1400;
+
+/// Start the optional exporters for deployments that can't be scraped
+/// Both read the same registry that backs `/metrics`:
+/// - `METRICS_PUSHGATEWAY_URL` pushes the Prometheus text format every
+///   `METRICS_PUSH_INTERVAL_SECONDS` to `<url>/metrics/job/<METRICS_PUSH_JOB>/instance/<instance>`
+/// - `STATSD_ADDR` sends DogStatsD datagrams every `STATSD_INTERVAL_SECONDS`,
+///   names prefixed with `STATSD_PREFIX` and labels sent as tags
+pub fn spawn_exporters(handle: PrometheusHandle) {
+    let interval = |var: &str, default: u64| {
+        Duration::from_secs(env::var(var).ok().and_then(|v| v.parse().ok()).unwrap_or(default).max(1))
+    };
+
+    if let Some(url) = env::var("METRICS_PUSHGATEWAY_URL").ok().filter(|v| !v.is_empty()) {
+        let job = env::var("METRICS_PUSH_JOB").unwrap_or_else(|_| DEFAULT_PUSH_JOB.to_string());
+        let endpoint = format!("{}/metrics/job/{}/instance/{}", url.trim_end_matches('/'), job, instance_name());
+        tracing::info!(endpoint = %endpoint, "pushing metrics");
+        tokio::spawn(run_push_gateway(
+            handle.clone(),
+            endpoint,
+            interval("METRICS_PUSH_INTERVAL_SECONDS", DEFAULT_PUSH_INTERVAL_SECONDS),
+        ));
+    }
+
+    if let Some(addr) = env::var("STATSD_ADDR").ok().filter(|v| !v.is_empty()) {
+        let prefix = env::var("STATSD_PREFIX").unwrap_or_default();
+        tracing::info!(addr = %addr, "sending StatsD metrics");
+        tokio::spawn(run_statsd(
+            handle,
+            addr,
+            prefix,
+            interval("STATSD_INTERVAL_SECONDS", DEFAULT_STATSD_INTERVAL_SECONDS),
+        ));
+    }
+}
+
+fn instance_name() -> String {
+    env::var("INSTANCE_ID")
+        .or_else(|_| env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+async fn run_push_gateway(handle: PrometheusHandle, endpoint: String, interval: Duration) {
+    let client = reqwest::Client::new();
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+
+        let result = client
+            .put(&endpoint)
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(handle.render())
+            .timeout(interval)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = result {
+            metrics::counter!("metrics_export_failures_total", 1, "exporter" => "push_gateway");
+            tracing::error!("Failed to push metrics: {}", e);
+        }
+    }
+}
+
+async fn run_statsd(handle: PrometheusHandle, addr: String, prefix: String, interval: Duration) {
+    let socket = match UdpSocket::bind("0.0.0.0:0").await {
+        Ok(socket) => socket,
+        Err(e) => {
+            tracing::error!(error = %e, "StatsD exporter disabled, failed to bind UDP socket");
+            return;
+        }
+    };
+    let mut translator = StatsdTranslator::new(prefix);
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+
+        for packet in translator.translate(&handle.render()) {
+            if let Err(e) = socket.send_to(packet.as_bytes(), &addr).await {
+                metrics::counter!("metrics_export_failures_total", 1, "exporter" => "statsd");
+                tracing::error!("Failed to send StatsD metrics: {}", e);
+                break;
+            }
+        }
+    }
+}
+
+/// One sample line of the Prometheus text format
+#[derive(Debug, PartialEq)]
+struct Sample<'a> {
+    name: &'a str,
+    labels: Vec<(&'a str, &'a str)>,
+    value: f64,
+}
+
+fn parse_sample(line: &str) -> Option<Sample<'_>> {
+    let (series, value) = line.rsplit_once(' ')?;
+    let value = value.parse().ok()?;
+
+    let Some((name, labels)) = series.split_once('{') else {
+        return Some(Sample { name: series, labels: Vec::new(), value });
+    };
+    let mut parsed = Vec::new();
+    let mut rest = labels.strip_suffix('}')?;
+    while !rest.is_empty() {
+        let (key, after) = rest.split_once("=\"")?;
+        // Label values escape quotes as \"
+        let mut end = 0;
+        let bytes = after.as_bytes();
+        while end < bytes.len() && !(bytes[end] == b'"' && (end == 0 || bytes[end - 1] != b'\\')) {
+            end += 1;
+        }
+        parsed.push((key, after.get(..end)?));
+        rest = after.get(end + 1..)?.trim_start_matches(',');
+    }
+    Some(Sample { name, labels: parsed, value })
+}
+
+/// Converts rendered Prometheus metrics into DogStatsD datagrams
+/// Counters (and summary `_sum`/`_count` series) become `|c` deltas since the
+/// previous flush; everything else is sent as a `|g` gauge
+struct StatsdTranslator {
+    prefix: String,
+    /// Last cumulative value per counter series; a series is only sent once it has a baseline
+    previous: HashMap<String, f64>,
+}
+
+impl StatsdTranslator {
+    fn new(prefix: String) -> Self {
+        Self { prefix, previous: HashMap::new() }
+    }
+
+    fn translate(&mut self, rendered: &str) -> Vec<String> {
+        let mut types: HashMap<&str, &str> = HashMap::new();
+        let mut lines = Vec::new();
+
+        for line in rendered.lines() {
+            if let Some(declaration) = line.strip_prefix("# TYPE ") {
+                if let Some((name, kind)) = declaration.split_once(' ') {
+                    types.insert(name, kind);
+                }
+                continue;
+            }
+            if line.starts_with('#') || line.is_empty() {
+                continue;
+            }
+            let Some(sample) = parse_sample(line) else {
+                continue;
+            };
+
+            let family = sample.name
+                .strip_suffix("_sum")
+                .or_else(|| sample.name.strip_suffix("_count"))
+                .filter(|family| matches!(types.get(family), Some(&"summary") | Some(&"histogram")));
+            let is_counter = family.is_some() || types.get(sample.name) == Some(&"counter");
+
+            let tags = sample.labels
+                .iter()
+                .map(|(key, value)| format!("{}:{}", key, value.replace([',', '|', '#'], "_")))
+                .collect::<Vec<_>>()
+                .join(",");
+            let tags = if tags.is_empty() { String::new() } else { format!("|#{}", tags) };
+
+            if is_counter {
+                let previous = self.previous.insert(format!("{}{}", sample.name, tags), sample.value);
+                let Some(previous) = previous else {
+                    continue;
+                };
+                // A drop means the process restarted its counter
+                let delta = if sample.value >= previous { sample.value - previous } else { sample.value };
+                if delta > 0.0 {
+                    lines.push(format!("{}{}:{}|c{}", self.prefix, sample.name, delta, tags));
+                }
+            } else {
+                lines.push(format!("{}{}:{}|g{}", self.prefix, sample.name, sample.value, tags));
+            }
+        }
+
+        pack(lines)
+    }
+}
+
+/// Join metric lines into datagrams no larger than the packet budget
+fn pack(lines: Vec<String>) -> Vec<String> {
+    let mut packets: Vec<String> = Vec::new();
+    for line in lines {
+        match packets.last_mut() {
+            Some(packet) if packet.len() + 1 + line.len() <= STATSD_MAX_PACKET_BYTES => {
+                packet.push('\n');
+                packet.push_str(&line);
+            }
+            _ => packets.push(line),
+        }
+    }
+    packets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sample() {
+        assert_eq!(
+            parse_sample(r#"http_requests_total{group="reads",tier="a \"b\""} 42"#),
+            Some(Sample {
+                name: "http_requests_total",
+                labels: vec![("group", "reads"), ("tier", r#"a \"b\""#)],
+                value: 42.0,
+            })
+        );
+        assert_eq!(
+            parse_sample("messages_per_second 1.5"),
+            Some(Sample { name: "messages_per_second", labels: vec![], value: 1.5 })
+        );
+        assert_eq!(parse_sample("garbage"), None);
+    }
+
+    #[test]
+    fn test_counters_become_deltas() {
+        let mut translator = StatsdTranslator::new("krib.".to_string());
+        let render = |count: u64| format!(
+            "# TYPE posts_total counter\nposts_total{{city=\"Pune\"}} {}\n# TYPE active gauge\nactive 3\n",
+            count
+        );
+
+        // First flush only establishes the counter baseline
+        assert_eq!(translator.translate(&render(10)), vec!["krib.active:3|g".to_string()]);
+        assert_eq!(
+            translator.translate(&render(15)),
+            vec!["krib.posts_total:5|c|#city:Pune\nkrib.active:3|g".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_pack_respects_packet_size() {
+        let line = "x".repeat(800);
+        assert_eq!(pack(vec![line.clone(), line.clone(), "y".to_string()]).len(), 2);
+    }
+}
@@ -1,4 +1,16 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::posting::PostRejection;
+use crate::security::rate_limiter::RateLimitResult;
+use crate::security::language::Language;
+use crate::state::MESSAGE_TTL;
+use crate::translation::Translation;
 
 /// Sanitize HTML content to prevent XSS attacks
 /// Allows safe HTML tags and removes potentially dangerous ones
@@ -20,6 +32,175 @@ pub struct ChatMessage {
     pub phone: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub location: Option<String>,
+    /// Detected language, kept for analytics and language filtering
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<Language>,
+    /// Whether the poster has confirmed the listing is still available
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub availability: Option<Availability>,
+    /// Set on responses for listings an admin pinned to the top of their city
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// When the listing expires (unix seconds), moved forward by renewals
+    /// Missing on listings stored before it was tracked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Structured fields the poster filled in next to the free text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<ListingDetails>,
+    /// When the poster last edited the listing (unix seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<u64>,
+}
+
+/// A change to a listing already in the feed, broadcast on the same channel as new
+/// listings (which are sent bare) so every connected client can update it in place
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ListingEvent {
+    /// The poster edited the listing; `message` is its new content
+    MessageUpdated { message: ChatMessage },
+    /// The listing was taken down after it was broadcast; clients drop it
+    MessageDeleted {
+        message_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        location: Option<String>,
+    },
+}
+
+impl ListingEvent {
+    pub fn message_id(&self) -> &str {
+        match self {
+            ListingEvent::MessageUpdated { message } => &message.id,
+            ListingEvent::MessageDeleted { message_id, .. } => message_id,
+        }
+    }
+
+    /// City of the listing, for per-city socket filters
+    pub fn location(&self) -> Option<&str> {
+        match self {
+            ListingEvent::MessageUpdated { message } => message.location.as_deref(),
+            ListingEvent::MessageDeleted { location, .. } => location.as_deref(),
+        }
+    }
+
+    /// The event as sent to sockets: contact numbers are only available via the API
+    pub fn without_phone(self) -> Self {
+        match self {
+            ListingEvent::MessageUpdated { message } => ListingEvent::MessageUpdated {
+                message: ChatMessage { phone: None, ..message },
+            },
+            deleted => deleted,
+        }
+    }
+}
+
+/// "Still available?" confirmation state of a listing near expiry
+/// A single listing, optionally with a translated rendering next to the original
+#[derive(Debug, Serialize)]
+pub struct MessageResponse {
+    #[serde(flatten)]
+    pub message: ChatMessage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub translation: Option<Translation>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Availability {
+    /// The poster was asked to confirm and hasn't answered yet
+    Pending { prompted_at: u64 },
+    /// The poster confirmed and the listing was renewed
+    Confirmed { confirmed_at: u64 },
+}
+
+/// Longest locality accepted on a listing
+pub const MAX_LOCALITY_LENGTH: usize = 64;
+/// Highest monthly rent or deposit accepted, in rupees
+pub const MAX_LISTING_AMOUNT: u32 = 10_000_000;
+/// Largest flat size accepted, in bedrooms
+pub const MAX_BHK: u8 = 10;
+
+/// Structured fields of a listing, so feeds can be filtered and sorted on them
+/// instead of parsing the message text. Checked while deserializing: a post with
+/// an out-of-range value is rejected before it reaches the posting pipeline
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RawListingDetails")]
+pub struct ListingDetails {
+    /// Monthly rent in rupees
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rent: Option<u32>,
+    /// Security deposit in rupees; 0 means no deposit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deposit: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bhk: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub furnished: Option<Furnishing>,
+    /// Neighbourhood within the listing's city
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locality: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Furnishing {
+    Furnished,
+    SemiFurnished,
+    Unfurnished,
+}
+
+/// `ListingDetails` as sent, before validation
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawListingDetails {
+    #[serde(default)]
+    rent: Option<u32>,
+    #[serde(default)]
+    deposit: Option<u32>,
+    #[serde(default)]
+    bhk: Option<u8>,
+    #[serde(default)]
+    furnished: Option<Furnishing>,
+    #[serde(default)]
+    locality: Option<String>,
+}
+
+impl TryFrom<RawListingDetails> for ListingDetails {
+    type Error = String;
+
+    fn try_from(raw: RawListingDetails) -> Result<Self, Self::Error> {
+        if raw.rent.is_some_and(|rent| rent == 0 || rent > MAX_LISTING_AMOUNT) {
+            return Err(format!("rent must be between 1 and {}", MAX_LISTING_AMOUNT));
+        }
+        if raw.deposit.is_some_and(|deposit| deposit > MAX_LISTING_AMOUNT) {
+            return Err(format!("deposit must be at most {}", MAX_LISTING_AMOUNT));
+        }
+        if raw.bhk.is_some_and(|bhk| bhk == 0 || bhk > MAX_BHK) {
+            return Err(format!("bhk must be between 1 and {}", MAX_BHK));
+        }
+
+        // A blank locality is the same as none
+        let locality = raw.locality.map(|l| l.trim().to_string()).filter(|l| !l.is_empty());
+        if locality.as_ref().is_some_and(|l| l.chars().count() > MAX_LOCALITY_LENGTH) {
+            return Err(format!("locality must be at most {} characters", MAX_LOCALITY_LENGTH));
+        }
+
+        Ok(Self {
+            rent: raw.rent,
+            deposit: raw.deposit,
+            bhk: raw.bhk,
+            furnished: raw.furnished,
+            locality: locality.map(|l| sanitize_html(&l)),
+        })
+    }
+}
+
+impl ListingDetails {
+    /// Whether no field was filled in
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -29,7 +210,7 @@ pub enum MessageType {
     Requested,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Debug)]
 pub struct PostMessageRequest {
     pub browser_id: String,
     pub message: String,
@@ -39,22 +220,74 @@ pub struct PostMessageRequest {
     #[serde(default)]
     pub website: Option<String>,
     pub location: Option<String>,
+    /// Token from a fixable rejection, resubmitting corrected content
+    #[serde(default)]
+    pub correction_token: Option<String>,
+    /// Privacy policy version the poster accepted, required to share a phone number
+    #[serde(default)]
+    pub consent: Option<String>,
+    /// Optional structured fields (rent, deposit, size, furnishing, locality)
+    #[serde(default)]
+    pub details: Option<ListingDetails>,
+    /// Turnstile/hCaptcha response, required once the poster is asked for a captcha
+    #[serde(default)]
+    pub captcha_token: Option<String>,
+}
+
+/// New text for a listing its poster is editing; the details replace the old ones
+#[derive(Deserialize)]
+pub struct EditMessageRequest {
+    pub message: String,
+    #[serde(default)]
+    pub details: Option<ListingDetails>,
 }
 
 impl ChatMessage {
     pub fn new(browser_id: String, message: String, message_type: MessageType, phone: Option<String>, location: Option<String>) -> Self {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
         Self {
             id: uuid::Uuid::new_v4().to_string(),
             browser_id,
             // Sanitize message content to prevent XSS
             message: sanitize_html(&message),
             message_type,
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            timestamp,
             phone,
             location,
+            language: None,
+            availability: None,
+            pinned: false,
+            expires_at: Some(timestamp + MESSAGE_TTL),
+            details: None,
+            edited_at: None,
+        }
+    }
+
+    /// This listing with the poster's new text and details
+    pub fn edited(self, message: &str, details: Option<ListingDetails>, language: Language) -> Self {
+        let edited_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        Self {
+            // Sanitize message content to prevent XSS
+            message: sanitize_html(message),
+            details: details.filter(|details| !details.is_empty()),
+            language: Some(language),
+            edited_at: Some(edited_at),
+            ..self
+        }
+    }
+
+    /// Everything the poster wrote in free text, for the moderation provider:
+    /// the message, then the locality if there is one
+    pub fn screened_text(&self) -> String {
+        match self.details.as_ref().and_then(|details| details.locality.as_deref()) {
+            Some(locality) => format!("{}\n{}", self.message, locality),
+            None => self.message.clone(),
         }
     }
 
@@ -65,6 +298,85 @@ impl ChatMessage {
     }
 }
 
+/// Longest listing text in the lite projection, in characters
+pub const LITE_MESSAGE_CHARS: usize = 140;
+
+/// Feed projection for feature phones and poor connections (`?fields=lite`):
+/// only what a one-line feed entry needs, with the text cut short
+#[derive(Debug, Serialize)]
+pub struct LiteListing {
+    pub id: String,
+    pub message: String,
+    pub message_type: MessageType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    pub timestamp: u64,
+    /// The full text is at `/messages/:id`
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+impl From<ChatMessage> for LiteListing {
+    fn from(message: ChatMessage) -> Self {
+        let (text, truncated) = truncate_listing_text(&message.message, LITE_MESSAGE_CHARS);
+        Self {
+            id: message.id,
+            message: text,
+            message_type: message.message_type,
+            city: message.location,
+            timestamp: message.timestamp,
+            truncated,
+        }
+    }
+}
+
+/// Cut stored (HTML-escaped) text to at most `max_chars` plus an ellipsis,
+/// preferring a word boundary and never splitting an escape like `&amp;`
+fn truncate_listing_text(text: &str, max_chars: usize) -> (String, bool) {
+    let Some((cut, _)) = text.char_indices().nth(max_chars) else {
+        return (text.to_string(), false);
+    };
+    let mut head = &text[..cut];
+    if let Some(amp) = head.rfind('&') {
+        if !head[amp..].contains(';') {
+            head = &head[..amp];
+        }
+    }
+    // Back up to the last space unless that would drop most of the text
+    if let Some(space) = head.rfind(char::is_whitespace) {
+        if space >= head.len() / 2 {
+            head = &head[..space];
+        }
+    }
+    (format!("{}…", head.trim_end()), true)
+}
+
+/// Contact reveals left in the current window
+#[derive(Debug, Serialize)]
+pub struct RevealQuota {
+    pub remaining: i64,
+    pub limit: i64,
+    /// When the next reveal frees up (unix seconds); now if none were used
+    pub reset_at: u64,
+    pub reset_in_seconds: u64,
+}
+
+impl RevealQuota {
+    pub fn new(remaining: i64, limit: i64, reset_at: u64) -> Self {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        Self {
+            remaining: remaining.max(0),
+            limit,
+            reset_at,
+            reset_in_seconds: reset_at.saturating_sub(now),
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct RateLimitError {
     pub error: String,
@@ -80,11 +392,7 @@ impl RateLimitError {
             .unwrap()
             .as_secs();
         
-        let seconds_remaining = if retry_after > now {
-            retry_after - now
-        } else {
-            0
-        };
+        let seconds_remaining = retry_after.saturating_sub(now);
         
         Self {
             error: "rate_limit_exceeded".to_string(),
@@ -110,10 +418,144 @@ impl ContentFilterError {
     }
 }
 
+/// Error returned by the public API handlers
+/// Every variant renders as `{"error": ..., "code": ...}` plus variant-specific
+/// fields; `code` is the stable, machine-readable part clients should branch on
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    Unauthorized(&'static str),
+    Forbidden(&'static str),
+    NotFound(&'static str),
+    Conflict(&'static str),
+    /// Keeps the `RateLimitError` fields; contact reveals also carry the quota
+    /// Sent with the `RateLimitResult` headers
+    RateLimited { result: RateLimitResult, quota: Option<RevealQuota> },
+    ContentViolation { reason: String },
+    /// A post or edit turned away by `PostingService`; its `kind()` is the code
+    Rejected(PostRejection),
+    NotImplemented(&'static str),
+    /// Temporarily refusing work; sent with a `Retry-After` header
+    Unavailable { error: &'static str, retry_after: u64 },
+    /// A call to an outside service (e.g. translation) failed
+    Upstream(&'static str),
+    Internal(&'static str),
+}
+
+impl ApiError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::RateLimited { .. } => "rate_limited",
+            ApiError::ContentViolation { .. } => "content_violation",
+            ApiError::Rejected(rejection) => rejection.kind(),
+            ApiError::NotImplemented(_) => "not_implemented",
+            ApiError::Unavailable { .. } => "unavailable",
+            ApiError::Upstream(_) => "upstream_failed",
+            ApiError::Internal(_) => "internal",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) | ApiError::ContentViolation { .. } => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Rejected(rejection) => rejection.status(),
+            ApiError::NotImplemented(_) => StatusCode::NOT_IMPLEMENTED,
+            ApiError::Unavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn body(&self) -> serde_json::Value {
+        let mut body = match self {
+            ApiError::BadRequest(error) => json!({"error": error}),
+            ApiError::Unauthorized(error)
+            | ApiError::Forbidden(error)
+            | ApiError::NotFound(error)
+            | ApiError::Conflict(error)
+            | ApiError::NotImplemented(error)
+            | ApiError::Unavailable { error, .. }
+            | ApiError::Upstream(error)
+            | ApiError::Internal(error) => json!({"error": error}),
+            ApiError::RateLimited { result, quota } => {
+                let mut body = json!(RateLimitError::new(result.reset_at));
+                if let Some(quota) = quota {
+                    body["quota"] = json!(quota);
+                }
+                body
+            }
+            ApiError::ContentViolation { reason } => json!(ContentFilterError::new(reason.clone())),
+            ApiError::Rejected(rejection) => rejection.body(),
+        };
+        body["code"] = json!(self.code());
+        body
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = Json(self.body());
+        match &self {
+            ApiError::Unavailable { retry_after, .. } => {
+                (self.status(), [(header::RETRY_AFTER, retry_after.to_string())], body).into_response()
+            }
+            ApiError::RateLimited { result, .. } => (self.status(), result.headers(), body).into_response(),
+            ApiError::Rejected(rejection) => (self.status(), rejection.headers(), body).into_response(),
+            _ => (self.status(), body).into_response(),
+        }
+    }
+}
+
+impl From<PostRejection> for ApiError {
+    fn from(rejection: PostRejection) -> Self {
+        ApiError::Rejected(rejection)
+    }
+}
+
+/// Why a listing was reported
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportReason {
+    Spam,
+    Scam,
+    Abusive,
+    #[serde(alias = "wrong-category")]
+    WrongCategory,
+}
+
+impl ReportReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportReason::Spam => "spam",
+            ReportReason::Scam => "scam",
+            ReportReason::Abusive => "abusive",
+            ReportReason::WrongCategory => "wrong_category",
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct ReportMessageRequest {
     pub message_id: String,
     pub reported_browser_id: String,
+    /// Older clients don't send one
+    #[serde(default)]
+    pub reason: Option<ReportReason>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RefreshSessionRequest {
+    pub refresh_token: String,
 }
 
 #[derive(Serialize)]
@@ -121,4 +563,243 @@ pub struct ReportResponse {
     pub success: bool,
     pub message: String,
     pub reports_on_ip: usize,
+    /// The caller had already reported this listing; the report wasn't counted again
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub already_reported: bool,
+}
+
+/// Command frame sent by a WebSocket client
+/// `id` is an optional client-supplied correlation id echoed back in the ack/error frame
+#[derive(Deserialize, Debug)]
+pub struct WsClientFrame {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub command: WsCommand,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsCommand {
+    /// Liveness check - always acknowledged
+    Ping,
+    /// Only receive listings posted in `city` from now on
+    Subscribe { city: String },
+    /// Receive listings from every city again
+    Unsubscribe,
+    /// Post a listing, answered with a `posted` or `rejected` frame
+    /// Takes the same fields as `POST /messages` and goes through the same checks
+    Post(Box<PostMessageRequest>),
+}
+
+/// Error codes carried by WebSocket error frames
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WsErrorCode {
+    /// The frame was not valid JSON or not a known command
+    InvalidFrame,
+    /// The connection's IP is blocked or over its request rate
+    RateLimited,
+    /// The server is too busy to take the command; retry shortly
+    Unavailable,
+}
+
+/// Why the server closed a WebSocket, sent as an application close code (4000-4999)
+/// with the snake_case name as the close reason
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WsCloseReason {
+    /// The instance is shutting down; reconnect after the `shutdown` event's hint
+    ServerShutdown,
+    /// The client couldn't keep up with the broadcast stream; reconnect and refetch
+    SlowClient,
+    /// The client kept sending invalid frames; don't reconnect automatically
+    PolicyViolation,
+}
+
+impl WsCloseReason {
+    pub fn code(&self) -> u16 {
+        match self {
+            WsCloseReason::ServerShutdown => 4000,
+            WsCloseReason::SlowClient => 4001,
+            WsCloseReason::PolicyViolation => 4002,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WsCloseReason::ServerShutdown => "server_shutdown",
+            WsCloseReason::SlowClient => "slow_client",
+            WsCloseReason::PolicyViolation => "policy_violation",
+        }
+    }
+}
+
+/// Per-command response frame sent from the server to a WebSocket client
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsResponseFrame {
+    Ack {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+    },
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        code: WsErrorCode,
+        message: String,
+    },
+    /// A `post` command's listing, as `POST /messages` would have returned it
+    Posted {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        message: Box<ChatMessage>,
+    },
+    /// A `post` command turned away; `code` is the rejection kind and the rest is
+    /// the body `POST /messages` would have sent
+    Rejected {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        code: &'static str,
+        #[serde(flatten)]
+        body: serde_json::Value,
+    },
+}
+
+/// Server-initiated event sent to a client, over its actor channel or its own socket
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsServerEvent {
+    /// Ask the poster whether a listing close to expiry is still available
+    AvailabilityPrompt {
+        message_id: String,
+        expires_in: u64,
+    },
+    /// Someone reacted to the poster's listing
+    Reaction {
+        message_id: String,
+    },
+    /// The poster's listing was reported and is under review
+    UnderReview {
+        message_id: String,
+    },
+    /// Someone revealed the contact number on the poster's listing
+    ContactRequested {
+        message_id: String,
+        city: Option<String>,
+    },
+    /// This instance is shutting down; reconnect after the hinted delay
+    Shutdown {
+        reconnect_after_ms: u64,
+    },
+    /// The resumed connection missed more than can be replayed; refetch the feed
+    ResyncRequired,
+}
+
+impl WsServerEvent {
+    /// Metric label for the event type
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WsServerEvent::AvailabilityPrompt { .. } => "availability_prompt",
+            WsServerEvent::Reaction { .. } => "reaction",
+            WsServerEvent::UnderReview { .. } => "under_review",
+            WsServerEvent::ContactRequested { .. } => "contact_requested",
+            WsServerEvent::Shutdown { .. } => "shutdown",
+            WsServerEvent::ResyncRequired => "resync_required",
+        }
+    }
+}
+
+impl WsResponseFrame {
+    pub fn ack(id: Option<String>) -> Self {
+        WsResponseFrame::Ack { id }
+    }
+
+    pub fn error(id: Option<String>, code: WsErrorCode, message: impl Into<String>) -> Self {
+        WsResponseFrame::Error {
+            id,
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lite_text_is_cut_at_a_word_boundary() {
+        assert_eq!(truncate_listing_text("2BHK in Baner", 140), ("2BHK in Baner".to_string(), false));
+        assert_eq!(truncate_listing_text("2BHK flat in Baner", 12), ("2BHK flat…".to_string(), true));
+        // Character-based, so Devanagari isn't split inside a code point
+        let (text, truncated) = truncate_listing_text("कमरा उपलब्ध है", 6);
+        assert!(truncated);
+        assert_eq!(text, "कमरा…");
+    }
+
+    #[test]
+    fn test_lite_text_never_splits_an_escape() {
+        let (text, _) = truncate_listing_text("Rent&amp;deposit negotiable", 7);
+        assert_eq!(text, "Rent…");
+    }
+
+    #[test]
+    fn test_report_reason_is_optional_and_accepts_kebab_case() {
+        let request: ReportMessageRequest =
+            serde_json::from_str(r#"{"message_id":"m1","reported_browser_id":"b1"}"#).unwrap();
+        assert_eq!(request.reason, None);
+        let request: ReportMessageRequest = serde_json::from_str(
+            r#"{"message_id":"m1","reported_browser_id":"b1","reason":"wrong-category"}"#
+        ).unwrap();
+        assert_eq!(request.reason, Some(ReportReason::WrongCategory));
+    }
+
+    #[test]
+    fn test_api_errors_carry_a_stable_code() {
+        let not_found = ApiError::NotFound("Message not found");
+        assert_eq!(not_found.status(), StatusCode::NOT_FOUND);
+        assert_eq!(not_found.body(), json!({"error": "Message not found", "code": "not_found"}));
+
+        // Rate limits keep the fields clients already read
+        let result = RateLimitResult { allowed: false, limit: 1, remaining: 0, reset_at: 0 };
+        let limited = ApiError::RateLimited { result, quota: None }.body();
+        assert_eq!(limited["code"], "rate_limited");
+        assert_eq!(limited["error"], "rate_limit_exceeded");
+        assert_eq!(limited["retry_after_seconds"], 0);
+
+        let rejected = ApiError::from(PostRejection::Empty);
+        assert_eq!(rejected.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(rejected.body()["code"], "empty");
+    }
+
+    #[test]
+    fn test_listing_details_are_validated_while_parsing() {
+        let details: ListingDetails = serde_json::from_str(
+            r#"{"rent":18000,"deposit":0,"bhk":2,"furnished":"semi_furnished","locality":"  Baner "}"#,
+        )
+        .unwrap();
+        assert_eq!(details.rent, Some(18000));
+        assert_eq!(details.furnished, Some(Furnishing::SemiFurnished));
+        assert_eq!(details.locality.as_deref(), Some("Baner"));
+
+        assert!(serde_json::from_str::<ListingDetails>(r#"{"locality":" "}"#).unwrap().is_empty());
+        for invalid in [r#"{"rent":0}"#, r#"{"bhk":11}"#, r#"{"deposit":-1}"#, r#"{"furnished":"yes"}"#, r#"{"area":900}"#] {
+            assert!(serde_json::from_str::<ListingDetails>(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_locality_is_screened_with_the_text() {
+        let details: ListingDetails = serde_json::from_str(r#"{"locality":"Baner, call 9876543210"}"#).unwrap();
+        let message = ChatMessage {
+            details: Some(details),
+            ..ChatMessage::new("b1".into(), "2BHK available".into(), MessageType::Offered, None, None)
+        };
+        assert_eq!(message.screened_text(), "2BHK available\nBaner, call 9876543210");
+
+        // The filter `PostingService` runs the locality through turns a phone number away
+        let locality = message.details.as_ref().and_then(|details| details.locality.as_deref()).unwrap();
+        let result = crate::security::content_filter::ContentFilter::new().check_message(locality);
+        assert_eq!(result.violation_type, Some(crate::security::content_filter::ViolationType::EmbeddedPhone));
+    }
 }
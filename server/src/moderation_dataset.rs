@@ -0,0 +1,223 @@
+use anyhow::{Result, anyhow};
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::redis_client::RedisClient;
+use crate::security::TokenSigner;
+use crate::keys;
+
+/// How long daily outcome lists are kept for export
+const DATASET_TTL_SECONDS: i64 = 7776000; // 90 days
+/// Longest date range a single export may cover
+pub const MAX_EXPORT_DAYS: i64 = 31;
+/// Reports are joined from this many days past the end of the range,
+/// since they typically arrive after the post
+const REPORT_JOIN_DAYS: i64 = 7;
+
+/// What moderation decided for a post
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Allowed,
+    Masked,
+    Blocked,
+}
+
+/// Anonymized record of one moderation decision
+/// Carries a keyed hash of the normalized text, never the text or the poster
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationOutcome {
+    pub text_hash: String,
+    pub verdict: Verdict,
+    /// Rules that matched, e.g. "profanity", "spam", "suspicious_pattern"
+    pub rules: Vec<String>,
+    pub toxicity: f64,
+    pub language: String,
+    pub city: Option<String>,
+    /// Only known for posts that were stored
+    pub message_id: Option<String>,
+    pub timestamp: i64,
+}
+
+/// What happened to a stored post after users reported it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportAction {
+    Reported,
+    Frozen,
+    Deleted,
+    PosterShadowbanned,
+    /// A moderator reviewed the reports and kept the post
+    Approved,
+    /// A moderator reviewed the reports and removed the post
+    RemovedByModerator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportOutcome {
+    pub message_id: String,
+    pub action: ReportAction,
+    pub timestamp: i64,
+}
+
+/// One exported line: a moderation outcome with the report history of its post
+#[derive(Debug, Clone, Serialize)]
+pub struct DatasetRow {
+    #[serde(flatten)]
+    pub outcome: ModerationOutcome,
+    pub reports: Vec<ReportOutcome>,
+}
+
+/// Daily lists of moderation and report outcomes for offline analysis
+/// (model training, threshold tuning)
+#[derive(Clone)]
+pub struct ModerationDataset {
+    redis: RedisClient,
+    hasher: TokenSigner,
+}
+
+impl ModerationDataset {
+    pub fn new(redis: RedisClient, server_secret: &str) -> Self {
+        Self {
+            redis,
+            hasher: TokenSigner::new(server_secret, "moderation-dataset"),
+        }
+    }
+
+    /// Keyed hash of the normalized text, so duplicates line up across the
+    /// dataset without the text itself being recoverable by dictionary lookup
+    pub fn text_hash(&self, text: &str) -> String {
+        self.hasher.digest(normalize(text).as_bytes())
+    }
+
+    pub async fn record_outcome(&self, outcome: &ModerationOutcome) -> Result<()> {
+        self.push(&outcomes_key(&day_of(outcome.timestamp)), &serde_json::to_string(outcome)?)
+            .await
+            .map_err(|e| anyhow!("Failed to record moderation outcome: {}", e))
+    }
+
+    pub async fn record_report(&self, message_id: &str, action: ReportAction) -> Result<()> {
+        let report = ReportOutcome {
+            message_id: message_id.to_string(),
+            action,
+            timestamp: chrono::Utc::now().timestamp(),
+        };
+        self.push(&reports_key(&day_of(report.timestamp)), &serde_json::to_string(&report)?)
+            .await
+            .map_err(|e| anyhow!("Failed to record report outcome: {}", e))
+    }
+
+    async fn push(&self, key: &str, value: &str) -> Result<(), redis::RedisError> {
+        self.redis.rpush(key, value).await?;
+        self.redis.expire(key, DATASET_TTL_SECONDS).await?;
+        Ok(())
+    }
+
+    /// Outcomes recorded between two dates (inclusive), joined with their reports
+    pub async fn export(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<DatasetRow>> {
+        let mut outcomes = Vec::new();
+        for day in days(from, to) {
+            outcomes.extend(self.read::<ModerationOutcome>(&outcomes_key(&day)).await?);
+        }
+
+        let mut reports = Vec::new();
+        for day in days(from, to + Duration::days(REPORT_JOIN_DAYS)) {
+            reports.extend(self.read::<ReportOutcome>(&reports_key(&day)).await?);
+        }
+
+        Ok(join(outcomes, reports))
+    }
+
+    async fn read<T: serde::de::DeserializeOwned>(&self, key: &str) -> Result<Vec<T>> {
+        let values = self.redis
+            .lrange(key, 0, -1)
+            .await
+            .map_err(|e| anyhow!("Failed to read moderation dataset: {}", e))?;
+        Ok(values.iter().filter_map(|v| serde_json::from_str(v).ok()).collect())
+    }
+}
+
+/// Lowercase alphanumeric words separated by single spaces, so trivial
+/// punctuation or spacing changes hash the same
+pub fn normalize(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Attach each post's reports to its outcome, in report order
+fn join(outcomes: Vec<ModerationOutcome>, reports: Vec<ReportOutcome>) -> Vec<DatasetRow> {
+    let mut by_message: HashMap<String, Vec<ReportOutcome>> = HashMap::new();
+    for report in reports {
+        by_message.entry(report.message_id.clone()).or_default().push(report);
+    }
+
+    outcomes
+        .into_iter()
+        .map(|outcome| {
+            let reports = outcome.message_id
+                .as_ref()
+                .and_then(|id| by_message.get(id).cloned())
+                .unwrap_or_default();
+            DatasetRow { outcome, reports }
+        })
+        .collect()
+}
+
+fn days(from: NaiveDate, to: NaiveDate) -> impl Iterator<Item = NaiveDate> {
+    from.iter_days().take_while(move |day| *day <= to)
+}
+
+fn day_of(timestamp: i64) -> NaiveDate {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|dt| dt.date_naive())
+        .unwrap_or_default()
+}
+
+fn outcomes_key(day: &NaiveDate) -> String {
+    keys::moderation_dataset(&day.format("%Y-%m-%d").to_string())
+}
+
+fn reports_key(day: &NaiveDate) -> String {
+    keys::moderation_dataset_reports(&day.format("%Y-%m-%d").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(message_id: Option<&str>) -> ModerationOutcome {
+        ModerationOutcome {
+            text_hash: "h".to_string(),
+            verdict: Verdict::Allowed,
+            rules: vec![],
+            toxicity: 0.0,
+            language: "en".to_string(),
+            city: None,
+            message_id: message_id.map(str::to_string),
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("  2BHK flat,  near METRO!!"), "2bhk flat near metro");
+        assert_eq!(normalize("2bhk-flat near metro"), normalize("2BHK flat near metro."));
+    }
+
+    #[test]
+    fn test_join_attaches_reports() {
+        let reports = vec![
+            ReportOutcome { message_id: "a".to_string(), action: ReportAction::Reported, timestamp: 1 },
+            ReportOutcome { message_id: "a".to_string(), action: ReportAction::Deleted, timestamp: 2 },
+            ReportOutcome { message_id: "b".to_string(), action: ReportAction::Reported, timestamp: 3 },
+        ];
+        let rows = join(vec![outcome(Some("a")), outcome(None)], reports);
+        let actions: Vec<_> = rows[0].reports.iter().map(|r| r.action).collect();
+        assert_eq!(actions, vec![ReportAction::Reported, ReportAction::Deleted]);
+        assert!(rows[1].reports.is_empty());
+    }
+}
@@ -0,0 +1,317 @@
+use crate::redis_client::RedisClient;
+use crate::security::audit::{AuditEvent, AuditEventKind};
+use crate::security::escalation::{Signals, Trigger};
+use crate::security::ip_reputation::RiskLevel;
+use crate::security::language::Language;
+use crate::state::AppState;
+use crate::keys;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+
+/// How long a worker waits before polling an empty queue again
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Attempts per job (provider calls or retractions) before it's dropped and the post stays up
+const MAX_ATTEMPTS: u32 = 3;
+/// Workers per instance unless `MODERATION_WORKERS` says otherwise
+const DEFAULT_WORKERS: usize = 4;
+/// How long an instance's claims survive without it refreshing its lease
+const WORKER_LEASE_SECONDS: u64 = 30;
+/// How often the lease is refreshed, stale claims re-queued and the depth gauge updated
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A published post waiting for the moderation provider's verdict
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingModeration {
+    pub message_id: String,
+    pub composite_key: String,
+    pub ip_address: String,
+    /// Header bot score of the posting request, for escalation
+    pub header_score: u8,
+    /// Text and locality as published, after masking (see `ChatMessage::screened_text`)
+    pub text: String,
+    pub language: Language,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    #[serde(default)]
+    pub attempts: u32,
+    pub enqueued_at: u64,
+}
+
+/// A job moved onto this instance's processing list, kept there until acked
+pub struct ClaimedModeration {
+    pub job: PendingModeration,
+    /// The list entry as stored, to remove it exactly
+    raw: String,
+}
+
+/// Posts published before the moderation provider was asked about them,
+/// in a Redis list so any instance's worker can pick them up
+/// Claimed jobs sit on a per-instance processing list until finished, and go back
+/// on the queue if that instance stops refreshing its lease
+#[derive(Clone)]
+pub struct ModerationQueue {
+    redis: RedisClient,
+    instance_id: String,
+    processing: String,
+}
+
+impl ModerationQueue {
+    pub fn new(redis: RedisClient) -> Self {
+        let instance_id = uuid::Uuid::new_v4().to_string();
+        let processing = keys::moderation_processing(&instance_id);
+        Self { redis, instance_id, processing }
+    }
+
+    pub async fn enqueue(&self, job: &PendingModeration) -> Result<()> {
+        let json = serde_json::to_string(job)?;
+        self.redis
+            .lpush(keys::MODERATION_PENDING, &json)
+            .await
+            .map_err(|e| anyhow!("Failed to enqueue post for moderation: {}", e))?;
+        metrics::counter!("moderation_deferred_total", 1);
+        Ok(())
+    }
+
+    /// Move the oldest waiting job onto this instance's processing list, if any
+    /// Polls rather than blocking, since the connection is shared with everything else
+    pub async fn claim(&self) -> Result<Option<ClaimedModeration>> {
+        let Some(raw) = self.redis
+            .lmove(keys::MODERATION_PENDING, &self.processing)
+            .await
+            .map_err(|e| anyhow!("Failed to read moderation queue: {}", e))?
+        else {
+            return Ok(None);
+        };
+        match serde_json::from_str(&raw) {
+            Ok(job) => Ok(Some(ClaimedModeration { job, raw })),
+            Err(e) => {
+                tracing::warn!("Dropping malformed moderation job: {}", e);
+                self.remove_claim(&raw).await?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Drop a finished job from the processing list
+    pub async fn ack(&self, claimed: &ClaimedModeration) -> Result<()> {
+        self.remove_claim(&claimed.raw).await
+    }
+
+    async fn remove_claim(&self, raw: &str) -> Result<()> {
+        self.redis
+            .lrem(&self.processing, 1, raw)
+            .await
+            .map_err(|e| anyhow!("Failed to ack moderation job: {}", e))?;
+        Ok(())
+    }
+
+    /// Mark this instance's claims as live for another lease
+    pub async fn refresh_lease(&self) -> Result<()> {
+        self.redis
+            .sadd(keys::MODERATION_WORKERS, &self.instance_id)
+            .await
+            .map_err(|e| anyhow!("Failed to register moderation worker: {}", e))?;
+        self.redis
+            .set_ex(&keys::moderation_worker_alive(&self.instance_id), "1", WORKER_LEASE_SECONDS)
+            .await
+            .map_err(|e| anyhow!("Failed to refresh moderation worker lease: {}", e))?;
+        Ok(())
+    }
+
+    /// Put jobs claimed by instances whose lease lapsed back on the queue
+    /// Returns how many were re-queued
+    pub async fn requeue_stale(&self) -> Result<usize> {
+        let instances = self.redis
+            .smembers(keys::MODERATION_WORKERS)
+            .await
+            .map_err(|e| anyhow!("Failed to list moderation workers: {}", e))?;
+
+        let mut requeued = 0;
+        for instance in instances.iter().filter(|instance| **instance != self.instance_id) {
+            let alive = self.redis
+                .exists(&keys::moderation_worker_alive(instance))
+                .await
+                .map_err(|e| anyhow!("Failed to check moderation worker lease: {}", e))?;
+            if alive {
+                continue;
+            }
+            let processing = keys::moderation_processing(instance);
+            while self.redis
+                .lmove(&processing, keys::MODERATION_PENDING)
+                .await
+                .map_err(|e| anyhow!("Failed to re-queue moderation job: {}", e))?
+                .is_some()
+            {
+                requeued += 1;
+            }
+            self.redis
+                .srem(keys::MODERATION_WORKERS, instance)
+                .await
+                .map_err(|e| anyhow!("Failed to unregister moderation worker: {}", e))?;
+        }
+        Ok(requeued)
+    }
+
+    /// Jobs waiting for a worker
+    pub async fn depth(&self) -> Result<i64> {
+        self.redis
+            .llen(keys::MODERATION_PENDING)
+            .await
+            .map_err(|e| anyhow!("Failed to read moderation queue depth: {}", e))
+    }
+}
+
+/// Workers per instance, from `MODERATION_WORKERS`
+fn worker_count() -> usize {
+    std::env::var("MODERATION_WORKERS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_WORKERS)
+        .max(1)
+}
+
+/// Ask the provider about queued posts with a bounded pool of workers, retracting
+/// the ones it blocks, while keeping this instance's lease and the depth gauge fresh
+pub async fn run_worker(state: AppState) {
+    // Registered before anything is claimed, so a crash right after is still recovered
+    if let Err(e) = state.moderation_queue.refresh_lease().await {
+        tracing::error!("{}", e);
+    }
+    for _ in 0..worker_count() {
+        tokio::spawn(run_claims(state.clone()));
+    }
+
+    let mut interval = tokio::time::interval(UPKEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let queue = &state.moderation_queue;
+        if let Err(e) = queue.refresh_lease().await {
+            tracing::error!("{}", e);
+        }
+        match queue.requeue_stale().await {
+            Ok(0) => {}
+            Ok(requeued) => {
+                metrics::counter!("moderation_deferred_requeued_total", requeued as u64);
+                tracing::warn!("Re-queued {} moderation jobs from stopped instances", requeued);
+            }
+            Err(e) => tracing::error!("{}", e),
+        }
+        match queue.depth().await {
+            Ok(depth) => metrics::gauge!("moderation_queue_depth", depth as f64),
+            Err(e) => tracing::error!("{}", e),
+        }
+    }
+}
+
+/// One worker: claim a job, ask the provider, then ack it
+async fn run_claims(state: AppState) {
+    loop {
+        match state.moderation_queue.claim().await {
+            Ok(Some(claimed)) => {
+                process(&state, &claimed.job).await;
+                if let Err(e) = state.moderation_queue.ack(&claimed).await {
+                    tracing::error!("{}", e);
+                }
+            }
+            Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+            Err(e) => {
+                tracing::error!("{}", e);
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+async fn process(state: &AppState, job: &PendingModeration) {
+    let config = state.config.current();
+    let Some(verdict) = config.moderation.provider_verdict(&job.text, None).await else {
+        // The provider didn't answer: try again later, failing open in the end like inline moderation
+        retry_later(state, job).await;
+        return;
+    };
+    metrics::histogram!(
+        "moderation_deferred_delay_seconds",
+        (chrono::Utc::now().timestamp() as u64).saturating_sub(job.enqueued_at) as f64
+    );
+
+    let Some(result) = verdict.into_result() else {
+        metrics::counter!("moderation_deferred_outcomes_total", 1, "outcome" => "allowed");
+        return;
+    };
+    let reason = result.reason.unwrap_or_else(|| "Content policy violation".to_string());
+
+    // An edit made since gets its own job; only the text that was checked is retracted
+    let live = state.get_message_by_id(&job.message_id)
+        .await
+        .filter(|message| message.screened_text() == job.text);
+    let outcome = match live {
+        Some(message) => match state.retract_message(&message).await {
+            Ok(()) => {
+                tracing::info!(message_id = %job.message_id, reason = %reason, "retracted after moderation");
+                "retracted"
+            }
+            Err(e) => {
+                // Asked again rather than leaving the post up; the poster is charged once it's retracted
+                tracing::error!("Failed to retract {}: {}", job.message_id, e);
+                metrics::counter!("moderation_deferred_outcomes_total", 1, "outcome" => "retract_failed");
+                retry_later(state, job).await;
+                return;
+            }
+        },
+        None => "stale",
+    };
+    metrics::counter!("moderation_deferred_outcomes_total", 1, "outcome" => outcome);
+
+    // Counted against the poster like an inline block, stale or not: the text was
+    // published, and editing or deleting it before the verdict mustn't dodge that
+    if let Ok(violations) = state.shadowban_manager.increment_violations(&job.composite_key).await {
+        let risk_level = state.ip_reputation
+            .get_ip_risk_level(&job.ip_address)
+            .await
+            .unwrap_or(RiskLevel::Level0);
+        let signals = Signals {
+            trigger: Trigger::ModerationViolation,
+            risk_level,
+            header_score: job.header_score,
+            violations,
+        };
+        config.escalation
+            .enforce(
+                state,
+                &signals,
+                &job.composite_key,
+                &job.ip_address,
+                "moderation_queue::worker",
+                &format!("Auto-banned: {} violations", violations),
+            )
+            .await;
+    }
+
+    let event = AuditEvent::new(AuditEventKind::MessageBlocked, "system", &job.composite_key, &reason)
+        .with_details(json!({
+            "message_id": job.message_id,
+            "city": job.city,
+            "language": job.language.as_str(),
+            "deferred": true,
+            "retracted": outcome == "retracted",
+        }));
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!("{}", e);
+    }
+}
+
+/// Queue a job again, or drop it (leaving the post up) once it has used its attempts
+async fn retry_later(state: &AppState, job: &PendingModeration) {
+    if job.attempts + 1 < MAX_ATTEMPTS {
+        let retry = PendingModeration { attempts: job.attempts + 1, ..job.clone() };
+        if let Err(e) = state.moderation_queue.enqueue(&retry).await {
+            tracing::error!("{}", e);
+        }
+    } else {
+        metrics::counter!("moderation_deferred_outcomes_total", 1, "outcome" => "gave_up");
+        tracing::warn!(message_id = %job.message_id, attempts = MAX_ATTEMPTS, "gave up moderating");
+    }
+}
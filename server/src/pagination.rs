@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::security::TokenSigner;
+
+/// How long an issued cursor stays valid (in seconds)
+const CURSOR_TTL_SECONDS: u64 = 3600;
+
+/// Default and maximum page sizes for paginated listings
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+pub const MAX_PAGE_SIZE: usize = 100;
+
+/// Position in the message feed a cursor points at
+/// Messages are ordered by (timestamp, id) so ties on timestamp stay stable
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CursorPosition {
+    pub timestamp: u64,
+    pub id: String,
+}
+
+/// Which way a page is read from its cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageDirection {
+    /// The newest messages before the cursor (`?before=`, or the first page)
+    Older,
+    /// The oldest messages after the cursor (`?after=`)
+    Newer,
+}
+
+/// Body of a paginated response; `next_cursor` continues in the direction the
+/// page was read and is absent once there's nothing further that way
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub messages: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+/// Trim chronologically sorted `items` to one page read in `direction`
+/// Returns the position to continue from when more remain beyond the page
+pub fn take_page<T>(
+    items: &mut Vec<T>,
+    page_size: usize,
+    direction: PageDirection,
+    position: impl Fn(&T) -> CursorPosition,
+) -> Option<CursorPosition> {
+    if items.len() <= page_size {
+        return None;
+    }
+    match direction {
+        PageDirection::Older => {
+            items.drain(..items.len() - page_size);
+            items.first().map(position)
+        }
+        PageDirection::Newer => {
+            items.truncate(page_size);
+            items.last().map(position)
+        }
+    }
+}
+
+/// Payload signed into an opaque cursor
+#[derive(Debug, Serialize, Deserialize)]
+struct CursorPayload {
+    #[serde(rename = "p")]
+    position: CursorPosition,
+    /// Hash of the filters the cursor was issued for
+    #[serde(rename = "f")]
+    filter_hash: String,
+    /// Unix timestamp after which the cursor is rejected
+    #[serde(rename = "e")]
+    expires_at: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CursorError {
+    /// Not a cursor we issued, or modified after issuance
+    Invalid,
+    /// Cursor was valid but has expired
+    Expired,
+    /// Cursor was issued for a different set of filters
+    FilterMismatch,
+}
+
+impl std::fmt::Display for CursorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CursorError::Invalid => write!(f, "Invalid pagination cursor"),
+            CursorError::Expired => write!(f, "Pagination cursor has expired"),
+            CursorError::FilterMismatch => write!(f, "Pagination cursor does not match the requested filters"),
+        }
+    }
+}
+
+/// Issues and verifies HMAC-signed opaque pagination cursors
+#[derive(Clone)]
+pub struct CursorSigner {
+    signer: TokenSigner,
+}
+
+impl CursorSigner {
+    pub fn new(server_secret: &str) -> Self {
+        Self {
+            signer: TokenSigner::new(server_secret, "pagination-cursor"),
+        }
+    }
+
+    /// Hash a set of filter values into the short form embedded in cursors
+    pub fn filter_hash(filters: &[Option<&str>]) -> String {
+        let mut hasher = Sha256::new();
+        for filter in filters {
+            hasher.update(filter.unwrap_or("").as_bytes());
+            hasher.update([0u8]);
+        }
+        hex::encode(&hasher.finalize()[..8])
+    }
+
+    /// Issue a cursor for a position under the given filters
+    pub fn issue(&self, position: CursorPosition, filter_hash: &str) -> String {
+        self.issue_at(position, filter_hash, now())
+    }
+
+    fn issue_at(&self, position: CursorPosition, filter_hash: &str, now: u64) -> String {
+        self.signer.sign(&CursorPayload {
+            position,
+            filter_hash: filter_hash.to_string(),
+            expires_at: now + CURSOR_TTL_SECONDS,
+        })
+    }
+
+    /// Verify a cursor and return the position it encodes
+    pub fn verify(&self, cursor: &str, filter_hash: &str) -> Result<CursorPosition, CursorError> {
+        self.verify_at(cursor, filter_hash, now())
+    }
+
+    fn verify_at(&self, cursor: &str, filter_hash: &str, now: u64) -> Result<CursorPosition, CursorError> {
+        let payload: CursorPayload = self.signer.verify(cursor).ok_or(CursorError::Invalid)?;
+
+        if payload.expires_at < now {
+            return Err(CursorError::Expired);
+        }
+        if payload.filter_hash != filter_hash {
+            return Err(CursorError::FilterMismatch);
+        }
+
+        Ok(payload.position)
+    }
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position() -> CursorPosition {
+        CursorPosition {
+            timestamp: 1_700_000_000,
+            id: "abc".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_pages_continue_in_their_direction() {
+        let at = |n: &u64| CursorPosition { timestamp: *n, id: n.to_string() };
+
+        let mut older: Vec<u64> = (1..=5).collect();
+        let next = take_page(&mut older, 2, PageDirection::Older, at);
+        assert_eq!(older, vec![4, 5]);
+        assert_eq!(next.map(|p| p.timestamp), Some(4));
+
+        let mut newer: Vec<u64> = (1..=5).collect();
+        let next = take_page(&mut newer, 2, PageDirection::Newer, at);
+        assert_eq!(newer, vec![1, 2]);
+        assert_eq!(next.map(|p| p.timestamp), Some(2));
+
+        let mut last: Vec<u64> = vec![1, 2];
+        assert!(take_page(&mut last, 2, PageDirection::Older, at).is_none());
+        assert_eq!(last, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_cursor_roundtrip() {
+        let signer = CursorSigner::new("secret");
+        let filters = CursorSigner::filter_hash(&[Some("Pune")]);
+        let cursor = signer.issue(position(), &filters);
+
+        assert_eq!(signer.verify(&cursor, &filters), Ok(position()));
+    }
+
+    #[test]
+    fn test_tampered_cursor_rejected() {
+        let signer = CursorSigner::new("secret");
+        let filters = CursorSigner::filter_hash(&[None]);
+        let cursor = signer.issue(position(), &filters);
+
+        let (body, _) = cursor.split_once('.').unwrap();
+        let forged = format!("{}.AAAA", body);
+
+        assert_eq!(signer.verify(&forged, &filters), Err(CursorError::Invalid));
+        assert_eq!(signer.verify("1700000000", &filters), Err(CursorError::Invalid));
+
+        let other = CursorSigner::new("other-secret");
+        assert_eq!(other.verify(&cursor, &filters), Err(CursorError::Invalid));
+    }
+
+    #[test]
+    fn test_expired_cursor_rejected() {
+        let signer = CursorSigner::new("secret");
+        let filters = CursorSigner::filter_hash(&[None]);
+        let cursor = signer.issue_at(position(), &filters, 1_000);
+
+        assert_eq!(
+            signer.verify_at(&cursor, &filters, 1_000 + CURSOR_TTL_SECONDS + 1),
+            Err(CursorError::Expired)
+        );
+    }
+
+    #[test]
+    fn test_filter_mismatch_rejected() {
+        let signer = CursorSigner::new("secret");
+        let pune = CursorSigner::filter_hash(&[Some("Pune")]);
+        let delhi = CursorSigner::filter_hash(&[Some("Delhi")]);
+        let cursor = signer.issue(position(), &pune);
+
+        assert_eq!(signer.verify(&cursor, &delhi), Err(CursorError::FilterMismatch));
+    }
+}
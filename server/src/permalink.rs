@@ -0,0 +1,195 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::models::{ChatMessage, MessageType};
+use crate::state::AppState;
+
+const SITE_NAME: &str = "Krib";
+/// Link previews show roughly this much of the listing
+const DESCRIPTION_MAX_CHARS: usize = 200;
+/// Crawlers re-fetch rarely; a renewal or edit shows up within this long
+const PERMALINK_MAX_AGE_SECONDS: u64 = 300;
+
+/// Shareable view of a listing, without the poster's phone or browser id
+#[derive(Debug, Serialize)]
+pub struct Permalink {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub message_type: MessageType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    pub timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Where the share link leads people
+    pub url: String,
+}
+
+impl Permalink {
+    fn new(message: &ChatMessage, app_url: &str) -> Self {
+        let kind = match message.message_type {
+            MessageType::Offered => "Room available",
+            MessageType::Requested => "Looking for a room",
+        };
+        let title = match &message.location {
+            Some(city) => format!("{} in {} · {}", kind, city, SITE_NAME),
+            None => format!("{} · {}", kind, SITE_NAME),
+        };
+        Self {
+            id: message.id.clone(),
+            title,
+            description: preview_text(&message.message),
+            message_type: message.message_type.clone(),
+            city: message.location.clone(),
+            timestamp: message.timestamp,
+            expires_at: message.expires_at,
+            url: app_url.to_string(),
+        }
+    }
+
+    /// Minimal page carrying OpenGraph tags for WhatsApp/Telegram link previews
+    fn render(&self) -> String {
+        let title = escape(&self.title);
+        let description = escape(&self.description);
+        let url = escape(&self.url);
+        format!(
+            r#"<!doctype html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<meta name="description" content="{description}">
+<meta property="og:site_name" content="{site}">
+<meta property="og:type" content="website">
+<meta property="og:title" content="{title}">
+<meta property="og:description" content="{description}">
+<meta property="og:url" content="{url}">
+<meta name="twitter:card" content="summary">
+<meta http-equiv="refresh" content="0; url={url}">
+</head>
+<body>
+<h1>{title}</h1>
+<p>{description}</p>
+<p><a href="{url}">See more listings on {site}</a></p>
+</body>
+</html>
+"#,
+            site = SITE_NAME,
+        )
+    }
+}
+
+/// A listing's text as plain, shortened prose
+/// Stored text is already sanitized but may keep harmless markup; previews drop it
+fn preview_text(message: &str) -> String {
+    let shortened: String = message.chars().take(DESCRIPTION_MAX_CHARS).collect();
+    let plain = ammonia::Builder::empty().clean(&shortened).to_string();
+    let plain = plain
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&");
+    let plain = plain.split_whitespace().collect::<Vec<_>>().join(" ");
+    if message.chars().count() > DESCRIPTION_MAX_CHARS {
+        format!("{}…", plain)
+    } else {
+        plain
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Where share links send people, from `PUBLIC_APP_URL` (default: the first `ALLOWED_ORIGIN`)
+fn app_url() -> String {
+    std::env::var("PUBLIC_APP_URL")
+        .ok()
+        .or_else(|| {
+            std::env::var("ALLOWED_ORIGIN")
+                .ok()
+                .and_then(|origins| origins.split(',').next().map(str::to_string))
+        })
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// GET /m/:id - a listing's share page, or its JSON with `Accept: application/json`
+/// The phone number is never included
+pub async fn get_permalink(
+    State(state): State<AppState>,
+    Path(message_id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let wants_json = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json"));
+
+    let Some(message) = state.get_message_by_id(&message_id).await else {
+        metrics::counter!("permalink_views_total", 1, "found" => "false");
+        return if wants_json {
+            (StatusCode::NOT_FOUND, Json(json!({"error": "Message not found"}))).into_response()
+        } else {
+            (StatusCode::NOT_FOUND, Html("<!doctype html><title>Listing not found</title><p>This listing has expired or was removed.</p>")).into_response()
+        };
+    };
+    metrics::counter!("permalink_views_total", 1, "found" => "true");
+
+    let permalink = Permalink::new(&message, &app_url());
+    let cache_control = [(header::CACHE_CONTROL, format!("public, max-age={}", PERMALINK_MAX_AGE_SECONDS))];
+    if wants_json {
+        (cache_control, Json(permalink)).into_response()
+    } else {
+        (cache_control, Html(permalink.render())).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(text: &str) -> ChatMessage {
+        ChatMessage::new(
+            "b".to_string(),
+            text.to_string(),
+            MessageType::Offered,
+            Some("9876543210".to_string()),
+            Some("Pune".to_string()),
+        )
+    }
+
+    #[test]
+    fn test_permalink_excludes_phone_and_escapes_text() {
+        let permalink = Permalink::new(&listing("2BHK <b>near</b> \"Baner\" & Aundh"), "https://krib.in");
+        assert_eq!(permalink.title, "Room available in Pune · Krib");
+        assert_eq!(permalink.description, "2BHK near \"Baner\" & Aundh");
+
+        let json = serde_json::to_string(&permalink).unwrap();
+        assert!(!json.contains("9876543210"));
+
+        let html = permalink.render();
+        assert!(!html.contains("9876543210"));
+        assert!(html.contains(r#"content="2BHK near &quot;Baner&quot; &amp; Aundh""#));
+    }
+
+    #[test]
+    fn test_long_listings_are_shortened() {
+        let permalink = Permalink::new(&listing(&"a".repeat(500)), "");
+        assert_eq!(permalink.description.chars().count(), DESCRIPTION_MAX_CHARS + 1);
+        assert!(permalink.description.ends_with('…'));
+    }
+}
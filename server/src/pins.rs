@@ -0,0 +1,73 @@
+use crate::redis_client::RedisClient;
+use anyhow::{Result, anyhow};
+use crate::keys;
+
+/// Default number of listings that can be pinned per city
+const DEFAULT_MAX_PINNED_PER_CITY: usize = 3;
+
+/// Result of trying to pin a listing
+#[derive(Debug, PartialEq, Eq)]
+pub enum PinOutcome {
+    Pinned,
+    AlreadyPinned,
+    CityFull,
+}
+
+/// Admin-curated listings shown first in a city's feed (e.g. verified community PGs)
+/// Pinned ids are kept per city in a sorted set scored by pin time
+#[derive(Clone)]
+pub struct PinnedListings {
+    redis: RedisClient,
+    max_per_city: usize,
+}
+
+impl PinnedListings {
+    pub fn new(redis: RedisClient) -> Self {
+        let max_per_city = std::env::var("PINNED_PER_CITY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_PINNED_PER_CITY);
+        Self { redis, max_per_city }
+    }
+
+    pub fn max_per_city(&self) -> usize {
+        self.max_per_city
+    }
+
+    /// Pin a listing in a city, respecting the per-city limit
+    pub async fn pin(&self, city: &str, message_id: &str) -> Result<PinOutcome> {
+        let pinned = self.list(city).await?;
+        if pinned.iter().any(|id| id == message_id) {
+            return Ok(PinOutcome::AlreadyPinned);
+        }
+        if pinned.len() >= self.max_per_city {
+            return Ok(PinOutcome::CityFull);
+        }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        self.redis
+            .zadd(&keys::city_pins(city), now as f64, message_id)
+            .await
+            .map_err(|e| anyhow!("Failed to pin listing: {}", e))?;
+        Ok(PinOutcome::Pinned)
+    }
+
+    /// Unpin a listing; returns false if it wasn't pinned
+    pub async fn unpin(&self, city: &str, message_id: &str) -> Result<bool> {
+        let removed = self.redis
+            .zrem(&keys::city_pins(city), message_id)
+            .await
+            .map_err(|e| anyhow!("Failed to unpin listing: {}", e))?;
+        Ok(removed > 0)
+    }
+
+    /// Pinned listing ids for a city, oldest pin first
+    pub async fn list(&self, city: &str) -> Result<Vec<String>> {
+        self.redis
+            .zrange(&keys::city_pins(city), 0, -1)
+            .await
+            .map_err(|e| anyhow!("Failed to list pinned listings: {}", e))
+    }
+}
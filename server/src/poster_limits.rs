@@ -0,0 +1,104 @@
+use anyhow::{Result, anyhow};
+
+use crate::redis_client::RedisClient;
+use crate::keys;
+
+const DEFAULT_MAX_ACTIVE_LISTINGS: usize = 5;
+/// Per-poster index outlives any single listing; renewals keep listings alive past MESSAGE_TTL
+const ACTIVE_INDEX_TTL_SECONDS: i64 = 2592000; // 30 days
+
+/// Caps how many live listings one composite key may hold at once
+/// so a single poster can't flood a city feed while staying under the rate limits
+#[derive(Clone)]
+pub struct PosterLimits {
+    redis: RedisClient,
+}
+
+impl PosterLimits {
+    pub fn new(redis: RedisClient) -> Self {
+        Self { redis }
+    }
+
+    /// Cap from `MAX_ACTIVE_LISTINGS_PER_POSTER` (default 5); reloadable
+    pub fn max_active_from_env() -> usize {
+        std::env::var("MAX_ACTIVE_LISTINGS_PER_POSTER")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_ACTIVE_LISTINGS)
+    }
+
+    /// Add a stored listing to its poster's index
+    pub async fn track(&self, composite_key: &str, message_id: &str, timestamp: u64) -> Result<()> {
+        let key = keys::active_listings(composite_key);
+        self.redis
+            .pipeline()
+            .zadd(&key, timestamp as f64, message_id).ignore()
+            .expire(&key, ACTIVE_INDEX_TTL_SECONDS).ignore()
+            .execute()
+            .await
+            .map_err(|e| anyhow!("Failed to track poster listing: {}", e))
+    }
+
+    /// Every listing the poster stored in the last 30 days with when it was posted, oldest first
+    /// Includes listings that have since expired or been deleted
+    pub async fn listings(&self, composite_key: &str) -> Result<Vec<(String, u64)>> {
+        self.redis
+            .zrange_withscores(&keys::active_listings(composite_key), 0, -1)
+            .await
+            .map(|entries| entries.into_iter().map(|(id, posted_at)| (id, posted_at as u64)).collect())
+            .map_err(|e| anyhow!("Failed to load poster listings: {}", e))
+    }
+
+    /// Number of the poster's listings still stored
+    /// Expired and deleted listings are dropped from the index as they're found
+    pub async fn active_count(&self, composite_key: &str) -> Result<usize> {
+        let key = keys::active_listings(composite_key);
+        let ids = self.redis
+            .zrange(&key, 0, -1)
+            .await
+            .map_err(|e| anyhow!("Failed to load poster listings: {}", e))?;
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut pipeline = self.redis.pipeline();
+        for id in &ids {
+            pipeline.exists(&keys::message(id));
+        }
+        let alive: Vec<bool> = pipeline
+            .query()
+            .await
+            .map_err(|e| anyhow!("Failed to check poster listings: {}", e))?;
+
+        let mut prune = self.redis.pipeline();
+        let mut pruned = 0;
+        for (id, _) in ids.iter().zip(&alive).filter(|(_, alive)| !**alive) {
+            prune.zrem(&key, id).ignore();
+            pruned += 1;
+        }
+        if pruned > 0 {
+            prune.execute().await.map_err(|e| anyhow!("Failed to prune poster listings: {}", e))?;
+        }
+
+        Ok(ids.len() - pruned)
+    }
+
+    pub async fn is_verified(&self, composite_key: &str) -> Result<bool> {
+        self.redis
+            .sismember(keys::VERIFIED_POSTERS, composite_key)
+            .await
+            .map_err(|e| anyhow!("Failed to check verified poster: {}", e))
+    }
+
+    /// Mark or unmark a poster as verified; returns whether anything changed
+    pub async fn set_verified(&self, composite_key: &str, verified: bool) -> Result<bool> {
+        let changed = if verified {
+            self.redis.sadd(keys::VERIFIED_POSTERS, composite_key).await
+        } else {
+            self.redis.srem(keys::VERIFIED_POSTERS, composite_key).await
+        };
+        changed
+            .map(|n| n > 0)
+            .map_err(|e| anyhow!("Failed to update verified poster: {}", e))
+    }
+}
@@ -0,0 +1,1241 @@
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use serde_json::json;
+use std::sync::Mutex;
+
+use crate::cities::{CityStatus, QueuedPost};
+use crate::deadline::Deadline;
+use crate::models::{ChatMessage, ContentFilterError, EditMessageRequest, ListingEvent, PostMessageRequest, RateLimitError};
+use crate::moderation_dataset::{ModerationOutcome, Verdict};
+use crate::moderation_queue::PendingModeration;
+use crate::recorder::Decisions;
+use crate::security::audit::{AuditEvent, AuditEventKind};
+use crate::security::content_filter::ViolationType;
+use crate::security::context_window::WindowEntry;
+use crate::security::enforcement::{EnforcementRecord, ReasonCode};
+use crate::security::escalation::{Enforced, Signals, Trigger};
+use crate::security::ip_reputation::RiskLevel;
+use crate::security::language::Language;
+use crate::security::middleware::SecurityContext;
+use crate::security::moderation::{ModerationService, ProviderVerdict};
+use crate::security::rate_limiter::{RateLimitResult, RateLimitType};
+use crate::security::review_queue::{ReviewItem, ReviewReason};
+use crate::security::trust_tier::TrustTier;
+use crate::security::visibility::{DeliveryPlan, VisibilityPolicy};
+use crate::state::AppState;
+use crate::keys;
+
+pub const MAX_MESSAGE_LENGTH: usize = 280;
+/// How long after posting a listing its poster may still edit it
+pub const EDIT_WINDOW_SECONDS: u64 = 900; // 15 minutes
+
+/// What happened to a post that passed every check
+#[derive(Debug)]
+pub enum PostOutcome {
+    /// Stored and broadcast to the feed
+    Published(ChatMessage),
+    /// Held in a waitlisted city's backlog until launch
+    Queued(ChatMessage),
+    /// Shadow-throttled poster: never stored, echoed only to the poster's own connections
+    Throttled(ChatMessage),
+    /// Shadowbanned or visibility-banned poster: reported as accepted but never stored
+    Suppressed(ChatMessage),
+}
+
+impl PostOutcome {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PostOutcome::Published(_) => "published",
+            PostOutcome::Queued(_) => "queued",
+            PostOutcome::Throttled(_) => "throttled",
+            PostOutcome::Suppressed(_) => "suppressed",
+        }
+    }
+
+    /// The message as the poster sees it, whichever way it went
+    pub fn into_message(self) -> ChatMessage {
+        match self {
+            PostOutcome::Published(message)
+            | PostOutcome::Queued(message)
+            | PostOutcome::Throttled(message)
+            | PostOutcome::Suppressed(message) => message,
+        }
+    }
+}
+
+/// Why a post was turned away
+/// Fixable rejections carry a one-time correction token (see `CorrectionTokens`)
+#[derive(Debug)]
+pub enum PostRejection {
+    /// The honeypot field was filled in; the poster is now permanently shadowbanned
+    BotDetected { reason: String },
+    SessionRequired,
+    /// The poster's violations or IP risk call for a captcha and none was solved
+    CaptchaRequired { provider: &'static str, site_key: Option<String> },
+    /// The captcha token was rejected by the provider
+    CaptchaFailed,
+    TooLong { correction_token: Option<String> },
+    Empty,
+    ListingCap { active: usize, max: usize },
+    /// Seconds left on the poster's cooldown
+    Cooldown { remaining: u64 },
+    RateLimited { result: RateLimitResult },
+    /// A phone number typed into the message body
+    EmbeddedPhone { reason: String, correction_token: Option<String> },
+    /// Blocked by the content filter or moderation
+    ContentViolation { reason: String },
+    InvalidPhone { correction_token: Option<String> },
+    /// A phone number without acceptance of the current privacy policy
+    ConsentRequired { policy_version: String, correction_token: Option<String> },
+    /// The request ran out of time before the post was committed; nothing was charged
+    TimedOut,
+    /// The listing to edit doesn't exist or wasn't posted by this poster
+    NotFound,
+    /// The listing is older than `EDIT_WINDOW_SECONDS`
+    EditWindowClosed,
+    /// Redis or storage failure
+    Internal { error: &'static str },
+}
+
+impl PostRejection {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PostRejection::BotDetected { .. } => "bot_detected",
+            PostRejection::SessionRequired => "session_required",
+            PostRejection::CaptchaRequired { .. } => "captcha_required",
+            PostRejection::CaptchaFailed => "captcha_failed",
+            PostRejection::TooLong { .. } => "too_long",
+            PostRejection::Empty => "empty",
+            PostRejection::ListingCap { .. } => "listing_cap",
+            PostRejection::Cooldown { .. } => "cooldown",
+            PostRejection::RateLimited { .. } => "rate_limited",
+            PostRejection::EmbeddedPhone { .. } => "embedded_phone",
+            PostRejection::ContentViolation { .. } => "content_violation",
+            PostRejection::InvalidPhone { .. } => "invalid_phone",
+            PostRejection::ConsentRequired { .. } => "consent_required",
+            PostRejection::TimedOut => "timed_out",
+            PostRejection::NotFound => "not_found",
+            PostRejection::EditWindowClosed => "edit_window_closed",
+            PostRejection::Internal { .. } => "internal",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            PostRejection::BotDetected { .. }
+            | PostRejection::EmbeddedPhone { .. }
+            | PostRejection::ContentViolation { .. }
+            | PostRejection::ConsentRequired { .. }
+            | PostRejection::CaptchaRequired { .. }
+            | PostRejection::CaptchaFailed
+            | PostRejection::EditWindowClosed => StatusCode::FORBIDDEN,
+            PostRejection::NotFound => StatusCode::NOT_FOUND,
+            PostRejection::SessionRequired => StatusCode::UNAUTHORIZED,
+            PostRejection::TooLong { .. } | PostRejection::Empty | PostRejection::InvalidPhone { .. } => {
+                StatusCode::BAD_REQUEST
+            }
+            PostRejection::ListingCap { .. } => StatusCode::CONFLICT,
+            PostRejection::Cooldown { .. } | PostRejection::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            PostRejection::TimedOut => StatusCode::REQUEST_TIMEOUT,
+            PostRejection::Internal { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn body(&self) -> serde_json::Value {
+        let (mut body, correction_token) = match self {
+            PostRejection::BotDetected { reason } | PostRejection::ContentViolation { reason } => {
+                (json!(ContentFilterError::new(reason.clone())), None)
+            }
+            PostRejection::SessionRequired => (json!({"error": "Session required to post"}), None),
+            PostRejection::CaptchaRequired { provider, site_key } => (
+                json!({
+                    "error": "Complete the captcha to keep posting",
+                    "captcha_provider": provider,
+                    "captcha_site_key": site_key,
+                }),
+                None,
+            ),
+            PostRejection::CaptchaFailed => (json!({"error": "Captcha verification failed, please try again"}), None),
+            PostRejection::TooLong { correction_token } => (
+                json!({"error": format!("Message too long (max {} characters)", MAX_MESSAGE_LENGTH)}),
+                correction_token.as_ref(),
+            ),
+            PostRejection::Empty => (json!({"error": "Message cannot be empty"}), None),
+            PostRejection::ListingCap { active, max } => (
+                json!({
+                    "error": format!(
+                        "You already have {} active listings (maximum {}). Wait for one to expire before posting another.",
+                        active, max
+                    ),
+                    "active_listings": active,
+                    "max_active_listings": max,
+                }),
+                None,
+            ),
+            // `RateLimitError` wants the reset time, not the seconds left
+            PostRejection::Cooldown { remaining } => {
+                let reset_at = chrono::Utc::now().timestamp() as u64 + remaining;
+                (json!(RateLimitError::new(reset_at)), None)
+            }
+            PostRejection::RateLimited { result } => (json!(RateLimitError::new(result.reset_at)), None),
+            PostRejection::EmbeddedPhone { reason, correction_token } => {
+                (json!(ContentFilterError::new(reason.clone())), correction_token.as_ref())
+            }
+            PostRejection::InvalidPhone { correction_token } => {
+                (json!({"error": "Invalid phone number format"}), correction_token.as_ref())
+            }
+            PostRejection::ConsentRequired { policy_version, correction_token } => (
+                json!({
+                    "error": "Accept the privacy policy to share a phone number",
+                    "policy_version": policy_version,
+                }),
+                correction_token.as_ref(),
+            ),
+            PostRejection::TimedOut => (json!({"error": "Request timed out"}), None),
+            PostRejection::NotFound => (json!({"error": "Message not found"}), None),
+            PostRejection::EditWindowClosed => (
+                json!({"error": format!("Listings can only be edited within {} minutes of posting", EDIT_WINDOW_SECONDS / 60)}),
+                None,
+            ),
+            PostRejection::Internal { error } => (json!({"error": error}), None),
+        };
+        if let Some(token) = correction_token {
+            body["correction_token"] = json!(token);
+        }
+        body
+    }
+
+    /// Rate limit headers for the rejections that say when to come back
+    pub fn headers(&self) -> HeaderMap {
+        match self {
+            PostRejection::RateLimited { result } => result.headers(),
+            PostRejection::Cooldown { remaining } => {
+                let mut headers = HeaderMap::new();
+                headers.insert(header::RETRY_AFTER, HeaderValue::from((*remaining).max(1)));
+                headers
+            }
+            _ => HeaderMap::new(),
+        }
+    }
+}
+
+/// The full posting pipeline shared by every way a listing can be submitted:
+/// honeypot, session and shadowban checks, content filters, moderation, listing
+/// cap, rate limits and cooldowns, visibility decisions, storage and broadcast
+/// Checks that only cost the poster on success (rate limit, cooldown) are
+/// consumed once everything else has passed
+pub struct PostingService<'a> {
+    state: &'a AppState,
+    /// Decision log of a request being recorded (see `recorder`)
+    decisions: Option<Decisions>,
+    /// Deadline of the HTTP request submitting the post, if any
+    deadline: Option<Deadline>,
+    /// Post rate limit window after this post was charged to it
+    rate_limit: Mutex<Option<RateLimitResult>>,
+}
+
+impl<'a> PostingService<'a> {
+    pub fn new(state: &'a AppState) -> Self {
+        Self { state, decisions: None, deadline: None, rate_limit: Mutex::new(None) }
+    }
+
+    /// The poster's post rate limit once a submitted post was charged, for response headers
+    /// `None` when the post was turned away before being charged
+    pub fn rate_limit(&self) -> Option<RateLimitResult> {
+        *self.rate_limit.lock().unwrap()
+    }
+
+    /// Stop before the expensive checks and before committing once `deadline` passes
+    pub fn with_deadline(mut self, deadline: Option<Deadline>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Give up on a post whose request has run out of time
+    fn check_deadline(&self) -> Result<(), PostRejection> {
+        match self.deadline {
+            Some(deadline) if deadline.is_expired() => Err(PostRejection::TimedOut),
+            _ => Ok(()),
+        }
+    }
+
+    /// Note the decisions taken into a recorded request's envelope
+    pub fn recording(mut self, decisions: Option<Decisions>) -> Self {
+        self.decisions = decisions;
+        self
+    }
+
+    pub async fn submit(
+        &self,
+        request: PostMessageRequest,
+        ctx: &SecurityContext,
+    ) -> Result<PostOutcome, PostRejection> {
+        let result = self.run(request, ctx).await;
+        self.note(match &result {
+            Ok(outcome) => outcome.kind(),
+            Err(rejection) => rejection.kind(),
+        });
+        result
+    }
+
+    /// Replace the text and details of the poster's own listing while it is within
+    /// `EDIT_WINDOW_SECONDS` of being posted. The new text goes through the same
+    /// content filter and moderation as a new post, and violations count against
+    /// the poster the same way; the listing keeps its id, place in the feed and
+    /// remaining lifetime, and isn't charged against rate limits. Like a post, an
+    /// edit needs a session, and a shadowbanned or throttled poster's edit is
+    /// reported as made without reaching anyone else
+    pub async fn edit(
+        &self,
+        message_id: &str,
+        request: EditMessageRequest,
+        ctx: &SecurityContext,
+    ) -> Result<ChatMessage, PostRejection> {
+        let result = self.run_edit(message_id, request, ctx).await;
+        self.note(match &result {
+            Ok(_) => "edited",
+            Err(rejection) => rejection.kind(),
+        });
+        result
+    }
+
+    fn note(&self, step: impl Into<String>) {
+        if let Some(decisions) = &self.decisions {
+            decisions.push(step);
+        }
+    }
+
+    async fn run(
+        &self,
+        mut request: PostMessageRequest,
+        ctx: &SecurityContext,
+    ) -> Result<PostOutcome, PostRejection> {
+        let state = self.state;
+        let composite_key = ctx.composite_key.as_str();
+        // One config snapshot for the whole post, even if a reload lands midway
+        let config = state.config.current();
+        // Store every spelling of a city under its registered name, so its feed, policy and stats are one
+        // A post without a city stays without one; the client pre-fills it from /api/geo
+        if let Some(location) = &request.location {
+            request.location = Some(state.city_registry.normalize(location).await);
+        }
+
+        // Check honeypot field
+        let honeypot_result = config.content_filter.check_honeypot(request.website.as_deref());
+        if !honeypot_result.is_allowed {
+            self.ban_honeypot(composite_key).await;
+            return Err(PostRejection::BotDetected {
+                reason: honeypot_result.reason.unwrap_or_else(|| "Bot detected".to_string()),
+            });
+        }
+
+        // Posting is unlocked by a session, which is where fingerprints get registered
+        Self::check_session(ctx)?;
+
+        let is_shadowbanned_total = self.is_shadowbanned(ctx).await;
+
+        // Suspicious posters prove they're human before escalation gets to a shadowban;
+        // already-shadowbanned ones aren't challenged, which would tip them off
+        if !is_shadowbanned_total {
+            self.check_captcha(ctx, request.captcha_token.as_deref()).await?;
+        }
+
+        // Validate message length
+        if request.message.len() > MAX_MESSAGE_LENGTH {
+            return Err(PostRejection::TooLong {
+                correction_token: self.correction_token(composite_key).await,
+            });
+        }
+
+        if request.message.trim().is_empty() {
+            return Err(PostRejection::Empty);
+        }
+
+        // Cap how many live listings one poster holds; verified posters are exempt
+        self.check_listing_cap(composite_key).await?;
+
+        // Fail fast on cooldown and rate limit before the expensive checks, without
+        // consuming either - a post that's rejected later must not cost the poster anything
+        self.check_quota(composite_key, request.correction_token.as_deref()).await?;
+
+        // Check content filters
+        let filter_result = config.content_filter.check_message(&request.message);
+        if filter_result.violation_type == Some(ViolationType::EmbeddedPhone) {
+            // An honest formatting mistake - not counted as a violation
+            return Err(PostRejection::EmbeddedPhone {
+                reason: filter_result.reason.unwrap_or_default(),
+                correction_token: self.correction_token(composite_key).await,
+            });
+        }
+        if !filter_result.is_allowed {
+            // Increment violation count
+            if let Ok(violations) = state.shadowban_manager
+                .increment_violations(composite_key)
+                .await
+            {
+                self.escalate(ctx, Trigger::ContentViolation, violations).await;
+
+                tracing::warn!(composite_key, violations, "content violation");
+            }
+
+            return Err(PostRejection::ContentViolation {
+                reason: filter_result.reason.unwrap_or_else(|| "Content policy violation".to_string()),
+            });
+        }
+
+        // Detect the language so moderation uses the matching wordlists
+        let language = Language::detect(&request.message);
+        metrics::counter!("messages_by_language_total", 1, "language" => language.as_str());
+
+        // Run comprehensive moderation checks (profanity, relevance, spam, OpenAI)
+        self.check_deadline()?;
+        let moderation_result = config.moderation
+            .moderate_message(&request.message, request.location.as_deref(), language, self.deadline)
+            .await;
+
+        // Scored on the original text so masked words still count towards the poster's window
+        let toxicity = config.moderation.toxicity_score(&request.message, language);
+
+        // Anonymized outcome for the offline moderation dataset
+        let mut outcome = ModerationOutcome {
+            text_hash: state.moderation_dataset.text_hash(&request.message),
+            verdict: Verdict::Allowed,
+            rules: moderation_result.violation_type
+                .iter()
+                .map(|violation| violation.as_str().to_string())
+                .collect(),
+            toxicity,
+            language: language.as_str().to_string(),
+            city: request.location.clone(),
+            message_id: None,
+            timestamp: chrono::Utc::now().timestamp(),
+        };
+
+        if let Some(violation) = &moderation_result.violation_type {
+            self.note(format!("moderation:{}", violation.as_str()));
+        }
+
+        if !moderation_result.is_allowed {
+            let reason = moderation_result.reason.unwrap_or_else(|| "Content policy violation".to_string());
+            self.spawn_record_outcome(ModerationOutcome { verdict: Verdict::Blocked, ..outcome });
+            self.record_moderation_violation(ctx, &reason, request.location.as_deref(), language).await;
+            return Err(PostRejection::ContentViolation { reason });
+        }
+
+        // The locality is published next to the text, so it's held to the same rules
+        let locality = request.details.as_ref().and_then(|details| details.locality.as_deref());
+        self.check_locality(ctx, locality, request.location.as_deref(), language, true).await?;
+
+        // Masked profanity is accepted but still counted against the poster
+        if let Some(masked) = moderation_result.masked_content {
+            metrics::counter!("moderation_masked_total", 1);
+            self.note("masked");
+            outcome.verdict = Verdict::Masked;
+            if let Err(e) = state.shadowban_manager
+                .increment_soft_violations(composite_key)
+                .await
+            {
+                tracing::error!("{}", e);
+            }
+            request.message = masked;
+        }
+
+        // Validate phone number format if provided
+        if !config.content_filter.validate_phone(request.phone.as_deref()) {
+            return Err(PostRejection::InvalidPhone {
+                correction_token: self.correction_token(composite_key).await,
+            });
+        }
+
+        // Phone numbers are only stored with consent to the current privacy policy
+        if request.phone.as_deref().is_some_and(|phone| !phone.trim().is_empty()) {
+            self.check_consent(composite_key, request.consent.as_deref()).await?;
+        }
+
+        // Check suspicious patterns
+        if config.content_filter.is_suspicious_pattern(&request.message) {
+            outcome.rules.push("suspicious_pattern".to_string());
+            // Increment violations for suspicious patterns
+            let _ = state.shadowban_manager
+                .increment_violations(composite_key)
+                .await;
+        }
+
+        // Check IP reputation risk level and apply cooldowns based on it
+        let (delivery, ip_risk_level) = self.delivery_plan(ctx, is_shadowbanned_total).await;
+        metrics::counter!("posts_by_delivery_total", 1, "plan" => delivery.as_str());
+        if let Some(decisions) = &self.decisions {
+            decisions.set_risk_level(ip_risk_level);
+            decisions.set_shadowbanned(is_shadowbanned_total);
+            decisions.push(format!("delivery:{}", delivery.as_str()));
+        }
+
+        // Every check has passed - only now is the post charged against the poster's quotas
+        // Past this point the post is committed, so it's the last place to give up
+        self.check_deadline()?;
+        self.consume_quota(composite_key, request.location.as_deref(), ip_risk_level).await?;
+
+        let message = ChatMessage {
+            language: Some(language),
+            details: request.details.filter(|details| !details.is_empty()),
+            ..ChatMessage::new(
+                request.browser_id,
+                request.message,
+                request.message_type,
+                request.phone,
+                request.location,
+            )
+        };
+
+        match delivery {
+            DeliveryPlan::Broadcast => {}
+            DeliveryPlan::PosterOnly => {
+                self.echo_to_poster(composite_key, &message).await;
+                return Ok(PostOutcome::Throttled(message));
+            }
+            // Pretend to succeed but don't store or broadcast
+            DeliveryPlan::Suppress => return Ok(PostOutcome::Suppressed(message)),
+        }
+
+        // Cities that haven't launched yet accept posts into a backlog instead
+        if let Some(city) = message.location.as_deref() {
+            match state.cities.status(city).await {
+                Ok(CityStatus::Waitlist) => {
+                    // A backlog post can't be retracted once the city launches
+                    self.check_provider_now(ctx, &config.moderation, &message.screened_text(), Some(city), language).await?;
+                    let post = QueuedPost {
+                        composite_key: composite_key.to_string(),
+                        message: message.clone(),
+                    };
+                    state.cities.queue_post(city, &post).await.map_err(|e| {
+                        tracing::error!("{}", e);
+                        PostRejection::Internal { error: "Failed to post message" }
+                    })?;
+                    let label = state.city_registry.directory().await.metric_label(city);
+                    metrics::counter!("waitlist_posts_total", 1, "city" => label);
+                    return Ok(PostOutcome::Queued(message));
+                }
+                Err(e) => tracing::error!("Failed to check city status: {}", e),
+                _ => {}
+            }
+        }
+
+        // Normal flow: add message to Redis and broadcast via pub/sub
+        state.add_message(message.clone())
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to add message: {}", e);
+                PostRejection::Internal { error: "Failed to post message" }
+            })?;
+
+        self.after_publish(composite_key, &message, outcome, toxicity).await;
+        self.defer_provider_check(ctx, &config.moderation, &message, language).await;
+
+        Ok(PostOutcome::Published(message))
+    }
+
+    async fn run_edit(
+        &self,
+        message_id: &str,
+        request: EditMessageRequest,
+        ctx: &SecurityContext,
+    ) -> Result<ChatMessage, PostRejection> {
+        let state = self.state;
+        let composite_key = ctx.composite_key.as_str();
+        let config = state.config.current();
+
+        // Held to the same session and shadowban gates as a new post
+        Self::check_session(ctx)?;
+        let is_shadowbanned_total = self.is_shadowbanned(ctx).await;
+
+        // Same response for "not yours" and "doesn't exist" so ids can't be probed
+        let is_owner = state.listing_stats
+            .is_owner(message_id, composite_key)
+            .await
+            .map_err(|e| {
+                tracing::error!("{}", e);
+                PostRejection::Internal { error: "Failed to edit message" }
+            })?;
+        let message = match is_owner {
+            true => state.get_message_by_id(message_id).await,
+            false => None,
+        };
+        let Some(message) = message else {
+            return Err(PostRejection::NotFound);
+        };
+
+        let now = chrono::Utc::now().timestamp() as u64;
+        if now.saturating_sub(message.timestamp) > EDIT_WINDOW_SECONDS {
+            return Err(PostRejection::EditWindowClosed);
+        }
+
+        if request.message.len() > MAX_MESSAGE_LENGTH {
+            return Err(PostRejection::TooLong { correction_token: None });
+        }
+        if request.message.trim().is_empty() {
+            return Err(PostRejection::Empty);
+        }
+
+        let filter_result = config.content_filter.check_message(&request.message);
+        if filter_result.violation_type == Some(ViolationType::EmbeddedPhone) {
+            return Err(PostRejection::EmbeddedPhone {
+                reason: filter_result.reason.unwrap_or_default(),
+                correction_token: None,
+            });
+        }
+        if !filter_result.is_allowed {
+            if let Ok(violations) = state.shadowban_manager
+                .increment_violations(composite_key)
+                .await
+            {
+                self.escalate(ctx, Trigger::ContentViolation, violations).await;
+                tracing::warn!(composite_key, violations, field = "edit", "content violation");
+            }
+            return Err(PostRejection::ContentViolation {
+                reason: filter_result.reason.unwrap_or_else(|| "Content policy violation".to_string()),
+            });
+        }
+
+        let language = Language::detect(&request.message);
+        self.check_deadline()?;
+        let moderation_result = config.moderation
+            .moderate_message(&request.message, message.location.as_deref(), language, self.deadline)
+            .await;
+        if let Some(violation) = &moderation_result.violation_type {
+            self.note(format!("moderation:{}", violation.as_str()));
+        }
+        if !moderation_result.is_allowed {
+            let reason = moderation_result.reason.unwrap_or_else(|| "Content policy violation".to_string());
+            self.record_moderation_violation(ctx, &reason, message.location.as_deref(), language).await;
+            return Err(PostRejection::ContentViolation { reason });
+        }
+        let locality = request.details.as_ref().and_then(|details| details.locality.as_deref());
+        self.check_locality(ctx, locality, message.location.as_deref(), language, false).await?;
+
+        let text = match moderation_result.masked_content {
+            Some(masked) => {
+                metrics::counter!("moderation_masked_total", 1);
+                self.note("masked");
+                if let Err(e) = state.shadowban_manager
+                    .increment_soft_violations(composite_key)
+                    .await
+                {
+                    tracing::error!("{}", e);
+                }
+                masked
+            }
+            None => request.message,
+        };
+        if config.content_filter.is_suspicious_pattern(&text) {
+            let _ = state.shadowban_manager
+                .increment_violations(composite_key)
+                .await;
+        }
+
+        // A poster shadowbanned or throttled since publishing can't rewrite what everyone sees
+        let (delivery, _) = self.delivery_plan(ctx, is_shadowbanned_total).await;
+        metrics::counter!("edits_by_delivery_total", 1, "plan" => delivery.as_str());
+        self.note(format!("delivery:{}", delivery.as_str()));
+
+        self.check_deadline()?;
+        let edited = message.edited(&text, request.details, language);
+        match delivery {
+            DeliveryPlan::Broadcast => {}
+            DeliveryPlan::PosterOnly => {
+                self.echo_edit_to_poster(composite_key, &edited).await;
+                return Ok(edited);
+            }
+            DeliveryPlan::Suppress => return Ok(edited),
+        }
+        state.edit_message(&edited).await.map_err(|e| {
+            tracing::error!("Failed to edit message: {}", e);
+            PostRejection::Internal { error: "Failed to edit message" }
+        })?;
+        self.defer_provider_check(ctx, &config.moderation, &edited, language).await;
+
+        metrics::counter!("messages_edited_total", 1);
+        Ok(edited)
+    }
+
+    /// Run a listing's free-text locality through the content filter and moderation
+    /// A phone number in it is a fixable mistake like one in the text; anything
+    /// else counts against the poster the same way
+    async fn check_locality(
+        &self,
+        ctx: &SecurityContext,
+        locality: Option<&str>,
+        city: Option<&str>,
+        language: Language,
+        correctable: bool,
+    ) -> Result<(), PostRejection> {
+        let Some(locality) = locality else {
+            return Ok(());
+        };
+        let state = self.state;
+        let config = state.config.current();
+        let composite_key = ctx.composite_key.as_str();
+
+        let filter_result = config.content_filter.check_message(locality);
+        if filter_result.violation_type == Some(ViolationType::EmbeddedPhone) {
+            let correction_token = match correctable {
+                true => self.correction_token(composite_key).await,
+                false => None,
+            };
+            return Err(PostRejection::EmbeddedPhone {
+                reason: "Phone numbers should be in the dedicated phone field, not in the locality".to_string(),
+                correction_token,
+            });
+        }
+        if !filter_result.is_allowed {
+            if let Ok(violations) = state.shadowban_manager
+                .increment_violations(composite_key)
+                .await
+            {
+                self.escalate(ctx, Trigger::ContentViolation, violations).await;
+                tracing::warn!(composite_key, violations, field = "locality", "content violation");
+            }
+            return Err(PostRejection::ContentViolation {
+                reason: filter_result.reason.unwrap_or_else(|| "Content policy violation".to_string()),
+            });
+        }
+
+        self.check_deadline()?;
+        let moderation_result = config.moderation
+            .moderate_field(locality, city, language, self.deadline)
+            .await;
+        if let Some(violation) = &moderation_result.violation_type {
+            self.note(format!("moderation:locality:{}", violation.as_str()));
+        }
+        if !moderation_result.is_allowed {
+            let reason = moderation_result.reason.unwrap_or_else(|| "Content policy violation".to_string());
+            self.record_moderation_violation(ctx, &reason, city, language).await;
+            return Err(PostRejection::ContentViolation { reason });
+        }
+        Ok(())
+    }
+
+    /// Record consent sent with the post, or require it to be on file already
+    async fn check_consent(&self, composite_key: &str, consent: Option<&str>) -> Result<(), PostRejection> {
+        let consents = &self.state.consents;
+        if consent.is_some_and(|version| consents.is_current(version)) {
+            return consents.record(composite_key).await.map_err(|e| {
+                tracing::error!("{}", e);
+                PostRejection::Internal { error: "Failed to record consent" }
+            });
+        }
+
+        let has_current = consents.has_current(composite_key).await.map_err(|e| {
+            tracing::error!("{}", e);
+            PostRejection::Internal { error: "Failed to check consent" }
+        })?;
+        if has_current {
+            return Ok(());
+        }
+        Err(PostRejection::ConsentRequired {
+            policy_version: consents.policy_version.clone(),
+            correction_token: self.correction_token(composite_key).await,
+        })
+    }
+
+    /// A session issued to this poster's composite key
+    fn check_session(ctx: &SecurityContext) -> Result<(), PostRejection> {
+        let has_session = ctx.session
+            .as_ref()
+            .is_some_and(|session| session.key == ctx.composite_key);
+        match has_session {
+            true => Ok(()),
+            false => Err(PostRejection::SessionRequired),
+        }
+    }
+
+    /// Whether the poster, or their fingerprint because of reports, is shadowbanned
+    async fn is_shadowbanned(&self, ctx: &SecurityContext) -> bool {
+        let shadowbans = &self.state.shadowban_manager;
+        let is_shadowbanned = shadowbans
+            .is_shadowbanned(&ctx.composite_key)
+            .await
+            .unwrap_or(false);
+        let reported_key = keys::reported(&ctx.fingerprint);
+        let is_reported_shadowbanned = shadowbans
+            .is_shadowbanned(&reported_key)
+            .await
+            .unwrap_or(false);
+        is_shadowbanned || is_reported_shadowbanned
+    }
+
+    /// How this poster's post or edit is delivered, with the IP risk level it was decided on
+    /// Scripted-looking headers raise the effective level even without reports
+    async fn delivery_plan(&self, ctx: &SecurityContext, shadowbanned: bool) -> (DeliveryPlan, RiskLevel) {
+        let risk_level = self.state.ip_reputation
+            .get_ip_risk_level(&ctx.ip_address)
+            .await
+            .unwrap_or(RiskLevel::Level0)
+            .max(ctx.header_score.risk_level());
+        let delivery = VisibilityPolicy {
+            shadowbanned,
+            risk_level,
+            trust_tier: TrustTier::of(ctx),
+        }
+        .delivery_plan();
+        (delivery, risk_level)
+    }
+
+    /// Deliver a throttled post to the poster's other live connections, phone stripped like any broadcast
+    async fn echo_to_poster(&self, composite_key: &str, message: &ChatMessage) {
+        let echo = ChatMessage { phone: None, ..message.clone() };
+        self.publish_to_poster(composite_key, serde_json::to_string(&echo)).await;
+    }
+
+    /// Deliver a throttled edit the same way, as the event everyone else would have got
+    async fn echo_edit_to_poster(&self, composite_key: &str, message: &ChatMessage) {
+        let echo = ListingEvent::MessageUpdated { message: ChatMessage { phone: None, ..message.clone() } };
+        self.publish_to_poster(composite_key, serde_json::to_string(&echo)).await;
+    }
+
+    async fn publish_to_poster(&self, composite_key: &str, json: serde_json::Result<String>) {
+        let result = match json {
+            Ok(json) => self.state.broadcast.publish_to_actor(composite_key, &json).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            tracing::error!("Failed to echo throttled post: {}", e);
+        }
+    }
+
+    /// Permanently shadowban a composite key that filled in the honeypot
+    async fn ban_honeypot(&self, composite_key: &str) {
+        let state = self.state;
+        if let Err(e) = state.shadowban_manager.shadowban(
+            composite_key,
+            EnforcementRecord::system(
+                ReasonCode::Honeypot,
+                "posting::submit",
+                "Honeypot triggered - bot detected",
+            ),
+            None, // Permanent
+        ).await {
+            tracing::error!("Failed to shadowban honeypot violator: {}", e);
+        }
+
+        let event = AuditEvent::new(
+            AuditEventKind::Shadowbanned,
+            "system",
+            composite_key,
+            "Honeypot triggered - bot detected",
+        );
+        if let Err(e) = state.audit_log.record(event).await {
+            tracing::error!("{}", e);
+        }
+    }
+
+    /// Count a moderation block against the poster, auto-shadowbanning repeat offenders
+    async fn record_moderation_violation(
+        &self,
+        ctx: &SecurityContext,
+        reason: &str,
+        city: Option<&str>,
+        language: Language,
+    ) {
+        let state = self.state;
+        let composite_key = ctx.composite_key.as_str();
+        if let Ok(violations) = state.shadowban_manager
+            .increment_violations(composite_key)
+            .await
+        {
+            let banned = self.escalate(ctx, Trigger::ModerationViolation, violations).await.shadowbanned;
+
+            tracing::warn!(composite_key, reason = %reason, violations, "moderation violation");
+
+            if banned {
+                let event = AuditEvent::new(
+                    AuditEventKind::Shadowbanned,
+                    "system",
+                    composite_key,
+                    &format!("Auto-banned: {} violations", violations),
+                );
+                if let Err(e) = state.audit_log.record(event).await {
+                    tracing::error!("{}", e);
+                }
+            }
+        }
+
+        let event = AuditEvent::new(
+            AuditEventKind::MessageBlocked,
+            "system",
+            composite_key,
+            reason,
+        )
+        .with_details(json!({
+            "city": city,
+            "language": language.as_str(),
+        }));
+        if let Err(e) = state.audit_log.record(event).await {
+            tracing::error!("{}", e);
+        }
+    }
+
+    /// Require a solved captcha from posters whose violations or IP risk crossed
+    /// the verifier's thresholds; a solve covers their posts for a while
+    /// Provider outages fail open, like moderation
+    async fn check_captcha(&self, ctx: &SecurityContext, token: Option<&str>) -> Result<(), PostRejection> {
+        let state = self.state;
+        let Some(provider) = state.captcha.provider() else {
+            return Ok(());
+        };
+        let composite_key = ctx.composite_key.as_str();
+        let violations = state.shadowban_manager
+            .get_violations(composite_key)
+            .await
+            .unwrap_or(0);
+        let risk_level = state.ip_reputation
+            .get_ip_risk_level(&ctx.ip_address)
+            .await
+            .unwrap_or(RiskLevel::Level0)
+            .max(ctx.header_score.risk_level());
+        if !state.captcha.requires_challenge(violations, risk_level) || state.captcha.has_passed(composite_key).await {
+            return Ok(());
+        }
+
+        let Some(token) = token.filter(|t| !t.is_empty()) else {
+            self.note("captcha:required");
+            return Err(PostRejection::CaptchaRequired {
+                provider: provider.as_str(),
+                site_key: state.captcha.site_key().map(str::to_string),
+            });
+        };
+        match state.captcha.verify(composite_key, token, &ctx.ip_address).await {
+            Ok(true) => {
+                self.note("captcha:passed");
+                Ok(())
+            }
+            Ok(false) => {
+                self.note("captcha:failed");
+                Err(PostRejection::CaptchaFailed)
+            }
+            Err(e) => {
+                tracing::error!("{}", e);
+                Ok(())
+            }
+        }
+    }
+
+    /// Hand a stored post to the background moderation worker when the provider
+    /// check was left out of `moderate_message`; it's retracted if the provider blocks it
+    async fn defer_provider_check(
+        &self,
+        ctx: &SecurityContext,
+        moderation: &ModerationService,
+        message: &ChatMessage,
+        language: Language,
+    ) {
+        if !moderation.defers_provider() {
+            return;
+        }
+        let job = PendingModeration {
+            message_id: message.id.clone(),
+            composite_key: ctx.composite_key.clone(),
+            ip_address: ctx.ip_address.clone(),
+            header_score: ctx.header_score.score,
+            text: message.screened_text(),
+            language,
+            city: message.location.clone(),
+            attempts: 0,
+            enqueued_at: chrono::Utc::now().timestamp() as u64,
+        };
+        if let Err(e) = self.state.moderation_queue.enqueue(&job).await {
+            tracing::error!("{}", e);
+        }
+        self.note("moderation:deferred");
+    }
+
+    /// The provider check `moderate_message` deferred, made now for a post that
+    /// won't be stored where the worker can retract it
+    async fn check_provider_now(
+        &self,
+        ctx: &SecurityContext,
+        moderation: &ModerationService,
+        text: &str,
+        city: Option<&str>,
+        language: Language,
+    ) -> Result<(), PostRejection> {
+        if !moderation.defers_provider() {
+            return Ok(());
+        }
+        let Some(result) = moderation
+            .provider_verdict(text, self.deadline)
+            .await
+            .and_then(ProviderVerdict::into_result)
+        else {
+            return Ok(());
+        };
+        let reason = result.reason.unwrap_or_else(|| "Content policy violation".to_string());
+        self.record_moderation_violation(ctx, &reason, city, language).await;
+        Err(PostRejection::ContentViolation { reason })
+    }
+
+    /// Apply the escalation policy to a poster who just racked up a violation
+    async fn escalate(&self, ctx: &SecurityContext, trigger: Trigger, violations: i64) -> Enforced {
+        let state = self.state;
+        let risk_level = state.ip_reputation
+            .get_ip_risk_level(&ctx.ip_address)
+            .await
+            .unwrap_or(RiskLevel::Level0)
+            .max(ctx.header_score.risk_level());
+        let signals = Signals {
+            trigger,
+            risk_level,
+            header_score: ctx.header_score.score,
+            violations,
+        };
+        let enforced = state.config.current().escalation
+            .enforce(
+                state,
+                &signals,
+                &ctx.composite_key,
+                &ctx.ip_address,
+                "posting::submit",
+                &format!("Auto-banned: {} violations", violations),
+            )
+            .await;
+        if let Some(decisions) = &self.decisions {
+            decisions.set_risk_level(risk_level);
+            if let Some(rule) = &enforced.rule {
+                decisions.push(format!("escalation:{}", rule));
+            }
+        }
+        enforced
+    }
+
+    /// Bookkeeping for a stored post: ownership, the poster's listing index,
+    /// city velocity, the moderation dataset, the context window and daily stats
+    async fn after_publish(&self, composite_key: &str, message: &ChatMessage, mut outcome: ModerationOutcome, toxicity: f64) {
+        let state = self.state;
+
+        // Remember the poster so only they can read the listing's stats
+        if let Err(e) = state.listing_stats.record_owner(&message.id, composite_key).await {
+            tracing::error!("{}", e);
+        }
+
+        if let Err(e) = state.poster_limits
+            .track(composite_key, &message.id, message.timestamp)
+            .await
+        {
+            tracing::error!("{}", e);
+        }
+
+        // Only registered cities are tracked, so typed-in names can't each add a
+        // lookup to every surge detection run
+        if let Some(city) = message.location.as_deref() {
+            if state.city_registry.directory().await.is_registered(city) {
+                if let Err(e) = state.city_surges.record_post(city).await {
+                    tracing::error!("{}", e);
+                }
+            }
+        }
+
+        // Only stored posts carry an id, so later reports can be joined to the outcome
+        outcome.message_id = Some(message.id.clone());
+        self.spawn_record_outcome(outcome);
+
+        // Aggregate checks over the poster's recent messages, off the response path
+        let entry = WindowEntry::new(&message.message, toxicity, message.timestamp);
+        let composite_key = composite_key.to_string();
+        let message_id = message.id.clone();
+        let window_state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = review_poster_window(&window_state, &composite_key, &message_id, &entry).await {
+                tracing::error!("{}", e);
+            }
+        });
+    }
+
+    /// Whether the poster may post right now, without consuming anything
+    /// A valid correction token resubmits a fixed post without waiting out the cooldown
+    async fn check_quota(&self, composite_key: &str, correction_token: Option<&str>) -> Result<(), PostRejection> {
+        let state = self.state;
+        let correcting = match correction_token {
+            Some(token) => state.corrections
+                .is_valid(composite_key, token)
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::error!("{}", e);
+                    false
+                }),
+            None => false,
+        };
+
+        if !correcting {
+            if let Ok(Some(remaining)) = state.ip_reputation.check_cooldown(composite_key).await {
+                return Err(PostRejection::Cooldown { remaining });
+            }
+        }
+
+        let rate_limit_result = state.rate_limiter
+            .check_rate_limit_status(composite_key, RateLimitType::PostMessage)
+            .await
+            .map_err(rate_limit_error)?;
+        if !rate_limit_result.allowed {
+            return Err(PostRejection::RateLimited { result: rate_limit_result });
+        }
+
+        Ok(())
+    }
+
+    /// Charge an accepted post: record it in the rate limit window, start the
+    /// risk-level cooldown and close any correction window
+    /// The rate limit is re-checked here because concurrent posts may have passed
+    /// `check_quota` together. Cooldowns are longer in a city with an active posting surge
+    async fn consume_quota(
+        &self,
+        composite_key: &str,
+        city: Option<&str>,
+        ip_risk_level: RiskLevel,
+    ) -> Result<(), PostRejection> {
+        let state = self.state;
+        let rate_limit_result = state.rate_limiter
+            .check_rate_limit(composite_key, RateLimitType::PostMessage)
+            .await
+            .map_err(rate_limit_error)?;
+        if !rate_limit_result.allowed {
+            return Err(PostRejection::RateLimited { result: rate_limit_result });
+        }
+        *self.rate_limit.lock().unwrap() = Some(rate_limit_result);
+
+        let surging = match city {
+            Some(city) => state.city_surges.is_active(city).await.unwrap_or_else(|e| {
+                tracing::error!("{}", e);
+                false
+            }),
+            None => false,
+        };
+        let (cooldown_seconds, reason) = if surging {
+            (
+                ip_risk_level.cooldown_seconds() * state.city_surges.cooldown_multiplier,
+                format!("Posting cooldown at IP risk level {} during a city posting surge", ip_risk_level as u8),
+            )
+        } else {
+            (
+                ip_risk_level.cooldown_seconds(),
+                format!("Posting cooldown at IP risk level {}", ip_risk_level as u8),
+            )
+        };
+
+        if let Err(e) = state.ip_reputation
+            .set_cooldown(
+                composite_key,
+                cooldown_seconds,
+                EnforcementRecord::system(ReasonCode::RiskCooldown, "posting::submit", &reason),
+            )
+            .await
+        {
+            tracing::error!("Failed to set IP reputation cooldown: {}", e);
+        }
+
+        if let Err(e) = state.corrections.invalidate(composite_key).await {
+            tracing::error!("{}", e);
+        }
+
+        Ok(())
+    }
+
+    /// One-time token for a rejection the poster can fix, so the corrected post
+    /// isn't held back by a cooldown
+    /// No token is issued to a poster already cooling down from a real post
+    async fn correction_token(&self, composite_key: &str) -> Option<String> {
+        let state = self.state;
+        let in_cooldown = state.ip_reputation
+            .check_cooldown(composite_key)
+            .await
+            .map_or(true, |remaining| remaining.is_some());
+        if in_cooldown {
+            return None;
+        }
+        state.corrections.issue(composite_key).await.map_err(|e| tracing::error!("{}", e)).ok()
+    }
+
+    /// Reject a post when the poster already holds the maximum number of live listings
+    /// Redis errors let the post through - the cap is a spam deterrent, not a hard guarantee
+    async fn check_listing_cap(&self, composite_key: &str) -> Result<(), PostRejection> {
+        let limits = &self.state.poster_limits;
+        let max = self.state.config.current().max_active_listings;
+        let active = match limits.active_count(composite_key).await {
+            Ok(active) if active >= max => active,
+            Ok(_) => return Ok(()),
+            Err(e) => {
+                tracing::error!("{}", e);
+                return Ok(());
+            }
+        };
+        if limits.is_verified(composite_key).await.unwrap_or(false) {
+            return Ok(());
+        }
+
+        metrics::counter!("listing_cap_rejections_total", 1);
+        Err(PostRejection::ListingCap { active, max })
+    }
+
+    /// Append a moderation outcome to the export dataset, off the response path
+    fn spawn_record_outcome(&self, outcome: ModerationOutcome) {
+        let dataset = self.state.moderation_dataset.clone();
+        tokio::spawn(async move {
+            if let Err(e) = dataset.record_outcome(&outcome).await {
+                tracing::error!("{}", e);
+            }
+        });
+    }
+}
+
+fn rate_limit_error(e: anyhow::Error) -> PostRejection {
+    tracing::error!("Rate limit check error: {}", e);
+    PostRejection::Internal { error: "Failed to check rate limit" }
+}
+
+/// Record a post in the poster's context window and queue them for review when
+/// their recent messages look abusive together
+async fn review_poster_window(
+    state: &AppState,
+    composite_key: &str,
+    message_id: &str,
+    entry: &WindowEntry,
+) -> anyhow::Result<()> {
+    let verdict = state.context_window.record(composite_key, entry).await?;
+    if !verdict.needs_review() || !state.context_window.mark_flagged(composite_key).await? {
+        return Ok(());
+    }
+
+    tracing::info!(composite_key, reasons = %verdict.reasons.join("; "), "queued for review");
+    let item = ReviewItem::new(
+        composite_key,
+        Some(message_id),
+        ReviewReason::PosterPattern { reasons: verdict.reasons.clone() },
+    )
+    .with_details(json!(verdict));
+    state.review_queue.enqueue(&item).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixable_rejections_carry_token() {
+        let rejection = PostRejection::TooLong { correction_token: Some("t".to_string()) };
+        assert_eq!(rejection.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(rejection.body()["correction_token"], "t");
+
+        let rejection = PostRejection::InvalidPhone { correction_token: None };
+        assert!(rejection.body().get("correction_token").is_none());
+    }
+
+    #[test]
+    fn test_rejection_statuses() {
+        assert_eq!(PostRejection::SessionRequired.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(PostRejection::ListingCap { active: 5, max: 5 }.status(), StatusCode::CONFLICT);
+        assert_eq!(PostRejection::Cooldown { remaining: 30 }.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after = PostRejection::Cooldown { remaining: 30 }.body()["retry_after_seconds"].as_u64();
+        assert!(retry_after.is_some_and(|seconds| (29..=30).contains(&seconds)));
+        assert_eq!(
+            PostRejection::ContentViolation { reason: "spam".to_string() }.body()["reason"],
+            "spam"
+        );
+    }
+}
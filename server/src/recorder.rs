@@ -0,0 +1,537 @@
+use anyhow::{Result, anyhow};
+use axum::{
+    body::{to_bytes, Body},
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::cities::CityStatus;
+use crate::models::PostMessageRequest;
+use crate::posting::MAX_MESSAGE_LENGTH;
+use crate::redis_client::RedisClient;
+use crate::security::content_filter::ViolationType;
+use crate::security::escalation::{Signals, Trigger};
+use crate::security::ip_reputation::RiskLevel;
+use crate::security::language::Language;
+use crate::security::middleware::SecurityContext;
+use crate::security::rate_limiter::{RateLimit, RateLimitType};
+use crate::security::trust_tier::TrustTier;
+use crate::security::visibility::{DeliveryPlan, VisibilityPolicy};
+use crate::security::TokenSigner;
+use crate::state::AppState;
+use crate::keys;
+
+const DEFAULT_MAXLEN: usize = 10000;
+/// Matches axum's default JSON body limit, so recording never rejects a body the handler would take
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+/// Headers kept in an envelope; cookies, auth, session and forwarding headers never are
+const RECORDED_HEADERS: &[&str] = &[
+    "user-agent",
+    "accept",
+    "accept-language",
+    "accept-encoding",
+    "content-type",
+    "origin",
+    "sec-fetch-site",
+    "sec-fetch-mode",
+    "sec-fetch-dest",
+    "sec-ch-ua",
+];
+/// Routes never worth recording
+const SKIPPED_PATHS: &[&str] = &["/ws", "/health"];
+
+/// Decisions taken while handling a recorded request, appended by the
+/// middleware and services it passes through
+#[derive(Clone, Default)]
+pub struct Decisions(Arc<Mutex<DecisionLog>>);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DecisionLog {
+    /// e.g. "moderation:spam", "escalation:burst-limit", "delivery:poster_only", "published"
+    pub steps: Vec<String>,
+    /// Effective risk level the posting pipeline worked with
+    pub risk_level: Option<RiskLevel>,
+    pub shadowbanned: Option<bool>,
+}
+
+impl Decisions {
+    pub fn push(&self, step: impl Into<String>) {
+        self.0.lock().unwrap().steps.push(step.into());
+    }
+
+    pub fn set_risk_level(&self, risk_level: RiskLevel) {
+        self.0.lock().unwrap().risk_level = Some(risk_level);
+    }
+
+    pub fn set_shadowbanned(&self, shadowbanned: bool) {
+        self.0.lock().unwrap().shadowbanned = Some(shadowbanned);
+    }
+
+    fn take(&self) -> DecisionLog {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+/// The parts of a post the moderation and rate limit pipeline looks at
+/// The listing text is public once posted; the phone number is reduced to its shape
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedPost {
+    pub message: String,
+    pub location: Option<String>,
+    /// Phone with every digit replaced by 9, enough to re-run format validation
+    pub phone_shape: Option<String>,
+    pub honeypot_filled: bool,
+    /// Resubmitted with a correction token, which skips the cooldown
+    pub correcting: bool,
+    /// Privacy policy version accepted with the post
+    #[serde(default)]
+    pub consent: Option<String>,
+}
+
+impl RecordedPost {
+    pub fn of(request: &PostMessageRequest) -> Self {
+        Self {
+            message: request.message.clone(),
+            location: request.location.clone(),
+            phone_shape: request.phone.as_deref().map(phone_shape),
+            honeypot_filled: request.website.as_ref().is_some_and(|website| !website.is_empty()),
+            correcting: request.correction_token.is_some(),
+            consent: request.consent.clone(),
+        }
+    }
+}
+
+/// Anonymized request envelope
+/// Actors appear by composite key only; IPs, fingerprints and tokens are never stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    #[serde(default)]
+    pub id: String,
+    pub recorded_at: i64,
+    pub method: String,
+    pub path: String,
+    pub actor: String,
+    pub headers: BTreeMap<String, String>,
+    /// Keyed hash of the raw body
+    pub body_hash: Option<String>,
+    pub header_score: u8,
+    pub trust_tier: TrustTier,
+    pub post: Option<RecordedPost>,
+    pub status: u16,
+    pub latency_ms: u64,
+    pub decisions: DecisionLog,
+}
+
+/// Opt-in recorder of anonymized request envelopes into a capped Redis stream,
+/// so "why was I banned" reports can be reproduced with `kirb-server replay`
+/// Enabled with `REQUEST_RECORDING=true`; `REQUEST_RECORDING_MAXLEN` caps the stream
+#[derive(Clone)]
+pub struct RequestRecorder {
+    redis: RedisClient,
+    enabled: bool,
+    maxlen: usize,
+    hasher: TokenSigner,
+}
+
+impl RequestRecorder {
+    pub fn from_env(redis: RedisClient, server_secret: &str) -> Self {
+        Self {
+            redis,
+            enabled: std::env::var("REQUEST_RECORDING").is_ok_and(|v| v == "true" || v == "1"),
+            maxlen: std::env::var("REQUEST_RECORDING_MAXLEN")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_MAXLEN),
+            hasher: TokenSigner::new(server_secret, "request-recorder"),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Record a listing posted over a WebSocket like a `POST /messages`, under
+    /// method `WS`; `status` is what the HTTP route would have answered
+    /// There are no request headers or body to keep, only the post itself
+    pub fn record_socket_post(
+        &self,
+        ctx: &SecurityContext,
+        post: RecordedPost,
+        status: u16,
+        started: Instant,
+        decisions: Decisions,
+    ) {
+        let envelope = RecordedRequest {
+            id: String::new(),
+            recorded_at: chrono::Utc::now().timestamp(),
+            method: "WS".to_string(),
+            path: "/messages".to_string(),
+            actor: ctx.composite_key.clone(),
+            headers: BTreeMap::new(),
+            body_hash: None,
+            header_score: ctx.header_score.score,
+            trust_tier: TrustTier::of(ctx),
+            post: Some(post),
+            status,
+            latency_ms: started.elapsed().as_millis() as u64,
+            decisions: decisions.take(),
+        };
+        self.spawn_record(envelope);
+    }
+
+    fn spawn_record(&self, envelope: RecordedRequest) {
+        let recorder = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = recorder.record(&envelope).await {
+                tracing::error!("{}", e);
+            }
+        });
+    }
+
+    async fn record(&self, envelope: &RecordedRequest) -> Result<()> {
+        let data = serde_json::to_string(envelope)?;
+        self.redis
+            .xadd_maxlen(keys::RECORDER_STREAM, self.maxlen, &[("data", &data)])
+            .await
+            .map_err(|e| anyhow!("Failed to record request: {}", e))?;
+        Ok(())
+    }
+
+    /// The newest `count` envelopes, oldest first
+    pub async fn recent(&self, count: usize) -> Result<Vec<RecordedRequest>> {
+        let entries = self.redis
+            .xrevrange(keys::RECORDER_STREAM, count)
+            .await
+            .map_err(|e| anyhow!("Failed to read recorded requests: {}", e))?;
+
+        Ok(entries
+            .into_iter()
+            .rev()
+            .filter_map(|(id, fields)| {
+                let mut envelope: RecordedRequest = serde_json::from_str(fields.get("data")?).ok()?;
+                envelope.id = id;
+                Some(envelope)
+            })
+            .collect())
+    }
+}
+
+/// Layer recording an envelope of every request that carries a security context
+/// Sits inside `security_middleware` so the context exists, and outside burst
+/// protection so its decisions are captured
+pub async fn recorder_middleware(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let recorder = &state.recorder;
+    let path = req.extensions().get::<MatchedPath>().map(|path| path.as_str().to_string());
+    let ctx = req.extensions().get::<SecurityContext>().cloned();
+    let (Some(path), Some(ctx), true) = (path, ctx, recorder.enabled()) else {
+        return next.run(req).await;
+    };
+    if SKIPPED_PATHS.contains(&path.as_str()) {
+        return next.run(req).await;
+    }
+
+    let (parts, body) = req.into_parts();
+    let bytes = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(_) => return next.run(Request::from_parts(parts, Body::empty())).await,
+    };
+    let body_hash = (!bytes.is_empty()).then(|| recorder.hasher.digest(&bytes));
+    let post = (path == "/messages" && parts.method == axum::http::Method::POST)
+        .then(|| serde_json::from_slice::<PostMessageRequest>(&bytes).ok())
+        .flatten()
+        .map(|request| RecordedPost::of(&request));
+    let headers = parts.headers
+        .iter()
+        .filter(|(name, _)| RECORDED_HEADERS.contains(&name.as_str()))
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let method = parts.method.to_string();
+
+    let decisions = Decisions::default();
+    let mut req = Request::from_parts(parts, Body::from(bytes));
+    req.extensions_mut().insert(decisions.clone());
+
+    let started = Instant::now();
+    let response = next.run(req).await;
+
+    let envelope = RecordedRequest {
+        id: String::new(),
+        recorded_at: chrono::Utc::now().timestamp(),
+        method,
+        path,
+        actor: ctx.composite_key.clone(),
+        headers,
+        body_hash,
+        header_score: ctx.header_score.score,
+        trust_tier: TrustTier::of(&ctx),
+        post,
+        status: response.status().as_u16(),
+        latency_ms: started.elapsed().as_millis() as u64,
+        decisions: decisions.take(),
+    };
+    recorder.spawn_record(envelope);
+
+    response
+}
+
+/// Every digit replaced by 9, keeping length and punctuation
+fn phone_shape(phone: &str) -> String {
+    phone.chars().map(|c| if c.is_ascii_digit() { '9' } else { c }).collect()
+}
+
+/// Arguments of `kirb-server replay [--actor <composite key>] [--count <n>]`
+pub struct ReplayArgs {
+    actor: Option<String>,
+    count: usize,
+}
+
+impl ReplayArgs {
+    /// Parsed from the command line when the first argument is `replay`
+    pub fn from_args() -> Option<Self> {
+        let mut args = std::env::args().skip(1);
+        if args.next().as_deref() != Some("replay") {
+            return None;
+        }
+        let mut replay = Self { actor: None, count: 1000 };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--actor" => replay.actor = args.next(),
+                "--count" => replay.count = args.next().and_then(|v| v.parse().ok()).unwrap_or(replay.count),
+                other => tracing::warn!(argument = other, "ignoring unknown replay argument"),
+            }
+        }
+        Some(replay)
+    }
+}
+
+/// A recorded post next to what the pipeline decides for it today
+#[derive(Debug, Serialize)]
+pub struct ReplayResult {
+    pub id: String,
+    pub recorded_at: i64,
+    pub actor: String,
+    pub recorded: Vec<String>,
+    pub replayed: Vec<String>,
+    /// The final outcome differs, e.g. after a wordlist or escalation rule change
+    pub diverged: bool,
+}
+
+/// Re-run recorded posts through the content, moderation, rate limit, escalation
+/// and visibility decisions with the local configuration, printing one JSON line
+/// per post. Nothing is enforced or stored; rate limits, cooldowns and violation
+/// counts are simulated from the recording alone, so they start from zero
+/// Run against a local Redis holding a copy of the stream and city policies
+pub async fn replay(state: &AppState, args: ReplayArgs) -> Result<()> {
+    let envelopes = state.recorder.recent(args.count).await?;
+    let mut session = ReplaySession::new(state.rate_limits.get(RateLimitType::PostMessage));
+
+    for envelope in envelopes {
+        if args.actor.as_ref().is_some_and(|actor| *actor != envelope.actor) {
+            continue;
+        }
+        let Some(post) = &envelope.post else {
+            continue;
+        };
+        let replayed = replay_post(state, &envelope, post, &mut session).await;
+        let recorded = envelope.decisions.steps;
+        let result = ReplayResult {
+            diverged: recorded.last() != replayed.last(),
+            id: envelope.id,
+            recorded_at: envelope.recorded_at,
+            actor: envelope.actor,
+            recorded,
+            replayed,
+        };
+        println!("{}", serde_json::to_string(&result)?);
+    }
+    Ok(())
+}
+
+/// Mirrors `PostingService::submit`, step for step, without side effects
+async fn replay_post(
+    state: &AppState,
+    envelope: &RecordedRequest,
+    post: &RecordedPost,
+    session: &mut ReplaySession,
+) -> Vec<String> {
+    let mut steps = Vec::new();
+    let actor = envelope.actor.as_str();
+    let at = envelope.recorded_at;
+    let risk_level = envelope.decisions.risk_level.unwrap_or(RiskLevel::Level0);
+    let config = state.config.current();
+    let escalate = |steps: &mut Vec<String>, trigger, violations| {
+        let signals = Signals { trigger, risk_level, header_score: envelope.header_score, violations };
+        if let Some(rule) = config.escalation.decide(&signals) {
+            steps.push(format!("escalation:{}", rule.name));
+        }
+    };
+
+    let outcome = 'outcome: {
+        if post.honeypot_filled {
+            break 'outcome "bot_detected";
+        }
+        if envelope.trust_tier != TrustTier::Session {
+            break 'outcome "session_required";
+        }
+        if post.message.len() > MAX_MESSAGE_LENGTH {
+            break 'outcome "too_long";
+        }
+        if post.message.trim().is_empty() {
+            break 'outcome "empty";
+        }
+        if let Some(rejection) = session.check_quota(actor, at, post.correcting) {
+            break 'outcome rejection;
+        }
+
+        let filter_result = config.content_filter.check_message(&post.message);
+        if filter_result.violation_type == Some(ViolationType::EmbeddedPhone) {
+            break 'outcome "embedded_phone";
+        }
+        if !filter_result.is_allowed {
+            let violations = session.add_violation(actor);
+            escalate(&mut steps, Trigger::ContentViolation, violations);
+            break 'outcome "content_violation";
+        }
+
+        let language = Language::detect(&post.message);
+        let moderation_result = config.moderation
+            .moderate_message(&post.message, post.location.as_deref(), language, None)
+            .await;
+        if let Some(violation) = &moderation_result.violation_type {
+            steps.push(format!("moderation:{}", violation.as_str()));
+        }
+        if !moderation_result.is_allowed {
+            let violations = session.add_violation(actor);
+            escalate(&mut steps, Trigger::ModerationViolation, violations);
+            break 'outcome "content_violation";
+        }
+        let message = match moderation_result.masked_content {
+            Some(masked) => {
+                steps.push("masked".to_string());
+                masked
+            }
+            None => post.message.clone(),
+        };
+
+        if !config.content_filter.validate_phone(post.phone_shape.as_deref()) {
+            break 'outcome "invalid_phone";
+        }
+        if post.phone_shape.as_deref().is_some_and(|phone| !phone.trim().is_empty()) {
+            let consented = post.consent.as_deref().is_some_and(|version| state.consents.is_current(version))
+                || state.consents.has_current(actor).await.unwrap_or(false);
+            if !consented {
+                break 'outcome "consent_required";
+            }
+        }
+        if config.content_filter.is_suspicious_pattern(&message) {
+            session.add_violation(actor);
+        }
+
+        let delivery = VisibilityPolicy {
+            shadowbanned: envelope.decisions.shadowbanned.unwrap_or(false),
+            risk_level,
+            trust_tier: envelope.trust_tier,
+        }
+        .delivery_plan();
+        steps.push(format!("delivery:{}", delivery.as_str()));
+        session.consume_quota(actor, at, risk_level);
+
+        match delivery {
+            DeliveryPlan::Broadcast => {}
+            DeliveryPlan::PosterOnly => break 'outcome "throttled",
+            DeliveryPlan::Suppress => break 'outcome "suppressed",
+        }
+        if let Some(city) = post.location.as_deref() {
+            if let Ok(CityStatus::Waitlist) = state.cities.status(city).await {
+                break 'outcome "queued";
+            }
+        }
+        "published"
+    };
+
+    steps.push(outcome.to_string());
+    steps
+}
+
+/// Per-actor posting limits and violation counts, rebuilt from the recording
+struct ReplaySession {
+    /// The posting limit in force locally
+    post_limit: RateLimit,
+    posts: HashMap<String, VecDeque<i64>>,
+    cooldown_until: HashMap<String, i64>,
+    violations: HashMap<String, i64>,
+}
+
+impl ReplaySession {
+    fn new(post_limit: RateLimit) -> Self {
+        Self {
+            post_limit,
+            posts: HashMap::new(),
+            cooldown_until: HashMap::new(),
+            violations: HashMap::new(),
+        }
+    }
+
+    fn check_quota(&mut self, actor: &str, at: i64, correcting: bool) -> Option<&'static str> {
+        if !correcting && self.cooldown_until.get(actor).is_some_and(|until| at < *until) {
+            return Some("cooldown");
+        }
+        let limit = self.post_limit;
+        let window = self.posts.entry(actor.to_string()).or_default();
+        while window.front().is_some_and(|posted| at - posted >= limit.window_seconds as i64) {
+            window.pop_front();
+        }
+        (window.len() as i64 >= limit.max_requests).then_some("rate_limited")
+    }
+
+    fn consume_quota(&mut self, actor: &str, at: i64, risk_level: RiskLevel) {
+        self.posts.entry(actor.to_string()).or_default().push_back(at);
+        self.cooldown_until.insert(actor.to_string(), at + risk_level.cooldown_seconds() as i64);
+    }
+
+    fn add_violation(&mut self, actor: &str) -> i64 {
+        let violations = self.violations.entry(actor.to_string()).or_default();
+        *violations += 1;
+        *violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::rate_limiter::RateLimitConfig;
+
+    #[test]
+    fn test_phone_shape_hides_digits() {
+        assert_eq!(phone_shape("+1 (415) 555-0100"), "+9 (999) 999-9999");
+    }
+
+    #[test]
+    fn test_replay_session_limits() {
+        let mut session = ReplaySession::new(RateLimitConfig::default().get(RateLimitType::PostMessage));
+        assert_eq!(session.check_quota("a", 0, false), None);
+        session.consume_quota("a", 0, RiskLevel::Level1);
+
+        // Other actors are unaffected
+        assert_eq!(session.check_quota("b", 10, false), None);
+        // The level 1 cooldown outlasts the one-minute rate limit window
+        assert_eq!(session.check_quota("a", 30, false), Some("cooldown"));
+        assert_eq!(session.check_quota("a", 30, true), Some("rate_limited"));
+        assert_eq!(session.check_quota("a", 120, false), Some("cooldown"));
+        assert_eq!(session.check_quota("a", 120, true), None);
+        assert_eq!(session.check_quota("a", 300, false), None);
+    }
+
+    #[test]
+    fn test_replay_session_counts_violations() {
+        let mut session = ReplaySession::new(RateLimitConfig::default().get(RateLimitType::PostMessage));
+        assert_eq!(session.add_violation("a"), 1);
+        assert_eq!(session.add_violation("a"), 2);
+        assert_eq!(session.add_violation("b"), 1);
+    }
+}
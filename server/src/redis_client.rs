@@ -1,11 +1,69 @@
-use redis::{aio::ConnectionManager, AsyncCommands, RedisError, Client};
+use redis::{aio::{ConnectionLike, ConnectionManager}, AsyncCommands, Cmd, Direction, FromRedisValue, Pipeline, RedisError, RedisFuture, Client, ToRedisArgs, Value};
 use anyhow::{Context, Result};
+use redis::streams::{StreamId, StreamRangeReply, StreamReadOptions, StreamReadReply};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::deadline::Deadline;
+use crate::load_shedding::LatencyWindow;
+
+/// Connection manager that times every command into a shared latency window
+#[derive(Clone)]
+struct TimedConnection {
+    inner: ConnectionManager,
+    latency: Arc<LatencyWindow>,
+}
+
+impl ConnectionLike for TimedConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            let started = Instant::now();
+            let result = within_request_deadline(self.inner.req_packed_command(cmd)).await;
+            self.latency.record(started.elapsed());
+            result
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            let started = Instant::now();
+            let result = within_request_deadline(self.inner.req_packed_commands(cmd, offset, count)).await;
+            self.latency.record(started.elapsed());
+            result
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.inner.get_db()
+    }
+}
+
+/// Bound a command by the deadline of the request it's sent for, if any
+/// (see `deadline`); background work runs without one
+async fn within_request_deadline<T>(command: RedisFuture<'_, T>) -> redis::RedisResult<T> {
+    let Some(deadline) = Deadline::current() else {
+        return command.await;
+    };
+    match deadline.run(command).await {
+        Ok(result) => result,
+        Err(e) => {
+            metrics::counter!("redis_commands_deadline_exceeded_total", 1);
+            Err(std::io::Error::new(std::io::ErrorKind::TimedOut, e).into())
+        }
+    }
+}
 
 /// Redis client wrapper for managing Redis connections and operations
 /// Enforces secure connection requirements (password authentication for production)
 #[derive(Clone)]
 pub struct RedisClient {
-    manager: ConnectionManager,
+    manager: TimedConnection,
     client: Client,
 }
 
@@ -30,9 +88,7 @@ impl RedisClient {
         
         // Log security warning if no password is detected
         if !has_password {
-            eprintln!("⚠️  WARNING: Redis URL does not include a password!");
-            eprintln!("🔒 For production, always use: redis://:yourpassword@host:port");
-            eprintln!("🔒 Generate a strong password and update REDIS_URL in your .env file");
+            tracing::warn!("Redis URL does not include a password; use redis://:yourpassword@host:port in production");
         }
 
         let client = redis::Client::open(redis_url)
@@ -42,20 +98,51 @@ impl RedisClient {
             .await
             .context("Failed to create Redis connection manager - check REDIS_URL and password")?;
         
+        let manager = TimedConnection {
+            inner: manager,
+            latency: Arc::new(LatencyWindow::default()),
+        };
         Ok(Self { manager, client })
     }
 
+    /// Rolling latency of commands sent through this client (see `load_shedding`)
+    pub fn latency(&self) -> Arc<LatencyWindow> {
+        self.manager.latency.clone()
+    }
+
+    /// Start a batch of commands sent in a single round trip
+    pub fn pipeline(&self) -> RedisPipeline {
+        RedisPipeline {
+            manager: self.manager.clone(),
+            pipe: redis::pipe(),
+        }
+    }
+
+    /// Start a batch of commands executed atomically (MULTI/EXEC)
+    pub fn transaction(&self) -> RedisPipeline {
+        let mut pipeline = self.pipeline();
+        pipeline.pipe.atomic();
+        pipeline
+    }
+
     /// Get the underlying client for pub/sub operations
     pub fn get_client(&self) -> Client {
         self.client.clone()
     }
 
+    /// Set a key-value pair without expiry
+    pub async fn set(&self, key: &str, value: &str) -> Result<(), RedisError> {
+        let mut conn = self.manager.clone();
+        conn.set(key, value).await
+    }
+
     /// Set a key-value pair with an expiration time (in seconds)
     pub async fn set_ex(&self, key: &str, value: &str, seconds: u64) -> Result<(), RedisError> {
         let mut conn = self.manager.clone();
         conn.set_ex(key, value, seconds).await
     }
 
+
     /// Get a value by key
     pub async fn get(&self, key: &str) -> Result<Option<String>, RedisError> {
         let mut conn = self.manager.clone();
@@ -96,7 +183,8 @@ impl RedisClient {
     }
 
     /// Add an element to a sorted set with a score (for sliding window)
-    pub async fn zadd(&self, key: &str, score: f64, member: &str) -> Result<(), RedisError> {
+    /// Returns the number of newly added members (0 if only the score was updated)
+    pub async fn zadd(&self, key: &str, score: f64, member: &str) -> Result<i64, RedisError> {
         let mut conn = self.manager.clone();
         conn.zadd(key, member, score).await
     }
@@ -137,31 +225,96 @@ impl RedisClient {
         conn.expire(key, seconds).await
     }
 
-    /// Get multiple values by keys
-    #[allow(dead_code)]
-    pub async fn mget(&self, keys: &[&str]) -> Result<Vec<Option<String>>, RedisError> {
+    /// Get a range of members from a sorted set (by rank)
+    pub async fn zrange(&self, key: &str, start: isize, stop: isize) -> Result<Vec<String>, RedisError> {
         let mut conn = self.manager.clone();
-        conn.get(keys).await
+        conn.zrange(key, start, stop).await
     }
 
-    /// Get all keys matching a pattern
-    pub async fn keys(&self, pattern: &str) -> Result<Vec<String>, RedisError> {
+    /// Get members of a sorted set within a score range
+    pub async fn zrangebyscore(&self, key: &str, min: f64, max: f64) -> Result<Vec<String>, RedisError> {
         let mut conn = self.manager.clone();
-        redis::cmd("KEYS")
-            .arg(pattern)
+        conn.zrangebyscore(key, min, max).await
+    }
+
+    /// Get the first `count` members (with scores) within a score range
+    pub async fn zrangebyscore_withscores_limit(&self, key: &str, min: f64, max: f64, count: isize) -> Result<Vec<(String, f64)>, RedisError> {
+        let mut conn = self.manager.clone();
+        conn.zrangebyscore_limit_withscores(key, min, max, 0, count).await
+    }
+
+    /// Get up to `count` members (with scores) within a score range, skipping the first `offset`
+    pub async fn zrangebyscore_withscores_page(
+        &self,
+        key: &str,
+        min: f64,
+        max: f64,
+        offset: isize,
+        count: isize,
+    ) -> Result<Vec<(String, f64)>, RedisError> {
+        let mut conn = self.manager.clone();
+        conn.zrangebyscore_limit_withscores(key, min, max, offset, count).await
+    }
+
+    /// Get multiple values by keys
+    pub async fn mget(&self, keys: &[&str]) -> Result<Vec<Option<String>>, RedisError> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.manager.clone();
+        // Always use MGET so a single key still yields a Vec
+        redis::cmd("MGET")
+            .arg(keys)
             .query_async(&mut conn)
             .await
     }
 
+    /// Get all keys matching a pattern using cursor-based SCAN
+    /// Unlike KEYS this never blocks Redis while walking the whole keyspace; `batch`
+    /// is the COUNT hint per step. SCAN can return a key twice, so results are deduplicated
+    pub async fn scan_match(&self, pattern: &str, batch: usize) -> Result<Vec<String>, RedisError> {
+        let mut seen = std::collections::HashSet::new();
+        let mut keys = Vec::new();
+        let mut cursor = 0;
+        loop {
+            let (next, batch_keys) = self.scan(cursor, Some(pattern), batch).await?;
+            for key in batch_keys {
+                if seen.insert(key.clone()) {
+                    keys.push(key);
+                }
+            }
+            if next == 0 {
+                return Ok(keys);
+            }
+            cursor = next;
+        }
+    }
+
     /// Add to a list (left push)
-    #[allow(dead_code)]
     pub async fn lpush(&self, key: &str, value: &str) -> Result<(), RedisError> {
         let mut conn = self.manager.clone();
         conn.lpush(key, value).await
     }
 
+    /// Append to a list (right push)
+    pub async fn rpush(&self, key: &str, value: &str) -> Result<(), RedisError> {
+        let mut conn = self.manager.clone();
+        conn.rpush(key, value).await
+    }
+
+    /// Remove and return the last element of a list
+    pub async fn rpop(&self, key: &str) -> Result<Option<String>, RedisError> {
+        let mut conn = self.manager.clone();
+        conn.rpop(key, None).await
+    }
+
+    /// Move the last element of `source` onto the head of `destination` and return it
+    pub async fn lmove(&self, source: &str, destination: &str) -> Result<Option<String>, RedisError> {
+        let mut conn = self.manager.clone();
+        conn.lmove(source, destination, Direction::Right, Direction::Left).await
+    }
+
     /// Get a range from a list
-    #[allow(dead_code)]
     pub async fn lrange(&self, key: &str, start: isize, stop: isize) -> Result<Vec<String>, RedisError> {
         let mut conn = self.manager.clone();
         conn.lrange(key, start, stop).await
@@ -174,18 +327,146 @@ impl RedisClient {
         conn.ltrim(key, start, stop).await
     }
 
+    /// Remove up to `count` occurrences of a value from a list (0 removes all)
+    pub async fn lrem(&self, key: &str, count: isize, value: &str) -> Result<i64, RedisError> {
+        let mut conn = self.manager.clone();
+        conn.lrem(key, count, value).await
+    }
+
+    /// Check whether a member is in a set
+    pub async fn sismember(&self, key: &str, member: &str) -> Result<bool, RedisError> {
+        let mut conn = self.manager.clone();
+        conn.sismember(key, member).await
+    }
+
+    /// Remove a member from a set
+    pub async fn srem(&self, key: &str, member: &str) -> Result<i64, RedisError> {
+        let mut conn = self.manager.clone();
+        conn.srem(key, member).await
+    }
+
+    /// Get all members of a set
+    pub async fn smembers(&self, key: &str) -> Result<Vec<String>, RedisError> {
+        let mut conn = self.manager.clone();
+        conn.smembers(key).await
+    }
+
+    /// Get the length of a list
+    pub async fn llen(&self, key: &str) -> Result<i64, RedisError> {
+        let mut conn = self.manager.clone();
+        conn.llen(key).await
+    }
+
     /// Add a member to a set
     pub async fn sadd(&self, key: &str, member: &str) -> Result<i64, RedisError> {
         let mut conn = self.manager.clone();
         conn.sadd(key, member).await
     }
 
+    /// Run a Lua script atomically (EVALSHA, loading it on first use)
+    pub async fn run_script<T: FromRedisValue>(
+        &self,
+        script: &redis::Script,
+        keys: &[String],
+        args: &[String],
+    ) -> Result<T, RedisError> {
+        let mut conn = self.manager.clone();
+        let mut invocation = script.prepare_invoke();
+        for key in keys {
+            invocation.key(key);
+        }
+        for arg in args {
+            invocation.arg(arg);
+        }
+        invocation.invoke_async(&mut conn).await
+    }
+
     /// Get the cardinality (number of members) of a set
     pub async fn scard(&self, key: &str) -> Result<i64, RedisError> {
         let mut conn = self.manager.clone();
         conn.scard(key).await
     }
 
+    /// Add one member to each of several HyperLogLogs in a single round trip,
+    /// refreshing each key's expiry
+    pub async fn pfadd_batch(&self, keys: &[String], member: &str, ttl_seconds: i64) -> Result<(), RedisError> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.manager.clone();
+        let mut pipe = redis::pipe();
+        for key in keys {
+            pipe.cmd("PFADD").arg(key).arg(member).ignore();
+            pipe.cmd("EXPIRE").arg(key).arg(ttl_seconds).ignore();
+        }
+        pipe.query_async(&mut conn).await
+    }
+
+    /// Get the approximate cardinality of a HyperLogLog
+    pub async fn pfcount(&self, key: &str) -> Result<i64, RedisError> {
+        let mut conn = self.manager.clone();
+        redis::cmd("PFCOUNT").arg(key).query_async(&mut conn).await
+    }
+
+    /// Append an entry to a stream, trimming it to approximately `maxlen` entries
+    /// Returns the generated entry ID
+    pub async fn xadd_maxlen(&self, key: &str, maxlen: usize, fields: &[(&str, &str)]) -> Result<String, RedisError> {
+        let mut conn = self.manager.clone();
+        conn.xadd_maxlen(key, redis::streams::StreamMaxlen::Approx(maxlen), "*", fields).await
+    }
+
+    /// Read the newest `count` entries of a stream (newest first)
+    /// Returns (entry ID, field map) pairs
+    pub async fn xrevrange(&self, key: &str, count: usize) -> Result<Vec<(String, HashMap<String, String>)>, RedisError> {
+        let mut conn = self.manager.clone();
+        let reply: StreamRangeReply = conn.xrevrange_count(key, "+", "-", count).await?;
+        Ok(stream_entries(reply.ids))
+    }
+
+    /// Read up to `count` entries of a stream from `start` (inclusive) onwards, oldest first
+    /// Returns (entry ID, field map) pairs
+    pub async fn xrange(&self, key: &str, start: &str, count: usize) -> Result<Vec<(String, HashMap<String, String>)>, RedisError> {
+        let mut conn = self.manager.clone();
+        let reply: StreamRangeReply = conn.xrange_count(key, start, "+", count).await?;
+        Ok(stream_entries(reply.ids))
+    }
+
+    /// Open a dedicated connection for blocking stream reads
+    pub async fn stream_reader(&self) -> Result<StreamReader, RedisError> {
+        Ok(StreamReader {
+            conn: self.client.get_async_connection().await?,
+        })
+    }
+
+    /// One SCAN step: returns the next cursor (0 when done) and a batch of keys
+    pub async fn scan(&self, cursor: u64, pattern: Option<&str>, count: usize) -> Result<(u64, Vec<String>), RedisError> {
+        let mut conn = self.manager.clone();
+        let mut cmd = redis::cmd("SCAN");
+        cmd.arg(cursor);
+        if let Some(pattern) = pattern {
+            cmd.arg("MATCH").arg(pattern);
+        }
+        cmd.arg("COUNT").arg(count).query_async(&mut conn).await
+    }
+
+    /// Number of keys in the current database
+    pub async fn dbsize(&self) -> Result<u64, RedisError> {
+        let mut conn = self.manager.clone();
+        redis::cmd("DBSIZE").query_async(&mut conn).await
+    }
+
+    /// INFO for one section, parsed into field/value pairs
+    pub async fn info(&self, section: &str) -> Result<HashMap<String, String>, RedisError> {
+        let mut conn = self.manager.clone();
+        let raw: String = redis::cmd("INFO").arg(section).query_async(&mut conn).await?;
+        Ok(raw
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.split_once(':'))
+            .map(|(field, value)| (field.to_string(), value.trim().to_string()))
+            .collect())
+    }
+
     /// Ping Redis to check if connection is alive
     pub async fn ping(&self) -> Result<bool, RedisError> {
         let mut conn = self.manager.clone();
@@ -195,3 +476,162 @@ impl RedisClient {
             .map(|resp| resp == "PONG")
     }
 }
+
+/// Connection of its own for `XREAD BLOCK`, which would otherwise stall every
+/// command sharing the multiplexed connection while it waits
+/// A read must not be cancelled midway, or its late reply desyncs the connection
+pub struct StreamReader {
+    conn: redis::aio::Connection,
+}
+
+impl StreamReader {
+    /// Entries after `after` (an entry ID, or `$` for only new ones), oldest first
+    /// Waits up to `block` for one to arrive; returns none on timeout
+    pub async fn read(
+        &mut self,
+        key: &str,
+        after: &str,
+        block: std::time::Duration,
+        count: usize,
+    ) -> Result<Vec<(String, HashMap<String, String>)>, RedisError> {
+        let options = StreamReadOptions::default()
+            .block(block.as_millis() as usize)
+            .count(count);
+        let reply: StreamReadReply = self.conn.xread_options(&[key], &[after], &options).await?;
+        Ok(reply
+            .keys
+            .into_iter()
+            .flat_map(|stream| stream_entries(stream.ids))
+            .collect())
+    }
+}
+
+/// Stream entries as (entry ID, field map) pairs, skipping non-string values
+fn stream_entries(ids: Vec<StreamId>) -> Vec<(String, HashMap<String, String>)> {
+    ids.into_iter()
+        .map(|entry| {
+            let fields = entry
+                .map
+                .iter()
+                .filter_map(|(field, value)| {
+                    redis::from_redis_value::<String>(value)
+                        .ok()
+                        .map(|v| (field.clone(), v))
+                })
+                .collect();
+            (entry.id, fields)
+        })
+        .collect()
+}
+
+/// Queued Redis commands, sent together by `execute` or `query`
+/// Each queued command contributes one value to the `query` result unless
+/// `ignore` is called right after it
+pub struct RedisPipeline {
+    manager: TimedConnection,
+    pipe: redis::Pipeline,
+}
+
+impl RedisPipeline {
+    pub fn set_ex<V: ToRedisArgs>(&mut self, key: &str, value: V, seconds: u64) -> &mut Self {
+        self.pipe.cmd("SET").arg(key).arg(value).arg("EX").arg(seconds);
+        self
+    }
+
+    /// Overwrite a value while keeping its remaining time to live
+    pub fn set_keepttl<V: ToRedisArgs>(&mut self, key: &str, value: V) -> &mut Self {
+        self.pipe.cmd("SET").arg(key).arg(value).arg("KEEPTTL");
+        self
+    }
+
+    pub fn del(&mut self, key: &str) -> &mut Self {
+        self.pipe.cmd("DEL").arg(key);
+        self
+    }
+
+    pub fn incr(&mut self, key: &str) -> &mut Self {
+        self.pipe.cmd("INCR").arg(key);
+        self
+    }
+
+    pub fn expire(&mut self, key: &str, seconds: i64) -> &mut Self {
+        self.pipe.cmd("EXPIRE").arg(key).arg(seconds);
+        self
+    }
+
+    pub fn zadd(&mut self, key: &str, score: f64, member: &str) -> &mut Self {
+        self.pipe.cmd("ZADD").arg(key).arg(score).arg(member);
+        self
+    }
+
+    pub fn zrem(&mut self, key: &str, member: &str) -> &mut Self {
+        self.pipe.cmd("ZREM").arg(key).arg(member);
+        self
+    }
+
+    pub fn zrembyscore(&mut self, key: &str, min: f64, max: f64) -> &mut Self {
+        self.pipe.cmd("ZREMRANGEBYSCORE").arg(key).arg(min).arg(max);
+        self
+    }
+
+    pub fn zrangebyscore(&mut self, key: &str, min: f64, max: f64) -> &mut Self {
+        self.pipe.cmd("ZRANGEBYSCORE").arg(key).arg(min).arg(max);
+        self
+    }
+
+    /// Members scored at least `min`, highest score first, at most `count`
+    pub fn zrevrangebyscore_limit(&mut self, key: &str, min: f64, count: usize) -> &mut Self {
+        self.pipe.cmd("ZREVRANGEBYSCORE").arg(key).arg("+inf").arg(min).arg("LIMIT").arg(0).arg(count);
+        self
+    }
+
+    /// Store the members present in every one of `keys` at `destination`,
+    /// each scored by its highest score
+    pub fn zinterstore_max(&mut self, destination: &str, keys: &[String]) -> &mut Self {
+        self.pipe.cmd("ZINTERSTORE").arg(destination).arg(keys.len()).arg(keys).arg("AGGREGATE").arg("MAX");
+        self
+    }
+
+    pub fn exists(&mut self, key: &str) -> &mut Self {
+        self.pipe.cmd("EXISTS").arg(key);
+        self
+    }
+
+    pub fn sadd(&mut self, key: &str, member: &str) -> &mut Self {
+        self.pipe.cmd("SADD").arg(key).arg(member);
+        self
+    }
+
+    pub fn scard(&mut self, key: &str) -> &mut Self {
+        self.pipe.cmd("SCARD").arg(key);
+        self
+    }
+
+    pub fn lpush(&mut self, key: &str, value: &str) -> &mut Self {
+        self.pipe.cmd("LPUSH").arg(key).arg(value);
+        self
+    }
+
+    pub fn ltrim(&mut self, key: &str, start: isize, stop: isize) -> &mut Self {
+        self.pipe.cmd("LTRIM").arg(key).arg(start).arg(stop);
+        self
+    }
+
+    /// Drop the previous command's reply from the `query` result
+    pub fn ignore(&mut self) -> &mut Self {
+        self.pipe.ignore();
+        self
+    }
+
+    /// Send the batch, discarding all replies
+    pub async fn execute(&mut self) -> Result<(), RedisError> {
+        self.query::<()>().await
+    }
+
+    /// Send the batch and decode the replies of the commands not ignored
+    /// (a tuple or Vec with one entry per command)
+    pub async fn query<T: FromRedisValue>(&mut self) -> Result<T, RedisError> {
+        let mut conn = self.manager.clone();
+        self.pipe.query_async(&mut conn).await
+    }
+}
@@ -0,0 +1,155 @@
+use crate::redis_client::RedisClient;
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Keys requested per SCAN step
+const SCAN_BATCH: usize = 1000;
+/// Stop sampling after this many keys; counts are then extrapolated from DBSIZE
+const SCAN_SAMPLE_LIMIT: usize = 200_000;
+/// Prefixes beyond this many are folded into "other" to keep gauge cardinality bounded
+const MAX_PREFIXES: usize = 50;
+
+/// INFO memory fields reported by the diagnostics endpoint
+const MEMORY_FIELDS: &[&str] = &[
+    "used_memory",
+    "used_memory_peak",
+    "used_memory_rss",
+    "maxmemory",
+    "mem_fragmentation_ratio",
+    "maxmemory_policy",
+];
+
+/// Snapshot of what is filling Redis
+#[derive(Debug, Serialize)]
+pub struct RedisUsage {
+    pub memory: HashMap<String, String>,
+    pub total_keys: u64,
+    pub scanned_keys: usize,
+    /// True when the sample stopped early and `keys_by_prefix` is extrapolated
+    pub sampled: bool,
+    /// Key counts grouped by the segment before the first ':' (e.g. "message", "ratelimit")
+    pub keys_by_prefix: Vec<PrefixCount>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct PrefixCount {
+    pub prefix: String,
+    pub keys: u64,
+}
+
+/// Sample INFO memory and per-prefix key counts using SCAN
+pub async fn collect(redis: &RedisClient) -> Result<RedisUsage> {
+    let info = redis
+        .info("memory")
+        .await
+        .map_err(|e| anyhow!("Failed to read Redis memory info: {}", e))?;
+    let memory: HashMap<String, String> = info
+        .into_iter()
+        .filter(|(field, _)| MEMORY_FIELDS.contains(&field.as_str()))
+        .collect();
+
+    let total_keys = redis
+        .dbsize()
+        .await
+        .map_err(|e| anyhow!("Failed to read Redis key count: {}", e))?;
+
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    let mut scanned = 0;
+    let mut cursor = 0;
+    loop {
+        let (next, keys) = redis
+            .scan(cursor, None, SCAN_BATCH)
+            .await
+            .map_err(|e| anyhow!("Failed to scan Redis keys: {}", e))?;
+        scanned += keys.len();
+        for key in &keys {
+            *counts.entry(key_prefix(key).to_string()).or_default() += 1;
+        }
+        cursor = next;
+        if cursor == 0 || scanned >= SCAN_SAMPLE_LIMIT {
+            break;
+        }
+    }
+
+    let sampled = cursor != 0;
+    let keys_by_prefix = summarize(counts, scanned, total_keys, sampled);
+    export_gauges(&memory, total_keys, &keys_by_prefix);
+
+    Ok(RedisUsage {
+        memory,
+        total_keys,
+        scanned_keys: scanned,
+        sampled,
+        keys_by_prefix,
+    })
+}
+
+/// Largest prefixes first, extrapolated when sampled, with the long tail folded into "other"
+fn summarize(counts: HashMap<String, u64>, scanned: usize, total_keys: u64, sampled: bool) -> Vec<PrefixCount> {
+    let scale = if sampled && scanned > 0 {
+        total_keys as f64 / scanned as f64
+    } else {
+        1.0
+    };
+
+    let mut prefixes: Vec<PrefixCount> = counts
+        .into_iter()
+        .map(|(prefix, keys)| PrefixCount {
+            prefix,
+            keys: (keys as f64 * scale).round() as u64,
+        })
+        .collect();
+    prefixes.sort_by(|a, b| b.keys.cmp(&a.keys).then_with(|| a.prefix.cmp(&b.prefix)));
+
+    if prefixes.len() > MAX_PREFIXES {
+        let other: u64 = prefixes.drain(MAX_PREFIXES - 1..).map(|p| p.keys).sum();
+        prefixes.push(PrefixCount { prefix: "other".to_string(), keys: other });
+    }
+    prefixes
+}
+
+fn export_gauges(memory: &HashMap<String, String>, total_keys: u64, prefixes: &[PrefixCount]) {
+    for (field, gauge) in [
+        ("used_memory", "redis_used_memory_bytes"),
+        ("used_memory_peak", "redis_used_memory_peak_bytes"),
+        ("maxmemory", "redis_maxmemory_bytes"),
+    ] {
+        if let Some(value) = memory.get(field).and_then(|v| v.parse::<f64>().ok()) {
+            metrics::gauge!(gauge, value);
+        }
+    }
+    metrics::gauge!("redis_keys_total", total_keys as f64);
+    for prefix in prefixes {
+        metrics::gauge!("redis_keys", prefix.keys as f64, "prefix" => prefix.prefix.clone());
+    }
+}
+
+fn key_prefix(key: &str) -> &str {
+    key.split(':').next().unwrap_or(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_extrapolates_and_folds_tail() {
+        let mut counts: HashMap<String, u64> = (0..60).map(|i| (format!("p{:02}", i), 1)).collect();
+        counts.insert("message".to_string(), 100);
+
+        // Half the keyspace scanned: counts double
+        let summary = summarize(counts, 160, 320, true);
+        assert_eq!(summary.len(), MAX_PREFIXES);
+        assert_eq!(summary[0], PrefixCount { prefix: "message".to_string(), keys: 200 });
+        let other = summary.last().unwrap();
+        assert_eq!(other.prefix, "other");
+        assert_eq!(other.keys, 2 * (60 - (MAX_PREFIXES as u64 - 2)));
+    }
+
+    #[test]
+    fn test_key_prefix() {
+        assert_eq!(key_prefix("ratelimit:post:abc"), "ratelimit");
+        assert_eq!(key_prefix("messages"), "messages");
+    }
+}
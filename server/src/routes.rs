@@ -1,20 +1,97 @@
-use axum::{routing::get, routing::post, Router, middleware};
-use crate::{handlers, state::AppState, security::middleware::{security_middleware, burst_protection_middleware}};
+use axum::{routing::any, routing::get, routing::post, routing::put, Router, middleware};
+use crate::{admin, handlers, permalink, state::AppState, security::middleware::{security_middleware, burst_protection_middleware}};
+use crate::concurrency::{ConcurrencyLimit, concurrency_limit_middleware};
+use crate::slo::slo_middleware;
+use crate::recorder::recorder_middleware;
+
+/// Paths no legitimate client of this API ever requests
+/// Hitting one marks the caller as a scanner/bot (see `handlers::bot_trap`)
+const BOT_TRAP_PATHS: &[&str] = &[
+    "/wp-login.php",
+    "/wp-admin",
+    "/xmlrpc.php",
+    "/administrator",
+    "/phpmyadmin",
+    "/.env",
+    "/.git/config",
+    "/config.php",
+    "/server-status",
+];
+/// Trapped only while the admin API is off the public listener, so an operator
+/// typing the bare path on the public listener isn't blocked
+const ADMIN_TRAP_PATH: &str = "/admin";
 
 pub fn create_router(state: AppState) -> Router {
-    Router::new()
-        .route("/ws", get(handlers::websocket_handler))
+    let router = BOT_TRAP_PATHS
+        .iter()
+        .fold(Router::new(), |router, path| router.route(path, any(handlers::bot_trap)));
+
+    // Admin API lives on the public listener only when no mutual-TLS listener is configured
+    let router = if state.admin.serves_on_public_listener() {
+        router.merge(admin::admin_routes(&state))
+    } else {
+        router.route(ADMIN_TRAP_PATH, any(handlers::bot_trap))
+    };
+
+    // Each route group gets its own concurrency ceiling so a pile-up of slow
+    // moderated posts can't starve cheap reads (see `concurrency`)
+    let posting = Router::new()
         .route("/messages", post(handlers::post_message))
+        // Edits run the same moderation as new posts
+        .route("/messages/:id", put(handlers::edit_message))
+        .route_layer(middleware::from_fn_with_state(
+            state.posting_limit.clone(),
+            concurrency_limit_middleware,
+        ));
+
+    let writes = Router::new()
+        .route("/messages/:id/reactions", post(handlers::react_to_message))
+        .route("/messages/:id/confirm", post(handlers::confirm_availability))
+        .route("/api/report", post(handlers::report_message))
+        .route("/api/session", post(handlers::create_session).delete(handlers::end_session))
+        .route("/api/session/refresh", post(handlers::refresh_session))
+        .route("/api/cities/:city/interest", post(handlers::register_city_interest))
+        .route("/api/track-visitor", post(handlers::track_visitor))
+        .route(
+            "/api/notifications/preferences",
+            get(handlers::get_notification_preferences).post(handlers::set_notification_preferences),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            ConcurrencyLimit::from_env("writes", 128, 1000),
+            concurrency_limit_middleware,
+        ));
+
+    let reads = Router::new()
         .route("/messages", get(handlers::get_messages))
+        .route("/messages/:id", get(handlers::get_message))
+        .route("/messages/:id/stats", get(handlers::get_listing_stats))
+        .route("/api/search", get(handlers::search_messages))
+        .route("/m/:id", get(permalink::get_permalink))
+        .route("/api/contact/quota", get(handlers::get_reveal_quota))
         .route("/api/contact/:message_id", get(handlers::get_contact))
         .route("/api/cooldown", get(handlers::get_cooldown))
-        .route("/api/report", post(handlers::report_message))
-        .route("/api/track-visitor", post(handlers::track_visitor))
+        .route("/api/geo", get(handlers::get_geo))
+        .route("/api/cities/:city/waitlist", get(handlers::get_city_waitlist))
         // Stats endpoints - use only burst protection, not rate limiting
         .route("/api/stats/daily", get(handlers::get_daily_stats))
         .route("/api/stats/cities", get(handlers::get_city_stats))
+        .route_layer(middleware::from_fn_with_state(
+            ConcurrencyLimit::from_env("reads", 256, 500),
+            concurrency_limit_middleware,
+        ));
+
+    // WebSockets are long-lived and /health must answer even when saturated
+    router
+        .route("/ws", get(handlers::websocket_handler))
         .route("/health", get(handlers::health_check))
+        .route("/health/live", get(handlers::health_live))
+        .route("/health/ready", get(handlers::health_ready))
+        .merge(posting)
+        .merge(writes)
+        .merge(reads)
+        .route_layer(middleware::from_fn_with_state(state.slo.clone(), slo_middleware))
         .layer(middleware::from_fn_with_state(state.clone(), burst_protection_middleware))
+        .layer(middleware::from_fn_with_state(state.clone(), recorder_middleware))
         .layer(middleware::from_fn_with_state(state.clone(), security_middleware))
         .with_state(state)
 }
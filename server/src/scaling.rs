@@ -1,61 +1,474 @@
 use anyhow::Result;
-use crate::redis_client::RedisClient;
-use std::sync::Arc;
+use crate::redis_client::{RedisClient, RedisPipeline};
+use crate::dependencies::{self, DependencyReport};
+use crate::fanout::{self, Fanout, FanoutFrame, Replay, StreamPosition, STREAM_PAYLOAD_FIELD};
+use crate::load_shedding::LoadStatus;
+use crate::state::AppState;
+use crate::keys;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
-const PUBSUB_CHANNEL: &str = "chat:messages";
+/// Outbox entries older than this are assumed orphaned by a crash and relayed
+const OUTBOX_GRACE_SECONDS: u64 = 10;
+/// Broadcasts still failing after this many retries are dropped
+const DEAD_LETTER_MAX_ATTEMPTS: u32 = 20;
+/// Dead letters retried per pass
+const DEAD_LETTER_BATCH: usize = 100;
+/// Entries kept in the broadcast stream, i.e. how far back a reconnecting client can resume
+const BROADCAST_STREAM_MAXLEN: usize = 10_000;
+/// Heartbeats kept in their stream; only the last few are ever read
+const HEARTBEAT_STREAM_MAXLEN: usize = 1_000;
+/// Whether a pub/sub channel is a per-actor channel
+pub fn is_actor_channel(channel: &str) -> bool {
+    channel.starts_with(keys::ACTOR_CHANNEL_PREFIX)
+}
+
+/// Pending broadcast recorded in the same transaction as the write it announces
+/// If the process dies between the write and its broadcast, the relay job broadcasts it later
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    id: String,
+    payload: String,
+}
+
+impl OutboxEntry {
+    pub fn new(payload: &str) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            payload: payload.to_string(),
+        }
+    }
+
+    fn member(&self) -> String {
+        serde_json::to_string(self).expect("outbox entry serializes")
+    }
+
+    /// Queue the outbox write on a transaction alongside the message write
+    pub fn enqueue<'a>(&self, transaction: &'a mut RedisPipeline) -> &'a mut RedisPipeline {
+        transaction.zadd(keys::BROADCAST_OUTBOX, now_secs() as f64, &self.member())
+    }
+}
+
+/// A broadcast that failed to reach the stream, waiting to be retried
+#[derive(Debug, Serialize, Deserialize)]
+struct DeadLetter {
+    payload: String,
+    attempts: u32,
+    first_failed_at: u64,
+}
+
+/// Outcome of one dead-letter retry pass
+#[derive(Debug, Default)]
+pub struct DeadLetterRetry {
+    pub delivered: usize,
+    pub dropped: usize,
+    pub backlog: i64,
+}
 
 /// Redis Broadcast Service for horizontal scaling
-/// Handles pub/sub operations to synchronize messages across multiple server instances
+/// Listings go through the broadcast stream, which every instance reads and
+/// reconnecting clients resume from; per-actor events go over pub/sub
 #[derive(Clone)]
 pub struct RedisBroadcastService {
     redis: RedisClient,
+    fanout: Fanout,
 }
 
 impl RedisBroadcastService {
     pub fn new(redis: RedisClient) -> Self {
-        Self { redis }
+        Self { redis, fanout: Fanout::new() }
+    }
+
+    /// Broadcasts and actor events reaching this instance, for one WebSocket
+    pub fn subscribe_local(&self) -> tokio::sync::broadcast::Receiver<Arc<FanoutFrame>> {
+        self.fanout.subscribe()
     }
 
-    /// Publish a message to all connected server instances
+    /// Hold this instance's stream reader and pub/sub subscription and fan them out to sockets
+    pub async fn run_fanout(self) {
+        self.fanout.run(self.redis).await
+    }
+
+    /// Listings a client reconnecting with `?last_id=` missed since that stream entry
+    pub async fn replay_since(&self, last_id: StreamPosition) -> Result<Replay> {
+        Ok(fanout::replay(&self.redis, last_id).await?)
+    }
+
+    /// Append a message to the broadcast stream every server instance reads
     pub async fn broadcast_message(&self, message: &str) -> Result<()> {
+        self.redis
+            .xadd_maxlen(keys::BROADCAST_STREAM, BROADCAST_STREAM_MAXLEN, &[(STREAM_PAYLOAD_FIELD, message)])
+            .await?;
+        Ok(())
+    }
+
+    /// Add a watchdog heartbeat to the heartbeat stream
+    pub async fn send_heartbeat(&self, heartbeat: &str) -> Result<()> {
+        self.redis
+            .xadd_maxlen(keys::BROADCAST_HEARTBEATS, HEARTBEAT_STREAM_MAXLEN, &[(STREAM_PAYLOAD_FIELD, heartbeat)])
+            .await?;
+        Ok(())
+    }
+
+    /// Broadcast a message, parking it in the dead-letter list if XADD fails
+    /// Used after the message is stored, so a Redis blip delays delivery
+    /// instead of leaving connected clients to never see the post
+    pub async fn broadcast_or_dead_letter(&self, message: &str) -> Result<()> {
+        let Err(publish_error) = self.broadcast_message(message).await else {
+            return Ok(());
+        };
+
+        tracing::warn!(error = %publish_error, "broadcast failed, queued for retry");
+        let letter = DeadLetter {
+            payload: message.to_string(),
+            attempts: 0,
+            first_failed_at: now_secs(),
+        };
+        self.redis
+            .lpush(keys::BROADCAST_DEAD_LETTER, &serde_json::to_string(&letter)?)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to broadcast ({}) or dead-letter it: {}", publish_error, e))?;
+        metrics::counter!("broadcast_dead_lettered_total", 1);
+        Ok(())
+    }
+
+    /// Broadcast an outbox entry and mark it done
+    /// A failed broadcast hands the entry over to the dead-letter list for retries
+    pub async fn relay(&self, entry: &OutboxEntry) -> Result<()> {
+        self.broadcast_or_dead_letter(&entry.payload).await?;
+        self.redis.zrem(keys::BROADCAST_OUTBOX, &entry.member()).await?;
+        Ok(())
+    }
+
+    /// Relay outbox entries left behind by a crash between write and broadcast
+    pub async fn relay_stale_outbox(&self) -> Result<usize> {
+        let cutoff = now_secs().saturating_sub(OUTBOX_GRACE_SECONDS) as f64;
+        let members = self.redis.zrangebyscore(keys::BROADCAST_OUTBOX, f64::NEG_INFINITY, cutoff).await?;
+
+        let mut relayed = 0;
+        for member in members {
+            match serde_json::from_str::<OutboxEntry>(&member) {
+                Ok(entry) => {
+                    self.relay(&entry).await?;
+                    relayed += 1;
+                }
+                Err(_) => {
+                    self.redis.zrem(keys::BROADCAST_OUTBOX, &member).await?;
+                }
+            }
+        }
+
+        metrics::gauge!("broadcast_outbox_pending", self.redis.zcount(keys::BROADCAST_OUTBOX, f64::NEG_INFINITY, f64::INFINITY).await? as f64);
+        Ok(relayed)
+    }
+
+    /// Retry dead-lettered broadcasts, oldest first
+    /// Stops at the first failure - Redis is most likely still unavailable
+    pub async fn retry_dead_letters(&self) -> Result<DeadLetterRetry> {
+        let mut outcome = DeadLetterRetry::default();
+
+        for _ in 0..DEAD_LETTER_BATCH {
+            let Some(raw) = self.redis.rpop(keys::BROADCAST_DEAD_LETTER).await? else {
+                break;
+            };
+            let Ok(mut letter) = serde_json::from_str::<DeadLetter>(&raw) else {
+                outcome.dropped += 1;
+                continue;
+            };
+
+            if self.broadcast_message(&letter.payload).await.is_ok() {
+                outcome.delivered += 1;
+                continue;
+            }
+
+            letter.attempts += 1;
+            if letter.attempts >= DEAD_LETTER_MAX_ATTEMPTS {
+                tracing::error!("Dropping broadcast after {} failed attempts", letter.attempts);
+                outcome.dropped += 1;
+            } else {
+                // Back on the tail so it stays the oldest
+                self.redis.rpush(keys::BROADCAST_DEAD_LETTER, &serde_json::to_string(&letter)?).await?;
+            }
+            break;
+        }
+
+        outcome.backlog = self.redis.llen(keys::BROADCAST_DEAD_LETTER).await?;
+        metrics::gauge!("broadcast_dead_letter_backlog", outcome.backlog as f64);
+        if outcome.dropped > 0 {
+            metrics::counter!("broadcast_dead_letter_dropped_total", outcome.dropped as u64);
+        }
+        Ok(outcome)
+    }
+
+    /// Publish an event to every connection of one composite key, on any instance
+    pub async fn publish_to_actor(&self, composite_key: &str, event: &str) -> Result<()> {
         let mut conn = self.redis.get_client().get_async_connection().await?;
         redis::cmd("PUBLISH")
-            .arg(PUBSUB_CHANNEL)
-            .arg(message)
+            .arg(keys::actor_channel(composite_key))
+            .arg(event)
             .query_async::<_, ()>(&mut conn)
             .await?;
         Ok(())
     }
+}
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+const HEARTBEAT_DEADLINE: Duration = Duration::from_secs(5);
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(2);
+/// How long one watchdog read of the heartbeat stream blocks, bounding how late a heartbeat goes out
+const HEARTBEAT_POLL: Duration = Duration::from_secs(1);
+
+/// Heartbeat frame added to the heartbeat stream by the watchdog
+/// Never sent to clients; older deployments added them to the broadcast stream,
+/// so forwarders still skip any found there
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PubSubHeartbeat {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub instance_id: String,
+    pub seq: u64,
+}
+
+impl PubSubHeartbeat {
+    const KIND: &'static str = "pubsub_heartbeat";
+
+    /// Cheap check used on the hot forwarding path before full parsing
+    pub fn is_heartbeat(payload: &str) -> bool {
+        payload.contains(Self::KIND)
+            && serde_json::from_str::<PubSubHeartbeat>(payload)
+                .map(|hb| hb.kind == Self::KIND)
+                .unwrap_or(false)
+    }
+}
+
+/// Watchdog that verifies this instance still receives its own broadcasts
+/// Adds a heartbeat to a stream of its own (see `keys::BROADCAST_HEARTBEATS`) and
+/// expects to read it back on a dedicated connection within a deadline, the way
+/// the fan-out reads the broadcast stream
+#[derive(Clone)]
+pub struct PubSubWatchdog {
+    instance_id: String,
+    healthy: Arc<AtomicBool>,
+    last_lag_ms: Arc<AtomicU64>,
+    /// Heartbeats seen back since startup
+    round_trips: Arc<AtomicU64>,
+}
+
+impl PubSubWatchdog {
+    pub fn new() -> Self {
+        Self {
+            instance_id: uuid::Uuid::new_v4().to_string(),
+            // Assume healthy until a heartbeat is actually missed
+            healthy: Arc::new(AtomicBool::new(true)),
+            last_lag_ms: Arc::new(AtomicU64::new(0)),
+            round_trips: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Wait until a heartbeat has made it round the broadcast stream at least once
+    /// Returns false if none did within `timeout`
+    pub async fn wait_for_round_trip(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.round_trips.load(Ordering::Relaxed) == 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        true
+    }
+
+    /// Whether the last heartbeat round-trip succeeded
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    /// Latency of the last successful heartbeat round-trip in milliseconds
+    pub fn last_lag_ms(&self) -> u64 {
+        self.last_lag_ms.load(Ordering::Relaxed)
+    }
+
+    fn set_healthy(&self, healthy: bool) {
+        self.healthy.store(healthy, Ordering::Relaxed);
+        metrics::gauge!("pubsub_healthy", if healthy { 1.0 } else { 0.0 });
+    }
+
+    /// Run the watchdog forever, reconnecting whenever its stream reader fails
+    pub async fn run(self, broadcast: RedisBroadcastService) {
+        loop {
+            if let Err(e) = self.watch(&broadcast).await {
+                tracing::error!("Pub/sub watchdog error: {}", e);
+            }
+            // The reader failed - that alone means delivery is broken
+            self.set_healthy(false);
+            tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+        }
+    }
+
+    /// Exchange heartbeats over the heartbeat stream until a read or write fails
+    /// Reads and heartbeats alternate on one task: a blocking read can't be cancelled
+    async fn watch(&self, broadcast: &RedisBroadcastService) -> Result<()> {
+        let mut reader = broadcast.redis.stream_reader().await?;
+        let mut last_id = fanout::stream_tail(&broadcast.redis, keys::BROADCAST_HEARTBEATS).await?;
 
-    /// Get the pub/sub channel name
-    #[allow(dead_code)]
-    pub fn get_channel(&self) -> &str {
-        PUBSUB_CHANNEL
+        let mut next_heartbeat = Instant::now();
+        let mut seq: u64 = 0;
+        let mut pending: Option<(u64, Instant)> = None;
+
+        loop {
+            if Instant::now() >= next_heartbeat {
+                if let Some((_, sent_at)) = pending {
+                    if sent_at.elapsed() > HEARTBEAT_DEADLINE {
+                        tracing::warn!(seq, deadline_ms = HEARTBEAT_DEADLINE.as_millis() as u64, "pub/sub heartbeat not received");
+                        metrics::counter!("pubsub_heartbeat_missed_total", 1);
+                        self.set_healthy(false);
+                    }
+                }
+
+                seq += 1;
+                let heartbeat = PubSubHeartbeat {
+                    kind: PubSubHeartbeat::KIND.to_string(),
+                    instance_id: self.instance_id.clone(),
+                    seq,
+                };
+                broadcast.send_heartbeat(&serde_json::to_string(&heartbeat)?).await?;
+                pending = Some((seq, Instant::now()));
+                next_heartbeat = Instant::now() + HEARTBEAT_INTERVAL;
+            }
+
+            let entries = reader.read(keys::BROADCAST_HEARTBEATS, &last_id, HEARTBEAT_POLL, 100).await?;
+            for (id, fields) in entries {
+                last_id = id;
+                let Some(heartbeat) = fields
+                    .get(STREAM_PAYLOAD_FIELD)
+                    .and_then(|payload| serde_json::from_str::<PubSubHeartbeat>(payload).ok())
+                else {
+                    continue;
+                };
+                if heartbeat.instance_id != self.instance_id {
+                    continue;
+                }
+                if let Some((pending_seq, sent_at)) = pending {
+                    if heartbeat.seq == pending_seq {
+                        let lag_ms = sent_at.elapsed().as_millis() as u64;
+                        self.last_lag_ms.store(lag_ms, Ordering::Relaxed);
+                        self.round_trips.fetch_add(1, Ordering::Relaxed);
+                        metrics::gauge!("pubsub_heartbeat_lag_ms", lag_ms as f64);
+                        self.set_healthy(true);
+                        pending = None;
+                    }
+                }
+            }
+        }
     }
+}
 
-    /// Subscribe to the broadcast channel (for WebSocket connections)
-    #[allow(dead_code)]
-    pub async fn subscribe(&self) -> Result<redis::aio::PubSub> {
-        let conn = self.redis.get_client().get_async_connection().await?;
-        Ok(conn.into_pubsub())
+impl Default for PubSubWatchdog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Sliding-window message rate tracker (overall and per city)
+/// Keeps one timestamp per message seen within the window
+#[derive(Debug)]
+pub struct MessageRateWindow {
+    window: Duration,
+    overall: VecDeque<Instant>,
+    per_city: HashMap<String, VecDeque<Instant>>,
+}
+
+/// Snapshot of message rates in messages per second
+#[derive(Debug, Clone, Default)]
+pub struct MessageRates {
+    pub overall: f64,
+    pub per_city: Vec<(String, f64)>,
+    /// Cities that dropped out of the window since the last snapshot
+    pub idle_cities: Vec<String>,
+}
+
+impl MessageRateWindow {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            overall: VecDeque::new(),
+            per_city: HashMap::new(),
+        }
+    }
+
+    /// Record a single message at the given instant
+    pub fn record(&mut self, city: Option<&str>, now: Instant) {
+        self.overall.push_back(now);
+        if let Some(city) = city {
+            self.per_city.entry(city.to_string()).or_default().push_back(now);
+        }
+    }
+
+    /// Drop entries outside the window and return the current rates
+    pub fn rates(&mut self, now: Instant) -> MessageRates {
+        let window = self.window;
+        let expired = |queue: &mut VecDeque<Instant>| {
+            while queue.front().is_some_and(|t| now.duration_since(*t) > window) {
+                queue.pop_front();
+            }
+        };
+
+        expired(&mut self.overall);
+
+        let mut idle_cities = Vec::new();
+        self.per_city.retain(|city, queue| {
+            expired(queue);
+            if queue.is_empty() {
+                idle_cities.push(city.clone());
+                false
+            } else {
+                true
+            }
+        });
+
+        let secs = window.as_secs_f64();
+        MessageRates {
+            overall: self.overall.len() as f64 / secs,
+            per_city: self.per_city
+                .iter()
+                .map(|(city, queue)| (city.clone(), queue.len() as f64 / secs))
+                .collect(),
+            idle_cities,
+        }
     }
 }
 
 /// Metrics tracker for monitoring server health and performance
+/// Every series is per instance: `messages_sent_total` and `contact_reveals_total`
+/// count the events this instance recorded since it started, so summing them
+/// across instances gives the cluster rate. Lifetime totals live in Redis (see
+/// `StatsService::lifetime_totals` and `GET /admin/totals`)
 #[derive(Clone)]
 pub struct MetricsTracker {
     active_connections: Arc<RwLock<i64>>,
-    messages_sent: Arc<RwLock<u64>>,
-    contact_reveals: Arc<RwLock<u64>>,
+    message_rate: Arc<Mutex<MessageRateWindow>>,
+    /// WebSocket frames this instance dropped for clients that couldn't keep up
+    dropped_frames: Arc<AtomicU64>,
+}
+
+impl Default for MetricsTracker {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MetricsTracker {
     pub fn new() -> Self {
         Self {
             active_connections: Arc::new(RwLock::new(0)),
-            messages_sent: Arc::new(RwLock::new(0)),
-            contact_reveals: Arc::new(RwLock::new(0)),
+            message_rate: Arc::new(Mutex::new(MessageRateWindow::new(RATE_WINDOW))),
+            dropped_frames: Arc::new(AtomicU64::new(0)),
         }
     }
 
@@ -71,45 +484,202 @@ impl MetricsTracker {
         metrics::gauge!("active_websocket_connections", *count as f64);
     }
 
-    pub async fn increment_messages(&self) {
-        let mut count = self.messages_sent.write().await;
-        *count += 1;
-        metrics::counter!("messages_per_second", 1);
+    /// Count a message this instance recorded
+    /// `city` is already a metric label (see `CityDirectory::metric_label`)
+    pub fn record_message(&self, city: Option<&str>) {
+        metrics::counter!("messages_sent_total", 1);
+
+        self.message_rate.lock().unwrap().record(city, Instant::now());
+        self.publish_message_rates();
     }
 
-    pub async fn increment_contact_reveals(&self) {
-        let mut count = self.contact_reveals.write().await;
-        *count += 1;
+    /// Recompute the sliding-window rates and export them as gauges
+    /// Called on every message and periodically so idle rates decay to zero
+    pub fn publish_message_rates(&self) {
+        let rates = self.message_rate.lock().unwrap().rates(Instant::now());
+
+        metrics::gauge!("messages_per_second", rates.overall);
+        for (city, rate) in rates.per_city {
+            metrics::gauge!("city_messages_per_second", rate, "city" => city);
+        }
+        for city in rates.idle_cities {
+            metrics::gauge!("city_messages_per_second", 0.0, "city" => city);
+        }
+    }
+
+    /// Count a contact reveal this instance recorded
+    pub fn record_contact_reveal(&self) {
         metrics::counter!("contact_reveals_total", 1);
     }
 
     pub async fn get_active_connections(&self) -> i64 {
         *self.active_connections.read().await
     }
+
+    /// Count frames dropped for a slow WebSocket client, by where they were dropped
+    /// ("buffer" when evicted from its outbound queue, "fanout" when it lagged the fan-out)
+    pub fn record_dropped_frames(&self, count: u64, stage: &'static str) {
+        self.dropped_frames.fetch_add(count, Ordering::Relaxed);
+        metrics::counter!("websocket_frames_dropped_total", count, "stage" => stage);
+    }
+
+    /// Frames dropped for slow WebSocket clients since this instance started
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped_frames.load(Ordering::Relaxed)
+    }
+}
+
+/// Liveness: the process is up and serving requests
+/// Deliberately checks nothing external, so a Redis outage doesn't get instances restarted
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LivenessStatus {
+    pub alive: bool,
+    pub timestamp: u64,
+}
+
+impl LivenessStatus {
+    pub fn check() -> Self {
+        Self { alive: true, timestamp: now_secs() }
+    }
 }
 
 /// Health check status for the server
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct HealthStatus {
     pub healthy: bool,
+    /// Whether the instance should be sent traffic: healthy and not draining for shutdown
+    pub ready: bool,
     pub redis_connected: bool,
+    pub pubsub_healthy: bool,
+    /// Shutdown has begun and WebSocket clients are being sent away
+    pub draining: bool,
+    pub pubsub_lag_ms: u64,
     pub active_connections: i64,
+    /// WebSocket frames dropped for slow clients since startup
+    pub dropped_websocket_frames: u64,
+    /// Load-shedding level and the Redis latency behind it; degraded is still healthy
+    pub load: LoadStatus,
+    /// Every external dependency with its status, latency and last error
+    /// Only critical ones affect `healthy`
+    pub dependencies: Vec<DependencyReport>,
     pub timestamp: u64,
 }
 
 impl HealthStatus {
-    pub async fn check(redis: &RedisClient, metrics: &MetricsTracker) -> Self {
-        let redis_connected = redis.ping().await.unwrap_or(false);
-        let active_connections = metrics.get_active_connections().await;
-        
+    pub async fn check(state: &AppState) -> Self {
+        let tracker = &state.dependencies;
+
+        let started = Instant::now();
+        let redis_connected = match state.redis.ping().await {
+            Ok(true) => {
+                tracker.record(dependencies::REDIS, started.elapsed(), Ok(()));
+                true
+            }
+            Ok(false) => {
+                tracker.record(dependencies::REDIS, started.elapsed(), Err("Unexpected PING reply".to_string()));
+                false
+            }
+            Err(e) => {
+                tracker.record(dependencies::REDIS, started.elapsed(), Err(e.to_string()));
+                false
+            }
+        };
+
+        let watchdog = &state.pubsub_watchdog;
+        let pubsub_healthy = watchdog.is_healthy();
+        let lag = Duration::from_millis(watchdog.last_lag_ms());
+        let bus_result = if pubsub_healthy { Ok(()) } else { Err("Heartbeat missed".to_string()) };
+        tracker.record(dependencies::EVENT_BUS, lag, bus_result);
+
+        let active_connections = state.metrics.get_active_connections().await;
+        let healthy = redis_connected && pubsub_healthy;
+        let draining = state.ws_handover.is_shutting_down();
+
         Self {
-            healthy: redis_connected,
+            healthy,
+            ready: healthy && !draining,
             redis_connected,
+            pubsub_healthy,
+            draining,
+            pubsub_lag_ms: watchdog.last_lag_ms(),
             active_connections,
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            dropped_websocket_frames: state.metrics.dropped_frames(),
+            load: state.load_shedder.status(),
+            dependencies: vec![
+                tracker.report(dependencies::REDIS, true, true),
+                tracker.report(dependencies::EVENT_BUS, true, true),
+                tracker.report(dependencies::MODERATION_PROVIDER, state.config.current().moderation.provider_configured(), false),
+                tracker.report(dependencies::TRANSLATION_PROVIDER, state.translator.enabled(), false),
+            ],
+            timestamp: now_secs(),
         }
     }
 }
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat_detection() {
+        let heartbeat = PubSubHeartbeat {
+            kind: PubSubHeartbeat::KIND.to_string(),
+            instance_id: "instance".to_string(),
+            seq: 1,
+        };
+        let payload = serde_json::to_string(&heartbeat).unwrap();
+        assert!(PubSubHeartbeat::is_heartbeat(&payload));
+
+        assert!(!PubSubHeartbeat::is_heartbeat(
+            r#"{"id":"1","browser_id":"b","message":"pubsub_heartbeat","message_type":"offered","timestamp":1}"#
+        ));
+    }
+
+    #[test]
+    fn test_outbox_member_roundtrip() {
+        let entry = OutboxEntry::new(r#"{"id":"m1"}"#);
+        let parsed: OutboxEntry = serde_json::from_str(&entry.member()).unwrap();
+        assert_eq!(parsed, entry);
+        // Same payload written twice must not collapse into one sorted-set member
+        assert_ne!(OutboxEntry::new(r#"{"id":"m1"}"#).member(), entry.member());
+    }
+
+    #[test]
+    fn test_rate_window_counts_recent_messages() {
+        let mut window = MessageRateWindow::new(Duration::from_secs(60));
+        let start = Instant::now();
+
+        for _ in 0..30 {
+            window.record(Some("Pune"), start);
+        }
+        window.record(None, start);
+
+        let rates = window.rates(start + Duration::from_secs(10));
+        assert!((rates.overall - 31.0 / 60.0).abs() < f64::EPSILON);
+        assert_eq!(rates.per_city, vec![("Pune".to_string(), 0.5)]);
+    }
+
+    #[test]
+    fn test_rate_window_expires_old_messages() {
+        let mut window = MessageRateWindow::new(Duration::from_secs(60));
+        let start = Instant::now();
+
+        window.record(Some("Pune"), start);
+        window.record(Some("Delhi"), start + Duration::from_secs(30));
+
+        let rates = window.rates(start + Duration::from_secs(61));
+        assert!((rates.overall - 1.0 / 60.0).abs() < f64::EPSILON);
+        assert_eq!(rates.idle_cities, vec!["Pune".to_string()]);
+
+        let rates = window.rates(start + Duration::from_secs(120));
+        assert_eq!(rates.overall, 0.0);
+        assert!(rates.per_city.is_empty());
+    }
+}
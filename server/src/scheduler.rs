@@ -0,0 +1,329 @@
+use crate::availability;
+use crate::config;
+use crate::load_shedding::SheddableWork;
+use crate::moderation_queue;
+use crate::redis_usage;
+use crate::security::audit::{AuditEvent, AuditEventKind};
+use crate::security::hotspots::HOTSPOT_WINDOW_SECONDS;
+use crate::state::AppState;
+use serde_json::json;
+use std::time::Duration;
+
+/// How often the message index is pruned of expired/deleted entries
+const INDEX_CLEANUP_INTERVAL: Duration = Duration::from_secs(600); // 10 minutes
+/// How often expired anonymous sessions are dropped from the session indexes
+const SESSION_CLEANUP_INTERVAL: Duration = Duration::from_secs(600); // 10 minutes
+/// How often the contact-reveal graph is analyzed for harvesting patterns
+const REVEAL_ANALYSIS_INTERVAL: Duration = Duration::from_secs(1800); // 30 minutes
+/// How often listings near expiry are checked for "still available?" prompts
+const AVAILABILITY_PROMPT_INTERVAL: Duration = Duration::from_secs(900); // 15 minutes
+/// How often Redis memory and key-count gauges are refreshed
+const REDIS_USAGE_INTERVAL: Duration = Duration::from_secs(300); // 5 minutes
+/// How often the wordlists file is checked for changes
+const WORDLIST_WATCH_INTERVAL: Duration = Duration::from_secs(30);
+/// How often buffered visitor stats are written to Redis
+const STATS_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+/// How often orphaned outbox entries are relayed
+const OUTBOX_RELAY_INTERVAL: Duration = Duration::from_secs(10);
+/// How often failed broadcasts are retried
+const DEAD_LETTER_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+/// How often message-rate gauges are refreshed so idle rates decay to zero
+const RATE_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+/// How often the load-shedding level is re-evaluated from Redis latency
+const LOAD_LEVEL_INTERVAL: Duration = Duration::from_secs(2);
+/// How often city posting rates are compared to their baselines
+const CITY_SURGE_INTERVAL: Duration = Duration::from_secs(60);
+/// How often IPs with a refilled quota are dropped from the governor limiter
+const GOVERNOR_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Spawn all periodic background jobs
+/// Each job runs on its own interval and logs (but never propagates) failures
+pub fn spawn_background_jobs(state: AppState) {
+    tokio::spawn(run_index_cleanup(state.clone()));
+    tokio::spawn(state.pubsub_watchdog.clone().run(state.broadcast.clone()));
+    tokio::spawn(state.broadcast.clone().run_fanout());
+    tokio::spawn(run_session_cleanup(state.clone()));
+    tokio::spawn(run_hotspot_rotation(state.clone()));
+    tokio::spawn(run_reveal_analysis(state.clone()));
+    tokio::spawn(run_availability_prompts(state.clone()));
+    tokio::spawn(run_redis_usage(state.clone()));
+    tokio::spawn(run_stats_flush(state.clone()));
+    tokio::spawn(run_outbox_relay(state.clone()));
+    tokio::spawn(run_dead_letter_retry(state.clone()));
+    tokio::spawn(run_rate_refresh(state.clone()));
+    tokio::spawn(run_city_surge_detection(state.clone()));
+    tokio::spawn(run_wordlist_watch(state.clone()));
+    tokio::spawn(run_governor_sweep(state.clone()));
+    tokio::spawn(moderation_queue::run_worker(state.clone()));
+    tokio::spawn(run_load_level(state));
+}
+
+/// Reload the runtime config whenever the wordlists file changes on disk, so a
+/// moderator's edit takes effect without a SIGHUP
+/// Does nothing unless `MODERATION_WORDLISTS_FILE` is set at startup
+async fn run_wordlist_watch(state: AppState) {
+    let Some(path) = std::env::var("MODERATION_WORDLISTS_FILE").ok().filter(|p| !p.is_empty()) else {
+        return;
+    };
+    let modified = || std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+    let mut last_modified = modified();
+    let mut interval = tokio::time::interval(WORDLIST_WATCH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let current = modified();
+        if current != last_modified {
+            last_modified = current;
+            config::reload(&state, "system", "wordlists_file").await;
+        }
+    }
+}
+
+/// Periodically prune the messages sorted-set index
+async fn run_index_cleanup(state: AppState) {
+    let mut interval = tokio::time::interval(INDEX_CLEANUP_INTERVAL);
+    // The first tick completes immediately - startup reconciliation already covered it
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        match state.cleanup_old_messages().await {
+            Ok(0) => {}
+            Ok(removed) => tracing::info!(removed, "pruned stale entries from message index"),
+            Err(e) => tracing::error!("Failed to clean up message index: {}", e),
+        }
+    }
+}
+
+/// Periodically export Redis memory and per-prefix key gauges
+async fn run_redis_usage(state: AppState) {
+    let mut interval = tokio::time::interval(REDIS_USAGE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = redis_usage::collect(&state.redis).await {
+            tracing::error!("Failed to collect Redis usage: {}", e);
+        }
+    }
+}
+
+/// Periodically forget IPs whose governor quota has refilled, so IP churn doesn't grow memory
+async fn run_governor_sweep(state: AppState) {
+    let mut interval = tokio::time::interval(GOVERNOR_SWEEP_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let stale = state.config.current().governor.sweep();
+        if stale > 0 {
+            tracing::debug!("Swept {} idle IPs from the governor limiter", stale);
+        }
+    }
+}
+
+/// Flush buffered visitor stats in pipelined batches
+async fn run_stats_flush(state: AppState) {
+    let mut interval = tokio::time::interval(STATS_FLUSH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        // Members stay buffered (bounded) until Redis recovers
+        if state.load_shedder.should_shed(SheddableWork::Stats) {
+            continue;
+        }
+
+        if let Err(e) = state.stats.flush().await {
+            tracing::error!("{}", e);
+        }
+    }
+}
+
+/// Publish broadcasts whose writer died between storing the message and publishing
+async fn run_outbox_relay(state: AppState) {
+    let mut interval = tokio::time::interval(OUTBOX_RELAY_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        match state.broadcast.relay_stale_outbox().await {
+            Ok(0) => {}
+            Ok(relayed) => tracing::info!(relayed, "relayed orphaned outbox broadcasts"),
+            Err(e) => tracing::error!("Failed to relay outbox: {}", e),
+        }
+    }
+}
+
+/// Retry broadcasts that failed to publish after their message was stored
+async fn run_dead_letter_retry(state: AppState) {
+    let mut interval = tokio::time::interval(DEAD_LETTER_RETRY_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        match state.broadcast.retry_dead_letters().await {
+            Ok(retry) if retry.delivered > 0 || retry.dropped > 0 => tracing::info!(
+                delivered = retry.delivered,
+                dropped = retry.dropped,
+                backlog = retry.backlog,
+                "dead-letter retry"
+            ),
+            Ok(_) => {}
+            Err(e) => tracing::error!("Failed to retry dead-lettered broadcasts: {}", e),
+        }
+    }
+}
+
+/// Periodically drop expired sessions from the per-key and expiry indexes
+async fn run_session_cleanup(state: AppState) {
+    let mut interval = tokio::time::interval(SESSION_CLEANUP_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        match state.sessions.cleanup_expired().await {
+            Ok(0) => {}
+            Ok(removed) => tracing::info!(removed, "cleaned up expired sessions"),
+            Err(e) => tracing::error!("Failed to clean up expired sessions: {}", e),
+        }
+    }
+}
+
+/// Rotate the hot-actor sketches so counts reflect recent traffic only
+async fn run_hotspot_rotation(state: AppState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(HOTSPOT_WINDOW_SECONDS));
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        state.hotspots.rotate();
+    }
+}
+
+/// Periodically revoke reveal ability from keys harvesting contact numbers
+async fn run_reveal_analysis(state: AppState) {
+    let mut interval = tokio::time::interval(REVEAL_ANALYSIS_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let flagged = match state.reveal_graph.analyze_recent().await {
+            Ok(flagged) => flagged,
+            Err(e) => {
+                tracing::error!("Failed to analyze reveal graph: {}", e);
+                continue;
+            }
+        };
+
+        for (composite_key, pattern) in flagged {
+            tracing::info!(
+                composite_key = %composite_key,
+                distinct_posters = pattern.distinct_posters,
+                active_days = pattern.active_days,
+                "revoked contact reveals"
+            );
+            metrics::counter!("reveal_harvesters_flagged_total", 1);
+
+            let event = AuditEvent::new(
+                AuditEventKind::RevealRevoked,
+                "system",
+                &composite_key,
+                "Breadth-first contact harvesting",
+            )
+            .with_details(serde_json::to_value(&pattern).unwrap_or_default());
+            if let Err(e) = state.audit_log.record(event).await {
+                tracing::error!("{}", e);
+            }
+        }
+    }
+}
+
+/// Periodically ask posters to confirm listings that are about to expire
+async fn run_availability_prompts(state: AppState) {
+    let mut interval = tokio::time::interval(AVAILABILITY_PROMPT_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        match availability::prompt_expiring_listings(&state).await {
+            Ok(0) => {}
+            Ok(sent) => tracing::info!(sent, "sent still-available prompts"),
+            Err(e) => tracing::error!("Failed to send availability prompts: {}", e),
+        }
+    }
+}
+
+/// Periodically re-export sliding-window message rates
+async fn run_rate_refresh(state: AppState) {
+    let mut interval = tokio::time::interval(RATE_REFRESH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        state.metrics.publish_message_rates();
+    }
+}
+
+/// Re-evaluate the load-shedding level from the rolling Redis latency
+/// Each tick also pings Redis so the window stays fresh when traffic is idle
+async fn run_load_level(state: AppState) {
+    let mut interval = tokio::time::interval(LOAD_LEVEL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let ping = state.redis.ping().await;
+        state.slo.record_redis_check(ping.is_ok());
+        if let Err(e) = ping {
+            tracing::error!("Failed to ping Redis: {}", e);
+        }
+        if let Some(level) = state.load_shedder.update() {
+            tracing::info!(level = ?level, "load shedding level changed");
+        }
+    }
+}
+
+/// Flag cities whose posting rate spikes far above baseline (usually a spam campaign)
+/// A new surge tightens that city's moderation and cooldowns and is raised on the audit stream
+async fn run_city_surge_detection(state: AppState) {
+    let mut interval = tokio::time::interval(CITY_SURGE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let surges = match state.city_surges.detect().await {
+            Ok(surges) => surges,
+            Err(e) => {
+                tracing::error!("Failed to detect city posting surges: {}", e);
+                continue;
+            }
+        };
+
+        let directory = state.city_registry.directory().await;
+        for surge in surges {
+            tracing::warn!(
+                city = %surge.city,
+                recent_posts = surge.recent_posts,
+                baseline_posts = surge.baseline_posts,
+                "posting surge"
+            );
+            metrics::counter!("city_surges_detected_total", 1, "city" => directory.metric_label(&surge.city));
+
+            let event = AuditEvent::new(
+                AuditEventKind::CitySurgeDetected,
+                "system",
+                &surge.city,
+                "Posting rate far above baseline; strict moderation and longer cooldowns enabled",
+            )
+            .with_details(json!({
+                "recent_posts": surge.recent_posts,
+                "baseline_posts": surge.baseline_posts,
+            }));
+            if let Err(e) = state.audit_log.record(event).await {
+                tracing::error!("{}", e);
+            }
+        }
+    }
+}
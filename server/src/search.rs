@@ -0,0 +1,229 @@
+use crate::models::ChatMessage;
+use crate::moderation_dataset;
+use crate::redis_client::{RedisClient, RedisPipeline};
+use crate::state::MESSAGE_TTL;
+use crate::keys;
+use anyhow::{Result, anyhow};
+use std::collections::HashSet;
+
+/// Terms indexed per listing; the rest of a very long text isn't searchable
+const MAX_TERMS_PER_MESSAGE: usize = 64;
+/// Terms considered per query; extra words are ignored
+pub const MAX_QUERY_TERMS: usize = 8;
+/// Newest matching candidates loaded per query, before city filtering
+const MAX_CANDIDATES: usize = 500;
+/// Words that follow a number in room counts ("2 BHK" is indexed as "2bhk")
+const ROOM_UNITS: [&str; 3] = ["bhk", "rk", "bk"];
+/// How long a search's intersection outlives it if the search fails before deleting it
+const QUERY_KEY_TTL_SECONDS: i64 = 10;
+
+/// Searchable terms of a text: lowercase alphanumeric words, with a number
+/// followed by a room unit joined into one term so "2 BHK" and "2BHK" match
+/// Single letters are dropped; numbers are kept
+pub fn terms(text: &str) -> Vec<String> {
+    let normalized = moderation_dataset::normalize(text);
+    let words: Vec<&str> = normalized.split(' ').filter(|w| !w.is_empty()).collect();
+    let mut seen = HashSet::new();
+    let mut terms = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let word = words[i];
+        let is_number = word.chars().all(|c| c.is_ascii_digit());
+        let term = match words.get(i + 1) {
+            Some(next) if is_number && ROOM_UNITS.contains(next) => {
+                i += 1;
+                format!("{}{}", word, next)
+            }
+            _ => word.to_string(),
+        };
+        i += 1;
+        if (term.chars().count() > 1 || is_number) && seen.insert(term.clone()) {
+            terms.push(term);
+        }
+    }
+    terms
+}
+
+/// Terms a listing is found by: its text, locality and room count
+pub fn message_terms(message: &ChatMessage) -> Vec<String> {
+    let mut text = message.message.clone();
+    if let Some(details) = &message.details {
+        if let Some(locality) = &details.locality {
+            text.push(' ');
+            text.push_str(locality);
+        }
+        if let Some(bhk) = details.bhk {
+            text.push_str(&format!(" {} bhk", bhk));
+        }
+    }
+    let mut terms = terms(&text);
+    terms.truncate(MAX_TERMS_PER_MESSAGE);
+    terms
+}
+
+/// When a listing stops being searchable: its expiry, so renewed listings stay in
+/// the index and expired ids can be trimmed by score
+fn index_score(message: &ChatMessage) -> f64 {
+    message.expires_at.unwrap_or(message.timestamp + MESSAGE_TTL) as f64
+}
+
+/// Inverted index over listing text: one sorted set per term (`search:term:<term>`)
+/// holding the ids of listings containing it, scored by expiry time
+/// Expired ids are trimmed whenever a term is written to, deleted listings are
+/// removed with the listing, and ids edited to no longer match are dropped when
+/// a search runs into them
+#[derive(Clone)]
+pub struct SearchIndex {
+    redis: RedisClient,
+}
+
+impl SearchIndex {
+    pub fn new(redis: RedisClient) -> Self {
+        Self { redis }
+    }
+
+    /// Queue the index writes on a transaction alongside the message write
+    /// Term sets expire with the newest listing in them
+    pub fn enqueue<'a>(message: &ChatMessage, transaction: &'a mut RedisPipeline) -> &'a mut RedisPipeline {
+        let now = chrono::Utc::now().timestamp() as f64;
+        for term in message_terms(message) {
+            let key = keys::search_term(&term);
+            transaction
+                .zrembyscore(&key, 0.0, now)
+                .ignore()
+                .zadd(&key, index_score(message), &message.id)
+                .ignore()
+                .expire(&key, MESSAGE_TTL as i64)
+                .ignore();
+        }
+        transaction
+    }
+
+    /// Queue removing a listing from its term sets alongside the message delete
+    pub fn dequeue<'a>(message: &ChatMessage, transaction: &'a mut RedisPipeline) -> &'a mut RedisPipeline {
+        for term in message_terms(message) {
+            transaction.zrem(&keys::search_term(&term), &message.id).ignore();
+        }
+        transaction
+    }
+
+    /// Live listings matching every term of `query`, latest expiry first, optionally in one city
+    pub async fn search(&self, query: &str, city: Option<&str>, limit: usize) -> Result<Vec<ChatMessage>> {
+        let mut query_terms = terms(query);
+        query_terms.truncate(MAX_QUERY_TERMS);
+        if query_terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let candidates = self.candidates(&query_terms).await?;
+        if candidates.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let message_keys: Vec<String> = candidates.iter().map(|id| keys::message(id)).collect();
+        let key_refs: Vec<&str> = message_keys.iter().map(String::as_str).collect();
+        let stored = self.redis
+            .mget(&key_refs)
+            .await
+            .map_err(|e| anyhow!("Failed to load search results: {}", e))?;
+
+        let mut results = Vec::new();
+        let mut stale = Vec::new();
+        for (id, json) in candidates.iter().zip(stored) {
+            let Some(message) = json.and_then(|json| serde_json::from_str::<ChatMessage>(&json).ok()) else {
+                stale.push(id.clone());
+                continue;
+            };
+            // The index may still hold terms from before an edit
+            let indexed = message_terms(&message);
+            if !query_terms.iter().all(|term| indexed.contains(term)) {
+                stale.push(id.clone());
+                continue;
+            }
+            let in_city = city.is_none_or(|city| {
+                message.location.as_deref().is_some_and(|loc| loc.eq_ignore_ascii_case(city))
+            });
+            if in_city && results.len() < limit {
+                results.push(message);
+            }
+        }
+
+        self.prune(&query_terms, &stale).await;
+        metrics::counter!("search_queries_total", 1);
+        Ok(results)
+    }
+
+    /// Unexpired ids in every term's set, latest expiry first, capped at `MAX_CANDIDATES`
+    /// Sets are intersected in Redis, so a search never loads a whole term set
+    async fn candidates(&self, query_terms: &[String]) -> Result<Vec<String>> {
+        let now = chrono::Utc::now().timestamp() as f64;
+        let term_keys: Vec<String> = query_terms.iter().map(|term| keys::search_term(term)).collect();
+        let mut pipeline = self.redis.pipeline();
+        let result = if let [key] = term_keys.as_slice() {
+            pipeline
+                .zrevrangebyscore_limit(key, now, MAX_CANDIDATES)
+                .query::<(Vec<String>,)>()
+                .await
+        } else {
+            let query_key = keys::search_query(&uuid::Uuid::new_v4().to_string());
+            pipeline
+                .zinterstore_max(&query_key, &term_keys).ignore()
+                .expire(&query_key, QUERY_KEY_TTL_SECONDS).ignore()
+                .zrevrangebyscore_limit(&query_key, now, MAX_CANDIDATES)
+                .del(&query_key).ignore()
+                .query::<(Vec<String>,)>()
+                .await
+        };
+        result
+            .map(|(ids,)| ids)
+            .map_err(|e| anyhow!("Failed to read search index: {}", e))
+    }
+
+    /// Drop ids that no longer match from the term sets a search read them from
+    async fn prune(&self, query_terms: &[String], stale: &[String]) {
+        if stale.is_empty() {
+            return;
+        }
+        let mut pipeline = self.redis.pipeline();
+        for term in query_terms {
+            let key = keys::search_term(term);
+            for id in stale {
+                pipeline.zrem(&key, id).ignore();
+            }
+        }
+        if let Err(e) = pipeline.execute().await {
+            tracing::error!("Failed to prune search index: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MessageType;
+
+    #[test]
+    fn test_terms_join_room_counts_and_drop_single_letters() {
+        assert_eq!(terms("2 BHK in Koramangala, a flat!"), vec!["2bhk", "in", "koramangala", "flat"]);
+        assert_eq!(terms("2BHK koramangala 2bhk"), vec!["2bhk", "koramangala"]);
+        assert_eq!(terms("1 RK near 5th block"), vec!["1rk", "near", "5th", "block"]);
+    }
+
+    #[test]
+    fn test_listings_are_indexed_until_they_expire() {
+        let mut message = ChatMessage::new(
+            "b".to_string(),
+            "2 BHK in Koramangala".to_string(),
+            MessageType::Offered,
+            None,
+            Some("Bengaluru".to_string()),
+        );
+        // Stored before expiry was tracked
+        message.timestamp = 1_000;
+        message.expires_at = None;
+        assert_eq!(index_score(&message), (1_000 + MESSAGE_TTL) as f64);
+        // A renewed listing stays searchable past its original expiry
+        message.expires_at = Some(1_000 + 3 * MESSAGE_TTL);
+        assert_eq!(index_score(&message), (1_000 + 3 * MESSAGE_TTL) as f64);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::redis_client::RedisClient;
+use anyhow::{Result, anyhow};
+use redis::streams::{StreamReadOptions, StreamReadReply};
+use serde::{Deserialize, Serialize};
+use crate::keys;
+
+/// Approximate number of audit entries retained in the stream
+const AUDIT_STREAM_MAXLEN: usize = 100_000;
+/// How long a tail read waits for new entries before returning empty
+const TAIL_BLOCK_MILLIS: usize = 15_000;
+/// Most entries returned by one tail read
+const TAIL_BATCH: usize = 100;
+
+/// Kinds of security-relevant actions recorded in the audit stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEventKind {
+    /// A decoy endpoint was requested
+    BotTrap,
+    /// A moderator signed in (or was refused) via the identity provider
+    AdminLogin,
+    /// Contact reveal ability was revoked for a harvesting pattern
+    RevealRevoked,
+    /// An admin restored reveal ability after review
+    RevealRestored,
+    /// An admin pinned a listing to the top of a city
+    ListingPinned,
+    /// An admin unpinned a listing
+    ListingUnpinned,
+    /// An admin put a city on the launch waitlist
+    CityWaitlisted,
+    /// An admin launched a waitlisted city and published its backlog
+    CityLaunched,
+    /// An admin set or cleared a city's moderation policy override
+    CityPolicyUpdated,
+    /// An admin replaced or reset the city registry
+    CityRegistryUpdated,
+    /// Report-driven auto-actions were frozen on a message that looked brigaded
+    ReportActionsFrozen,
+    /// A moderator kept a queued item's message
+    ReviewApproved,
+    /// A moderator removed a queued item's message
+    ReviewRemoved,
+    /// A post was rejected by content moderation
+    MessageBlocked,
+    /// A user reported a listing
+    ReportSubmitted,
+    /// A composite key was shadowbanned automatically
+    Shadowbanned,
+    /// The burst profiler flagged a composite key as a bot
+    BurstDetected,
+    /// An admin cleared rate limit windows for a key
+    RateLimitReset,
+    /// An admin blocked an IP address or network
+    IpBlocked,
+    /// An admin lifted an IP address or network block
+    IpUnblocked,
+    /// An admin exempted a poster from the active listing cap
+    PosterVerified,
+    /// An admin removed a poster's exemption from the active listing cap
+    PosterUnverified,
+    /// A city's posting rate jumped far above its baseline
+    CitySurgeDetected,
+    /// An admin ended a city's posting surge early
+    CitySurgeCleared,
+    /// Runtime configuration was reloaded without a restart
+    ConfigReloaded,
+}
+
+/// A single audit stream entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub kind: AuditEventKind,
+    /// Who performed the action ("system" for automatic enforcement)
+    pub actor: String,
+    /// What the action applied to (IP, composite key, message id, ...)
+    pub target: String,
+    pub reason: String,
+    #[serde(default)]
+    pub details: serde_json::Value,
+    pub timestamp: u64,
+}
+
+impl AuditEvent {
+    pub fn new(kind: AuditEventKind, actor: &str, target: &str, reason: &str) -> Self {
+        Self {
+            kind,
+            actor: actor.to_string(),
+            target: target.to_string(),
+            reason: reason.to_string(),
+            details: serde_json::Value::Null,
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        }
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = details;
+        self
+    }
+}
+
+/// Append-only audit log backed by a capped Redis stream
+#[derive(Clone)]
+pub struct AuditLog {
+    redis: RedisClient,
+}
+
+impl AuditLog {
+    pub fn new(redis: RedisClient) -> Self {
+        Self { redis }
+    }
+
+    /// Append an event to the audit stream
+    pub async fn record(&self, event: AuditEvent) -> Result<()> {
+        let data = serde_json::to_string(&event)?;
+        let kind = serde_json::to_value(event.kind)?
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        self.redis
+            .xadd_maxlen(keys::AUDIT_STREAM, AUDIT_STREAM_MAXLEN, &[("kind", &kind), ("data", &data)])
+            .await
+            .map_err(|e| anyhow!("Failed to write audit event: {}", e))?;
+
+        Ok(())
+    }
+
+    /// Read the most recent audit events (newest first)
+    pub async fn recent(&self, count: usize) -> Result<Vec<AuditEvent>> {
+        let entries = self.redis
+            .xrevrange(keys::AUDIT_STREAM, count)
+            .await
+            .map_err(|e| anyhow!("Failed to read audit events: {}", e))?;
+
+        Ok(entries
+            .into_iter()
+            .filter_map(|(_, fields)| fields.get("data").cloned())
+            .filter_map(|data| serde_json::from_str(&data).ok())
+            .collect())
+    }
+
+    /// Follow events appended from now on
+    /// Uses its own connection, since blocking reads would stall the shared one
+    pub async fn tail(&self) -> Result<AuditTail> {
+        let last_id = self.redis
+            .xrevrange(keys::AUDIT_STREAM, 1)
+            .await
+            .map_err(|e| anyhow!("Failed to read audit events: {}", e))?
+            .into_iter()
+            .next()
+            .map(|(id, _)| id)
+            .unwrap_or_else(|| "0-0".to_string());
+
+        let conn = self.redis
+            .get_client()
+            .get_async_connection()
+            .await
+            .map_err(|e| anyhow!("Failed to open audit tail connection: {}", e))?;
+
+        Ok(AuditTail { conn, last_id })
+    }
+}
+
+/// Cursor over new audit stream entries
+pub struct AuditTail {
+    conn: redis::aio::Connection,
+    last_id: String,
+}
+
+impl AuditTail {
+    /// Wait for the next events (oldest first); empty if none arrived in time
+    pub async fn next_batch(&mut self) -> Result<Vec<AuditEvent>> {
+        let options = StreamReadOptions::default()
+            .block(TAIL_BLOCK_MILLIS)
+            .count(TAIL_BATCH);
+        let reply: Option<StreamReadReply> = redis::AsyncCommands::xread_options(
+            &mut self.conn,
+            &[keys::AUDIT_STREAM],
+            &[self.last_id.as_str()],
+            &options,
+        )
+        .await
+        .map_err(|e| anyhow!("Failed to tail audit events: {}", e))?;
+
+        let mut events = Vec::new();
+        for entry in reply.into_iter().flat_map(|r| r.keys).flat_map(|k| k.ids) {
+            self.last_id = entry.id.clone();
+            let event = entry
+                .get::<String>("data")
+                .and_then(|data| serde_json::from_str(&data).ok());
+            events.extend(event);
+        }
+        Ok(events)
+    }
+}
@@ -1,5 +1,6 @@
 use anyhow::Result;
 use crate::redis_client::RedisClient;
+use crate::keys;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 const BURST_WINDOW_MS: u64 = 500; // 500ms window
@@ -24,7 +25,7 @@ impl BurstProfiler {
             .duration_since(UNIX_EPOCH)?
             .as_millis() as u64;
         
-        let burst_key = format!("burst:{}", composite_key);
+        let burst_key = keys::burst(composite_key);
         
         // Add current endpoint with timestamp score
         self.redis.zadd(&burst_key, now as f64, endpoint).await?;
@@ -45,11 +46,11 @@ impl BurstProfiler {
         
         // If user hits 5+ different endpoints in under 500ms, flag as bot
         if unique_endpoints.len() >= BURST_THRESHOLD {
-            eprintln!(
-                "🚨 Burst detection triggered for {}: {} unique endpoints in {}ms",
+            tracing::warn!(
                 composite_key,
-                unique_endpoints.len(),
-                BURST_WINDOW_MS
+                unique_endpoints = unique_endpoints.len(),
+                window_ms = BURST_WINDOW_MS,
+                "burst detection triggered"
             );
             return Ok(true);
         }
@@ -60,7 +61,7 @@ impl BurstProfiler {
     /// Get burst statistics for a composite key
     #[allow(dead_code)]
     pub async fn get_burst_stats(&self, composite_key: &str) -> Result<BurstStats> {
-        let burst_key = format!("burst:{}", composite_key);
+        let burst_key = keys::burst(composite_key);
         let entries = self.redis.zrange_withscores(&burst_key, 0, -1).await?;
         
         let unique_endpoints: std::collections::HashSet<String> = 
@@ -0,0 +1,224 @@
+use crate::redis_client::RedisClient;
+use crate::security::ip_reputation::RiskLevel;
+use crate::keys;
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Violations on a composite key before its posts need a captcha
+/// (one below the default shadowban threshold, so a poster is challenged first)
+const DEFAULT_MIN_VIOLATIONS: i64 = 2;
+/// IP risk level from which posts need a captcha
+const DEFAULT_MIN_RISK_LEVEL: u8 = 2;
+/// How long a solved captcha covers a poster's further posts
+const DEFAULT_PASS_TTL_SECONDS: u64 = 3600;
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptchaProvider {
+    Turnstile,
+    HCaptcha,
+}
+
+impl CaptchaProvider {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "turnstile" => Some(CaptchaProvider::Turnstile),
+            "hcaptcha" => Some(CaptchaProvider::HCaptcha),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CaptchaProvider::Turnstile => "turnstile",
+            CaptchaProvider::HCaptcha => "hcaptcha",
+        }
+    }
+
+    fn verify_url(&self) -> &'static str {
+        match self {
+            CaptchaProvider::Turnstile => "https://challenges.cloudflare.com/turnstile/v0/siteverify",
+            CaptchaProvider::HCaptcha => "https://api.hcaptcha.com/siteverify",
+        }
+    }
+}
+
+/// When a poster has to solve a captcha before posting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptchaThresholds {
+    pub min_violations: i64,
+    pub min_risk_level: RiskLevel,
+}
+
+impl Default for CaptchaThresholds {
+    fn default() -> Self {
+        Self {
+            min_violations: DEFAULT_MIN_VIOLATIONS,
+            min_risk_level: RiskLevel::from_u8(DEFAULT_MIN_RISK_LEVEL),
+        }
+    }
+}
+
+impl CaptchaThresholds {
+    pub fn requires_challenge(&self, violations: i64, risk_level: RiskLevel) -> bool {
+        violations >= self.min_violations || risk_level >= self.min_risk_level
+    }
+}
+
+#[derive(Deserialize)]
+struct SiteVerifyResponse {
+    success: bool,
+    #[serde(default, rename = "error-codes")]
+    error_codes: Vec<String>,
+}
+
+/// Verifies Cloudflare Turnstile or hCaptcha tokens for posters whose violations
+/// or IP risk crossed a threshold, giving a suspicious poster a way to prove
+/// they're human before the escalation policy shadowbans them
+/// A solved captcha is remembered per composite key for a while, since tokens are single-use
+#[derive(Clone)]
+pub struct CaptchaVerifier {
+    redis: RedisClient,
+    provider: Option<CaptchaProvider>,
+    secret: Option<String>,
+    /// Public key the client renders the widget with
+    site_key: Option<String>,
+    thresholds: CaptchaThresholds,
+    pass_ttl: u64,
+    http_client: reqwest::Client,
+}
+
+impl CaptchaVerifier {
+    pub fn new(redis: RedisClient, provider: CaptchaProvider, secret: String, site_key: Option<String>) -> Self {
+        Self {
+            redis,
+            provider: Some(provider),
+            secret: Some(secret),
+            site_key,
+            thresholds: CaptchaThresholds::default(),
+            pass_ttl: DEFAULT_PASS_TTL_SECONDS,
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    /// A verifier that never asks for a captcha
+    pub fn disabled(redis: RedisClient) -> Self {
+        Self {
+            redis,
+            provider: None,
+            secret: None,
+            site_key: None,
+            thresholds: CaptchaThresholds::default(),
+            pass_ttl: DEFAULT_PASS_TTL_SECONDS,
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    /// Enabled when both CAPTCHA_PROVIDER and CAPTCHA_SECRET are set
+    pub fn from_env(redis: RedisClient) -> Self {
+        let provider = std::env::var("CAPTCHA_PROVIDER").ok().and_then(|v| CaptchaProvider::parse(&v));
+        let secret = std::env::var("CAPTCHA_SECRET").ok().filter(|s| !s.is_empty());
+        let (Some(provider), Some(secret)) = (provider, secret) else {
+            return Self::disabled(redis);
+        };
+        let site_key = std::env::var("CAPTCHA_SITE_KEY").ok().filter(|s| !s.is_empty());
+        let thresholds = CaptchaThresholds {
+            min_violations: std::env::var("CAPTCHA_MIN_VIOLATIONS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_MIN_VIOLATIONS),
+            min_risk_level: RiskLevel::from_u8(
+                std::env::var("CAPTCHA_MIN_RISK_LEVEL")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(DEFAULT_MIN_RISK_LEVEL),
+            ),
+        };
+        let pass_ttl = std::env::var("CAPTCHA_PASS_TTL_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_PASS_TTL_SECONDS);
+        Self {
+            thresholds,
+            pass_ttl,
+            ..Self::new(redis, provider, secret, site_key)
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.provider.is_some()
+    }
+
+    pub fn provider(&self) -> Option<CaptchaProvider> {
+        self.provider
+    }
+
+    pub fn site_key(&self) -> Option<&str> {
+        self.site_key.as_deref()
+    }
+
+    /// Whether a poster at these signals must solve a captcha, ignoring any recent pass
+    pub fn requires_challenge(&self, violations: i64, risk_level: RiskLevel) -> bool {
+        self.enabled() && self.thresholds.requires_challenge(violations, risk_level)
+    }
+
+    /// Whether the composite key solved a captcha within the pass TTL
+    pub async fn has_passed(&self, composite_key: &str) -> bool {
+        self.redis
+            .exists(&keys::captcha_passed(composite_key))
+            .await
+            .unwrap_or(false)
+    }
+
+    /// Check a token with the provider, remembering a successful solve for the composite key
+    pub async fn verify(&self, composite_key: &str, token: &str, remote_ip: &str) -> Result<bool> {
+        let (Some(provider), Some(secret)) = (self.provider, self.secret.as_ref()) else {
+            return Err(anyhow!("Captcha is not configured"));
+        };
+
+        let response = self.http_client
+            .post(provider.verify_url())
+            .form(&[("secret", secret.as_str()), ("response", token), ("remoteip", remote_ip)])
+            .timeout(VERIFY_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| anyhow!("Failed to call captcha provider: {}", e))?;
+        let body: SiteVerifyResponse = response
+            .json()
+            .await
+            .map_err(|e| anyhow!("Failed to parse captcha response: {}", e))?;
+
+        metrics::counter!(
+            "captcha_verifications_total", 1,
+            "provider" => provider.as_str(),
+            "result" => if body.success { "passed" } else { "failed" }
+        );
+        if !body.success {
+            tracing::warn!(composite_key, error_codes = ?body.error_codes, "captcha rejected");
+            return Ok(false);
+        }
+
+        self.redis
+            .set_ex(&keys::captcha_passed(composite_key), "1", self.pass_ttl)
+            .await
+            .map_err(|e| anyhow!("Failed to record captcha pass: {}", e))?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_challenge_below_shadowban_threshold() {
+        let thresholds = CaptchaThresholds::default();
+        assert!(!thresholds.requires_challenge(0, RiskLevel::Level0));
+        assert!(!thresholds.requires_challenge(1, RiskLevel::Level1));
+        assert!(thresholds.requires_challenge(2, RiskLevel::Level0));
+        assert!(thresholds.requires_challenge(0, RiskLevel::Level2));
+        assert_eq!(CaptchaProvider::parse(" Turnstile "), Some(CaptchaProvider::Turnstile));
+        assert_eq!(CaptchaProvider::parse("recaptcha"), None);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::redis_client::RedisClient;
+use crate::security::city_surge::CitySurgeDetector;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::keys;
+
+/// How aggressively a city's messages are moderated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strictness {
+    /// Exact wordlist matches only - no fuzzy or spaced-out detection
+    Relaxed,
+    #[default]
+    Standard,
+    /// Relevance applies to short messages too and only one URL is allowed
+    Strict,
+}
+
+/// Per-city moderation overrides
+/// Unset fields fall back to the global defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CityModerationPolicy {
+    pub strictness: Strictness,
+    /// Local slang or spam terms blocked in this city on top of the global lists
+    pub extra_blocked_words: Vec<String>,
+    /// Local rental vocabulary counted towards relevance (e.g. "gala", "chawl")
+    pub extra_rental_keywords: Vec<String>,
+    /// Minimum rental keyword density (global default 0.1)
+    pub relevance_threshold: Option<f64>,
+}
+
+/// Resolves the moderation policy for a city
+/// Defaults come from MODERATION_CITY_POLICIES (JSON object keyed by city);
+/// admin overrides are stored in Redis and take precedence.
+/// A city in an active posting surge is moderated strictly whatever its policy
+#[derive(Clone, Default)]
+pub struct CityPolicyStore {
+    redis: Option<RedisClient>,
+    configured: HashMap<String, CityModerationPolicy>,
+    surges: Option<CitySurgeDetector>,
+}
+
+impl CityPolicyStore {
+    pub fn new(redis: RedisClient) -> Self {
+        let configured = match std::env::var("MODERATION_CITY_POLICIES") {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                tracing::warn!(error = %e, "invalid MODERATION_CITY_POLICIES, ignoring");
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        Self {
+            redis: Some(redis),
+            configured,
+            surges: None,
+        }
+    }
+
+    pub fn with_surges(mut self, surges: CitySurgeDetector) -> Self {
+        self.surges = Some(surges);
+        self
+    }
+
+    /// Policy for a message's location (global defaults when unknown)
+    pub async fn resolve(&self, location: Option<&str>) -> CityModerationPolicy {
+        let Some(city) = location else {
+            return CityModerationPolicy::default();
+        };
+
+        let mut policy = match self.get_override(city).await {
+            Ok(Some(policy)) => policy,
+            Ok(None) => self.configured.get(city).cloned().unwrap_or_default(),
+            Err(e) => {
+                tracing::error!("{}", e);
+                self.configured.get(city).cloned().unwrap_or_default()
+            }
+        };
+
+        if let Some(surges) = &self.surges {
+            match surges.is_active(city).await {
+                Ok(true) => policy.strictness = Strictness::Strict,
+                Ok(false) => {}
+                Err(e) => tracing::error!("{}", e),
+            }
+        }
+        policy
+    }
+
+    /// Admin override for a city, if one is set
+    pub async fn get_override(&self, city: &str) -> Result<Option<CityModerationPolicy>> {
+        let Some(redis) = &self.redis else {
+            return Ok(None);
+        };
+        let json = redis
+            .get(&keys::city_policy(city))
+            .await
+            .map_err(|e| anyhow!("Failed to load city moderation policy: {}", e))?;
+        Ok(json.and_then(|j| serde_json::from_str(&j).ok()))
+    }
+
+    /// Store an admin override for a city
+    pub async fn set_override(&self, city: &str, policy: &CityModerationPolicy) -> Result<()> {
+        let redis = self.redis.as_ref().ok_or_else(|| anyhow!("City policy store has no Redis"))?;
+        let json = serde_json::to_string(policy)?;
+        redis
+            .set(&keys::city_policy(city), &json)
+            .await
+            .map_err(|e| anyhow!("Failed to store city moderation policy: {}", e))
+    }
+
+    /// Remove an admin override, falling back to configured defaults
+    pub async fn clear_override(&self, city: &str) -> Result<()> {
+        let redis = self.redis.as_ref().ok_or_else(|| anyhow!("City policy store has no Redis"))?;
+        redis
+            .del(&keys::city_policy(city))
+            .await
+            .map_err(|e| anyhow!("Failed to clear city moderation policy: {}", e))
+    }
+
+    /// Configured (environment) default for a city
+    pub fn configured(&self, city: &str) -> Option<&CityModerationPolicy> {
+        self.configured.get(city)
+    }
+}
@@ -0,0 +1,199 @@
+use crate::redis_client::RedisClient;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use crate::keys;
+
+const DEFAULT_WINDOW_MINUTES: u64 = 5;
+const DEFAULT_BASELINE_MINUTES: u64 = 60;
+const DEFAULT_FACTOR: f64 = 4.0;
+const DEFAULT_MIN_POSTS: u64 = 20;
+const DEFAULT_HOLD_SECONDS: u64 = 1800; // 30 minutes
+const DEFAULT_COOLDOWN_MULTIPLIER: u64 = 4;
+
+/// A city whose posting rate jumped well above its baseline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CitySurge {
+    pub city: String,
+    /// Posts in the detection window when the surge was flagged
+    pub recent_posts: u64,
+    /// Average posts per detection window over the baseline period
+    pub baseline_posts: f64,
+    pub detected_at: u64,
+}
+
+/// Per-city posting velocity tracking and flood detection
+/// Posts are counted per city per minute; the detector job compares the last
+/// `CITY_SURGE_WINDOW_MINUTES` against the preceding `CITY_SURGE_BASELINE_MINUTES`.
+/// While a surge is active (`CITY_SURGE_HOLD_SECONDS`, extended while the flood
+/// continues) the city is moderated at strict strictness and posting cooldowns
+/// there are multiplied by `CITY_SURGE_COOLDOWN_MULTIPLIER`
+#[derive(Clone)]
+pub struct CitySurgeDetector {
+    redis: RedisClient,
+    window_minutes: u64,
+    baseline_minutes: u64,
+    /// How many times the baseline rate counts as a surge
+    factor: f64,
+    /// Floor on the window count, so quiet cities aren't flagged for a handful of posts
+    min_posts: u64,
+    hold_seconds: u64,
+    pub cooldown_multiplier: u64,
+}
+
+impl CitySurgeDetector {
+    pub fn from_env(redis: RedisClient) -> Self {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        }
+        Self {
+            redis,
+            window_minutes: var("CITY_SURGE_WINDOW_MINUTES", DEFAULT_WINDOW_MINUTES).max(1),
+            baseline_minutes: var("CITY_SURGE_BASELINE_MINUTES", DEFAULT_BASELINE_MINUTES).max(1),
+            factor: var("CITY_SURGE_FACTOR", DEFAULT_FACTOR),
+            min_posts: var("CITY_SURGE_MIN_POSTS", DEFAULT_MIN_POSTS),
+            hold_seconds: var("CITY_SURGE_HOLD_SECONDS", DEFAULT_HOLD_SECONDS),
+            cooldown_multiplier: var("CITY_SURGE_COOLDOWN_MULTIPLIER", DEFAULT_COOLDOWN_MULTIPLIER).max(1),
+        }
+    }
+
+    /// Count an accepted post towards its city's velocity
+    /// Callers only pass registered cities; each tracked city is read every detection run
+    pub async fn record_post(&self, city: &str) -> Result<()> {
+        let key = keys::city_velocity(city, current_minute());
+        let ttl = ((self.window_minutes + self.baseline_minutes + 1) * 60) as i64;
+        self.redis
+            .pipeline()
+            .incr(&key).ignore()
+            .expire(&key, ttl).ignore()
+            .sadd(keys::CITY_VELOCITY_CITIES, city).ignore()
+            .execute()
+            .await
+            .map_err(|e| anyhow!("Failed to record city velocity: {}", e))
+    }
+
+    /// Compare every city's recent rate to its baseline
+    /// Returns only surges that weren't already active; ongoing ones are extended
+    pub async fn detect(&self) -> Result<Vec<CitySurge>> {
+        let cities = self.redis
+            .smembers(keys::CITY_VELOCITY_CITIES)
+            .await
+            .map_err(|e| anyhow!("Failed to list tracked cities: {}", e))?;
+        let now = current_minute();
+        let mut detected = Vec::new();
+
+        for city in cities {
+            let keys: Vec<String> = (0..self.window_minutes + self.baseline_minutes)
+                .map(|offset| keys::city_velocity(&city, now - offset))
+                .collect();
+            let counts: Vec<u64> = self.redis
+                .mget(&keys.iter().map(String::as_str).collect::<Vec<_>>())
+                .await
+                .map_err(|e| anyhow!("Failed to load city velocity: {}", e))?
+                .into_iter()
+                .map(|count| count.and_then(|c| c.parse().ok()).unwrap_or(0))
+                .collect();
+
+            let (recent, baseline) = counts.split_at(self.window_minutes as usize);
+            let recent: u64 = recent.iter().sum();
+            if recent == 0 && baseline.iter().all(|c| *c == 0) {
+                // Nothing posted for the whole period - stop tracking until it's posted to again
+                self.redis
+                    .srem(keys::CITY_VELOCITY_CITIES, &city)
+                    .await
+                    .map_err(|e| anyhow!("Failed to untrack city: {}", e))?;
+                continue;
+            }
+            let baseline_posts = baseline.iter().sum::<u64>() as f64
+                / (self.baseline_minutes as f64 / self.window_minutes as f64);
+
+            if !is_surge(recent, baseline_posts, self.factor, self.min_posts) {
+                continue;
+            }
+            let surge = CitySurge {
+                city: city.clone(),
+                recent_posts: recent,
+                baseline_posts,
+                detected_at: now * 60,
+            };
+            if self.start_or_extend(&surge).await? {
+                detected.push(surge);
+            }
+        }
+
+        Ok(detected)
+    }
+
+    /// Store a new surge, or push back the expiry of one already running
+    /// Returns whether the surge is new
+    async fn start_or_extend(&self, surge: &CitySurge) -> Result<bool> {
+        let key = keys::city_surge(&surge.city);
+        let json = serde_json::to_string(surge)?;
+        let started = self.redis
+            .set_nx_ex(&key, &json, self.hold_seconds)
+            .await
+            .map_err(|e| anyhow!("Failed to store city surge: {}", e))?;
+        if !started {
+            self.redis
+                .expire(&key, self.hold_seconds as i64)
+                .await
+                .map_err(|e| anyhow!("Failed to extend city surge: {}", e))?;
+        }
+        Ok(started)
+    }
+
+    pub async fn is_active(&self, city: &str) -> Result<bool> {
+        self.redis
+            .exists(&keys::city_surge(city))
+            .await
+            .map_err(|e| anyhow!("Failed to check city surge: {}", e))
+    }
+
+    /// Surges currently in force
+    pub async fn active(&self) -> Result<Vec<CitySurge>> {
+        let cities = self.redis
+            .smembers(keys::CITY_VELOCITY_CITIES)
+            .await
+            .map_err(|e| anyhow!("Failed to list tracked cities: {}", e))?;
+        let keys: Vec<String> = cities.iter().map(|city| keys::city_surge(city)).collect();
+        let surges = self.redis
+            .mget(&keys.iter().map(String::as_str).collect::<Vec<_>>())
+            .await
+            .map_err(|e| anyhow!("Failed to load city surges: {}", e))?;
+        Ok(surges.into_iter().flatten().filter_map(|json| serde_json::from_str(&json).ok()).collect())
+    }
+
+    /// End a surge early; returns whether one was active
+    pub async fn clear(&self, city: &str) -> Result<bool> {
+        let active = self.is_active(city).await?;
+        self.redis
+            .del(&keys::city_surge(city))
+            .await
+            .map_err(|e| anyhow!("Failed to clear city surge: {}", e))?;
+        Ok(active)
+    }
+}
+
+/// Whether a window count is far enough above the baseline to be a flood
+fn is_surge(recent: u64, baseline_posts: f64, factor: f64, min_posts: u64) -> bool {
+    recent >= min_posts && recent as f64 >= baseline_posts * factor
+}
+
+fn current_minute() -> u64 {
+    chrono::Utc::now().timestamp() as u64 / 60
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_surge() {
+        // Quiet city: a few posts never count, however low the baseline
+        assert!(!is_surge(5, 0.0, 4.0, 20));
+        // New activity with no history past the floor
+        assert!(is_surge(20, 0.0, 4.0, 20));
+        // Busy city posting at a normal rate
+        assert!(!is_surge(90, 30.0, 4.0, 20));
+        assert!(is_surge(120, 30.0, 4.0, 20));
+    }
+}
@@ -1,6 +1,8 @@
 use regex::Regex;
 use once_cell::sync::Lazy;
 
+use crate::security::wordlists::Wordlists;
+
 /// Content filter for detecting scams, spam, and policy violations
 #[derive(Clone)]
 pub struct ContentFilter {
@@ -14,7 +16,6 @@ pub struct ContentFilter {
 pub struct FilterResult {
     pub is_allowed: bool,
     pub reason: Option<String>,
-    #[allow(dead_code)]
     pub violation_type: Option<ViolationType>,
 }
 
@@ -45,10 +46,6 @@ impl FilterResult {
 }
 
 // Compile regexes once at startup
-static SCAM_URL_REGEX: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(r"(?i)(t\.me|telegram\.me|telegram\.org/bot|bit\.ly|tinyurl\.com|goo\.gl|rebrand\.ly|ow\.ly)").unwrap()
-});
-
 static PHONE_REGEX: Lazy<Regex> = Lazy::new(|| {
     // Match various phone number patterns
     Regex::new(r"(?:\+?\d{1,3}[-.\s]?)?\(?\d{3}\)?[-.\s]?\d{3}[-.\s]?\d{4}|\+?\d{10,15}|\d{3}[-.\s]\d{3}[-.\s]\d{4}").unwrap()
@@ -60,8 +57,14 @@ static SPAM_PHRASES_REGEX: Lazy<Regex> = Lazy::new(|| {
 
 impl ContentFilter {
     pub fn new() -> Self {
+        Self::with_wordlists(&Wordlists::built_in())
+    }
+
+    /// Flag links to the scam domains in `wordlists`
+    pub fn with_wordlists(wordlists: &Wordlists) -> Self {
+        let domains: Vec<String> = wordlists.scam_domains.iter().map(|domain| regex::escape(domain)).collect();
         Self {
-            scam_url_regex: SCAM_URL_REGEX.clone(),
+            scam_url_regex: Regex::new(&format!("(?i)({})", domains.join("|"))).unwrap(),
             phone_regex: PHONE_REGEX.clone(),
             spam_phrases_regex: SPAM_PHRASES_REGEX.clone(),
         }
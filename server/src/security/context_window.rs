@@ -0,0 +1,186 @@
+use crate::redis_client::RedisClient;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::keys;
+
+/// Number of recent messages kept per composite key
+const WINDOW_SIZE: usize = 10;
+/// How long a quiet poster's window is kept
+const WINDOW_TTL_SECONDS: i64 = 3600;
+
+/// Copies of (nearly) the same text within the window that count as repetition
+const REPETITION_THRESHOLD: usize = 3;
+/// Consecutive posts whose toxicity must keep rising to count as escalation
+const ESCALATION_RUN: usize = 3;
+/// Toxicity the last post of an escalating run must reach
+const ESCALATION_FLOOR: f64 = 0.5;
+/// Borderline (non-zero toxicity) posts within the window that warrant review
+const BORDERLINE_THRESHOLD: usize = 4;
+
+/// One accepted message in a poster's rolling window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowEntry {
+    /// Normalized text used to spot repeats
+    pub fingerprint: String,
+    /// 0.0 (clean) to 1.0 (clearly abusive)
+    pub toxicity: f64,
+    pub timestamp: u64,
+}
+
+impl WindowEntry {
+    pub fn new(content: &str, toxicity: f64, timestamp: u64) -> Self {
+        Self {
+            fingerprint: fingerprint(content),
+            toxicity,
+            timestamp,
+        }
+    }
+}
+
+/// Aggregate checks over a poster's recent messages
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct WindowVerdict {
+    pub messages: usize,
+    /// Most copies of a single text
+    pub max_repeats: usize,
+    pub borderline_posts: usize,
+    pub escalating: bool,
+    /// Human-readable reasons the window needs review (empty when it doesn't)
+    pub reasons: Vec<String>,
+}
+
+impl WindowVerdict {
+    /// `entries` is oldest first
+    pub fn analyze(entries: &[WindowEntry]) -> Self {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for entry in entries.iter().filter(|e| !e.fingerprint.is_empty()) {
+            *counts.entry(entry.fingerprint.as_str()).or_default() += 1;
+        }
+        let max_repeats = counts.values().copied().max().unwrap_or(0);
+        let borderline_posts = entries.iter().filter(|e| e.toxicity > 0.0).count();
+
+        let escalating = entries.len() >= ESCALATION_RUN && {
+            let run = &entries[entries.len() - ESCALATION_RUN..];
+            run.windows(2).all(|pair| pair[1].toxicity > pair[0].toxicity)
+                && run[ESCALATION_RUN - 1].toxicity >= ESCALATION_FLOOR
+        };
+
+        let mut reasons = Vec::new();
+        if max_repeats >= REPETITION_THRESHOLD {
+            reasons.push(format!("Same text posted {} times", max_repeats));
+        }
+        if escalating {
+            reasons.push("Escalating toxicity across recent posts".to_string());
+        }
+        if borderline_posts >= BORDERLINE_THRESHOLD {
+            reasons.push(format!("{} borderline posts in a short window", borderline_posts));
+        }
+
+        Self {
+            messages: entries.len(),
+            max_repeats,
+            borderline_posts,
+            escalating,
+            reasons,
+        }
+    }
+
+    pub fn needs_review(&self) -> bool {
+        !self.reasons.is_empty()
+    }
+}
+
+/// Rolling window of each composite key's recent messages
+/// Single posts may each pass moderation while the stream as a whole is abusive
+#[derive(Clone)]
+pub struct ContextWindow {
+    redis: RedisClient,
+}
+
+impl ContextWindow {
+    pub fn new(redis: RedisClient) -> Self {
+        Self { redis }
+    }
+
+    /// Add a message to the poster's window and analyze the result
+    pub async fn record(&self, composite_key: &str, entry: &WindowEntry) -> Result<WindowVerdict> {
+        let key = keys::moderation_recent(composite_key);
+        let json = serde_json::to_string(entry)?;
+
+        self.redis
+            .pipeline()
+            .lpush(&key, &json)
+            .ltrim(&key, 0, WINDOW_SIZE as isize - 1)
+            .expire(&key, WINDOW_TTL_SECONDS)
+            .execute()
+            .await
+            .map_err(|e| anyhow!("Failed to record context window: {}", e))?;
+
+        let mut entries: Vec<WindowEntry> = self.redis
+            .lrange(&key, 0, -1)
+            .await
+            .map_err(|e| anyhow!("Failed to load context window: {}", e))?
+            .iter()
+            .filter_map(|e| serde_json::from_str(e).ok())
+            .collect();
+        // Stored newest first
+        entries.reverse();
+
+        Ok(WindowVerdict::analyze(&entries))
+    }
+
+    /// Claim the right to queue this poster for review (once per window)
+    pub async fn mark_flagged(&self, composite_key: &str) -> Result<bool> {
+        self.redis
+            .set_nx_ex(&keys::moderation_context_flagged(composite_key), "1", WINDOW_TTL_SECONDS as u64)
+            .await
+            .map_err(|e| anyhow!("Failed to flag context window: {}", e))
+    }
+}
+
+/// Lowercased alphanumeric words, so trivial edits don't hide a repeat
+fn fingerprint(content: &str) -> String {
+    content
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repetition_with_trivial_edits() {
+        let entries: Vec<WindowEntry> = ["2BHK for rent, call now!", "2bhk for rent call now", "2BHK  FOR RENT... call now"]
+            .iter()
+            .enumerate()
+            .map(|(i, text)| WindowEntry::new(text, 0.0, i as u64))
+            .collect();
+
+        let verdict = WindowVerdict::analyze(&entries);
+        assert_eq!(verdict.max_repeats, 3);
+        assert!(verdict.needs_review());
+    }
+
+    #[test]
+    fn test_escalating_toxicity() {
+        let entries: Vec<WindowEntry> = [0.0, 0.2, 0.5, 0.8]
+            .iter()
+            .enumerate()
+            .map(|(i, toxicity)| WindowEntry::new(&format!("post {}", i), *toxicity, i as u64))
+            .collect();
+        assert!(WindowVerdict::analyze(&entries).escalating);
+
+        // Rising but never reaching the floor is just noise
+        let mild: Vec<WindowEntry> = [0.0, 0.1, 0.2, 0.3]
+            .iter()
+            .enumerate()
+            .map(|(i, toxicity)| WindowEntry::new(&format!("post {}", i), *toxicity, i as u64))
+            .collect();
+        assert!(!WindowVerdict::analyze(&mild).needs_review());
+    }
+}
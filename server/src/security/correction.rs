@@ -0,0 +1,49 @@
+use crate::redis_client::RedisClient;
+use anyhow::{Result, anyhow};
+use crate::keys;
+
+/// How long a rejected poster has to resubmit corrected content
+const CORRECTION_TOKEN_TTL_SECONDS: u64 = 120;
+
+/// One-time tokens handed out with fixable post rejections (embedded phone
+/// number, message too long, malformed phone field)
+/// Presenting the token with the corrected post lets it through without waiting
+/// out a cooldown. A poster holds at most one token, and any successful post
+/// invalidates it, so a token can never be used to skip a real post's cooldown
+#[derive(Clone)]
+pub struct CorrectionTokens {
+    redis: RedisClient,
+}
+
+impl CorrectionTokens {
+    pub fn new(redis: RedisClient) -> Self {
+        Self { redis }
+    }
+
+    /// Issue a fresh token, replacing any earlier one
+    pub async fn issue(&self, composite_key: &str) -> Result<String> {
+        let token = uuid::Uuid::new_v4().to_string();
+        self.redis
+            .set_ex(&keys::correction_token(composite_key), &token, CORRECTION_TOKEN_TTL_SECONDS)
+            .await
+            .map_err(|e| anyhow!("Failed to issue correction token: {}", e))?;
+        Ok(token)
+    }
+
+    /// Whether the token is the poster's current, unexpired one
+    pub async fn is_valid(&self, composite_key: &str, token: &str) -> Result<bool> {
+        let current = self.redis
+            .get(&keys::correction_token(composite_key))
+            .await
+            .map_err(|e| anyhow!("Failed to check correction token: {}", e))?;
+        Ok(current.as_deref() == Some(token))
+    }
+
+    /// Drop the poster's token once they've posted
+    pub async fn invalidate(&self, composite_key: &str) -> Result<()> {
+        self.redis
+            .del(&keys::correction_token(composite_key))
+            .await
+            .map_err(|e| anyhow!("Failed to invalidate correction token: {}", e))
+    }
+}
@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+
+/// Machine-readable cause of a shadowban, block or cooldown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReasonCode {
+    /// Filled in the honeypot form field
+    Honeypot,
+    /// Burst profiler saw bot-like endpoint hopping
+    BurstPattern,
+    /// Exceeded the burst protection rate limit
+    BurstLimit,
+    /// Repeated content moderation violations
+    ModerationViolations,
+    /// Reported by enough users
+    UserReports,
+    /// Requested a decoy endpoint
+    BotTrap,
+    /// Posting cooldown from the IP's risk level
+    RiskCooldown,
+    /// Applied by a moderator
+    Manual,
+    /// Stored before structured metadata existed
+    Unknown,
+}
+
+/// Metadata stored as the value of a shadowban, IP block or cooldown key
+/// so moderators and the appeals process can see why and by whom it was applied
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnforcementRecord {
+    pub code: ReasonCode,
+    pub reason: String,
+    /// "system" for automatic enforcement, otherwise the admin subject
+    pub actor: String,
+    /// Module that applied it
+    pub source: String,
+    pub created_at: u64,
+    /// None for permanent enforcement
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Message that triggered it, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+}
+
+impl EnforcementRecord {
+    pub fn system(code: ReasonCode, source: &str, reason: &str) -> Self {
+        Self {
+            code,
+            reason: reason.to_string(),
+            actor: "system".to_string(),
+            source: source.to_string(),
+            created_at: now(),
+            expires_at: None,
+            message_id: None,
+        }
+    }
+
+    pub fn manual(actor: &str, reason: &str) -> Self {
+        Self {
+            actor: actor.to_string(),
+            ..Self::system(ReasonCode::Manual, "admin", reason)
+        }
+    }
+
+    pub fn with_message(mut self, message_id: &str) -> Self {
+        self.message_id = Some(message_id.to_string());
+        self
+    }
+
+    /// Stamp the expiry for a given duration from creation (None = permanent)
+    pub fn expiring_in(mut self, duration_seconds: Option<u64>) -> Self {
+        self.expires_at = duration_seconds.map(|duration| self.created_at + duration);
+        self
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("enforcement record serializes")
+    }
+
+    /// Read a stored value; older values are plain reason strings
+    pub fn parse(value: &str) -> Self {
+        serde_json::from_str(value).unwrap_or_else(|_| Self {
+            created_at: 0,
+            ..Self::system(ReasonCode::Unknown, "unknown", value)
+        })
+    }
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let record = EnforcementRecord::system(ReasonCode::UserReports, "handlers::report_message", "3 reports")
+            .with_message("msg-1")
+            .expiring_in(Some(60));
+        assert_eq!(EnforcementRecord::parse(&record.to_json()), record);
+        assert_eq!(record.expires_at, Some(record.created_at + 60));
+    }
+
+    #[test]
+    fn test_parse_legacy_value() {
+        let record = EnforcementRecord::parse("Bot detected - burst pattern");
+        assert_eq!(record.code, ReasonCode::Unknown);
+        assert_eq!(record.reason, "Bot detected - burst pattern");
+    }
+}
@@ -0,0 +1,250 @@
+use serde::{Deserialize, Serialize};
+
+use crate::security::enforcement::{EnforcementRecord, ReasonCode};
+use crate::security::ip_reputation::RiskLevel;
+use crate::state::AppState;
+
+/// Event that asks the policy for a decision
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    /// Burst profiler saw bot-like endpoint hopping
+    BurstPattern,
+    /// The burst protection rate limit was exceeded
+    BurstLimit,
+    /// A post was rejected by the content filter
+    ContentViolation,
+    /// A post was blocked by moderation
+    ModerationViolation,
+}
+
+impl Trigger {
+    fn reason_code(&self) -> ReasonCode {
+        match self {
+            Trigger::BurstPattern => ReasonCode::BurstPattern,
+            Trigger::BurstLimit => ReasonCode::BurstLimit,
+            Trigger::ContentViolation | Trigger::ModerationViolation => ReasonCode::ModerationViolations,
+        }
+    }
+}
+
+/// What is known about the actor when a trigger fires
+#[derive(Debug, Clone, Copy)]
+pub struct Signals {
+    pub trigger: Trigger,
+    pub risk_level: RiskLevel,
+    /// Bot-likelihood from request headers (0-100)
+    pub header_score: u8,
+    /// Violations on record for the composite key, including this one
+    pub violations: i64,
+}
+
+/// Enforcement applied when a rule matches
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    /// Hold back the composite key's next post
+    Cooldown { seconds: u64 },
+    /// Shadowban the composite key; permanent without `seconds`
+    Shadowban {
+        #[serde(default)]
+        seconds: Option<u64>,
+    },
+    BlockIp { seconds: u64 },
+}
+
+/// A rule matches when every condition it sets holds
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Conditions {
+    pub trigger: Option<Trigger>,
+    pub min_risk_level: Option<u8>,
+    pub min_header_score: Option<u8>,
+    pub min_violations: Option<i64>,
+}
+
+impl Conditions {
+    fn matches(&self, signals: &Signals) -> bool {
+        self.trigger.is_none_or(|trigger| trigger == signals.trigger)
+            && self.min_risk_level.is_none_or(|level| signals.risk_level as u8 >= level)
+            && self.min_header_score.is_none_or(|score| signals.header_score >= score)
+            && self.min_violations.is_none_or(|violations| signals.violations >= violations)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    #[serde(default)]
+    pub when: Conditions,
+    pub actions: Vec<Action>,
+}
+
+/// Ordered enforcement rules mapping signals to actions; the first match wins
+/// and no match means no enforcement
+/// Rules come from `ESCALATION_RULES` (JSON array) or the file named by
+/// `ESCALATION_RULES_FILE`, replacing the built-in defaults, e.g.
+/// `[{"name": "repeat-offender", "when": {"trigger": "moderation_violation", "min_violations": 3},
+///    "actions": [{"action": "shadowban", "seconds": 86400}]}]`
+#[derive(Debug, Clone)]
+pub struct EscalationPolicy {
+    rules: Vec<Rule>,
+}
+
+impl Default for EscalationPolicy {
+    /// The behaviour from before rules were configurable
+    fn default() -> Self {
+        let repeat_offender = |name: &str, trigger| Rule {
+            name: name.to_string(),
+            when: Conditions { trigger: Some(trigger), min_violations: Some(3), ..Default::default() },
+            actions: vec![Action::Shadowban { seconds: Some(86400) }],
+        };
+        Self {
+            rules: vec![
+                Rule {
+                    name: "burst-pattern".to_string(),
+                    when: Conditions { trigger: Some(Trigger::BurstPattern), ..Default::default() },
+                    actions: vec![Action::Shadowban { seconds: Some(86400) }, Action::BlockIp { seconds: 1800 }],
+                },
+                Rule {
+                    name: "burst-limit".to_string(),
+                    when: Conditions { trigger: Some(Trigger::BurstLimit), ..Default::default() },
+                    actions: vec![Action::BlockIp { seconds: 1800 }],
+                },
+                repeat_offender("repeat-content-violations", Trigger::ContentViolation),
+                repeat_offender("repeat-moderation-violations", Trigger::ModerationViolation),
+            ],
+        }
+    }
+}
+
+impl EscalationPolicy {
+    pub fn from_env() -> Self {
+        let json = match (std::env::var("ESCALATION_RULES"), std::env::var("ESCALATION_RULES_FILE")) {
+            (Ok(json), _) => json,
+            (Err(_), Ok(path)) => match std::fs::read_to_string(&path) {
+                Ok(json) => json,
+                Err(e) => {
+                    tracing::warn!(path = %path, error = %e, "failed to read ESCALATION_RULES_FILE, using defaults");
+                    return Self::default();
+                }
+            },
+            _ => return Self::default(),
+        };
+        match serde_json::from_str(&json) {
+            Ok(rules) => Self { rules },
+            Err(e) => {
+                tracing::warn!(error = %e, "invalid escalation rules, using defaults");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// The first rule matching the signals
+    pub fn decide(&self, signals: &Signals) -> Option<&Rule> {
+        self.rules.iter().find(|rule| rule.when.matches(signals))
+    }
+
+    /// Decide and carry out the matching rule's actions against an actor
+    /// Failures are logged; enforcement is best effort like the checks that trigger it
+    pub async fn enforce(
+        &self,
+        state: &AppState,
+        signals: &Signals,
+        composite_key: &str,
+        ip: &str,
+        source: &str,
+        reason: &str,
+    ) -> Enforced {
+        let mut enforced = Enforced::default();
+        let Some(rule) = self.decide(signals) else {
+            return enforced;
+        };
+        metrics::counter!("escalation_rule_matches_total", 1, "rule" => rule.name.clone());
+        enforced.rule = Some(rule.name.clone());
+
+        let record = EnforcementRecord::system(signals.trigger.reason_code(), source, reason);
+        for action in &rule.actions {
+            let result = match action {
+                Action::Cooldown { seconds } => state.ip_reputation
+                    .set_cooldown(composite_key, *seconds, record.clone())
+                    .await,
+                Action::Shadowban { seconds } => state.shadowban_manager
+                    .shadowban(composite_key, record.clone(), *seconds)
+                    .await
+                    .map(|_| enforced.shadowbanned = true),
+                Action::BlockIp { seconds } => state.rate_limiter
+                    .block_ip(ip, *seconds, record.clone())
+                    .await
+                    .map(|_| enforced.ip_blocked = true),
+            };
+            if let Err(e) = result {
+                tracing::error!("Failed to apply escalation rule {}: {}", rule.name, e);
+            }
+        }
+        enforced
+    }
+}
+
+/// Enforcement actually carried out
+#[derive(Debug, Default, Clone)]
+pub struct Enforced {
+    /// Name of the rule that matched, if any
+    pub rule: Option<String>,
+    pub shadowbanned: bool,
+    pub ip_blocked: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signals(trigger: Trigger, violations: i64) -> Signals {
+        Signals { trigger, risk_level: RiskLevel::Level0, header_score: 0, violations }
+    }
+
+    #[test]
+    fn test_defaults_match_previous_behaviour() {
+        let policy = EscalationPolicy::default();
+        assert_eq!(policy.decide(&signals(Trigger::BurstPattern, 0)).unwrap().actions.len(), 2);
+        assert_eq!(
+            policy.decide(&signals(Trigger::BurstLimit, 0)).unwrap().actions,
+            vec![Action::BlockIp { seconds: 1800 }]
+        );
+        assert!(policy.decide(&signals(Trigger::ModerationViolation, 2)).is_none());
+        assert_eq!(
+            policy.decide(&signals(Trigger::ContentViolation, 3)).unwrap().actions,
+            vec![Action::Shadowban { seconds: Some(86400) }]
+        );
+    }
+
+    #[test]
+    fn test_configured_rules_first_match_wins() {
+        let rules: Vec<Rule> = serde_json::from_str(r#"[
+            {"name": "scripted", "when": {"min_header_score": 70, "min_risk_level": 2},
+             "actions": [{"action": "shadowban"}]},
+            {"name": "offender", "when": {"min_violations": 2},
+             "actions": [{"action": "cooldown", "seconds": 600}]}
+        ]"#).unwrap();
+        let policy = EscalationPolicy { rules };
+
+        let scripted = Signals { risk_level: RiskLevel::Level2, header_score: 80, ..signals(Trigger::ContentViolation, 5) };
+        let rule = policy.decide(&scripted).unwrap();
+        assert_eq!(rule.name, "scripted");
+        assert_eq!(rule.actions, vec![Action::Shadowban { seconds: None }]);
+
+        assert_eq!(policy.decide(&signals(Trigger::BurstLimit, 2)).unwrap().name, "offender");
+        assert!(policy.decide(&signals(Trigger::BurstLimit, 1)).is_none());
+    }
+
+    #[test]
+    fn test_unknown_conditions_are_rejected() {
+        assert!(serde_json::from_str::<Vec<Rule>>(
+            r#"[{"name": "typo", "when": {"min_violation": 2}, "actions": []}]"#
+        ).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use crate::redis_client::RedisClient;
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use crate::keys;
+
+/// Fingerprint used when the header is missing or fails validation
+/// Every such client on an IP shares one composite key, so junk values can't mint new identities
+pub const UNKNOWN_FINGERPRINT: &str = "unknown";
+
+/// Shortest accepted fingerprint (the client's fallback hash is a base36 32-bit value)
+const MIN_LENGTH: usize = 6;
+/// ThumbmarkJS hashes are 32 hex characters; leave headroom for other encodings
+const MAX_LENGTH: usize = 128;
+/// Fewest distinct characters a genuine hash plausibly has
+const MIN_DISTINCT_CHARS: usize = 4;
+/// Minimum Shannon entropy in bits per character
+const MIN_ENTROPY_BITS: f64 = 1.5;
+/// Placeholder values scripts tend to send
+const PLACEHOLDERS: &[&str] = &["unknown", "undefined", "null", "none", "test", "fingerprint"];
+
+/// How long an IP remembers the fingerprints registered from it
+const KNOWN_FINGERPRINTS_TTL_SECONDS: i64 = 2592000; // 30 days
+
+/// Why a fingerprint header was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FingerprintError {
+    Length,
+    Charset,
+    Placeholder,
+    LowEntropy,
+}
+
+impl FingerprintError {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FingerprintError::Length => "length",
+            FingerprintError::Charset => "charset",
+            FingerprintError::Placeholder => "placeholder",
+            FingerprintError::LowEntropy => "low_entropy",
+        }
+    }
+}
+
+/// Check that a fingerprint looks like a hash rather than a hand-picked value
+pub fn validate(fingerprint: &str) -> Result<(), FingerprintError> {
+    if !(MIN_LENGTH..=MAX_LENGTH).contains(&fingerprint.len()) {
+        return Err(FingerprintError::Length);
+    }
+    if !fingerprint.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(FingerprintError::Charset);
+    }
+    if PLACEHOLDERS.contains(&fingerprint.to_ascii_lowercase().as_str()) {
+        return Err(FingerprintError::Placeholder);
+    }
+
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for c in fingerprint.chars() {
+        *counts.entry(c).or_default() += 1;
+    }
+    let len = fingerprint.len() as f64;
+    let entropy: f64 = counts
+        .values()
+        .map(|&n| {
+            let p = n as f64 / len;
+            -p * p.log2()
+        })
+        .sum();
+    if counts.len() < MIN_DISTINCT_CHARS || entropy < MIN_ENTROPY_BITS {
+        return Err(FingerprintError::LowEntropy);
+    }
+
+    Ok(())
+}
+
+/// Fingerprints registered through the session flow, per IP
+/// Used to meter how fast one IP can introduce new identities
+#[derive(Clone)]
+pub struct FingerprintRegistry {
+    redis: RedisClient,
+}
+
+impl FingerprintRegistry {
+    pub fn new(redis: RedisClient) -> Self {
+        Self { redis }
+    }
+
+    pub async fn is_known(&self, ip: &str, fingerprint: &str) -> Result<bool> {
+        self.redis
+            .sismember(&keys::ip_fingerprints(ip), fingerprint)
+            .await
+            .map_err(|e| anyhow!("Failed to check fingerprint registry: {}", e))
+    }
+
+    pub async fn register(&self, ip: &str, fingerprint: &str) -> Result<()> {
+        let key = keys::ip_fingerprints(ip);
+        self.redis
+            .pipeline()
+            .sadd(&key, fingerprint).ignore()
+            .expire(&key, KNOWN_FINGERPRINTS_TTL_SECONDS).ignore()
+            .execute()
+            .await
+            .map_err(|e| anyhow!("Failed to register fingerprint: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepts_real_hashes() {
+        assert!(validate("3f9a1c07be5d4e2a9c8b7f6e5d4c3b2a").is_ok());
+        // Client fallback: base36 of a 32-bit hash
+        assert!(validate("1x9k2pq").is_ok());
+    }
+
+    #[test]
+    fn test_rejects_junk() {
+        assert_eq!(validate("abc"), Err(FingerprintError::Length));
+        assert_eq!(validate("abc def 123"), Err(FingerprintError::Charset));
+        assert_eq!(validate("Undefined"), Err(FingerprintError::Placeholder));
+        assert_eq!(validate("aaaaaaaaaaaaaaaa"), Err(FingerprintError::LowEntropy));
+        assert_eq!(validate("abababababababab"), Err(FingerprintError::LowEntropy));
+    }
+}
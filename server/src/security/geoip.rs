@@ -0,0 +1,123 @@
+use maxminddb::{geoip2, Reader};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Where a client address is, as far as the GeoLite2 database knows
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct GeoLocation {
+    /// ISO 3166-1 alpha-2 code, e.g. "IN"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    /// Largest subdivision (state), in English
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// City in English, as spelled by MaxMind (run it through `CityRegistry`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+}
+
+/// MaxMind GeoLite2-City lookups for client addresses, plus per-country blocking
+/// Disabled (every lookup misses) unless GEOIP_DATABASE_PATH points at a
+/// readable .mmdb file; the database is loaded into memory once at startup
+#[derive(Clone)]
+pub struct GeoIp {
+    reader: Option<Arc<Reader<Vec<u8>>>>,
+    /// Uppercase ISO country codes turned away by the security middleware
+    blocked_countries: HashSet<String>,
+}
+
+impl GeoIp {
+    pub fn new(reader: Option<Reader<Vec<u8>>>, blocked_countries: HashSet<String>) -> Self {
+        Self {
+            reader: reader.map(Arc::new),
+            blocked_countries,
+        }
+    }
+
+    /// Read GEOIP_DATABASE_PATH and GEOIP_BLOCKED_COUNTRIES (comma-separated ISO codes)
+    pub fn from_env() -> Self {
+        let reader = std::env::var("GEOIP_DATABASE_PATH")
+            .ok()
+            .filter(|path| !path.is_empty())
+            .and_then(|path| match Reader::open_readfile(&path) {
+                Ok(reader) => {
+                    tracing::info!(path = %path, "GeoIP database loaded");
+                    Some(reader)
+                }
+                Err(e) => {
+                    tracing::warn!(path = %path, error = %e, "GeoIP disabled, failed to open database");
+                    None
+                }
+            });
+        let blocked_countries = parse_countries(&std::env::var("GEOIP_BLOCKED_COUNTRIES").unwrap_or_default());
+        if reader.is_none() && !blocked_countries.is_empty() {
+            tracing::warn!("GEOIP_BLOCKED_COUNTRIES is set but no GeoIP database is loaded");
+        }
+        Self::new(reader, blocked_countries)
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.reader.is_some()
+    }
+
+    /// Location of an address; `None` when disabled, for private ranges and for unknown addresses
+    pub fn lookup(&self, ip: &str) -> Option<GeoLocation> {
+        let reader = self.reader.as_ref()?;
+        let ip: IpAddr = ip.parse().ok()?;
+        let record: geoip2::City = match reader.lookup(ip) {
+            Ok(record) => record,
+            Err(_) => {
+                metrics::counter!("geoip_lookups_total", 1, "result" => "miss");
+                return None;
+            }
+        };
+        metrics::counter!("geoip_lookups_total", 1, "result" => "hit");
+
+        let english = |names: Option<&std::collections::BTreeMap<&str, &str>>| {
+            names.and_then(|names| names.get("en")).map(|name| name.to_string())
+        };
+        Some(GeoLocation {
+            country: record.country.as_ref().and_then(|c| c.iso_code).map(str::to_string),
+            region: record.subdivisions
+                .as_ref()
+                .and_then(|subdivisions| subdivisions.first())
+                .and_then(|subdivision| english(subdivision.names.as_ref())),
+            city: record.city.as_ref().and_then(|city| english(city.names.as_ref())),
+        })
+    }
+
+    /// Whether requests from this location are refused
+    /// Addresses without a known country are never blocked
+    pub fn is_blocked(&self, location: Option<&GeoLocation>) -> bool {
+        location
+            .and_then(|location| location.country.as_deref())
+            .is_some_and(|country| self.blocked_countries.contains(country))
+    }
+}
+
+fn parse_countries(value: &str) -> HashSet<String> {
+    value
+        .split(',')
+        .map(|code| code.trim().to_ascii_uppercase())
+        .filter(|code| !code.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks_listed_countries_only() {
+        let geoip = GeoIp::new(None, parse_countries(" kp, IR ,"));
+        let located = |country: &str| GeoLocation { country: Some(country.to_string()), ..Default::default() };
+        assert!(geoip.is_blocked(Some(&located("KP"))));
+        assert!(geoip.is_blocked(Some(&located("IR"))));
+        assert!(!geoip.is_blocked(Some(&located("IN"))));
+        assert!(!geoip.is_blocked(Some(&GeoLocation::default())));
+        assert!(!geoip.is_blocked(None));
+        assert_eq!(geoip.lookup("8.8.8.8"), None);
+    }
+}
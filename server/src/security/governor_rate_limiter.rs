@@ -1,45 +1,60 @@
-use governor::{Quota, RateLimiter, state::{InMemoryState, NotKeyed}, clock::DefaultClock};
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use governor::{DefaultKeyedRateLimiter, Quota, RateLimiter};
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+const DEFAULT_REQUESTS_PER_MINUTE: u32 = 50;
 
 /// Governor-based IP rate limiter
-/// Limits requests to 50 per minute per IP address
+/// Limits requests to 50 per minute per IP address unless given another quota
+/// One keyed limiter tracks every IP; `sweep` drops IPs whose quota has fully
+/// refilled, which is equivalent to forgetting them, so memory follows the
+/// number of recently active IPs rather than every IP ever seen
 #[derive(Clone)]
 pub struct GovernorRateLimiter {
-    // Map of IP addresses to their rate limiters
-    limiters: Arc<Mutex<HashMap<String, RateLimiter<NotKeyed, InMemoryState, DefaultClock>>>>,
+    limiter: Arc<DefaultKeyedRateLimiter<String>>,
+    per_minute: u32,
 }
 
 impl GovernorRateLimiter {
     pub fn new() -> Self {
+        Self::with_quota(DEFAULT_REQUESTS_PER_MINUTE)
+    }
+
+    pub fn with_quota(per_minute: u32) -> Self {
+        let per_minute = per_minute.max(1);
+        let quota = Quota::per_minute(NonZeroU32::new(per_minute).unwrap());
         Self {
-            limiters: Arc::new(Mutex::new(HashMap::new())),
+            limiter: Arc::new(RateLimiter::keyed(quota)),
+            per_minute,
         }
     }
 
-    /// Check if an IP is allowed to make a request (50 per minute)
-    pub fn check_ip_rate_limit(&self, ip: &str) -> bool {
-        let mut limiters = self.limiters.lock().unwrap();
-
-        // Get or create rate limiter for this IP (50 requests per 60 seconds)
-        let limiter = limiters
-            .entry(ip.to_string())
-            .or_insert_with(|| RateLimiter::direct(Quota::per_minute(std::num::NonZeroU32::new(50).unwrap())));
+    pub fn per_minute(&self) -> u32 {
+        self.per_minute
+    }
 
-        limiter.check().is_ok()
+    /// Check if an IP is allowed to make a request within the per-minute quota
+    pub fn check_ip_rate_limit(&self, ip: &str) -> bool {
+        self.limiter.check_key(&ip.to_string()).is_ok()
     }
 
-    /// Get remaining quota for an IP (for client feedback)
-    #[allow(dead_code)]
-    pub fn get_remaining_quota(&self, ip: &str) -> u32 {
-        let limiters = self.limiters.lock().unwrap();
+    /// IPs currently tracked, including stale ones not yet swept
+    pub fn tracked(&self) -> usize {
+        self.limiter.len()
+    }
 
-        if let Some(_limiter) = limiters.get(ip) {
-            // Return approximate remaining quota
-            50 // Conservative estimate - actual value requires more complex tracking
-        } else {
-            50
-        }
+    /// Drop IPs whose quota has fully refilled and return how many were stale
+    pub fn sweep(&self) -> usize {
+        let before = self.limiter.len();
+        self.limiter.retain_recent();
+        self.limiter.shrink_to_fit();
+        let tracked = self.limiter.len();
+        let stale = before.saturating_sub(tracked);
+
+        metrics::gauge!("governor_tracked_ips", tracked as f64);
+        metrics::gauge!("governor_stale_ips", stale as f64);
+        metrics::counter!("governor_stale_ips_evicted_total", stale as u64);
+        stale
     }
 }
 
@@ -97,4 +112,31 @@ mod tests {
         // IP2 should still have quota
         assert!(limiter.check_ip_rate_limit(ip2));
     }
+
+    #[test]
+    fn test_custom_quota() {
+        let limiter = GovernorRateLimiter::with_quota(3);
+        for _ in 0..3 {
+            assert!(limiter.check_ip_rate_limit("10.0.0.1"));
+        }
+        assert!(!limiter.check_ip_rate_limit("10.0.0.1"));
+    }
+
+    #[test]
+    fn test_sweep_keeps_ips_with_used_quota() {
+        let limiter = GovernorRateLimiter::with_quota(3);
+        assert!(limiter.check_ip_rate_limit("10.0.0.1"));
+        assert!(limiter.check_ip_rate_limit("10.0.0.2"));
+        assert_eq!(limiter.tracked(), 2);
+
+        // Neither bucket has refilled yet
+        assert_eq!(limiter.sweep(), 0);
+        assert_eq!(limiter.tracked(), 2);
+
+        // Clones share the same buckets
+        let clone = limiter.clone();
+        assert!(clone.check_ip_rate_limit("10.0.0.1"));
+        assert!(clone.check_ip_rate_limit("10.0.0.1"));
+        assert!(!limiter.check_ip_rate_limit("10.0.0.1"));
+    }
 }
@@ -0,0 +1,147 @@
+use axum::http::HeaderMap;
+use crate::security::ip_reputation::RiskLevel;
+
+/// User-agent fragments used by HTTP libraries and automation tools
+const SCRIPTED_USER_AGENTS: &[&str] = &[
+    "python-requests",
+    "python-urllib",
+    "aiohttp",
+    "httpx",
+    "curl/",
+    "wget/",
+    "go-http-client",
+    "okhttp",
+    "java/",
+    "apache-httpclient",
+    "libwww-perl",
+    "node-fetch",
+    "axios/",
+    "scrapy",
+    "headlesschrome",
+    "phantomjs",
+    "puppeteer",
+    "playwright",
+];
+
+/// Score at or above which a request is treated as likely automated
+pub const HIGH_SCORE_THRESHOLD: u8 = 70;
+/// Score at or above which a request is treated as suspicious
+pub const ELEVATED_SCORE_THRESHOLD: u8 = 40;
+
+/// Bot-likelihood score derived from request headers (0 = browser-like, 100 = certainly scripted)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderScore {
+    pub score: u8,
+    /// Names of the signals that contributed to the score
+    pub signals: Vec<&'static str>,
+}
+
+impl HeaderScore {
+    fn add(&mut self, points: u8, signal: &'static str) {
+        self.score = self.score.saturating_add(points).min(100);
+        self.signals.push(signal);
+    }
+
+    /// Minimum risk level implied by this score
+    pub fn risk_level(&self) -> RiskLevel {
+        if self.score >= HIGH_SCORE_THRESHOLD {
+            RiskLevel::Level2
+        } else if self.score >= ELEVATED_SCORE_THRESHOLD {
+            RiskLevel::Level1
+        } else {
+            RiskLevel::Level0
+        }
+    }
+}
+
+/// Score request headers for signs of headless scripts and HTTP libraries
+pub fn score_headers(headers: &HeaderMap) -> HeaderScore {
+    let mut score = HeaderScore::default();
+    let header = |name: &str| headers.get(name).and_then(|h| h.to_str().ok());
+
+    let user_agent = header("user-agent").unwrap_or("").to_ascii_lowercase();
+    if user_agent.is_empty() {
+        score.add(50, "missing_user_agent");
+    } else if SCRIPTED_USER_AGENTS.iter().any(|ua| user_agent.contains(ua)) {
+        score.add(60, "scripted_user_agent");
+    }
+
+    if header("accept-language").is_none() {
+        score.add(20, "missing_accept_language");
+    }
+
+    if header("accept").is_none() {
+        score.add(10, "missing_accept");
+    }
+
+    // Modern Chromium and Firefox always send Sec-Fetch-* metadata headers
+    let claims_modern_browser = user_agent.contains("chrome/") || user_agent.contains("firefox/");
+    let has_fetch_mode = header("sec-fetch-mode").is_some();
+    let has_fetch_site = header("sec-fetch-site").is_some();
+    if claims_modern_browser && !has_fetch_mode && !has_fetch_site {
+        score.add(25, "browser_ua_without_sec_fetch");
+    } else if has_fetch_mode != has_fetch_site {
+        score.add(15, "inconsistent_sec_fetch");
+    }
+
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, HeaderValue::from_static(value));
+        }
+        map
+    }
+
+    #[test]
+    fn test_browser_headers_score_low() {
+        let score = score_headers(&headers(&[
+            ("user-agent", "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 Chrome/120.0 Safari/537.36"),
+            ("accept", "application/json"),
+            ("accept-language", "en-IN,en;q=0.9"),
+            ("sec-fetch-mode", "cors"),
+            ("sec-fetch-site", "same-site"),
+        ]));
+
+        assert_eq!(score.score, 0);
+        assert_eq!(score.risk_level(), RiskLevel::Level0);
+    }
+
+    #[test]
+    fn test_python_requests_scores_high() {
+        let score = score_headers(&headers(&[
+            ("user-agent", "python-requests/2.31.0"),
+            ("accept", "*/*"),
+        ]));
+
+        assert!(score.score >= HIGH_SCORE_THRESHOLD);
+        assert!(score.signals.contains(&"scripted_user_agent"));
+        assert_eq!(score.risk_level(), RiskLevel::Level2);
+    }
+
+    #[test]
+    fn test_spoofed_chrome_without_fetch_metadata() {
+        let score = score_headers(&headers(&[
+            ("user-agent", "Mozilla/5.0 Chrome/120.0"),
+            ("accept", "*/*"),
+            ("accept-language", "en"),
+        ]));
+
+        assert_eq!(score.signals, vec!["browser_ua_without_sec_fetch"]);
+        assert_eq!(score.risk_level(), RiskLevel::Level0);
+    }
+
+    #[test]
+    fn test_empty_headers_capped() {
+        let score = score_headers(&HeaderMap::new());
+        assert_eq!(score.score, 80);
+        assert_eq!(score.risk_level(), RiskLevel::Level2);
+    }
+}
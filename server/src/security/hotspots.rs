@@ -0,0 +1,194 @@
+use serde::Serialize;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::sync::{Arc, Mutex};
+
+/// Counters per sketch row - error is roughly total_requests / SKETCH_WIDTH
+const SKETCH_WIDTH: usize = 2048;
+/// Independent hash rows - more rows lower the chance of an overestimate
+const SKETCH_DEPTH: usize = 4;
+/// Number of heavy-hitter candidates tracked per sketch
+const CANDIDATE_CAPACITY: usize = 64;
+/// Length of a counting window before the sketches rotate
+pub const HOTSPOT_WINDOW_SECONDS: u64 = 60;
+
+/// Count-min sketch with a small heavy-hitter candidate set
+/// Memory is fixed (width * depth counters plus the candidates) no matter how
+/// many distinct actors are seen
+struct HotKeySketch {
+    counters: Vec<u32>,
+    hashers: Vec<RandomState>,
+    candidates: HashMap<String, u32>,
+}
+
+impl HotKeySketch {
+    fn new() -> Self {
+        Self {
+            counters: vec![0; SKETCH_WIDTH * SKETCH_DEPTH],
+            hashers: (0..SKETCH_DEPTH).map(|_| RandomState::new()).collect(),
+            candidates: HashMap::with_capacity(CANDIDATE_CAPACITY),
+        }
+    }
+
+    /// Count one occurrence of `key` and return its estimated total
+    fn record(&mut self, key: &str) -> u32 {
+        let mut estimate = u32::MAX;
+        for (row, hasher) in self.hashers.iter().enumerate() {
+            let column = (hasher.hash_one(key) as usize) % SKETCH_WIDTH;
+            let counter = &mut self.counters[row * SKETCH_WIDTH + column];
+            *counter = counter.saturating_add(1);
+            estimate = estimate.min(*counter);
+        }
+
+        if let Some(count) = self.candidates.get_mut(key) {
+            *count = estimate;
+        } else if self.candidates.len() < CANDIDATE_CAPACITY {
+            self.candidates.insert(key.to_string(), estimate);
+        } else if let Some((coldest, coldest_count)) = self
+            .candidates
+            .iter()
+            .min_by_key(|(_, count)| **count)
+            .map(|(k, c)| (k.clone(), *c))
+        {
+            if estimate > coldest_count {
+                self.candidates.remove(&coldest);
+                self.candidates.insert(key.to_string(), estimate);
+            }
+        }
+
+        estimate
+    }
+
+    /// The `limit` most active keys, most active first
+    fn top(&self, limit: usize) -> Vec<HotKey> {
+        let mut top: Vec<HotKey> = self
+            .candidates
+            .iter()
+            .map(|(key, count)| HotKey {
+                key: key.clone(),
+                estimated_requests: *count,
+            })
+            .collect();
+        top.sort_by(|a, b| b.estimated_requests.cmp(&a.estimated_requests).then_with(|| a.key.cmp(&b.key)));
+        top.truncate(limit);
+        top
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct HotKey {
+    pub key: String,
+    pub estimated_requests: u32,
+}
+
+/// Top actors for one counting window
+#[derive(Debug, Serialize)]
+pub struct HotspotReport {
+    pub composite_keys: Vec<HotKey>,
+    pub ips: Vec<HotKey>,
+}
+
+struct HotspotWindows {
+    current_keys: HotKeySketch,
+    current_ips: HotKeySketch,
+    previous: Option<(Vec<HotKey>, Vec<HotKey>)>,
+}
+
+/// Tracks the most active composite keys and IPs in the request path
+/// Counting is in-memory and per instance; the sketches rotate every
+/// HOTSPOT_WINDOW_SECONDS and the last complete window is kept for reporting
+#[derive(Clone)]
+pub struct HotspotTracker {
+    windows: Arc<Mutex<HotspotWindows>>,
+}
+
+impl HotspotTracker {
+    pub fn new() -> Self {
+        Self {
+            windows: Arc::new(Mutex::new(HotspotWindows {
+                current_keys: HotKeySketch::new(),
+                current_ips: HotKeySketch::new(),
+                previous: None,
+            })),
+        }
+    }
+
+    /// Count a request from an actor
+    pub fn record(&self, composite_key: &str, ip: &str) {
+        let mut windows = self.windows.lock().unwrap();
+        windows.current_keys.record(composite_key);
+        windows.current_ips.record(ip);
+    }
+
+    /// Close the current window and start a fresh one
+    pub fn rotate(&self) {
+        let mut windows = self.windows.lock().unwrap();
+        let finished = (
+            windows.current_keys.top(CANDIDATE_CAPACITY),
+            windows.current_ips.top(CANDIDATE_CAPACITY),
+        );
+        windows.previous = Some(finished);
+        windows.current_keys = HotKeySketch::new();
+        windows.current_ips = HotKeySketch::new();
+    }
+
+    /// Top actors in the in-progress window
+    pub fn current(&self, limit: usize) -> HotspotReport {
+        let windows = self.windows.lock().unwrap();
+        HotspotReport {
+            composite_keys: windows.current_keys.top(limit),
+            ips: windows.current_ips.top(limit),
+        }
+    }
+
+    /// Top actors in the last complete window
+    pub fn previous(&self, limit: usize) -> Option<HotspotReport> {
+        let windows = self.windows.lock().unwrap();
+        windows.previous.as_ref().map(|(keys, ips)| HotspotReport {
+            composite_keys: keys.iter().take(limit).cloned().collect(),
+            ips: ips.iter().take(limit).cloned().collect(),
+        })
+    }
+}
+
+impl Default for HotspotTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heavy_hitter_ranks_first() {
+        let mut sketch = HotKeySketch::new();
+        for i in 0..5_000 {
+            sketch.record(&format!("actor-{}", i));
+        }
+        for _ in 0..500 {
+            sketch.record("abuser");
+        }
+
+        let top = sketch.top(3);
+        assert_eq!(top[0].key, "abuser");
+        // Count-min never underestimates
+        assert!(top[0].estimated_requests >= 500);
+        assert!(top[0].estimated_requests < 600);
+    }
+
+    #[test]
+    fn test_rotate_keeps_previous_window() {
+        let tracker = HotspotTracker::new();
+        tracker.record("key-a", "10.0.0.1");
+        tracker.record("key-a", "10.0.0.1");
+        tracker.rotate();
+
+        assert!(tracker.current(10).composite_keys.is_empty());
+        let previous = tracker.previous(10).unwrap();
+        assert_eq!(previous.composite_keys, vec![HotKey { key: "key-a".to_string(), estimated_requests: 2 }]);
+        assert_eq!(previous.ips[0].key, "10.0.0.1");
+    }
+}
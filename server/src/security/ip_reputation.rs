@@ -1,15 +1,17 @@
 use crate::redis_client::RedisClient;
+use crate::security::enforcement::{EnforcementRecord, ReasonCode};
+use crate::keys;
 use anyhow::{Result, anyhow};
 use serde::{Deserialize, Serialize};
 
 /// Risk levels for IP reputation based on unique fingerprint reports
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum RiskLevel {
     /// 0-1 unique reports: 60s cooldown, full broadcast
     Level0 = 0,
     /// 2 unique reports: 300s (5m) cooldown, full broadcast
     Level1 = 1,
-    /// 3-5 unique reports: 900s (15m) cooldown, shadow-throttle (only to sender's IP)
+    /// 3-5 unique reports: 900s (15m) cooldown, shadow-throttle (only to the poster's own connections)
     Level2 = 2,
     /// 6+ unique reports: 7200s (2h) cooldown, hard shadowban (no broadcast)
     Level3 = 3,
@@ -36,6 +38,16 @@ impl RiskLevel {
         }
     }
 
+    /// Convert a stored numeric level back into a RiskLevel (clamped to Level3)
+    pub fn from_u8(level: u8) -> Self {
+        match level {
+            0 => RiskLevel::Level0,
+            1 => RiskLevel::Level1,
+            2 => RiskLevel::Level2,
+            _ => RiskLevel::Level3,
+        }
+    }
+
     /// Determine risk level from number of unique reports
     pub fn from_report_count(count: usize) -> Self {
         match count {
@@ -52,7 +64,7 @@ impl RiskLevel {
 pub enum VisibilityMode {
     /// Full broadcast to all users
     Normal,
-    /// Broadcast only to the sender's IP (shadow-throttle)
+    /// Delivered only to the poster's own connections (shadow-throttle)
     Throttled,
     /// No broadcast at all (hard shadowban)
     Banned,
@@ -72,32 +84,26 @@ impl IpReputationManager {
     /// Add a report for a fingerprint from a specific IP
     /// Returns the new total count of unique reported fingerprints for that IP
     pub async fn add_report(&self, ip: &str, fingerprint: &str) -> Result<usize> {
-        let key = format!("reports:ip:{}", ip);
+        let key = keys::ip_reports(ip);
         
-        // Add fingerprint to the set (automatically handles duplicates)
-        self.redis
-            .sadd(&key, fingerprint)
-            .await
-            .map_err(|e| anyhow!("Failed to add report: {}", e))?;
-        
-        // Set expiration to 7 days - reports older than this are forgiven
-        self.redis
-            .expire(&key, 604800)
-            .await
-            .map_err(|e| anyhow!("Failed to set expiration on reports: {}", e))?;
-        
-        // Get the count of unique fingerprints reported from this IP
-        let count = self.redis
+        // Add fingerprint to the set (automatically handles duplicates), expire it
+        // after 7 days so old reports are forgiven, and count the unique fingerprints
+        // reported from this IP - all in one round trip
+        let (count,): (i64,) = self.redis
+            .pipeline()
+            .sadd(&key, fingerprint).ignore()
+            .expire(&key, 604800).ignore()
             .scard(&key)
+            .query()
             .await
-            .map_err(|e| anyhow!("Failed to count reports: {}", e))?;
+            .map_err(|e| anyhow!("Failed to add report: {}", e))?;
         
         Ok(count as usize)
     }
 
     /// Get the number of unique reported fingerprints for an IP
     pub async fn get_report_count(&self, ip: &str) -> Result<usize> {
-        let key = format!("reports:ip:{}", ip);
+        let key = keys::ip_reports(ip);
         let count = self.redis
             .scard(&key)
             .await
@@ -107,15 +113,40 @@ impl IpReputationManager {
     }
 
     /// Get the risk level for an IP based on reports
+    /// A manual/automatic escalation overrides the report-based level when higher
     pub async fn get_ip_risk_level(&self, ip: &str) -> Result<RiskLevel> {
         let count = self.get_report_count(ip).await?;
-        Ok(RiskLevel::from_report_count(count))
+        let from_reports = RiskLevel::from_report_count(count);
+
+        let escalated = self.get_escalated_level(ip).await?;
+        Ok(escalated.map_or(from_reports, |level| level.max(from_reports)))
+    }
+
+    /// Raise an IP's risk level for a period, regardless of report count
+    pub async fn escalate_risk(&self, ip: &str, level: RiskLevel, duration_seconds: u64) -> Result<()> {
+        let key = keys::ip_risk(ip);
+        self.redis
+            .set_ex(&key, &(level as u8).to_string(), duration_seconds)
+            .await
+            .map_err(|e| anyhow!("Failed to escalate IP risk level: {}", e))?;
+        Ok(())
+    }
+
+    /// Get the escalated risk level for an IP, if any
+    async fn get_escalated_level(&self, ip: &str) -> Result<Option<RiskLevel>> {
+        let key = keys::ip_risk(ip);
+        let value = self.redis
+            .get(&key)
+            .await
+            .map_err(|e| anyhow!("Failed to get escalated risk level: {}", e))?;
+
+        Ok(value.and_then(|v| v.parse::<u8>().ok()).map(RiskLevel::from_u8))
     }
 
     /// Check if a composite key is in cooldown and return remaining seconds
     /// Returns None if not in cooldown, Some(seconds) if still cooling down
     pub async fn check_cooldown(&self, composite_key: &str) -> Result<Option<u64>> {
-        let key = format!("cooldown:{}", composite_key);
+        let key = keys::cooldown(composite_key);
         
         match self.redis.ttl(&key).await {
             Ok(ttl) if ttl > 0 => Ok(Some(ttl as u64)),
@@ -124,12 +155,27 @@ impl IpReputationManager {
         }
     }
 
+    /// Get the metadata recorded with an active cooldown
+    pub async fn get_cooldown(&self, composite_key: &str) -> Result<Option<EnforcementRecord>> {
+        let key = keys::cooldown(composite_key);
+        self.redis
+            .get(&key)
+            .await
+            .map(|value| value.as_deref().map(EnforcementRecord::parse))
+            .map_err(|e| anyhow!("Failed to get cooldown: {}", e))
+    }
+
     /// Set a cooldown for a composite key
-    pub async fn set_cooldown(&self, composite_key: &str, duration_seconds: u64) -> Result<()> {
-        let key = format!("cooldown:{}", composite_key);
+    pub async fn set_cooldown(
+        &self,
+        composite_key: &str,
+        duration_seconds: u64,
+        record: EnforcementRecord,
+    ) -> Result<()> {
+        let key = keys::cooldown(composite_key);
         
         self.redis
-            .set_ex(&key, "1", duration_seconds)
+            .set_ex(&key, &record.expiring_in(Some(duration_seconds)).to_json(), duration_seconds)
             .await
             .map_err(|e| anyhow!("Failed to set cooldown: {}", e))?;
         
@@ -153,7 +199,12 @@ impl IpReputationManager {
         let risk_level = self.get_ip_risk_level(ip).await?;
         let cooldown_duration = risk_level.cooldown_seconds();
         
-        self.set_cooldown(composite_key, cooldown_duration).await?;
+        let record = EnforcementRecord::system(
+            ReasonCode::RiskCooldown,
+            "ip_reputation::check_and_update_cooldown",
+            &format!("Posting cooldown at IP risk level {}", risk_level as u8),
+        );
+        self.set_cooldown(composite_key, cooldown_duration, record).await?;
         
         Ok(Ok(()))
     }
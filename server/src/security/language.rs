@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+use whatlang::{Lang, Script};
+
+/// Romanized Hindi function words that mark a Latin-script message as Hinglish
+const HINGLISH_MARKERS: &[&str] = &[
+    "hai", "hain", "nahi", "nahin", "chahiye", "kya", "aur", "mein", "ka", "ki", "ke",
+    "ko", "se", "wala", "wali", "bhai", "ji", "hoga", "milega", "karo", "dena",
+];
+/// Fraction of words that must be Hinglish markers
+const HINGLISH_MARKER_RATIO: f64 = 0.2;
+
+/// Language a message is written in, as far as moderation is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    English,
+    Hindi,
+    /// Hindi written in Latin script, usually mixed with English
+    Hinglish,
+    Kannada,
+    Tamil,
+    Other,
+}
+
+impl Language {
+    /// Detect the language of a message
+    /// Script decides the Indic languages; Latin text is English unless it reads as Hinglish
+    pub fn detect(text: &str) -> Self {
+        let Some(info) = whatlang::detect(text) else {
+            return Language::Other;
+        };
+
+        match info.script() {
+            Script::Devanagari => Language::Hindi,
+            Script::Kannada => Language::Kannada,
+            Script::Tamil => Language::Tamil,
+            Script::Latin => {
+                if is_hinglish(text) {
+                    Language::Hinglish
+                } else if info.lang() == Lang::Eng || !info.is_reliable() {
+                    // Short listings rarely give a confident guess; English is the default
+                    Language::English
+                } else {
+                    Language::Other
+                }
+            }
+            _ => Language::Other,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Language::English => "english",
+            Language::Hindi => "hindi",
+            Language::Hinglish => "hinglish",
+            Language::Kannada => "kannada",
+            Language::Tamil => "tamil",
+            Language::Other => "other",
+        }
+    }
+
+    /// ISO 639-1 code, when the language has one
+    pub fn iso_code(&self) -> Option<&'static str> {
+        match self {
+            Language::English => Some("en"),
+            Language::Hindi => Some("hi"),
+            Language::Kannada => Some("kn"),
+            Language::Tamil => Some("ta"),
+            Language::Hinglish | Language::Other => None,
+        }
+    }
+
+    /// Offensive words in this language's native script
+    /// English and romanized Hinglish lists apply to every message and live in moderation.rs
+    pub fn profanity_words(&self) -> &'static [&'static str] {
+        match self {
+            Language::Hindi => &["चूतिया", "मादरचोद", "बहनचोद", "भोसड़ी", "रंडी", "गांडू", "हरामी", "कमीना"],
+            Language::Kannada => &["ಸೂಳೆ", "ಬೋಳಿಮಗ", "ಬೋಳಿಮಗನೆ", "ಹಲ್ಕಾ", "ತಿಕ"],
+            Language::Tamil => &["தேவடியா", "புண்டை", "ஓத்த", "சுன்னி", "கூதி"],
+            Language::English | Language::Hinglish | Language::Other => &[],
+        }
+    }
+
+    /// Rental vocabulary counted towards relevance on top of the English keywords
+    pub fn rental_keywords(&self) -> &'static [&'static str] {
+        match self {
+            Language::Hindi => &["किराया", "किराये", "किराए", "मकान", "कमरा", "कमरे", "फ्लैट", "घर", "किरायेदार", "मालिक", "उपलब्ध"],
+            Language::Hinglish => &["kiraya", "kiraye", "kiraaya", "makaan", "makan", "kamra", "kamre", "ghar", "chahiye", "khali"],
+            Language::Kannada => &["ಮನೆ", "ಬಾಡಿಗೆ", "ಕೊಠಡಿ", "ರೂಮ್", "ಫ್ಲಾಟ್", "ಮುಂಗಡ", "ಲಭ್ಯ"],
+            Language::Tamil => &["வீடு", "வாடகை", "அறை", "ரூம்", "பிளாட்", "முன்பணம்", "கிடைக்கும்"],
+            Language::English | Language::Other => &[],
+        }
+    }
+}
+
+fn is_hinglish(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    let words: Vec<&str> = lower
+        .split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()))
+        .filter(|w| !w.is_empty())
+        .collect();
+    if words.is_empty() {
+        return false;
+    }
+
+    let markers = words.iter().filter(|w| HINGLISH_MARKERS.contains(w)).count();
+    markers >= 2 && markers as f64 / words.len() as f64 >= HINGLISH_MARKER_RATIO
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_scripts_and_hinglish() {
+        assert_eq!(Language::detect("Spacious 2 BHK flat available for rent near the metro station"), Language::English);
+        assert_eq!(Language::detect("Koramangala mein 1 BHK chahiye, budget 20k hai"), Language::Hinglish);
+        assert_eq!(Language::detect("मुझे दिल्ली में किराये पर कमरा चाहिए"), Language::Hindi);
+        assert_eq!(Language::detect("ಜಯನಗರದಲ್ಲಿ ಮನೆ ಬಾಡಿಗೆಗೆ ಲಭ್ಯವಿದೆ"), Language::Kannada);
+        assert_eq!(Language::detect("சென்னையில் வீடு வாடகைக்கு கிடைக்கும்"), Language::Tamil);
+    }
+}
@@ -2,11 +2,22 @@ use axum::{
     extract::{Request, State, ConnectInfo},
     middleware::Next,
     response::{IntoResponse, Response},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
 };
 
 use crate::state::AppState;
-use crate::security::rate_limiter::RateLimitType;
+use crate::security::rate_limiter::{RateLimitResult, RateLimitType};
+use crate::load_shedding::SheddableWork;
+use crate::security::audit::{AuditEvent, AuditEventKind};
+use crate::recorder::Decisions;
+use crate::security::escalation::{Enforced, Signals, Trigger};
+use crate::security::ip_reputation::RiskLevel;
+use crate::security::header_heuristics::{self, HeaderScore};
+use crate::security::session::AccessClaims;
+use crate::security::trusted_proxy::TrustedProxies;
+use crate::security::fingerprint::{self, UNKNOWN_FINGERPRINT};
+use crate::security::geoip::GeoLocation;
+use serde_json::json;
 use std::net::SocketAddr;
 
 /// Security context extracted from request
@@ -15,6 +26,12 @@ pub struct SecurityContext {
     pub composite_key: String,
     pub ip_address: String,
     pub fingerprint: String,
+    /// Bot-likelihood score from request header heuristics
+    pub header_score: HeaderScore,
+    /// Anonymous session presented via X-Session-Token, if any
+    pub session: Option<AccessClaims>,
+    /// Where the client IP is, when GeoIP is enabled and knows the address
+    pub geo: Option<GeoLocation>,
 }
 
 /// Extension trait to get security context from request
@@ -25,7 +42,7 @@ pub trait SecurityContextExt {
 
 /// Middleware that extracts IP and fingerprint to create composite key
 /// and checks for IP blocks
-/// Handles X-Forwarded-For and Cf-Connecting-Ip headers for load balancers
+/// Handles X-Forwarded-For and Cf-Connecting-Ip headers from trusted load balancers
 pub async fn security_middleware(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State(state): State<AppState>,
@@ -33,7 +50,18 @@ pub async fn security_middleware(
     next: Next,
 ) -> Response {
     // Extract real IP from load balancer headers
-    let ip_str = extract_real_ip(&req, &addr);
+    let ip_str = extract_real_ip(&req, &addr, &state.trusted_proxies);
+
+    // Turn away countries listed in GEOIP_BLOCKED_COUNTRIES (health probes excepted)
+    let geo = state.geoip.lookup(&ip_str);
+    if state.geoip.is_blocked(geo.as_ref()) && !req.uri().path().starts_with("/health") {
+        let country = geo.as_ref().and_then(|geo| geo.country.clone()).unwrap_or_default();
+        metrics::counter!("geoip_blocked_requests_total", 1, "country" => country);
+        return (
+            StatusCode::FORBIDDEN,
+            "Krib is not available in your region",
+        ).into_response();
+    }
 
     // Check if IP is globally blocked
     match state.rate_limiter.is_ip_blocked(&ip_str).await {
@@ -44,28 +72,75 @@ pub async fn security_middleware(
             ).into_response();
         }
         Err(e) => {
-            eprintln!("Error checking IP block: {}", e);
+            tracing::error!("Error checking IP block: {}", e);
             // Continue anyway - don't let Redis errors block legitimate traffic
         }
         _ => {}
     }
 
     // Extract fingerprint from header (sent by frontend using ThumbmarkJS)
-    let fingerprint = req
+    // Implausible values collapse to the shared unknown fingerprint so a client
+    // can't mint unlimited composite keys by varying the header
+    let fingerprint = match req
         .headers()
         .get("X-Browser-Fingerprint")
         .and_then(|h| h.to_str().ok())
-        .unwrap_or("unknown")
-        .to_string();
+    {
+        Some(value) => match fingerprint::validate(value) {
+            Ok(()) => value.to_string(),
+            Err(reason) => {
+                metrics::counter!("invalid_fingerprints_total", 1, "reason" => reason.as_str());
+                UNKNOWN_FINGERPRINT.to_string()
+            }
+        },
+        None => UNKNOWN_FINGERPRINT.to_string(),
+    };
 
     // Generate composite key
     let composite_key = state.key_generator.generate(&ip_str, &fingerprint);
+    tracing::Span::current().record("composite_key", composite_key.as_str());
+
+    // Count the request towards the hot-actor sketches
+    state.hotspots.record(&composite_key, &ip_str);
+
+    // Score headers for signs of scripted clients
+    let header_score = header_heuristics::score_headers(req.headers());
+    if header_score.score >= header_heuristics::HIGH_SCORE_THRESHOLD {
+        metrics::counter!("suspicious_header_requests_total", 1);
+    }
+
+    // Validate the anonymous session token if one was presented
+    // An expired or revoked token is rejected outright so the client refreshes
+    let session_token = req
+        .headers()
+        .get("X-Session-Token")
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_string);
+    let session = match session_token {
+        Some(token) => match state.sessions.authenticate(&token).await {
+            Ok(Some(claims)) => Some(claims),
+            Ok(None) => {
+                return (
+                    StatusCode::UNAUTHORIZED,
+                    "Session expired or revoked",
+                ).into_response();
+            }
+            Err(e) => {
+                tracing::error!("Error checking session: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
 
     // Create security context
     let security_ctx = SecurityContext {
         composite_key,
         ip_address: ip_str,
         fingerprint,
+        header_score,
+        session,
+        geo,
     };
 
     // Insert security context into request extensions
@@ -76,34 +151,18 @@ pub async fn security_middleware(
 
 /// Extract real IP address from load balancer headers
 /// Priority: Cf-Connecting-Ip > X-Forwarded-For > Direct connection
-fn extract_real_ip(req: &Request, addr: &SocketAddr) -> String {
-    // Check Cloudflare header first
-    if let Some(cf_ip) = req.headers()
-        .get("Cf-Connecting-Ip")
-        .and_then(|h| h.to_str().ok())
-    {
-        return cf_ip.to_string();
-    }
-
-    // Check X-Forwarded-For header (standard for proxies/load balancers)
-    // This header contains the original client IP when behind a proxy/load balancer
-    if let Some(forwarded) = req.headers()
-        .get("X-Forwarded-For")
-        .and_then(|h| h.to_str().ok())
-    {
-        // X-Forwarded-For can be comma-separated, take the first (original client)
-        if let Some(first_ip) = forwarded.split(',').next() {
-            return first_ip.trim().to_string();
-        }
-    }
+/// Headers are only honored when the direct peer is a trusted proxy
+fn extract_real_ip(req: &Request, addr: &SocketAddr, proxies: &TrustedProxies) -> String {
+    let header = |name: &str| req.headers().get(name).and_then(|h| h.to_str().ok());
 
-    // Fallback to direct connection IP
-    addr.ip().to_string()
+    proxies
+        .client_ip(addr.ip(), header("Cf-Connecting-Ip"), header("X-Forwarded-For"))
+        .to_string()
 }
 
 /// Middleware for burst protection (20 requests in 2 seconds)
 /// Also includes burst profiler to detect bot-like behavior (5 endpoints in 500ms)
-/// Enforces IP rate limiting with governor (50 requests per minute per IP)
+/// Enforces IP rate limiting with governor (IP_RATE_LIMIT_PER_MINUTE per IP, default 50)
 pub async fn burst_protection_middleware(
     State(state): State<AppState>,
     req: Request,
@@ -111,85 +170,163 @@ pub async fn burst_protection_middleware(
 ) -> Response {
     // Get security context from request extensions
     let security_ctx = req.extensions().get::<SecurityContext>();
+    let decisions = req.extensions().get::<Decisions>();
     let uri_path = req.uri().path().to_string();
     let method = req.method().clone();
 
-    // Skip rate limiting for read-only stats endpoints
+    // Skip rate limiting for read-only stats endpoints and GET requests (harmless reads)
     let is_stats_endpoint = uri_path.starts_with("/api/stats/") 
         || uri_path == "/health"
         || uri_path == "/api/cooldown";
     let is_get_request = method == axum::http::Method::GET;
 
     if let Some(ctx) = security_ctx {
-        // Check governor-based IP rate limiting (50 requests per minute)
-        // Skip for stats endpoints and GET requests (read-only, harmless)
-        if !is_stats_endpoint && !is_get_request && !state.governor_limiter.check_ip_rate_limit(&ctx.ip_address) {
-            eprintln!("🚫 IP rate limit exceeded for: {}", ctx.ip_address);
-            return (
-                StatusCode::TOO_MANY_REQUESTS,
-                "Rate limit exceeded: 50 requests per minute per IP",
-            ).into_response();
+        if !is_stats_endpoint && !is_get_request {
+            if let Err(rejection) = check_write_burst(&state, ctx, &uri_path, decisions).await {
+                return rejection.into_response();
+            }
         }
+    }
 
-        // Check burst profiler for bot detection - skip for GET requests (harmless reads)
-        if !is_get_request {
-            match state.burst_profiler.check_burst(&ctx.composite_key, &uri_path).await {
-                Ok(true) => {
-                    // Bot detected - shadowban immediately
-                    eprintln!("🤖 Bot detected via burst profiler: {}", ctx.composite_key);
-                    
-                    if let Err(e) = state.shadowban_manager.shadowban(
-                        &ctx.composite_key,
-                        Some("Bot detected - burst pattern"),
-                        Some(86400), // 24 hour ban
-                    ).await {
-                        eprintln!("Failed to shadowban bot: {}", e);
-                    }
-
-                    // Also block the IP
-                    if let Err(e) = state.rate_limiter.block_ip(&ctx.ip_address, 1800).await {
-                        eprintln!("Failed to block IP: {}", e);
-                    }
-
-                    return (
-                        StatusCode::TOO_MANY_REQUESTS,
-                        "Suspicious activity detected",
-                    ).into_response();
-                }
-                Err(e) => {
-                    eprintln!("Error checking burst profiler: {}", e);
-                }
-                _ => {}
+    next.run(req).await
+}
+
+/// Why burst protection turned a write away
+#[derive(Debug)]
+pub enum BurstRejection {
+    /// Over the per-IP governor quota
+    IpRateLimited { per_minute: u32 },
+    /// The burst profiler saw a bot-like pattern
+    BurstPattern,
+    /// Over the burst protection rate limit; escalation may have blocked the IP
+    BurstLimited { result: RateLimitResult, ip_blocked: bool },
+}
+
+impl BurstRejection {
+    pub fn message(&self) -> String {
+        match self {
+            BurstRejection::IpRateLimited { per_minute } => {
+                format!("Rate limit exceeded: {} requests per minute per IP", per_minute)
             }
+            BurstRejection::BurstPattern => "Suspicious activity detected".to_string(),
+            BurstRejection::BurstLimited { ip_blocked: true, .. } => "Too many requests - IP blocked".to_string(),
+            BurstRejection::BurstLimited { ip_blocked: false, .. } => "Too many requests".to_string(),
         }
+    }
+}
 
-        // Check burst protection rate limit (20 requests in 2 seconds)
-        // Skip for stats endpoints and GET requests (read-only, harmless)
-        if !is_stats_endpoint && !is_get_request {
-            match state.rate_limiter
-                .check_rate_limit(&ctx.composite_key, RateLimitType::BurstProtection)
-                .await
-            {
-                Ok(result) => {
-                    if !result.allowed {
-                        // Block IP for 30 minutes
-                        if let Err(e) = state.rate_limiter.block_ip(&ctx.ip_address, 1800).await {
-                            eprintln!("Failed to block IP: {}", e);
-                        }
-
-                        return (
-                            StatusCode::TOO_MANY_REQUESTS,
-                            "Too many requests - IP blocked for 30 minutes",
-                        ).into_response();
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Error checking burst protection: {}", e);
-                    // Continue anyway
+impl IntoResponse for BurstRejection {
+    fn into_response(self) -> Response {
+        let headers = match &self {
+            BurstRejection::BurstLimited { result, .. } => result.headers(),
+            _ => HeaderMap::new(),
+        };
+        (StatusCode::TOO_MANY_REQUESTS, headers, self.message()).into_response()
+    }
+}
+
+/// The burst checks every write goes through, whichever way it arrives:
+/// the per-IP governor, the burst profiler and the burst protection rate limit
+/// `path` is what the burst profiler counts as the endpoint hit
+pub async fn check_write_burst(
+    state: &AppState,
+    ctx: &SecurityContext,
+    path: &str,
+    decisions: Option<&Decisions>,
+) -> Result<(), BurstRejection> {
+    // Check governor-based IP rate limiting
+    let governor = &state.config.current().governor;
+    if !governor.check_ip_rate_limit(&ctx.ip_address) {
+        tracing::warn!(ip = %ctx.ip_address, "IP rate limit exceeded");
+        note(decisions, "ip_rate_limited");
+        return Err(BurstRejection::IpRateLimited { per_minute: governor.per_minute() });
+    }
+
+    // Check burst profiler for bot detection, except while Redis is critically
+    // slow, so posting keeps its Redis budget
+    if !state.load_shedder.should_shed(SheddableWork::BurstProfiling) {
+        match state.burst_profiler.check_burst(&ctx.composite_key, path).await {
+            Ok(true) => {
+                // Bot detected - enforcement comes from the escalation policy
+                tracing::warn!(composite_key = %ctx.composite_key, "bot detected via burst profiler");
+                note(decisions, "burst_pattern");
+                let enforced = escalate(state, ctx, Trigger::BurstPattern, "Bot detected - burst pattern").await;
+                note_escalation(decisions, &enforced);
+
+                let event = AuditEvent::new(
+                    AuditEventKind::BurstDetected,
+                    "system",
+                    &ctx.composite_key,
+                    "Bot detected - burst pattern",
+                )
+                .with_details(json!({
+                    "ip": ctx.ip_address,
+                    "path": path,
+                }));
+                if let Err(e) = state.audit_log.record(event).await {
+                    tracing::error!("{}", e);
                 }
+
+                return Err(BurstRejection::BurstPattern);
             }
+            Err(e) => {
+                tracing::error!("Error checking burst profiler: {}", e);
+            }
+            _ => {}
         }
     }
 
-    next.run(req).await
+    // Check burst protection rate limit (20 requests in 2 seconds)
+    match state.rate_limiter
+        .check_rate_limit(&ctx.composite_key, RateLimitType::BurstProtection)
+        .await
+    {
+        Ok(result) => {
+            if !result.allowed {
+                note(decisions, "burst_limited");
+                let enforced = escalate(state, ctx, Trigger::BurstLimit, "Burst protection limit exceeded").await;
+                note_escalation(decisions, &enforced);
+                return Err(BurstRejection::BurstLimited { result, ip_blocked: enforced.ip_blocked });
+            }
+        }
+        Err(e) => {
+            tracing::error!("Error checking burst protection: {}", e);
+            // Continue anyway
+        }
+    }
+    Ok(())
+}
+
+fn note(decisions: Option<&Decisions>, step: &str) {
+    if let Some(decisions) = decisions {
+        decisions.push(step);
+    }
+}
+
+fn note_escalation(decisions: Option<&Decisions>, enforced: &Enforced) {
+    if let (Some(decisions), Some(rule)) = (decisions, &enforced.rule) {
+        decisions.push(format!("escalation:{}", rule));
+    }
+}
+
+/// Apply the escalation policy to a request that tripped burst protection
+async fn escalate(state: &AppState, ctx: &SecurityContext, trigger: Trigger, reason: &str) -> Enforced {
+    let violations = state.shadowban_manager
+        .get_violations(&ctx.composite_key)
+        .await
+        .unwrap_or(0);
+    let risk_level = state.ip_reputation
+        .get_ip_risk_level(&ctx.ip_address)
+        .await
+        .unwrap_or(RiskLevel::Level0)
+        .max(ctx.header_score.risk_level());
+    let signals = Signals {
+        trigger,
+        risk_level,
+        header_score: ctx.header_score.score,
+        violations,
+    };
+    state.config.current().escalation
+        .enforce(state, &signals, &ctx.composite_key, &ctx.ip_address, "middleware::burst_protection", reason)
+        .await
 }
@@ -7,6 +7,28 @@ pub mod ip_reputation;
 pub mod burst_profiler;
 pub mod governor_rate_limiter;
 pub mod moderation;
+pub mod audit;
+pub mod header_heuristics;
+pub mod signing;
+pub mod session;
+pub mod hotspots;
+pub mod reveal_graph;
+pub mod city_policy;
+pub mod language;
+pub mod context_window;
+pub mod review_queue;
+pub mod report_guard;
+pub mod trusted_proxy;
+pub mod fingerprint;
+pub mod enforcement;
+pub mod trust_tier;
+pub mod correction;
+pub mod city_surge;
+pub mod visibility;
+pub mod escalation;
+pub mod wordlists;
+pub mod captcha;
+pub mod geoip;
 
 pub use composite_key::CompositeKeyGenerator;
 pub use rate_limiter::RateLimiter;
@@ -16,3 +38,18 @@ pub use ip_reputation::IpReputationManager;
 pub use burst_profiler::BurstProfiler;
 pub use governor_rate_limiter::GovernorRateLimiter;
 pub use moderation::ModerationService;
+pub use audit::AuditLog;
+pub use signing::TokenSigner;
+pub use session::SessionManager;
+pub use hotspots::HotspotTracker;
+pub use reveal_graph::RevealGraph;
+pub use city_policy::CityPolicyStore;
+pub use context_window::ContextWindow;
+pub use review_queue::ReviewQueue;
+pub use report_guard::ReportGuard;
+pub use trusted_proxy::TrustedProxies;
+pub use fingerprint::FingerprintRegistry;
+pub use correction::CorrectionTokens;
+pub use city_surge::CitySurgeDetector;
+pub use captcha::CaptchaVerifier;
+pub use geoip::GeoIp;
@@ -1,18 +1,39 @@
 use regex::Regex;
 use once_cell::sync::Lazy;
-use serde::Deserialize;
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+use crate::security::city_policy::{CityModerationPolicy, CityPolicyStore, Strictness};
+use crate::security::language::Language;
+use crate::security::wordlists::Wordlists;
+use crate::slo::SloTracker;
+use crate::dependencies::{self, DependencyTracker};
+use crate::deadline::Deadline;
+use crate::keys;
+use crate::moderation_dataset::normalize;
+use crate::redis_client::RedisClient;
+use std::time::Duration;
+
+/// Longest a single moderation provider call may take, deadline or not
+const PROVIDER_TIMEOUT: Duration = Duration::from_secs(10);
+/// Below this much time left, the provider call isn't started
+const MIN_PROVIDER_BUDGET: Duration = Duration::from_millis(250);
+/// How long a provider verdict is reused for reposts of the same text
+const VERDICT_CACHE_TTL: u64 = 86400; // 24 hours
 
 /// Moderation result from various checks
 #[derive(Debug, Clone)]
 pub struct ModerationResult {
     pub is_allowed: bool,
     pub reason: Option<String>,
-    #[allow(dead_code)]
     pub violation_type: Option<ModerationViolationType>,
+    /// Accepted content with offending tokens replaced by asterisks (soft violation)
+    pub masked_content: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ModerationViolationType {
     Profanity,
     OffTopic,
@@ -23,12 +44,37 @@ pub enum ModerationViolationType {
     OpenAiViolation,
 }
 
+impl ModerationViolationType {
+    /// Stable rule name for logs and datasets
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModerationViolationType::Profanity => "profanity",
+            ModerationViolationType::OffTopic => "off_topic",
+            ModerationViolationType::Spam => "spam",
+            ModerationViolationType::HateContent => "hate",
+            ModerationViolationType::HarassmentContent => "harassment",
+            ModerationViolationType::SexualContent => "sexual",
+            ModerationViolationType::OpenAiViolation => "openai",
+        }
+    }
+}
+
 impl ModerationResult {
     pub fn allowed() -> Self {
         Self {
             is_allowed: true,
             reason: None,
             violation_type: None,
+            masked_content: None,
+        }
+    }
+
+    pub fn masked(content: String) -> Self {
+        Self {
+            is_allowed: true,
+            reason: Some("Offensive language masked".to_string()),
+            violation_type: Some(ModerationViolationType::Profanity),
+            masked_content: Some(content),
         }
     }
 
@@ -37,10 +83,136 @@ impl ModerationResult {
             is_allowed: false,
             reason: Some(reason),
             violation_type: Some(violation_type),
+            masked_content: None,
         }
     }
 }
 
+/// How bad a profane token is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfanitySeverity {
+    /// Mild swearing ("damn", "crap")
+    Mild,
+    /// Slurs, sexual and strong abusive language
+    Severe,
+}
+
+/// What to do with a message containing profanity of a given severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfanityAction {
+    /// Reject the whole message
+    Block,
+    /// Replace the offending tokens with asterisks and accept the message
+    Mask,
+}
+
+impl ProfanityAction {
+    fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "block" => Some(ProfanityAction::Block),
+            "mask" => Some(ProfanityAction::Mask),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProfanityAction::Block => "block",
+            ProfanityAction::Mask => "mask",
+        }
+    }
+}
+
+/// Per-severity profanity handling
+#[derive(Debug, Clone, Copy)]
+pub struct MaskingConfig {
+    pub mild: ProfanityAction,
+    pub severe: ProfanityAction,
+}
+
+impl Default for MaskingConfig {
+    fn default() -> Self {
+        Self {
+            mild: ProfanityAction::Block,
+            severe: ProfanityAction::Block,
+        }
+    }
+}
+
+impl MaskingConfig {
+    /// Read MODERATION_MILD_PROFANITY_ACTION / MODERATION_SEVERE_PROFANITY_ACTION ("block" or "mask")
+    pub fn from_env() -> Self {
+        let action = |var: &str| std::env::var(var).ok().and_then(|v| ProfanityAction::parse(&v));
+        let defaults = Self::default();
+        Self {
+            mild: action("MODERATION_MILD_PROFANITY_ACTION").unwrap_or(defaults.mild),
+            severe: action("MODERATION_SEVERE_PROFANITY_ACTION").unwrap_or(defaults.severe),
+        }
+    }
+
+    fn action(&self, severity: ProfanitySeverity) -> ProfanityAction {
+        match severity {
+            ProfanitySeverity::Mild => self.mild,
+            ProfanitySeverity::Severe => self.severe,
+        }
+    }
+}
+
+/// When the moderation provider is consulted for a new post
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderMode {
+    /// Before the post is accepted, on the request path
+    Inline,
+    /// After the post is published, by the background moderation worker,
+    /// which retracts it if the provider blocks it
+    Deferred,
+}
+
+impl ProviderMode {
+    /// Read MODERATION_PROVIDER_MODE ("inline" or "deferred", the default)
+    pub fn from_env() -> Self {
+        match std::env::var("MODERATION_PROVIDER_MODE").ok().as_deref().map(str::trim) {
+            Some("inline") => ProviderMode::Inline,
+            _ => ProviderMode::Deferred,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProviderMode::Inline => "inline",
+            ProviderMode::Deferred => "deferred",
+        }
+    }
+}
+
+/// A provider verdict as cached by normalized text
+/// Only answers are cached; failed or skipped calls are retried next time
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "verdict", rename_all = "snake_case")]
+pub enum ProviderVerdict {
+    Allowed,
+    Blocked { reason: String, violation_type: ModerationViolationType },
+}
+
+impl ProviderVerdict {
+    pub fn into_result(self) -> Option<ModerationResult> {
+        match self {
+            ProviderVerdict::Allowed => None,
+            ProviderVerdict::Blocked { reason, violation_type } => {
+                Some(ModerationResult::blocked(reason, violation_type))
+            }
+        }
+    }
+}
+
+/// Cache key for a text's verdict: trivial case, punctuation or spacing changes
+/// in a repost hash the same
+fn verdict_cache_key(content: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(normalize(content).as_bytes());
+    keys::moderation_verdict(&hex::encode(hasher.finalize()))
+}
+
 /// OpenAI Moderation API Response
 #[derive(Debug, Deserialize)]
 pub struct OpenAiModerationResponse {
@@ -110,74 +282,28 @@ static LEET_SPEAK_MAP: &[(&str, &str)] = &[
     ("9", "g"),
 ];
 
-// Extended profanity list with semantic variations and common typos
-static PROFANITY_WORDS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
-    vec![
-        // Original offensive words
-        "damn", "hell", "crap", "ass", "bitch", "bastard", "piss", "fuck", "shit",
-        "asshole", "dick", "cock", "pussy", "whore", "slut", "cunt",
-        // Semantic variations and euphemisms
-        "fk", "f*k", "f***", "fu*k", "fck", "fcuk",
-        "sh*t", "s*it", "sh1t", "shyt", "sheit",
-        "b*tch", "bit*h", "b!tch", "biatch", "btch",
-        "a**", "a$s", "azz", "arse",
-        "h*ll", "hel", "h3ll",
-        "d@mn", "damn", "dammit", "damnit",
-        "c*ck", "c0ck", "c**k", "cawk",
-        "pu$$y", "p*ssy", "puss1", "kitty", // some are context-dependent
-        "wh0re", "wh*re", "hoar",
-        "sl*t", "slyt", "sloot",
-        "c*nt", "cunt", "cnt", // might catch false positives
-        // Indian Hinglish variations with typos
-        "bc", "b.c", "b c", "bhd",
-        "mf", "m.f", "m f", "mofo",
-        // Extended Hinglish (case-insensitive handled by regex)
-        "lodu", "lod", "loda", "lodu",
-        "chutiya", "chut", "chutya", "chutiye",
-        "gaandu", "gandu", "gaand",
-        "harami", "haram", "haramkhor",
-        "madarchod", "madarc", "maadarc",
-        "behenchod", "bewakoof", "bevkoof",
-        "randi", "rand", "randiya",
-        "ullu", "ull",
-        "saali", "sali",
-        "teri", "tere",
-    ]
-    .into_iter()
-    .collect()
-});
+// Whitespace-delimited tokens, for masking
+static TOKEN_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\S+").unwrap());
 
 // Compile regexes at startup
 static URL_REGEX: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"https?://[^\s]+|www\.[^\s]+").unwrap());
 
-// Known scam domains
-static SCAM_DOMAINS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
-    vec![
-        "t.me",
-        "telegram.me",
-        "telegram.org",
-        "bit.ly",
-        "tinyurl.com",
-        "goo.gl",
-        "rebrand.ly",
-        "ow.ly",
-        "lnk.co",
-        "short.link",
-        "bitly.com",
-        "adf.ly",
-        "j.mp",
-        "clickbank.net",
-    ]
-    .into_iter()
-    .collect()
-});
-
 /// Content moderation service with profanity filter, context check, and OpenAI integration
 #[derive(Clone)]
 pub struct ModerationService {
     openai_api_key: Option<String>,
     http_client: Option<reqwest::Client>,
+    city_policies: CityPolicyStore,
+    masking: MaskingConfig,
+    wordlists: Arc<Wordlists>,
+    /// Records provider call outcomes for the moderation SLO
+    slo: Option<SloTracker>,
+    /// Records provider call outcomes for /health
+    dependencies: Option<DependencyTracker>,
+    /// Provider verdicts by normalized text, so reposts don't pay for another call
+    verdict_cache: Option<RedisClient>,
+    provider_mode: ProviderMode,
 }
 
 impl ModerationService {
@@ -187,43 +313,264 @@ impl ModerationService {
         Self {
             openai_api_key,
             http_client,
+            city_policies: CityPolicyStore::default(),
+            masking: MaskingConfig::default(),
+            wordlists: Wordlists::built_in(),
+            slo: None,
+            dependencies: None,
+            verdict_cache: None,
+            provider_mode: ProviderMode::Inline,
         }
     }
 
+    /// Mask rather than block profanity for the configured severity tiers
+    pub fn with_masking(mut self, masking: MaskingConfig) -> Self {
+        self.masking = masking;
+        self
+    }
+
+    pub fn masking(&self) -> MaskingConfig {
+        self.masking
+    }
+
+    /// Match against these wordlists instead of the built-in ones
+    pub fn with_wordlists(mut self, wordlists: Arc<Wordlists>) -> Self {
+        self.wordlists = wordlists;
+        self
+    }
+
+    pub fn wordlists(&self) -> &Arc<Wordlists> {
+        &self.wordlists
+    }
+
+    /// This service with masking and wordlists re-read from the environment
+    /// and `MODERATION_WORDLISTS_FILE`; the result replaces it in the runtime config
+    pub fn reload(&self) -> Self {
+        self.clone()
+            .with_masking(MaskingConfig::from_env())
+            .with_wordlists(Wordlists::from_env())
+            .with_provider_mode(ProviderMode::from_env())
+    }
+
+    /// Use per-city policy overrides when moderating
+    pub fn with_city_policies(mut self, city_policies: CityPolicyStore) -> Self {
+        self.city_policies = city_policies;
+        self
+    }
+
+    pub fn with_slo(mut self, slo: SloTracker) -> Self {
+        self.slo = Some(slo);
+        self
+    }
+
+    pub fn with_dependencies(mut self, dependencies: DependencyTracker) -> Self {
+        self.dependencies = Some(dependencies);
+        self
+    }
+
+    pub fn with_provider_mode(mut self, provider_mode: ProviderMode) -> Self {
+        self.provider_mode = provider_mode;
+        self
+    }
+
+    pub fn provider_mode(&self) -> ProviderMode {
+        self.provider_mode
+    }
+
+    /// Whether `moderate_message` leaves the provider call to the background worker
+    pub fn defers_provider(&self) -> bool {
+        self.provider_configured() && self.provider_mode == ProviderMode::Deferred
+    }
+
+    /// Cache provider verdicts in Redis, keyed by the normalized text
+    pub fn with_verdict_cache(mut self, redis: RedisClient) -> Self {
+        self.verdict_cache = Some(redis);
+        self
+    }
+
+    /// Whether an external moderation provider is configured
+    pub fn provider_configured(&self) -> bool {
+        self.openai_api_key.is_some()
+    }
+
+    pub fn city_policies(&self) -> &CityPolicyStore {
+        &self.city_policies
+    }
+
     /// Run all moderation checks asynchronously
-    /// Returns ModerationResult with the first violation found
-    pub async fn moderate_message(&self, content: &str) -> ModerationResult {
-        // 1. Check for profanity/vulgar language
-        let profanity_result = self.check_profanity(content).await;
+    /// The policy for the message's location adjusts strictness, wordlists and thresholds,
+    /// and the detected language adds that language's native-script wordlists
+    /// Returns ModerationResult with the first violation found, or the masked content
+    /// when profanity was masked rather than blocked
+    /// The provider call is bounded by the request's `deadline`, and skipped when
+    /// too little of it is left to be worth starting; in deferred provider mode it
+    /// isn't made here at all (see `provider_verdict`)
+    pub async fn moderate_message(
+        &self,
+        content: &str,
+        location: Option<&str>,
+        language: Language,
+        deadline: Option<Deadline>,
+    ) -> ModerationResult {
+        let policy = self.city_policies.resolve(location).await;
+
+        // 1. Check for profanity/vulgar language, masking it where configured
+        let mut masked = None;
+        let profanity_result = self.check_all_profanity(content, &policy, language).await;
         if !profanity_result.is_allowed {
-            return profanity_result;
+            match self.mask_profanity(content, &policy, language).await {
+                Some(masked_content) => masked = Some(masked_content),
+                None => return profanity_result,
+            }
         }
+        let content = masked.as_deref().unwrap_or(content);
 
         // 2. Check for relevance to rentals (context check)
-        let relevance_result = self.check_rental_relevance(content);
+        let relevance_result = self.check_rental_relevance(content, &policy, language);
         if !relevance_result.is_allowed {
             return relevance_result;
         }
 
         // 3. Check for spam (URLs and patterns)
-        let spam_result = self.check_spam(content);
+        let spam_result = self.check_spam(content, &policy);
+        if !spam_result.is_allowed {
+            return spam_result;
+        }
+
+        // 4. OpenAI Moderation API check (if configured and not deferred)
+        if !self.defers_provider() {
+            if let Some(result) = self.check_openai_moderation(content, deadline).await {
+                if !result.is_allowed {
+                    return result;
+                }
+            }
+        }
+
+        match masked {
+            Some(masked_content) => ModerationResult::masked(masked_content),
+            None => ModerationResult::allowed(),
+        }
+    }
+
+    /// Moderate a short free-text field published alongside a message (a listing's
+    /// locality): the profanity, spam and provider checks of `moderate_message`,
+    /// without the rental relevance check a place name can't pass, and without
+    /// masking, since a field is small enough to just retype
+    pub async fn moderate_field(
+        &self,
+        content: &str,
+        location: Option<&str>,
+        language: Language,
+        deadline: Option<Deadline>,
+    ) -> ModerationResult {
+        let policy = self.city_policies.resolve(location).await;
+
+        let profanity_result = self.check_all_profanity(content, &policy, language).await;
+        if !profanity_result.is_allowed {
+            return profanity_result;
+        }
+
+        let spam_result = self.check_spam(content, &policy);
         if !spam_result.is_allowed {
             return spam_result;
         }
 
-        // 4. OpenAI Moderation API check (if configured)
-        if let Some(result) = self.check_openai_moderation(content).await {
-            if !result.is_allowed {
-                return result;
+        if !self.defers_provider() {
+            if let Some(result) = self.check_openai_moderation(content, deadline).await {
+                if !result.is_allowed {
+                    return result;
+                }
             }
         }
 
         ModerationResult::allowed()
     }
 
+    /// Global, city and language profanity checks together
+    async fn check_all_profanity(&self, content: &str, policy: &CityModerationPolicy, language: Language) -> ModerationResult {
+        let result = self.check_profanity(content, policy).await;
+        if !result.is_allowed {
+            return result;
+        }
+        self.check_language_profanity(content, language)
+    }
+
+    /// Rough 0.0-1.0 toxicity of an accepted message, for aggregate checks over
+    /// a poster's recent posts
+    pub fn toxicity_score(&self, content: &str, language: Language) -> f64 {
+        let mut score: f64 = content
+            .split_whitespace()
+            .filter_map(|token| self.token_severity(token, language))
+            .map(|severity| match severity {
+                ProfanitySeverity::Mild => 0.3,
+                ProfanitySeverity::Severe => 1.0,
+            })
+            .sum();
+
+        // Shouting
+        let letters: Vec<char> = content.chars().filter(|c| c.is_alphabetic()).collect();
+        if letters.len() >= 10 {
+            let upper = letters.iter().filter(|c| c.is_uppercase()).count();
+            if upper as f64 / letters.len() as f64 > 0.7 {
+                score += 0.2;
+            }
+        }
+
+        score.min(1.0)
+    }
+
+    /// Severity of a single token, if it is profane
+    fn token_severity(&self, token: &str, language: Language) -> Option<ProfanitySeverity> {
+        let normalized = self.normalize_text_for_profanity_check(token).to_lowercase();
+        let core = normalized.trim_matches(|c: char| !c.is_alphanumeric());
+        if core.is_empty() {
+            return None;
+        }
+
+        if self.wordlists.mild_profanity.contains(core) {
+            return Some(ProfanitySeverity::Mild);
+        }
+        if self.wordlists.profanity.contains(core)
+            || self.fuzzy_profanity_check(core)
+            || self.wordlists.hinglish_patterns.iter().any(|re| re.is_match(core))
+            || language.profanity_words().contains(&core)
+        {
+            return Some(ProfanitySeverity::Severe);
+        }
+        None
+    }
+
+    /// Replace profane tokens whose severity is set to mask with asterisks
+    /// Returns the masked content only if nothing blockable remains once they're gone
+    async fn mask_profanity(&self, content: &str, policy: &CityModerationPolicy, language: Language) -> Option<String> {
+        let mut masked_any = false;
+        let mut remainder = String::with_capacity(content.len());
+        let masked = TOKEN_REGEX.replace_all(content, |caps: &regex::Captures| {
+            let token = &caps[0];
+            let maskable = self.token_severity(token, language)
+                .is_some_and(|severity| self.masking.action(severity) == ProfanityAction::Mask);
+            if maskable {
+                masked_any = true;
+                "*".repeat(token.chars().count())
+            } else {
+                remainder.push_str(token);
+                remainder.push(' ');
+                token.to_string()
+            }
+        });
+
+        if !masked_any {
+            return None;
+        }
+
+        // Re-check without the masked tokens so the asterisks can't form new matches
+        let remaining = self.check_all_profanity(&remainder, policy, language).await;
+        remaining.is_allowed.then(|| masked.into_owned())
+    }
+
     /// Check for profanity and vulgar language
     /// Handles English profanity patterns, Hinglish text, leet speak, and common typos
-    async fn check_profanity(&self, content: &str) -> ModerationResult {
+    async fn check_profanity(&self, content: &str, policy: &CityModerationPolicy) -> ModerationResult {
         // Normalize the text for checking (handle leet speak, special characters, etc.)
         let normalized = self.normalize_text_for_profanity_check(content);
         let normalized_lower = normalized.to_lowercase();
@@ -236,21 +583,42 @@ impl ModerationService {
             );
         }
 
+        // City-specific slang and spam terms
+        let content_lower = content.to_lowercase();
+        for extra in &policy.extra_blocked_words {
+            let extra = extra.to_lowercase();
+            let matched = if extra.contains(' ') {
+                content_lower.contains(&extra)
+            } else {
+                normalized_lower
+                    .split_whitespace()
+                    .any(|w| w.trim_matches(|c: char| !c.is_alphanumeric()) == extra)
+            };
+            if matched {
+                return ModerationResult::blocked(
+                    "Message contains a term not allowed in this city".to_string(),
+                    ModerationViolationType::Profanity,
+                );
+            }
+        }
+
+        let relaxed = policy.strictness == Strictness::Relaxed;
+
         // Check normalized text against profanity word list
         let words: Vec<&str> = normalized_lower.split_whitespace().collect();
         for word in &words {
             // Remove punctuation from word for checking
             let clean_word = word.trim_matches(|c: char| !c.is_alphanumeric());
             
-            if PROFANITY_WORDS.contains(clean_word) {
+            if self.wordlists.profanity.contains(clean_word) {
                 return ModerationResult::blocked(
                     "Profanity or offensive language detected".to_string(),
                     ModerationViolationType::Profanity,
                 );
             }
 
-            // Check for partial matches with fuzzy detection
-            if self.fuzzy_profanity_check(clean_word) {
+            // Check for partial matches with fuzzy detection (skipped for relaxed cities)
+            if !relaxed && self.fuzzy_profanity_check(clean_word) {
                 return ModerationResult::blocked(
                     "Offensive or vulgar language detected".to_string(),
                     ModerationViolationType::Profanity,
@@ -259,9 +627,9 @@ impl ModerationService {
         }
 
         // Check for character-spaced profanity (e.g., "b i t c h", "f*** you")
-        let despaced = content.to_lowercase().split_whitespace().collect::<Vec<_>>().join("");
-        for word in PROFANITY_WORDS.iter() {
-            if despaced.contains(word) && word.len() > 2 {
+        let despaced = content_lower.split_whitespace().collect::<Vec<_>>().join("");
+        for word in &self.wordlists.profanity {
+            if !relaxed && despaced.contains(word.as_str()) && word.len() > 2 {
                 return ModerationResult::blocked(
                     "Offensive or vulgar language detected".to_string(),
                     ModerationViolationType::Profanity,
@@ -270,21 +638,24 @@ impl ModerationService {
         }
 
         // Hinglish pattern checks (unchanged for robustness)
-        let hinglish_offensive = vec![
-            r"(?i)\b(bc|bhosdike|lodu|chutiya|gaandu|gandu|harami|besharam)\b",
-            r"(?i)\b(madarchod|mdarc|behenchod|bevkuf|chakka)\b",
-            r"(?i)\b(randi|teri|terepa|saali|ullu|chakli)\b",
-        ];
+        if self.wordlists.hinglish_patterns.iter().any(|re| re.is_match(content)) {
+            return ModerationResult::blocked(
+                "Offensive or vulgar language detected".to_string(),
+                ModerationViolationType::Profanity,
+            );
+        }
 
-        for pattern in hinglish_offensive {
-            if let Ok(re) = Regex::new(pattern) {
-                if re.is_match(content) {
-                    return ModerationResult::blocked(
-                        "Offensive or vulgar language detected".to_string(),
-                        ModerationViolationType::Profanity,
-                    );
-                }
-            }
+        ModerationResult::allowed()
+    }
+
+    /// Check native-script profanity for Hindi, Kannada and Tamil messages
+    fn check_language_profanity(&self, content: &str, language: Language) -> ModerationResult {
+        let words = language.profanity_words();
+        if words.iter().any(|word| content.contains(word)) {
+            return ModerationResult::blocked(
+                "Offensive or vulgar language detected".to_string(),
+                ModerationViolationType::Profanity,
+            );
         }
 
         ModerationResult::allowed()
@@ -333,15 +704,15 @@ impl ModerationService {
         // Only do Levenshtein check for words that are within a reasonable range
         // of known profane words, and only if word is at least 4 chars
         if word.len() >= 4 {
-            for profane_word in PROFANITY_WORDS.iter() {
+            for profane_word in &self.wordlists.profanity {
                 // Only compare against profane words with similar length
-                if profane_word.len() > 2 && (word.len() as i32 - profane_word.len() as i32).abs() <= 2 {
-                    if self.levenshtein_distance(word, profane_word) <= 1 {
-                        // Double-check it's actually a profanity variant
-                        if self.is_profanity_variant(word, profane_word) {
-                            return true;
-                        }
-                    }
+                if profane_word.len() > 2
+                    && (word.len() as i32 - profane_word.len() as i32).abs() <= 2
+                    && self.levenshtein_distance(word, profane_word) <= 1
+                    // Double-check it's actually a profanity variant
+                    && self.is_profanity_variant(word, profane_word)
+                {
+                    return true;
                 }
             }
         }
@@ -391,11 +762,11 @@ impl ModerationService {
 
         let mut matrix = vec![vec![0; len2 + 1]; len1 + 1];
 
-        for i in 0..=len1 {
-            matrix[i][0] = i;
+        for (i, row) in matrix.iter_mut().enumerate() {
+            row[0] = i;
         }
-        for j in 0..=len2 {
-            matrix[0][j] = j;
+        for (j, cell) in matrix[0].iter_mut().enumerate() {
+            *cell = j;
         }
 
         let s1_chars: Vec<char> = s1.chars().collect();
@@ -419,17 +790,7 @@ impl ModerationService {
 
     /// Check if message is relevant to rental/property context
     /// Uses keyword density to determine relevance
-    fn check_rental_relevance(&self, content: &str) -> ModerationResult {
-        // Rental-related keywords
-        let rental_keywords = vec![
-            "room", "rooms", "flat", "flat", "apartment", "bhk", "bh", "studio", "rent",
-            "rented", "rental", "lease", "property", "location", "area", "locality",
-            "available", "looking", "wanted", "accommodation", "lodging", "tenant",
-            "landlord", "owner", "deposit", "advance", "monthly", "furnished",
-            "unfurnished", "sharing", "pg", "hostel", "shared", "attached", "bathroom",
-            "kitchen", "parking", "vegetarian", "non-veg", "pets", "furnishing",
-        ];
-
+    fn check_rental_relevance(&self, content: &str, policy: &CityModerationPolicy, language: Language) -> ModerationResult {
         let content_lower = content.to_lowercase();
         let words: Vec<&str> = content_lower.split_whitespace().collect();
 
@@ -437,22 +798,31 @@ impl ModerationService {
             return ModerationResult::allowed(); // Empty messages are OK (will be caught elsewhere)
         }
 
+        let extra_keywords: Vec<String> = policy
+            .extra_rental_keywords
+            .iter()
+            .map(|k| k.to_lowercase())
+            .collect();
+
         // Count keyword matches
         let keyword_count = words
             .iter()
             .filter(|word| {
-                rental_keywords
-                    .iter()
-                    .any(|keyword| word.contains(keyword))
+                self.wordlists.rental_keywords.iter().any(|keyword| word.contains(keyword.as_str()))
+                    || language.rental_keywords().iter().any(|keyword| word.contains(keyword))
+                    || extra_keywords.iter().any(|keyword| word.contains(keyword.as_str()))
             })
             .count();
 
         // Calculate keyword density
         let keyword_density = keyword_count as f64 / words.len() as f64;
+        let threshold = policy.relevance_threshold.unwrap_or(0.1);
+        // Strict cities don't exempt short messages
+        let min_words = if policy.strictness == Strictness::Strict { 1 } else { 3 };
 
-        // Messages with less than 10% keyword density are considered off-topic
-        // unless they're very short (3 words or less, which might be sparse but legitimate)
-        if keyword_density < 0.1 && words.len() > 3 {
+        // Messages below the keyword density threshold (10% by default) are considered
+        // off-topic unless they're very short (which might be sparse but legitimate)
+        if keyword_density < threshold && words.len() > min_words {
             return ModerationResult::blocked(
                 "Message appears off-topic for rental platform".to_string(),
                 ModerationViolationType::OffTopic,
@@ -463,16 +833,18 @@ impl ModerationService {
     }
 
     /// Check for spam patterns - multiple URLs and known scam domains
-    fn check_spam(&self, content: &str) -> ModerationResult {
+    fn check_spam(&self, content: &str, policy: &CityModerationPolicy) -> ModerationResult {
         // Count external URLs
         let url_matches: Vec<&str> = URL_REGEX.find_iter(content).map(|m| m.as_str()).collect();
 
-        // Check if more than 2 URLs
-        if url_matches.len() > 2 {
+        // Check if more than 2 URLs (1 for strict cities)
+        let max_urls = if policy.strictness == Strictness::Strict { 1 } else { 2 };
+        if url_matches.len() > max_urls {
             return ModerationResult::blocked(
                 format!(
-                    "Message contains too many URLs ({} found, max 2 allowed)",
-                    url_matches.len()
+                    "Message contains too many URLs ({} found, max {} allowed)",
+                    url_matches.len(),
+                    max_urls
                 ),
                 ModerationViolationType::Spam,
             );
@@ -480,8 +852,8 @@ impl ModerationService {
 
         // Check for known scam domains
         for url in &url_matches {
-            for scam_domain in SCAM_DOMAINS.iter() {
-                if url.to_lowercase().contains(scam_domain) {
+            for scam_domain in &self.wordlists.scam_domains {
+                if url.to_lowercase().contains(scam_domain.as_str()) {
                     return ModerationResult::blocked(
                         format!("Message contains link to known scam domain: {}", scam_domain),
                         ModerationViolationType::Spam,
@@ -494,72 +866,129 @@ impl ModerationService {
     }
 
     /// Check message against OpenAI's moderation API
-    /// Returns None if API check is disabled or fails, Some(result) otherwise
-    async fn check_openai_moderation(&self, content: &str) -> Option<ModerationResult> {
+    /// A verdict cached for the same normalized text is reused without a call
+    /// Returns None if API check is disabled, fails or runs out of time, Some(result) otherwise
+    async fn check_openai_moderation(&self, content: &str, deadline: Option<Deadline>) -> Option<ModerationResult> {
+        self.provider_verdict(content, deadline).await?.into_result()
+    }
+
+    /// The provider's verdict on a text, from the verdict cache when possible
+    /// None if no provider is configured or it gave no answer
+    pub async fn provider_verdict(&self, content: &str, deadline: Option<Deadline>) -> Option<ProviderVerdict> {
         // Skip if API key is not configured
+        self.openai_api_key.as_ref()?;
+
+        let cache_key = verdict_cache_key(content);
+        if let Some(verdict) = self.cached_verdict(&cache_key).await {
+            metrics::counter!("moderation_verdict_cache_hits_total", 1);
+            return Some(verdict);
+        }
+
+        let verdict = self.call_openai_moderation(content, deadline).await?;
+        self.cache_verdict(&cache_key, &verdict).await;
+        Some(verdict)
+    }
+
+    async fn cached_verdict(&self, cache_key: &str) -> Option<ProviderVerdict> {
+        let redis = self.verdict_cache.as_ref()?;
+        match redis.get(cache_key).await {
+            Ok(cached) => cached.and_then(|json| serde_json::from_str(&json).ok()),
+            Err(e) => {
+                tracing::error!("Failed to read cached moderation verdict: {}", e);
+                None
+            }
+        }
+    }
+
+    async fn cache_verdict(&self, cache_key: &str, verdict: &ProviderVerdict) {
+        let Some(redis) = &self.verdict_cache else {
+            return;
+        };
+        let Ok(json) = serde_json::to_string(verdict) else {
+            return;
+        };
+        if let Err(e) = redis.set_ex(cache_key, &json, VERDICT_CACHE_TTL).await {
+            tracing::error!("Failed to cache moderation verdict: {}", e);
+        }
+    }
+
+    /// Ask OpenAI's moderation API for a verdict
+    /// Returns None if the call fails or there's too little time left to make it
+    async fn call_openai_moderation(&self, content: &str, deadline: Option<Deadline>) -> Option<ProviderVerdict> {
         let api_key = self.openai_api_key.as_ref()?;
         let client = self.http_client.as_ref()?;
 
+        // Fail open like any other provider error rather than hold a request that's out of time
+        let timeout = deadline.map_or(PROVIDER_TIMEOUT, |deadline| deadline.remaining().min(PROVIDER_TIMEOUT));
+        if timeout < MIN_PROVIDER_BUDGET {
+            metrics::counter!("moderation_provider_skipped_total", 1, "reason" => "deadline");
+            return None;
+        }
+
         // Prepare request to OpenAI Moderation API
         let request_body = serde_json::json!({
             "input": content,
             "model": "text-moderation-latest"
         });
 
+        let started = std::time::Instant::now();
         match client
             .post("https://api.openai.com/v1/moderations")
             .header("Authorization", format!("Bearer {}", api_key))
             .json(&request_body)
+            .timeout(timeout)
             .send()
             .await
         {
             Ok(response) => match response.json::<OpenAiModerationResponse>().await {
                 Ok(moderation_response) => {
-                    if let Some(result) = moderation_response.results.first() {
-                        // Check categories
-                        if result.categories.hate {
-                            return Some(ModerationResult::blocked(
-                                "Content violates hate speech policy".to_string(),
-                                ModerationViolationType::HateContent,
-                            ));
-                        }
-
-                        if result.categories.harassment {
-                            return Some(ModerationResult::blocked(
-                                "Content violates harassment policy".to_string(),
-                                ModerationViolationType::HarassmentContent,
-                            ));
-                        }
-
-                        if result.categories.sexual {
-                            return Some(ModerationResult::blocked(
-                                "Content violates sexual content policy".to_string(),
-                                ModerationViolationType::SexualContent,
-                            ));
-                        }
-
+                    self.record_provider_call(started, Ok(()));
+                    let blocked = |reason: &str, violation_type| ProviderVerdict::Blocked {
+                        reason: reason.to_string(),
+                        violation_type,
+                    };
+                    let Some(result) = moderation_response.results.first() else {
+                        return Some(ProviderVerdict::Allowed);
+                    };
+
+                    // Check categories
+                    let verdict = if result.categories.hate {
+                        blocked("Content violates hate speech policy", ModerationViolationType::HateContent)
+                    } else if result.categories.harassment {
+                        blocked("Content violates harassment policy", ModerationViolationType::HarassmentContent)
+                    } else if result.categories.sexual {
+                        blocked("Content violates sexual content policy", ModerationViolationType::SexualContent)
+                    } else if result.categories.violence {
                         // Also check violence
-                        if result.categories.violence {
-                            return Some(ModerationResult::blocked(
-                                "Content violates violence policy".to_string(),
-                                ModerationViolationType::OpenAiViolation,
-                            ));
-                        }
-                    }
-                    None
+                        blocked("Content violates violence policy", ModerationViolationType::OpenAiViolation)
+                    } else {
+                        ProviderVerdict::Allowed
+                    };
+                    Some(verdict)
                 }
                 Err(e) => {
-                    eprintln!("Failed to parse OpenAI moderation response: {}", e);
+                    self.record_provider_call(started, Err(format!("Invalid response: {}", e)));
+                    tracing::error!("Failed to parse OpenAI moderation response: {}", e);
                     None
                 }
             },
             Err(e) => {
-                eprintln!("OpenAI moderation API request failed: {}", e);
+                self.record_provider_call(started, Err(format!("Request failed: {}", e)));
+                tracing::error!("OpenAI moderation API request failed: {}", e);
                 None
             }
         }
     }
 
+    fn record_provider_call(&self, started: std::time::Instant, result: Result<(), String>) {
+        if let Some(slo) = &self.slo {
+            slo.record_moderation_call(result.is_ok());
+        }
+        if let Some(dependencies) = &self.dependencies {
+            dependencies.record(dependencies::MODERATION_PROVIDER, started.elapsed(), result);
+        }
+    }
+
     /// Helper function for external rental relevance check
     /// Can be extended with more sophisticated NLP or ML models
     #[allow(dead_code)]
@@ -610,21 +1039,47 @@ mod tests {
     #[tokio::test]
     async fn test_profanity_check() {
         let service = ModerationService::new(None);
-        let result = service.check_profanity("This is a normal message").await;
+        let result = service.check_profanity("This is a normal message", &CityModerationPolicy::default()).await;
         assert!(result.is_allowed);
 
         // Test with potential profanity (rustrict might catch it)
-        let result = service
-            .check_profanity("This message contains damn profanity")
-            .await;
+        let _result = service
+            .check_profanity("This message contains damn profanity", &CityModerationPolicy::default()).await;
         // Result depends on rustrict's dictionary
     }
 
+    #[tokio::test]
+    async fn test_fields_skip_relevance_but_not_profanity() {
+        let service = ModerationService::new(None);
+        let locality = service.moderate_field("Koregaon Park", None, Language::English, None).await;
+        assert!(locality.is_allowed);
+
+        let abusive = service.moderate_field("f*ck you", None, Language::English, None).await;
+        assert!(!abusive.is_allowed);
+    }
+
+    #[test]
+    fn test_verdicts_are_cached_by_normalized_text() {
+        assert_eq!(verdict_cache_key("2BHK in Baner!"), verdict_cache_key("  2bhk IN baner "));
+        assert_ne!(verdict_cache_key("2BHK in Baner"), verdict_cache_key("3BHK in Baner"));
+
+        let verdict = ProviderVerdict::Blocked {
+            reason: "Content violates harassment policy".to_string(),
+            violation_type: ModerationViolationType::HarassmentContent,
+        };
+        let json = serde_json::to_string(&verdict).unwrap();
+        assert_eq!(serde_json::from_str::<ProviderVerdict>(&json).unwrap(), verdict);
+        let result = verdict.into_result().unwrap();
+        assert!(!result.is_allowed);
+        assert_eq!(result.violation_type, Some(ModerationViolationType::HarassmentContent));
+        assert!(ProviderVerdict::Allowed.into_result().is_none());
+    }
+
     #[test]
     fn test_spam_multiple_urls() {
         let service = ModerationService::new(None);
         let content_with_urls = "Check https://example.com and http://test.com and https://another.com";
-        let result = service.check_spam(content_with_urls);
+        let result = service.check_spam(content_with_urls, &CityModerationPolicy::default());
         assert!(!result.is_allowed);
         assert_eq!(
             result.violation_type,
@@ -636,7 +1091,7 @@ mod tests {
     fn test_spam_scam_domains() {
         let service = ModerationService::new(None);
         let content = "Contact me on https://t.me/username";
-        let result = service.check_spam(content);
+        let result = service.check_spam(content, &CityModerationPolicy::default());
         assert!(!result.is_allowed);
     }
 
@@ -644,7 +1099,7 @@ mod tests {
     fn test_valid_single_url() {
         let service = ModerationService::new(None);
         let content = "Check my portfolio at https://example.com";
-        let result = service.check_spam(content);
+        let result = service.check_spam(content, &CityModerationPolicy::default());
         assert!(result.is_allowed);
     }
 
@@ -663,7 +1118,7 @@ mod tests {
         ];
         
         for case in test_cases {
-            let result = service.check_profanity(case).await;
+            let result = service.check_profanity(case, &CityModerationPolicy::default()).await;
             assert!(!result.is_allowed, "Failed to detect: {}", case);
         }
     }
@@ -681,7 +1136,7 @@ mod tests {
         ];
         
         for case in test_cases {
-            let result = service.check_profanity(case).await;
+            let result = service.check_profanity(case, &CityModerationPolicy::default()).await;
             assert!(!result.is_allowed, "Failed to detect spaced: {}", case);
         }
     }
@@ -702,7 +1157,7 @@ mod tests {
         ];
         
         for case in test_cases {
-            let result = service.check_profanity(case).await;
+            let result = service.check_profanity(case, &CityModerationPolicy::default()).await;
             assert!(!result.is_allowed, "Failed to detect Hinglish: {}", case);
         }
     }
@@ -712,7 +1167,7 @@ mod tests {
         let service = ModerationService::new(None);
         
         // Test obvious shorthand variations
-        let result = service.check_profanity("fk you").await;
+        let result = service.check_profanity("fk you", &CityModerationPolicy::default()).await;
         assert!(!result.is_allowed, "Should detect 'fk' as profanity variant");
     }
 
@@ -721,7 +1176,7 @@ mod tests {
         let service = ModerationService::new(None);
         
         // Test repeated characters with profane roots
-        let result = service.check_profanity("fuckkkk").await;
+        let result = service.check_profanity("fuckkkk", &CityModerationPolicy::default()).await;
         assert!(!result.is_allowed, "Should detect repeated profanity");
     }
 
@@ -730,10 +1185,10 @@ mod tests {
         let service = ModerationService::new(None);
         
         // Test legitimate rental-related messages
-        let result = service.check_profanity("Looking for a 2 BHK flat in Mumbai").await;
+        let result = service.check_profanity("Looking for a 2 BHK flat in Mumbai", &CityModerationPolicy::default()).await;
         assert!(result.is_allowed, "Should not flag legitimate flat rental query");
         
-        let result = service.check_profanity("What's the rent for this property?").await;
+        let result = service.check_profanity("What's the rent for this property?", &CityModerationPolicy::default()).await;
         assert!(result.is_allowed, "Should not flag rent inquiry");
     }
 
@@ -746,4 +1201,73 @@ mod tests {
         assert_eq!(service.levenshtein_distance("cat", "car"), 1);
         assert_eq!(service.levenshtein_distance("fuck", "fuk"), 1);
         assert_eq!(service.levenshtein_distance("shit", "sheit"), 1);
-    }}
\ No newline at end of file
+    }
+
+    #[tokio::test]
+    async fn test_city_policy_overrides() {
+        let service = ModerationService::new(None);
+        let policy = CityModerationPolicy {
+            extra_blocked_words: vec!["Broker".to_string()],
+            extra_rental_keywords: vec!["chawl".to_string()],
+            ..Default::default()
+        };
+
+        let result = service.check_profanity("No broker please", &policy).await;
+        assert!(!result.is_allowed, "Should block city-specific word");
+        let result = service.check_profanity("No broker please", &CityModerationPolicy::default()).await;
+        assert!(result.is_allowed, "Extra words only apply to that city");
+
+        let local = "Chawl near station, contact me soon";
+        assert!(!service.check_rental_relevance(local, &CityModerationPolicy::default(), Language::English).is_allowed);
+        assert!(service.check_rental_relevance(local, &policy, Language::English).is_allowed);
+
+        let strict = CityModerationPolicy { strictness: Strictness::Strict, ..Default::default() };
+        let two_links = "Flat for rent https://a.example.com https://b.example.com";
+        assert!(service.check_spam(two_links, &CityModerationPolicy::default()).is_allowed);
+        assert!(!service.check_spam(two_links, &strict).is_allowed);
+    }
+
+    #[tokio::test]
+    async fn test_language_routing() {
+        let service = ModerationService::new(None);
+        let policy = CityModerationPolicy::default();
+
+        let hindi = "दिल्ली में कमरा किराये पर उपलब्ध है संपर्क करें";
+        assert!(!service.check_rental_relevance(hindi, &policy, Language::English).is_allowed);
+        assert!(service.check_rental_relevance(hindi, &policy, Language::Hindi).is_allowed);
+
+        assert!(!service.check_language_profanity("तू हरामी है", Language::Hindi).is_allowed);
+        assert!(service.check_language_profanity("कमरा उपलब्ध है", Language::Hindi).is_allowed);
+    }
+
+    #[tokio::test]
+    async fn test_masking_by_severity() {
+        let mild_only = ModerationService::new(None).with_masking(MaskingConfig {
+            mild: ProfanityAction::Mask,
+            severe: ProfanityAction::Block,
+        });
+        let policy = CityModerationPolicy::default();
+
+        let masked = mild_only.mask_profanity("Damn, great flat near the station", &policy, Language::English).await;
+        assert_eq!(masked.as_deref(), Some("***** great flat near the station"));
+
+        let severe = mild_only.mask_profanity("damn this shit flat", &policy, Language::English).await;
+        assert_eq!(severe, None, "Severe words stay blocked");
+
+        let mask_all = ModerationService::new(None).with_masking(MaskingConfig {
+            mild: ProfanityAction::Mask,
+            severe: ProfanityAction::Mask,
+        });
+        let masked = mask_all.mask_profanity("damn this shit flat", &policy, Language::English).await;
+        assert_eq!(masked.as_deref(), Some("**** this **** flat"));
+    }
+
+    #[test]
+    fn test_toxicity_score() {
+        let service = ModerationService::new(None);
+        assert_eq!(service.toxicity_score("2 BHK flat available in Indiranagar", Language::English), 0.0);
+        assert!((service.toxicity_score("damn nice flat", Language::English) - 0.3).abs() < 1e-9);
+        assert!((service.toxicity_score("WHY IS NOBODY CALLING ABOUT THIS FLAT", Language::English) - 0.2).abs() < 1e-9);
+        assert_eq!(service.toxicity_score("shit shit damn", Language::English), 1.0);
+    }
+}
@@ -1,39 +1,87 @@
 use crate::redis_client::RedisClient;
 use anyhow::{Result, anyhow};
+use axum::http::{header, HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use crate::security::enforcement::EnforcementRecord;
+use crate::keys;
+use ipnet::IpNet;
+use std::net::IpAddr;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+const BLOCK_SCAN_BATCH: usize = 500;
+
 /// Rate limiter using sliding window algorithm with Redis
 #[derive(Clone)]
 pub struct RateLimiter {
     redis: RedisClient,
+    limits: RateLimitConfig,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RateLimitType {
-    /// 1 post per 60 seconds
+    /// 1 post per 60 seconds by default
     PostMessage,
-    /// 5 reveals per hour
+    /// 5 reveals per hour by default
     ContactReveal,
-    /// 20 requests per 2 seconds (burst protection)
+    /// 20 requests per 2 seconds by default (burst protection)
     BurstProtection,
+    /// 10 reports per hour per composite key by default
+    ReportMessage,
+    /// 20 reports per hour per IP by default, however many identities share it
+    ReportPerIp,
+    /// 5 newly registered fingerprints per IP per day by default
+    NewFingerprint,
 }
 
 impl RateLimitType {
-    /// Get the window size in seconds
-    fn window_seconds(&self) -> u64 {
+    pub const ALL: [RateLimitType; 6] = [
+        RateLimitType::PostMessage,
+        RateLimitType::ContactReveal,
+        RateLimitType::BurstProtection,
+        RateLimitType::ReportMessage,
+        RateLimitType::ReportPerIp,
+        RateLimitType::NewFingerprint,
+    ];
+
+    /// Whether the limit is keyed by IP address rather than composite key
+    pub fn keyed_by_ip(&self) -> bool {
+        matches!(self, RateLimitType::ReportPerIp | RateLimitType::NewFingerprint)
+    }
+
+    /// Window size in seconds unless configured otherwise
+    fn default_window_seconds(&self) -> u64 {
         match self {
             RateLimitType::PostMessage => 60,
             RateLimitType::ContactReveal => 3600, // 1 hour
             RateLimitType::BurstProtection => 2,
+            RateLimitType::ReportMessage => 3600,
+            RateLimitType::ReportPerIp => 3600,
+            RateLimitType::NewFingerprint => 86400, // 24 hours
         }
     }
 
-    /// Get the maximum allowed requests in the window
-    fn max_requests(&self) -> i64 {
+    /// Requests allowed in the window unless configured otherwise
+    fn default_max_requests(&self) -> i64 {
         match self {
             RateLimitType::PostMessage => 1,
             RateLimitType::ContactReveal => 5,
             RateLimitType::BurstProtection => 20,
+            RateLimitType::ReportMessage => 10,
+            RateLimitType::ReportPerIp => 20,
+            RateLimitType::NewFingerprint => 5,
+        }
+    }
+
+    /// Name used in the `RATE_LIMIT_<NAME>_WINDOW` / `RATE_LIMIT_<NAME>_MAX` variables
+    fn env_name(&self) -> &'static str {
+        match self {
+            RateLimitType::PostMessage => "POST",
+            RateLimitType::ContactReveal => "REVEAL",
+            RateLimitType::BurstProtection => "BURST",
+            RateLimitType::ReportMessage => "REPORT",
+            RateLimitType::ReportPerIp => "REPORT_IP",
+            RateLimitType::NewFingerprint => "NEW_FINGERPRINT",
         }
     }
 
@@ -43,21 +91,146 @@ impl RateLimitType {
             RateLimitType::PostMessage => "ratelimit:post",
             RateLimitType::ContactReveal => "ratelimit:reveal",
             RateLimitType::BurstProtection => "ratelimit:burst",
+            RateLimitType::ReportMessage => "ratelimit:report",
+            RateLimitType::ReportPerIp => "ratelimit:report_ip",
+            RateLimitType::NewFingerprint => "ratelimit:new_fingerprint",
         }
     }
 }
 
-#[derive(Debug)]
+/// Window and quota of one rate limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub window_seconds: u64,
+    pub max_requests: i64,
+}
+
+/// Window and quota for every rate limit type, read once at startup
+/// `RATE_LIMIT_<NAME>_WINDOW` (seconds) and `RATE_LIMIT_<NAME>_MAX` override the
+/// defaults, with NAME one of POST, REVEAL, BURST, REPORT, REPORT_IP, NEW_FINGERPRINT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitConfig {
+    limits: [RateLimit; RateLimitType::ALL.len()],
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            limits: RateLimitType::ALL.map(|limit_type| RateLimit {
+                window_seconds: limit_type.default_window_seconds(),
+                max_requests: limit_type.default_max_requests(),
+            }),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        for limit_type in RateLimitType::ALL {
+            let limit = &mut config.limits[limit_type as usize];
+            // Zero would disable the limit (or block everything), so it's ignored
+            if let Some(window) = env_positive::<u64>(&format!("RATE_LIMIT_{}_WINDOW", limit_type.env_name())) {
+                limit.window_seconds = window;
+            }
+            if let Some(max) = env_positive::<i64>(&format!("RATE_LIMIT_{}_MAX", limit_type.env_name())) {
+                limit.max_requests = max;
+            }
+        }
+        config
+    }
+
+    pub fn get(&self, limit_type: RateLimitType) -> RateLimit {
+        self.limits[limit_type as usize]
+    }
+
+    pub fn window_seconds(&self, limit_type: RateLimitType) -> u64 {
+        self.get(limit_type).window_seconds
+    }
+
+    pub fn max_requests(&self, limit_type: RateLimitType) -> i64 {
+        self.get(limit_type).max_requests
+    }
+}
+
+fn env_positive<T: std::str::FromStr + PartialOrd + Default>(name: &str) -> Option<T> {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v| *v > T::default())
+}
+
+/// An active block on an IP address or network
+#[derive(Debug, Serialize)]
+pub struct IpBlock {
+    /// IP address or CIDR
+    pub target: String,
+    #[serde(flatten)]
+    pub record: EnforcementRecord,
+    /// Seconds until the block lifts
+    pub ttl: i64,
+}
+
+/// Whether a network covers exactly one address
+pub fn is_single_host(network: &IpNet) -> bool {
+    network.prefix_len() == network.max_prefix_len()
+}
+
+/// Current state of one rate limit window, for inspection
+#[derive(Debug, Serialize)]
+pub struct RateLimitWindow {
+    #[serde(rename = "type")]
+    pub limit_type: RateLimitType,
+    /// Keyed by IP address rather than composite key
+    pub keyed_by_ip: bool,
+    pub window_seconds: u64,
+    pub max_requests: i64,
+    /// Requests counted in the current window
+    pub count: i64,
+    /// Seconds until the window key expires (-2 when absent)
+    pub ttl: i64,
+    /// When the oldest counted request leaves the window
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oldest_expires_at: Option<u64>,
+}
+
+pub const RATE_LIMIT_LIMIT_HEADER: &str = "x-ratelimit-limit";
+pub const RATE_LIMIT_REMAINING_HEADER: &str = "x-ratelimit-remaining";
+
+#[derive(Debug, Clone, Copy)]
 pub struct RateLimitResult {
     pub allowed: bool,
-    #[allow(dead_code)]
+    /// Requests allowed per window
+    pub limit: i64,
     pub remaining: i64,
     pub reset_at: u64,
 }
 
+impl RateLimitResult {
+    /// Seconds until `reset_at`, at least 1 so a limited client never retries immediately
+    pub fn retry_after(&self) -> u64 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        self.reset_at.saturating_sub(now).max(1)
+    }
+
+    /// `X-RateLimit-Limit` and `X-RateLimit-Remaining`, plus `Retry-After` once the limit is hit
+    pub fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RATE_LIMIT_LIMIT_HEADER, HeaderValue::from(self.limit));
+        headers.insert(RATE_LIMIT_REMAINING_HEADER, HeaderValue::from(self.remaining.max(0)));
+        if !self.allowed {
+            headers.insert(header::RETRY_AFTER, HeaderValue::from(self.retry_after()));
+        }
+        headers
+    }
+}
+
 impl RateLimiter {
-    pub fn new(redis: RedisClient) -> Self {
-        Self { redis }
+    pub fn new(redis: RedisClient, limits: RateLimitConfig) -> Self {
+        Self { redis, limits }
     }
 
     /// Check the current rate limit status without consuming a request
@@ -78,9 +251,8 @@ impl RateLimiter {
             .unwrap()
             .as_secs_f64();
 
-        let window_seconds = limit_type.window_seconds();
-        let max_requests = limit_type.max_requests();
-        let key = format!("{}:{}", limit_type.key_prefix(), composite_key);
+        let RateLimit { window_seconds, max_requests } = self.limits.get(limit_type);
+        let key = keys::rate_limit(limit_type.key_prefix(), composite_key);
 
         // Calculate the window start time
         let window_start = now - window_seconds as f64;
@@ -109,15 +281,29 @@ impl RateLimiter {
             
             return Ok(RateLimitResult {
                 allowed: false,
+                limit: max_requests,
                 remaining: 0,
                 reset_at,
             });
         }
 
+        // With requests in the window, the next slot frees up when the oldest one leaves it
+        let reset_at = if current_count > 0 {
+            self.redis
+                .zrangebyscore_withscores_limit(&key, window_start, now, 1)
+                .await
+                .unwrap_or_else(|_| vec![])
+                .first()
+                .map_or(now, |(_, oldest_timestamp)| oldest_timestamp + window_seconds as f64)
+        } else {
+            now
+        };
+
         Ok(RateLimitResult {
             allowed: true,
+            limit: max_requests,
             remaining: max_requests - current_count,
-            reset_at: (now + window_seconds as f64) as u64,
+            reset_at: reset_at as u64,
         })
     }
 
@@ -139,9 +325,8 @@ impl RateLimiter {
             .unwrap()
             .as_secs_f64();
 
-        let window_seconds = limit_type.window_seconds();
-        let max_requests = limit_type.max_requests();
-        let key = format!("{}:{}", limit_type.key_prefix(), composite_key);
+        let RateLimit { window_seconds, max_requests } = self.limits.get(limit_type);
+        let key = keys::rate_limit(limit_type.key_prefix(), composite_key);
 
         // Calculate the window start time
         let window_start = now - window_seconds as f64;
@@ -176,6 +361,7 @@ impl RateLimiter {
             
             return Ok(RateLimitResult {
                 allowed: false,
+                limit: max_requests,
                 remaining: 0,
                 reset_at,
             });
@@ -196,38 +382,209 @@ impl RateLimiter {
 
         Ok(RateLimitResult {
             allowed: true,
+            limit: max_requests,
             remaining: max_requests - current_count - 1,
             reset_at: (now + window_seconds as f64) as u64,
         })
     }
 
+    /// Current windows for a key across every rate limit type
+    /// IP-keyed types only have data when `key` is an IP address
+    pub async fn inspect(&self, key: &str) -> Result<Vec<RateLimitWindow>> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs_f64();
+
+        let mut windows = Vec::with_capacity(RateLimitType::ALL.len());
+        for limit_type in RateLimitType::ALL {
+            let window_seconds = self.limits.window_seconds(limit_type);
+            let redis_key = keys::rate_limit(limit_type.key_prefix(), key);
+            let window_start = now - window_seconds as f64;
+
+            let count = self.redis
+                .zcount(&redis_key, window_start, now)
+                .await
+                .map_err(|e| anyhow!("Failed to count requests: {}", e))?;
+            let ttl = self.redis
+                .ttl(&redis_key)
+                .await
+                .map_err(|e| anyhow!("Failed to read rate limit TTL: {}", e))?;
+            let oldest_expires_at = if count > 0 {
+                self.redis
+                    .zrangebyscore_withscores_limit(&redis_key, window_start, now, 1)
+                    .await
+                    .map_err(|e| anyhow!("Failed to read rate limit window: {}", e))?
+                    .first()
+                    .map(|(_, score)| (score + window_seconds as f64) as u64)
+            } else {
+                None
+            };
+
+            windows.push(RateLimitWindow {
+                limit_type,
+                keyed_by_ip: limit_type.keyed_by_ip(),
+                window_seconds,
+                max_requests: self.limits.max_requests(limit_type),
+                count,
+                ttl,
+                oldest_expires_at,
+            });
+        }
+        Ok(windows)
+    }
+
+    /// Clear the windows for a key, for one type or all of them
+    /// Returns the types that had a window to clear
+    pub async fn reset(&self, key: &str, only: Option<RateLimitType>) -> Result<Vec<RateLimitType>> {
+        let types: Vec<RateLimitType> = match only {
+            Some(limit_type) => vec![limit_type],
+            None => RateLimitType::ALL.to_vec(),
+        };
+
+        let mut cleared = Vec::new();
+        for limit_type in types {
+            let redis_key = keys::rate_limit(limit_type.key_prefix(), key);
+            let existed = self.redis
+                .exists(&redis_key)
+                .await
+                .map_err(|e| anyhow!("Failed to check rate limit: {}", e))?;
+            if existed {
+                self.redis
+                    .del(&redis_key)
+                    .await
+                    .map_err(|e| anyhow!("Failed to reset rate limit: {}", e))?;
+                cleared.push(limit_type);
+            }
+        }
+        Ok(cleared)
+    }
+
     /// Block an IP address globally for a specified duration
     /// 
     /// # Arguments
     /// * `ip` - The IP address to block
     /// * `duration_seconds` - How long to block the IP (in seconds)
-    pub async fn block_ip(&self, ip: &str, duration_seconds: u64) -> Result<()> {
-        let key = format!("blocked:ip:{}", ip);
+    /// * `record` - Why, by whom and from where, shown to admins
+    pub async fn block_ip(&self, ip: &str, duration_seconds: u64, record: EnforcementRecord) -> Result<()> {
+        let key = keys::blocked_ip(ip);
         self.redis
-            .set_ex(&key, "1", duration_seconds)
+            .set_ex(&key, &record.expiring_in(Some(duration_seconds)).to_json(), duration_seconds)
             .await
             .map_err(|e| anyhow!("Failed to block IP: {}", e))?;
         Ok(())
     }
 
-    /// Check if an IP address is currently blocked
-    pub async fn is_ip_blocked(&self, ip: &str) -> Result<bool> {
-        let key = format!("blocked:ip:{}", ip);
+    /// Block every address in a network for a specified duration
+    pub async fn block_network(&self, network: &IpNet, duration_seconds: u64, record: EnforcementRecord) -> Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let member = network.to_string();
+
         self.redis
+            .pipeline()
+            .zrembyscore(keys::BLOCKED_NETWORKS, 0.0, now as f64).ignore()
+            .zadd(keys::BLOCKED_NETWORKS, (now + duration_seconds) as f64, &member).ignore()
+            .set_ex(
+                &keys::blocked_cidr(&member),
+                record.expiring_in(Some(duration_seconds)).to_json(),
+                duration_seconds,
+            ).ignore()
+            .execute()
+            .await
+            .map_err(|e| anyhow!("Failed to block network: {}", e))
+    }
+
+    /// Lift a block on an IP address or network
+    /// Returns false if nothing was blocked under that target
+    pub async fn unblock(&self, target: &IpNet) -> Result<bool> {
+        let (removed,): (i64,) = if is_single_host(target) {
+            self.redis
+                .pipeline()
+                .del(&keys::blocked_ip(&target.addr().to_string()))
+                .query()
+                .await
+        } else {
+            let member = target.to_string();
+            self.redis
+                .pipeline()
+                .zrem(keys::BLOCKED_NETWORKS, &member)
+                .del(&keys::blocked_cidr(&member)).ignore()
+                .query()
+                .await
+        }
+        .map_err(|e| anyhow!("Failed to unblock: {}", e))?;
+
+        Ok(removed > 0)
+    }
+
+    /// Every active IP and network block with its reason and remaining time
+    pub async fn list_blocks(&self) -> Result<Vec<IpBlock>> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+        let mut block_keys = self.redis
+            .scan_match(&format!("{}*", keys::BLOCKED_IP_PREFIX), BLOCK_SCAN_BATCH)
+            .await
+            .map_err(|e| anyhow!("Failed to list IP blocks: {}", e))?;
+        let networks = self.redis
+            .zrangebyscore(keys::BLOCKED_NETWORKS, now as f64, f64::INFINITY)
+            .await
+            .map_err(|e| anyhow!("Failed to list network blocks: {}", e))?;
+        block_keys.extend(networks.iter().map(|network| keys::blocked_cidr(network)));
+
+        let mut blocks = Vec::with_capacity(block_keys.len());
+        for key in block_keys {
+            let value = self.redis
+                .get(&key)
+                .await
+                .map_err(|e| anyhow!("Failed to read block: {}", e))?;
+            let ttl = self.redis
+                .ttl(&key)
+                .await
+                .map_err(|e| anyhow!("Failed to read block TTL: {}", e))?;
+            // Expired between the scan and the read
+            let Some(value) = value else {
+                continue;
+            };
+
+            let target = key
+                .strip_prefix(keys::BLOCKED_IP_PREFIX)
+                .or_else(|| key.strip_prefix(keys::BLOCKED_CIDR_PREFIX))
+                .unwrap_or(&key)
+                .to_string();
+            blocks.push(IpBlock { target, record: EnforcementRecord::parse(&value), ttl });
+        }
+        Ok(blocks)
+    }
+
+    /// Check if an IP address is currently blocked, directly or by network
+    pub async fn is_ip_blocked(&self, ip: &str) -> Result<bool> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let key = keys::blocked_ip(ip);
+
+        let (blocked, networks): (bool, Vec<String>) = self.redis
+            .pipeline()
             .exists(&key)
+            .zrangebyscore(keys::BLOCKED_NETWORKS, now as f64, f64::INFINITY)
+            .query()
             .await
-            .map_err(|e| anyhow!("Failed to check if IP is blocked: {}", e))
+            .map_err(|e| anyhow!("Failed to check if IP is blocked: {}", e))?;
+        if blocked || networks.is_empty() {
+            return Ok(blocked);
+        }
+
+        let Ok(addr) = ip.parse::<IpAddr>() else {
+            return Ok(false);
+        };
+        Ok(networks
+            .iter()
+            .filter_map(|network| network.parse::<IpNet>().ok())
+            .any(|network| network.contains(&addr)))
     }
 
     /// Get the remaining time for an IP block in seconds
     #[allow(dead_code)]
     pub async fn get_ip_block_ttl(&self, ip: &str) -> Result<i64> {
-        let key = format!("blocked:ip:{}", ip);
+        let key = keys::blocked_ip(ip);
         self.redis
             .ttl(&key)
             .await
@@ -241,13 +598,40 @@ mod tests {
 
     #[test]
     fn test_rate_limit_type_values() {
-        assert_eq!(RateLimitType::PostMessage.window_seconds(), 60);
-        assert_eq!(RateLimitType::PostMessage.max_requests(), 1);
+        let limits = RateLimitConfig::default();
+        assert_eq!(limits.window_seconds(RateLimitType::PostMessage), 60);
+        assert_eq!(limits.max_requests(RateLimitType::PostMessage), 1);
         
-        assert_eq!(RateLimitType::ContactReveal.window_seconds(), 3600);
-        assert_eq!(RateLimitType::ContactReveal.max_requests(), 5);
+        assert_eq!(limits.window_seconds(RateLimitType::ContactReveal), 3600);
+        assert_eq!(limits.max_requests(RateLimitType::ContactReveal), 5);
         
-        assert_eq!(RateLimitType::BurstProtection.window_seconds(), 2);
-        assert_eq!(RateLimitType::BurstProtection.max_requests(), 20);
+        assert_eq!(limits.window_seconds(RateLimitType::BurstProtection), 2);
+        assert_eq!(limits.max_requests(RateLimitType::BurstProtection), 20);
+    }
+
+    #[test]
+    fn test_all_types_are_indexed_in_order() {
+        for (i, limit_type) in RateLimitType::ALL.into_iter().enumerate() {
+            assert_eq!(limit_type as usize, i);
+        }
+    }
+
+    #[test]
+    fn test_all_types_have_distinct_prefixes() {
+        let prefixes: std::collections::HashSet<&str> =
+            RateLimitType::ALL.iter().map(|t| t.key_prefix()).collect();
+        assert_eq!(prefixes.len(), RateLimitType::ALL.len());
+    }
+
+    #[test]
+    fn test_headers_add_retry_after_only_when_limited() {
+        let allowed = RateLimitResult { allowed: true, limit: 5, remaining: 3, reset_at: 0 };
+        let headers = allowed.headers();
+        assert_eq!(headers["x-ratelimit-limit"], "5");
+        assert_eq!(headers["x-ratelimit-remaining"], "3");
+        assert!(headers.get("retry-after").is_none());
+
+        let limited = RateLimitResult { allowed: false, limit: 5, remaining: 0, reset_at: 0 };
+        assert_eq!(limited.headers()["retry-after"], "1");
     }
 }
@@ -0,0 +1,303 @@
+use crate::redis_client::RedisClient;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use crate::keys;
+
+/// How long a message's report history is kept
+const REPORT_HISTORY_TTL_SECONDS: i64 = 604800; // 7 days
+/// Most recent reports kept per message
+const REPORT_HISTORY_SIZE: usize = 50;
+/// How long auto-actions stay frozen on a brigaded message
+const FREEZE_TTL_SECONDS: u64 = 604800; // 7 days
+/// How long a reported message stays marked as queued for review
+const QUEUED_TTL_SECONDS: u64 = 604800; // 7 days
+
+/// Reports needed before correlation is judged at all
+const MIN_REPORTS_FOR_ANALYSIS: usize = 3;
+/// Share of reports from one network that counts as a coordinated source
+const SHARED_NETWORK_RATIO: f64 = 0.5;
+/// Reports arriving within this many seconds of each other count as a burst
+const BURST_WINDOW_SECONDS: u64 = 60;
+/// Reports within one burst window that look scripted rather than organic
+const BURST_THRESHOLD: usize = 3;
+
+/// Who filed a report against a message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportSource {
+    pub composite_key: String,
+    /// /24 (IPv4) or /48 (IPv6) the reporter came from
+    pub network: String,
+    pub timestamp: u64,
+}
+
+impl ReportSource {
+    pub fn new(composite_key: &str, ip: &str, timestamp: u64) -> Self {
+        Self {
+            composite_key: composite_key.to_string(),
+            network: network_of(ip),
+            timestamp,
+        }
+    }
+}
+
+/// Correlation checks over the reports filed against one message
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BrigadeVerdict {
+    pub reports: usize,
+    pub distinct_reporters: usize,
+    /// Most reports from a single network
+    pub max_from_network: usize,
+    /// Most reports inside any one burst window
+    pub max_in_burst: usize,
+    /// Human-readable reasons the reports look coordinated (empty when they don't)
+    pub reasons: Vec<String>,
+}
+
+impl BrigadeVerdict {
+    /// `sources` is oldest first
+    pub fn analyze(sources: &[ReportSource]) -> Self {
+        let distinct_reporters = sources
+            .iter()
+            .map(|s| s.composite_key.as_str())
+            .collect::<HashSet<_>>()
+            .len();
+
+        // Count each reporter once per network so one user re-reporting isn't "a network"
+        let mut per_network: HashMap<&str, HashSet<&str>> = HashMap::new();
+        for source in sources {
+            per_network
+                .entry(source.network.as_str())
+                .or_default()
+                .insert(source.composite_key.as_str());
+        }
+        let max_from_network = per_network.values().map(HashSet::len).max().unwrap_or(0);
+
+        let mut max_in_burst = 0;
+        for (i, first) in sources.iter().enumerate() {
+            let in_burst = sources[i..]
+                .iter()
+                .take_while(|s| s.timestamp.saturating_sub(first.timestamp) <= BURST_WINDOW_SECONDS)
+                .count();
+            max_in_burst = max_in_burst.max(in_burst);
+        }
+
+        let mut reasons = Vec::new();
+        if distinct_reporters >= MIN_REPORTS_FOR_ANALYSIS {
+            if max_from_network >= 2
+                && max_from_network as f64 / distinct_reporters as f64 >= SHARED_NETWORK_RATIO
+            {
+                reasons.push(format!(
+                    "{} of {} reporters share a network",
+                    max_from_network, distinct_reporters
+                ));
+            }
+            if max_in_burst >= BURST_THRESHOLD {
+                reasons.push(format!(
+                    "{} reports within {} seconds",
+                    max_in_burst, BURST_WINDOW_SECONDS
+                ));
+            }
+        }
+
+        Self {
+            reports: sources.len(),
+            distinct_reporters,
+            max_from_network,
+            max_in_burst,
+            reasons,
+        }
+    }
+
+    pub fn is_correlated(&self) -> bool {
+        !self.reasons.is_empty()
+    }
+}
+
+/// Reports against a poster's fingerprint after which a listing is deleted
+/// automatically, when nobody can review the queue
+const DEFAULT_AUTO_DELETE_AFTER: i64 = 5;
+/// Reports against a poster's fingerprint after which the poster is shadowbanned
+const DEFAULT_AUTO_SHADOWBAN_AFTER: i64 = 3;
+
+/// Automatic actions taken on reports while they wait in the review queue
+/// A threshold of 0 turns that action off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportPolicy {
+    pub auto_delete_after: i64,
+    pub auto_shadowban_after: i64,
+}
+
+impl ReportPolicy {
+    /// From `REPORT_AUTO_DELETE_AFTER` and `REPORT_AUTO_SHADOWBAN_AFTER`
+    /// With moderators around to work the review queue, deletion is left to them
+    /// unless a threshold is set explicitly
+    pub fn from_env(reviewed: bool) -> Self {
+        let threshold = |name: &str, default: i64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            auto_delete_after: threshold(
+                "REPORT_AUTO_DELETE_AFTER",
+                if reviewed { 0 } else { DEFAULT_AUTO_DELETE_AFTER },
+            ),
+            auto_shadowban_after: threshold("REPORT_AUTO_SHADOWBAN_AFTER", DEFAULT_AUTO_SHADOWBAN_AFTER),
+        }
+    }
+
+    pub fn deletes(&self, reports: i64) -> bool {
+        self.auto_delete_after > 0 && reports >= self.auto_delete_after
+    }
+
+    pub fn shadowbans(&self, reports: i64) -> bool {
+        self.auto_shadowban_after > 0 && reports >= self.auto_shadowban_after
+    }
+}
+
+impl Default for ReportPolicy {
+    fn default() -> Self {
+        Self {
+            auto_delete_after: DEFAULT_AUTO_DELETE_AFTER,
+            auto_shadowban_after: DEFAULT_AUTO_SHADOWBAN_AFTER,
+        }
+    }
+}
+
+/// Tracks who reported each message and freezes automatic enforcement
+/// when the reports look like a brigade rather than independent users
+#[derive(Clone)]
+pub struct ReportGuard {
+    redis: RedisClient,
+    /// Fallback auto-actions applied before a moderator gets to a report
+    pub policy: ReportPolicy,
+}
+
+impl ReportGuard {
+    pub fn new(redis: RedisClient) -> Self {
+        Self { redis, policy: ReportPolicy::default() }
+    }
+
+    pub fn with_policy(mut self, policy: ReportPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Mark a reported message as queued for review
+    /// Returns false if it already was
+    pub async fn mark_queued(&self, message_id: &str) -> Result<bool> {
+        self.redis
+            .set_nx_ex(&keys::report_queued(message_id), "1", QUEUED_TTL_SECONDS)
+            .await
+            .map_err(|e| anyhow!("Failed to mark report queued: {}", e))
+    }
+
+    /// Add a report to the message's history and analyze the result
+    pub async fn record(&self, message_id: &str, source: &ReportSource) -> Result<BrigadeVerdict> {
+        let key = keys::report_sources(message_id);
+        let json = serde_json::to_string(source)?;
+
+        self.redis
+            .pipeline()
+            .lpush(&key, &json)
+            .ltrim(&key, 0, REPORT_HISTORY_SIZE as isize - 1)
+            .expire(&key, REPORT_HISTORY_TTL_SECONDS)
+            .execute()
+            .await
+            .map_err(|e| anyhow!("Failed to record report source: {}", e))?;
+
+        let mut sources: Vec<ReportSource> = self.redis
+            .lrange(&key, 0, -1)
+            .await
+            .map_err(|e| anyhow!("Failed to load report sources: {}", e))?
+            .iter()
+            .filter_map(|s| serde_json::from_str(s).ok())
+            .collect();
+        // Stored newest first
+        sources.reverse();
+
+        Ok(BrigadeVerdict::analyze(&sources))
+    }
+
+    /// Stop report-driven auto-actions on a message
+    /// Returns false if it was already frozen
+    pub async fn freeze(&self, message_id: &str) -> Result<bool> {
+        self.redis
+            .set_nx_ex(&keys::report_frozen(message_id), "1", FREEZE_TTL_SECONDS)
+            .await
+            .map_err(|e| anyhow!("Failed to freeze report actions: {}", e))
+    }
+
+    pub async fn is_frozen(&self, message_id: &str) -> Result<bool> {
+        self.redis
+            .exists(&keys::report_frozen(message_id))
+            .await
+            .map_err(|e| anyhow!("Failed to check report freeze: {}", e))
+    }
+}
+
+/// Network prefix an address belongs to, so a handful of addresses from
+/// one provider block are treated as one source
+fn network_of(ip: &str) -> String {
+    match ip.parse::<std::net::IpAddr>() {
+        Ok(std::net::IpAddr::V4(v4)) => {
+            let [a, b, c, _] = v4.octets();
+            format!("{}.{}.{}.0/24", a, b, c)
+        }
+        Ok(std::net::IpAddr::V6(v6)) => {
+            let s = v6.segments();
+            format!("{:x}:{:x}:{:x}::/48", s[0], s[1], s[2])
+        }
+        Err(_) => ip.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_network_is_correlated() {
+        let sources = vec![
+            ReportSource::new("a", "203.0.113.10", 0),
+            ReportSource::new("b", "203.0.113.77", 600),
+            ReportSource::new("c", "198.51.100.4", 1200),
+        ];
+        let verdict = BrigadeVerdict::analyze(&sources);
+        assert_eq!(verdict.max_from_network, 2);
+        assert!(verdict.is_correlated());
+    }
+
+    #[test]
+    fn test_independent_reports_are_not_correlated() {
+        let sources = vec![
+            ReportSource::new("a", "203.0.113.10", 0),
+            ReportSource::new("b", "198.51.100.4", 900),
+            ReportSource::new("c", "192.0.2.8", 4000),
+        ];
+        assert!(!BrigadeVerdict::analyze(&sources).is_correlated());
+    }
+
+    #[test]
+    fn test_zero_threshold_turns_auto_action_off() {
+        let policy = ReportPolicy { auto_delete_after: 0, auto_shadowban_after: 3 };
+        assert!(!policy.deletes(50));
+        assert!(!policy.shadowbans(2));
+        assert!(policy.shadowbans(3));
+        assert!(ReportPolicy::default().deletes(5));
+    }
+
+    #[test]
+    fn test_burst_is_correlated() {
+        let sources = vec![
+            ReportSource::new("a", "203.0.113.10", 100),
+            ReportSource::new("b", "198.51.100.4", 120),
+            ReportSource::new("c", "192.0.2.8", 150),
+        ];
+        let verdict = BrigadeVerdict::analyze(&sources);
+        assert_eq!(verdict.max_in_burst, 3);
+        assert!(verdict.is_correlated());
+    }
+}
@@ -0,0 +1,273 @@
+use crate::redis_client::RedisClient;
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::collections::HashSet;
+use crate::keys;
+
+/// How far back the analysis looks at an actor's reveals
+const ANALYSIS_WINDOW_SECONDS: u64 = 604800; // 7 days
+/// How long reveal edges are kept at all
+const EDGE_RETENTION_SECONDS: i64 = 2592000; // 30 days
+/// How long a harvester loses reveal ability
+const REVOCATION_SECONDS: u64 = 2592000; // 30 days
+
+/// Minimum distinct posters revealed in the window before a pattern is considered
+const MIN_DISTINCT_POSTERS: usize = 25;
+/// Fraction of reveals that must hit a poster not seen before (breadth over depth)
+const MIN_BREADTH_RATIO: f64 = 0.9;
+/// Harvesting must be sustained across days or spread across cities
+const MIN_ACTIVE_DAYS: usize = 3;
+const MIN_DISTINCT_CITIES: usize = 3;
+
+/// One contact reveal: a composite key looked up a poster's number
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RevealEdge {
+    pub poster: String,
+    pub city: Option<String>,
+    pub timestamp: u64,
+}
+
+impl RevealEdge {
+    fn to_member(&self, message_id: &str) -> String {
+        format!("{}|{}|{}", message_id, self.poster, self.city.as_deref().unwrap_or(""))
+    }
+
+    fn from_member(member: &str, timestamp: u64) -> Option<Self> {
+        let mut parts = member.splitn(3, '|');
+        let _message_id = parts.next()?;
+        let poster = parts.next()?.to_string();
+        let city = parts.next().filter(|c| !c.is_empty()).map(str::to_string);
+        Some(Self { poster, city, timestamp })
+    }
+}
+
+/// Summary of an actor's reveal behaviour over the analysis window
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RevealPattern {
+    pub reveals: usize,
+    pub distinct_posters: usize,
+    pub distinct_cities: usize,
+    pub active_days: usize,
+    /// True when the actor sweeps across many posters rather than following up on a few
+    pub harvesting: bool,
+}
+
+impl RevealPattern {
+    pub fn analyze(edges: &[RevealEdge]) -> Self {
+        let distinct_posters = edges.iter().map(|e| e.poster.as_str()).collect::<HashSet<_>>().len();
+        let distinct_cities = edges.iter().filter_map(|e| e.city.as_deref()).collect::<HashSet<_>>().len();
+        let active_days = edges.iter().map(|e| e.timestamp / 86400).collect::<HashSet<_>>().len();
+
+        let breadth = if edges.is_empty() {
+            0.0
+        } else {
+            distinct_posters as f64 / edges.len() as f64
+        };
+
+        let harvesting = distinct_posters >= MIN_DISTINCT_POSTERS
+            && breadth >= MIN_BREADTH_RATIO
+            && (active_days >= MIN_ACTIVE_DAYS || distinct_cities >= MIN_DISTINCT_CITIES);
+
+        Self {
+            reveals: edges.len(),
+            distinct_posters,
+            distinct_cities,
+            active_days,
+            harvesting,
+        }
+    }
+}
+
+/// Records who revealed which posters and revokes reveal ability from harvesters
+/// The per-hour rate limit stops bursts; this catches patient scrapers that stay
+/// under it for days
+#[derive(Clone)]
+pub struct RevealGraph {
+    redis: RedisClient,
+}
+
+impl RevealGraph {
+    pub fn new(redis: RedisClient) -> Self {
+        Self { redis }
+    }
+
+    /// Record a successful contact reveal
+    pub async fn record(&self, composite_key: &str, message_id: &str, edge: &RevealEdge) -> Result<()> {
+        let key = keys::reveals_by(composite_key);
+        self.redis
+            .zadd(&key, edge.timestamp as f64, &edge.to_member(message_id))
+            .await
+            .map_err(|e| anyhow!("Failed to record reveal: {}", e))?;
+        self.redis
+            .expire(&key, EDGE_RETENTION_SECONDS)
+            .await
+            .map_err(|e| anyhow!("Failed to record reveal: {}", e))?;
+        self.redis
+            .zadd(keys::ACTIVE_REVEALERS, edge.timestamp as f64, composite_key)
+            .await
+            .map_err(|e| anyhow!("Failed to record reveal: {}", e))?;
+        Ok(())
+    }
+
+    /// A composite key's reveals over the retention period, oldest first, by message id
+    pub async fn reveals(&self, composite_key: &str) -> Result<Vec<(String, RevealEdge)>> {
+        let entries = self.redis
+            .zrange_withscores(&keys::reveals_by(composite_key), 0, -1)
+            .await
+            .map_err(|e| anyhow!("Failed to load reveal edges: {}", e))?;
+        Ok(entries
+            .into_iter()
+            .filter_map(|(member, score)| {
+                let message_id = member.split('|').next()?.to_string();
+                RevealEdge::from_member(&member, score as u64).map(|edge| (message_id, edge))
+            })
+            .collect())
+    }
+
+    /// Whether a composite key has lost the ability to reveal contacts
+    pub async fn is_revoked(&self, composite_key: &str) -> Result<bool> {
+        self.redis
+            .exists(&keys::reveal_revoked(composite_key))
+            .await
+            .map_err(|e| anyhow!("Failed to check reveal revocation: {}", e))
+    }
+
+    /// Analyze every actor that revealed a contact within the window
+    /// Harvesters are revoked and flagged for review; returns the newly flagged actors
+    pub async fn analyze_recent(&self) -> Result<Vec<(String, RevealPattern)>> {
+        let now = now();
+        let since = now.saturating_sub(ANALYSIS_WINDOW_SECONDS) as f64;
+
+        // Actors idle for a whole window can no longer match
+        let _ = self.redis.zrembyscore(keys::ACTIVE_REVEALERS, 0.0, since).await;
+        let actors = self.redis
+            .zrangebyscore(keys::ACTIVE_REVEALERS, since, f64::INFINITY)
+            .await
+            .map_err(|e| anyhow!("Failed to list active revealers: {}", e))?;
+
+        let mut flagged = Vec::new();
+        for actor in actors {
+            if self.is_revoked(&actor).await? {
+                continue;
+            }
+
+            let key = keys::reveals_by(&actor);
+            let _ = self.redis.zrembyscore(&key, 0.0, since).await;
+            let edges: Vec<RevealEdge> = self.redis
+                .zrange_withscores(&key, 0, -1)
+                .await
+                .map_err(|e| anyhow!("Failed to load reveal edges: {}", e))?
+                .into_iter()
+                .filter_map(|(member, score)| RevealEdge::from_member(&member, score as u64))
+                .collect();
+
+            let pattern = RevealPattern::analyze(&edges);
+            if pattern.harvesting {
+                self.revoke(&actor, &pattern, now).await?;
+                flagged.push((actor, pattern));
+            }
+        }
+
+        Ok(flagged)
+    }
+
+    async fn revoke(&self, composite_key: &str, pattern: &RevealPattern, now: u64) -> Result<()> {
+        let summary = serde_json::to_string(pattern)?;
+        self.redis
+            .set_ex(&keys::reveal_revoked(composite_key), &summary, REVOCATION_SECONDS)
+            .await
+            .map_err(|e| anyhow!("Failed to revoke reveal ability: {}", e))?;
+        self.redis
+            .zadd(keys::FLAGGED_REVEALERS, now as f64, composite_key)
+            .await
+            .map_err(|e| anyhow!("Failed to flag revealer: {}", e))?;
+        Ok(())
+    }
+
+    /// Actors flagged for review, newest first, with the pattern that flagged them
+    pub async fn flagged(&self, limit: usize) -> Result<Vec<serde_json::Value>> {
+        let entries = self.redis
+            .zrange_withscores(keys::FLAGGED_REVEALERS, -(limit as isize), -1)
+            .await
+            .map_err(|e| anyhow!("Failed to list flagged revealers: {}", e))?;
+
+        let mut flagged = Vec::with_capacity(entries.len());
+        for (composite_key, flagged_at) in entries.into_iter().rev() {
+            let pattern = self.redis
+                .get(&keys::reveal_revoked(&composite_key))
+                .await
+                .map_err(|e| anyhow!("Failed to load revocation: {}", e))?
+                .and_then(|p| serde_json::from_str::<serde_json::Value>(&p).ok());
+            flagged.push(serde_json::json!({
+                "composite_key": composite_key,
+                "flagged_at": flagged_at as u64,
+                "revoked": pattern.is_some(),
+                "pattern": pattern,
+            }));
+        }
+        Ok(flagged)
+    }
+
+    /// Restore reveal ability after review and clear the flag
+    pub async fn restore(&self, composite_key: &str) -> Result<()> {
+        self.redis
+            .del(&keys::reveal_revoked(composite_key))
+            .await
+            .map_err(|e| anyhow!("Failed to restore reveal ability: {}", e))?;
+        // Start from a clean slate so the old edges don't immediately re-flag
+        let _ = self.redis.del(&keys::reveals_by(composite_key)).await;
+        let _ = self.redis.zrem(keys::FLAGGED_REVEALERS, composite_key).await;
+        Ok(())
+    }
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(poster: usize, city: &str, day: u64) -> RevealEdge {
+        RevealEdge {
+            poster: format!("poster-{}", poster),
+            city: Some(city.to_string()),
+            timestamp: day * 86400 + 3600,
+        }
+    }
+
+    #[test]
+    fn test_breadth_first_sweep_is_harvesting() {
+        // Five reveals a day for six days, every one a new poster
+        let edges: Vec<RevealEdge> = (0..30).map(|i| edge(i, "Pune", (i / 5) as u64)).collect();
+        let pattern = RevealPattern::analyze(&edges);
+
+        assert_eq!(pattern.distinct_posters, 30);
+        assert_eq!(pattern.active_days, 6);
+        assert!(pattern.harvesting);
+    }
+
+    #[test]
+    fn test_repeat_contacts_are_not_harvesting() {
+        // Someone following up with a handful of posters over and over
+        let edges: Vec<RevealEdge> = (0..60).map(|i| edge(i % 6, "Pune", (i / 5) as u64)).collect();
+        let pattern = RevealPattern::analyze(&edges);
+
+        assert_eq!(pattern.distinct_posters, 6);
+        assert!(!pattern.harvesting);
+    }
+
+    #[test]
+    fn test_member_roundtrip() {
+        let original = edge(1, "Mumbai", 2);
+        let member = original.to_member("msg-1");
+        assert_eq!(RevealEdge::from_member(&member, original.timestamp), Some(original));
+
+        let no_city = RevealEdge { poster: "p".to_string(), city: None, timestamp: 5 };
+        assert_eq!(RevealEdge::from_member(&no_city.to_member("m"), 5), Some(no_city));
+    }
+}
@@ -0,0 +1,118 @@
+use crate::models::ReportReason;
+use crate::redis_client::RedisClient;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use crate::keys;
+
+/// Oldest items are dropped beyond this many
+const REVIEW_QUEUE_MAX_LEN: isize = 10_000;
+
+/// Why something was placed in the moderator review queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ReviewReason {
+    /// A poster's recent messages look abusive together even though each one passed
+    PosterPattern { reasons: Vec<String> },
+    /// Reports against a message came from suspiciously correlated sources
+    ReportBrigade { reasons: Vec<String> },
+    /// A message received its first report; queued once per message
+    UserReports {
+        reported_browser_id: String,
+        /// Reason given with the first report, if any
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<ReportReason>,
+    },
+}
+
+/// An entry awaiting moderator review
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewItem {
+    pub id: String,
+    pub composite_key: String,
+    /// Message that triggered the placement, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    #[serde(flatten)]
+    pub reason: ReviewReason,
+    #[serde(default)]
+    pub details: serde_json::Value,
+    pub queued_at: u64,
+}
+
+impl ReviewItem {
+    pub fn new(composite_key: &str, message_id: Option<&str>, reason: ReviewReason) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            composite_key: composite_key.to_string(),
+            message_id: message_id.map(str::to_string),
+            reason,
+            details: serde_json::Value::Null,
+            queued_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        }
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = details;
+        self
+    }
+}
+
+/// Moderator review queue backed by a capped Redis list (newest first)
+#[derive(Clone)]
+pub struct ReviewQueue {
+    redis: RedisClient,
+}
+
+impl ReviewQueue {
+    pub fn new(redis: RedisClient) -> Self {
+        Self { redis }
+    }
+
+    pub async fn enqueue(&self, item: &ReviewItem) -> Result<()> {
+        let json = serde_json::to_string(item)?;
+        self.redis
+            .pipeline()
+            .lpush(keys::REVIEW_QUEUE, &json)
+            .ltrim(keys::REVIEW_QUEUE, 0, REVIEW_QUEUE_MAX_LEN - 1)
+            .execute()
+            .await
+            .map_err(|e| anyhow!("Failed to enqueue review item: {}", e))?;
+        metrics::counter!("moderation_review_queued_total", 1);
+        Ok(())
+    }
+
+    /// Most recent items, newest first
+    pub async fn list(&self, limit: usize) -> Result<Vec<ReviewItem>> {
+        let items = self.redis
+            .lrange(keys::REVIEW_QUEUE, 0, limit as isize - 1)
+            .await
+            .map_err(|e| anyhow!("Failed to list review queue: {}", e))?;
+        Ok(items.iter().filter_map(|i| serde_json::from_str(i).ok()).collect())
+    }
+
+    /// Remove an item once a moderator has decided on it
+    /// Returns None if no item has that id (already decided, or trimmed away)
+    pub async fn take(&self, id: &str) -> Result<Option<ReviewItem>> {
+        let items = self.redis
+            .lrange(keys::REVIEW_QUEUE, 0, -1)
+            .await
+            .map_err(|e| anyhow!("Failed to list review queue: {}", e))?;
+        let Some((raw, item)) = items.iter().find_map(|raw| {
+            serde_json::from_str::<ReviewItem>(raw)
+                .ok()
+                .filter(|item| item.id == id)
+                .map(|item| (raw, item))
+        }) else {
+            return Ok(None);
+        };
+        // Two moderators deciding the same item: only one of them removes it
+        let removed = self.redis
+            .lrem(keys::REVIEW_QUEUE, 1, raw)
+            .await
+            .map_err(|e| anyhow!("Failed to remove review item: {}", e))?;
+        Ok((removed > 0).then_some(item))
+    }
+}
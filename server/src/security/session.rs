@@ -0,0 +1,243 @@
+use crate::redis_client::RedisClient;
+use crate::security::TokenSigner;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::keys;
+
+/// Lifetime of an access token - kept short so a stolen token is only briefly useful
+pub const ACCESS_TOKEN_TTL_SECONDS: u64 = 900; // 15 minutes
+/// Lifetime of a session (and its refresh token) since the last refresh
+const REFRESH_TOKEN_TTL_SECONDS: u64 = 604800; // 7 days
+
+/// Claims signed into an anonymous access token
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessClaims {
+    pub sid: String,
+    /// Composite key the session was issued to
+    pub key: String,
+    pub exp: u64,
+}
+
+/// Server-side session record (the revocable half of a session)
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionRecord {
+    composite_key: String,
+    /// SHA-256 of the current refresh secret - rotated on every refresh
+    refresh_hash: String,
+    created_at: u64,
+}
+
+/// Tokens handed to the client when a session is created or refreshed
+#[derive(Debug, Serialize)]
+pub struct SessionTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_in: u64,
+}
+
+/// Anonymous session lifecycle: issue, refresh, revoke and clean up
+/// Access tokens are stateless signed tokens, but every use is checked against
+/// the Redis session record so revocation takes effect immediately
+#[derive(Clone)]
+pub struct SessionManager {
+    redis: RedisClient,
+    signer: TokenSigner,
+}
+
+impl SessionManager {
+    pub fn new(redis: RedisClient, server_secret: &str) -> Self {
+        Self {
+            redis,
+            signer: TokenSigner::new(server_secret, "anonymous-session"),
+        }
+    }
+
+    /// Create a new session bound to a composite key
+    pub async fn create(&self, composite_key: &str) -> Result<SessionTokens> {
+        let sid = uuid::Uuid::new_v4().simple().to_string();
+        let secret = uuid::Uuid::new_v4().simple().to_string();
+        let record = SessionRecord {
+            composite_key: composite_key.to_string(),
+            refresh_hash: hash_secret(&secret),
+            created_at: now(),
+        };
+
+        self.store(&sid, &record).await?;
+        Ok(self.tokens(&sid, composite_key, &secret))
+    }
+
+    /// Exchange a refresh token for a new access token and rotated refresh token
+    /// Presenting an already-rotated refresh token revokes the session, since it
+    /// means the token was copied
+    pub async fn refresh(&self, refresh_token: &str) -> Result<Option<SessionTokens>> {
+        let Some((sid, secret)) = refresh_token.split_once('.') else {
+            return Ok(None);
+        };
+
+        let Some(mut record) = self.load(sid).await? else {
+            return Ok(None);
+        };
+
+        if record.refresh_hash != hash_secret(secret) {
+            tracing::warn!(session = %sid, "refresh token reuse detected, revoking session");
+            self.revoke(sid, &record.composite_key).await?;
+            return Ok(None);
+        }
+
+        let new_secret = uuid::Uuid::new_v4().simple().to_string();
+        record.refresh_hash = hash_secret(&new_secret);
+        self.store(sid, &record).await?;
+
+        Ok(Some(self.tokens(sid, &record.composite_key, &new_secret)))
+    }
+
+    /// Verify an access token and check its session has not been revoked
+    pub async fn authenticate(&self, access_token: &str) -> Result<Option<AccessClaims>> {
+        let Some(claims) = verify_access_token(&self.signer, access_token, now()) else {
+            return Ok(None);
+        };
+
+        let active = self.redis
+            .exists(&keys::session(&claims.sid))
+            .await
+            .map_err(|e| anyhow!("Failed to check session: {}", e))?;
+
+        Ok(active.then_some(claims))
+    }
+
+    /// Revoke a single session
+    pub async fn revoke(&self, sid: &str, composite_key: &str) -> Result<()> {
+        self.redis
+            .del(&keys::session(sid))
+            .await
+            .map_err(|e| anyhow!("Failed to revoke session: {}", e))?;
+        let _ = self.redis.zrem(&keys::sessions_by_key(composite_key), sid).await;
+        let _ = self.redis.zrem(keys::SESSION_EXPIRY_INDEX, &expiry_member(sid, composite_key)).await;
+        Ok(())
+    }
+
+    /// Revoke every session issued to a composite key (e.g. on shadowban)
+    /// Returns the number of sessions revoked
+    pub async fn revoke_all(&self, composite_key: &str) -> Result<usize> {
+        let sids = self.redis
+            .zrange(&keys::sessions_by_key(composite_key), 0, -1)
+            .await
+            .map_err(|e| anyhow!("Failed to list sessions: {}", e))?;
+
+        for sid in &sids {
+            self.revoke(sid, composite_key).await?;
+        }
+        Ok(sids.len())
+    }
+
+    /// Drop index entries for sessions whose records have expired
+    /// Returns the number of expired sessions removed
+    pub async fn cleanup_expired(&self) -> Result<usize> {
+        let expired = self.redis
+            .zrangebyscore(keys::SESSION_EXPIRY_INDEX, 0.0, now() as f64)
+            .await
+            .map_err(|e| anyhow!("Failed to list expired sessions: {}", e))?;
+
+        for member in &expired {
+            if let Some((sid, composite_key)) = member.split_once(':') {
+                let _ = self.redis.zrem(&keys::sessions_by_key(composite_key), sid).await;
+            }
+            let _ = self.redis.zrem(keys::SESSION_EXPIRY_INDEX, member).await;
+        }
+        Ok(expired.len())
+    }
+
+    async fn store(&self, sid: &str, record: &SessionRecord) -> Result<()> {
+        let json = serde_json::to_string(record)
+            .map_err(|e| anyhow!("Failed to serialize session: {}", e))?;
+        let expires_at = (now() + REFRESH_TOKEN_TTL_SECONDS) as f64;
+
+        self.redis
+            .set_ex(&keys::session(sid), &json, REFRESH_TOKEN_TTL_SECONDS)
+            .await
+            .map_err(|e| anyhow!("Failed to store session: {}", e))?;
+        self.redis
+            .zadd(&keys::sessions_by_key(&record.composite_key), expires_at, sid)
+            .await
+            .map_err(|e| anyhow!("Failed to index session: {}", e))?;
+        self.redis
+            .expire(&keys::sessions_by_key(&record.composite_key), REFRESH_TOKEN_TTL_SECONDS as i64)
+            .await
+            .map_err(|e| anyhow!("Failed to index session: {}", e))?;
+        self.redis
+            .zadd(keys::SESSION_EXPIRY_INDEX, expires_at, &expiry_member(sid, &record.composite_key))
+            .await
+            .map_err(|e| anyhow!("Failed to index session: {}", e))?;
+        Ok(())
+    }
+
+    async fn load(&self, sid: &str) -> Result<Option<SessionRecord>> {
+        let json = self.redis
+            .get(&keys::session(sid))
+            .await
+            .map_err(|e| anyhow!("Failed to load session: {}", e))?;
+        Ok(json.and_then(|j| serde_json::from_str(&j).ok()))
+    }
+
+    fn tokens(&self, sid: &str, composite_key: &str, secret: &str) -> SessionTokens {
+        SessionTokens {
+            access_token: issue_access_token(&self.signer, sid, composite_key, now()),
+            refresh_token: format!("{}.{}", sid, secret),
+            expires_in: ACCESS_TOKEN_TTL_SECONDS,
+        }
+    }
+}
+
+fn issue_access_token(signer: &TokenSigner, sid: &str, composite_key: &str, now: u64) -> String {
+    signer.sign(&AccessClaims {
+        sid: sid.to_string(),
+        key: composite_key.to_string(),
+        exp: now + ACCESS_TOKEN_TTL_SECONDS,
+    })
+}
+
+fn verify_access_token(signer: &TokenSigner, token: &str, now: u64) -> Option<AccessClaims> {
+    let claims: AccessClaims = signer.verify(token)?;
+    (claims.exp >= now).then_some(claims)
+}
+
+fn expiry_member(sid: &str, composite_key: &str) -> String {
+    format!("{}:{}", sid, composite_key)
+}
+
+fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_access_token_expires() {
+        let signer = TokenSigner::new("secret", "anonymous-session");
+        let token = issue_access_token(&signer, "sid", "key", 1_000);
+
+        let claims = verify_access_token(&signer, &token, 1_000).unwrap();
+        assert_eq!(claims.sid, "sid");
+        assert_eq!(claims.key, "key");
+        assert!(verify_access_token(&signer, &token, 1_000 + ACCESS_TOKEN_TTL_SECONDS + 1).is_none());
+    }
+
+    #[test]
+    fn test_access_token_bound_to_purpose() {
+        let signer = TokenSigner::new("secret", "anonymous-session");
+        let token = issue_access_token(&signer, "sid", "key", 1_000);
+
+        let admin_signer = TokenSigner::new("secret", "admin-session");
+        assert!(verify_access_token(&admin_signer, &token, 1_000).is_none());
+    }
+}
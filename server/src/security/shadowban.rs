@@ -1,4 +1,7 @@
 use crate::redis_client::RedisClient;
+use crate::security::SessionManager;
+use crate::security::enforcement::EnforcementRecord;
+use crate::keys;
 use anyhow::{Result, anyhow};
 
 /// Manages shadowban functionality for users
@@ -6,11 +9,12 @@ use anyhow::{Result, anyhow};
 #[derive(Clone)]
 pub struct ShadowbanManager {
     redis: RedisClient,
+    sessions: SessionManager,
 }
 
 impl ShadowbanManager {
-    pub fn new(redis: RedisClient) -> Self {
-        Self { redis }
+    pub fn new(redis: RedisClient, sessions: SessionManager) -> Self {
+        Self { redis, sessions }
     }
 
     /// Check if a composite key is shadowbanned
@@ -21,7 +25,7 @@ impl ShadowbanManager {
     /// # Returns
     /// True if the user is shadowbanned, false otherwise
     pub async fn is_shadowbanned(&self, composite_key: &str) -> Result<bool> {
-        let key = format!("shadowban:{}", composite_key);
+        let key = keys::shadowban(composite_key);
         self.redis
             .exists(&key)
             .await
@@ -32,16 +36,17 @@ impl ShadowbanManager {
     /// 
     /// # Arguments
     /// * `composite_key` - The composite key to shadowban
-    /// * `reason` - Optional reason for the shadowban (for admin tracking)
+    /// * `record` - Why, by whom and from where (for admin tracking and appeals)
     /// * `duration_seconds` - Optional duration in seconds (None = permanent)
     pub async fn shadowban(
         &self,
         composite_key: &str,
-        reason: Option<&str>,
+        record: EnforcementRecord,
         duration_seconds: Option<u64>,
     ) -> Result<()> {
-        let key = format!("shadowban:{}", composite_key);
-        let value = reason.unwrap_or("no_reason");
+        let key = keys::shadowban(composite_key);
+        let value = record.expiring_in(duration_seconds).to_json();
+        let value = value.as_str();
 
         match duration_seconds {
             Some(duration) => {
@@ -59,6 +64,9 @@ impl ShadowbanManager {
             }
         }
 
+        // Outstanding sessions must not outlive the ban
+        self.sessions.revoke_all(composite_key).await?;
+
         Ok(())
     }
 
@@ -68,7 +76,7 @@ impl ShadowbanManager {
     /// * `composite_key` - The composite key to un-shadowban
     #[allow(dead_code)]
     pub async fn remove_shadowban(&self, composite_key: &str) -> Result<()> {
-        let key = format!("shadowban:{}", composite_key);
+        let key = keys::shadowban(composite_key);
         self.redis
             .del(&key)
             .await
@@ -76,21 +84,20 @@ impl ShadowbanManager {
         Ok(())
     }
 
-    /// Get the reason for a shadowban (if available)
-    #[allow(dead_code)]
-    pub async fn get_shadowban_reason(&self, composite_key: &str) -> Result<Option<String>> {
-        let key = format!("shadowban:{}", composite_key);
+    /// Get the metadata recorded with a shadowban (if banned)
+    pub async fn get_shadowban(&self, composite_key: &str) -> Result<Option<EnforcementRecord>> {
+        let key = keys::shadowban(composite_key);
         self.redis
             .get(&key)
             .await
+            .map(|value| value.as_deref().map(EnforcementRecord::parse))
             .map_err(|e| anyhow!("Failed to get shadowban reason: {}", e))
     }
 
     /// Get the time-to-live for a shadowban in seconds
     /// Returns -1 for permanent bans, -2 if key doesn't exist
-    #[allow(dead_code)]
     pub async fn get_shadowban_ttl(&self, composite_key: &str) -> Result<i64> {
-        let key = format!("shadowban:{}", composite_key);
+        let key = keys::shadowban(composite_key);
         self.redis
             .ttl(&key)
             .await
@@ -100,25 +107,46 @@ impl ShadowbanManager {
     /// Increment the violation count for a composite key
     /// This can be used to auto-shadowban after a certain number of violations
     pub async fn increment_violations(&self, composite_key: &str) -> Result<i64> {
-        let key = format!("violations:{}", composite_key);
-        let count = self.redis
+        let key = keys::violations(composite_key);
+        // Set expiration for violations counter (e.g., reset after 24 hours)
+        let (count,): (i64,) = self.redis
+            .pipeline()
             .incr(&key)
+            .expire(&key, 86400).ignore()
+            .query()
             .await
             .map_err(|e| anyhow!("Failed to increment violations: {}", e))?;
         
-        // Set expiration for violations counter (e.g., reset after 24 hours)
-        self.redis
-            .expire(&key, 86400)
+        Ok(count)
+    }
+
+    /// Record a soft violation (content accepted after masking)
+    /// Kept apart from hard violations so masked posts never lead to an auto-shadowban
+    pub async fn increment_soft_violations(&self, composite_key: &str) -> Result<i64> {
+        let key = keys::soft_violations(composite_key);
+        let (count,): (i64,) = self.redis
+            .pipeline()
+            .incr(&key)
+            .expire(&key, 86400).ignore()
+            .query()
             .await
-            .map_err(|e| anyhow!("Failed to set expiration on violations: {}", e))?;
-        
+            .map_err(|e| anyhow!("Failed to increment soft violations: {}", e))?;
+
         Ok(count)
     }
 
     /// Get the current violation count for a composite key
     pub async fn get_violations(&self, composite_key: &str) -> Result<i64> {
-        let key = format!("violations:{}", composite_key);
-        match self.redis.get(&key).await {
+        self.read_count(&keys::violations(composite_key)).await
+    }
+
+    /// Get the current soft violation count for a composite key
+    pub async fn get_soft_violations(&self, composite_key: &str) -> Result<i64> {
+        self.read_count(&keys::soft_violations(composite_key)).await
+    }
+
+    async fn read_count(&self, key: &str) -> Result<i64> {
+        match self.redis.get(key).await {
             Ok(Some(count_str)) => {
                 count_str.parse::<i64>()
                     .map_err(|e| anyhow!("Failed to parse violation count: {}", e))
@@ -127,33 +155,4 @@ impl ShadowbanManager {
             Err(e) => Err(anyhow!("Failed to get violations: {}", e)),
         }
     }
-
-    /// Auto-shadowban if violations exceed a threshold
-    /// 
-    /// # Arguments
-    /// * `composite_key` - The composite key to check
-    /// * `threshold` - Number of violations before auto-shadowban
-    /// * `duration_seconds` - Duration of the auto-shadowban
-    /// 
-    /// # Returns
-    /// True if user was shadowbanned, false otherwise
-    pub async fn auto_shadowban_on_violations(
-        &self,
-        composite_key: &str,
-        threshold: i64,
-        duration_seconds: u64,
-    ) -> Result<bool> {
-        let violations = self.get_violations(composite_key).await?;
-        
-        if violations >= threshold {
-            self.shadowban(
-                composite_key,
-                Some(&format!("Auto-banned: {} violations", violations)),
-                Some(duration_seconds),
-            ).await?;
-            Ok(true)
-        } else {
-            Ok(false)
-        }
-    }
 }
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+
+use crate::security::fingerprint::UNKNOWN_FINGERPRINT;
+use crate::security::header_heuristics::HIGH_SCORE_THRESHOLD;
+use crate::security::middleware::SecurityContext;
+
+const DEFAULT_ANONYMOUS_SHARE: f64 = 0.5;
+const DEFAULT_UNVERIFIED_SHARE: f64 = 0.25;
+
+/// How much a request's identity is trusted, lowest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrustTier {
+    /// No usable fingerprint, or headers that look scripted
+    Unverified,
+    /// Valid fingerprint but no session
+    Anonymous,
+    /// Presented a live session issued to this composite key
+    Session,
+}
+
+impl TrustTier {
+    pub fn of(ctx: &SecurityContext) -> Self {
+        if ctx.session.as_ref().is_some_and(|session| session.key == ctx.composite_key) {
+            TrustTier::Session
+        } else if ctx.fingerprint == UNKNOWN_FINGERPRINT
+            || ctx.header_score.score >= HIGH_SCORE_THRESHOLD
+        {
+            TrustTier::Unverified
+        } else {
+            TrustTier::Anonymous
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrustTier::Unverified => "unverified",
+            TrustTier::Anonymous => "anonymous",
+            TrustTier::Session => "session",
+        }
+    }
+}
+
+/// Share of a capacity each tier may occupy before it is shed
+/// Sessions may use all of it; lower tiers stop being admitted earlier so
+/// headroom is left for trusted traffic when the server is busy
+/// Configured by `TRUST_TIER_ANONYMOUS_SHARE` and `TRUST_TIER_UNVERIFIED_SHARE` (0.0-1.0)
+#[derive(Debug, Clone, Copy)]
+pub struct TierShares {
+    anonymous: f64,
+    unverified: f64,
+}
+
+impl Default for TierShares {
+    fn default() -> Self {
+        Self {
+            anonymous: DEFAULT_ANONYMOUS_SHARE,
+            unverified: DEFAULT_UNVERIFIED_SHARE,
+        }
+    }
+}
+
+impl TierShares {
+    pub fn from_env() -> Self {
+        let share = |var: &str, default: f64| {
+            std::env::var(var)
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .map_or(default, |v| v.clamp(0.0, 1.0))
+        };
+        let anonymous = share("TRUST_TIER_ANONYMOUS_SHARE", DEFAULT_ANONYMOUS_SHARE);
+        Self {
+            anonymous,
+            // A lower tier never gets more room than the one above it
+            unverified: share("TRUST_TIER_UNVERIFIED_SHARE", DEFAULT_UNVERIFIED_SHARE).min(anonymous),
+        }
+    }
+
+    /// Most slots of `capacity` a tier may occupy
+    pub fn ceiling(&self, tier: TrustTier, capacity: usize) -> usize {
+        let share = match tier {
+            TrustTier::Session => return capacity,
+            TrustTier::Anonymous => self.anonymous,
+            TrustTier::Unverified => self.unverified,
+        };
+        (capacity as f64 * share).ceil() as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ceilings_by_tier() {
+        let shares = TierShares::default();
+        assert_eq!(shares.ceiling(TrustTier::Session, 32), 32);
+        assert_eq!(shares.ceiling(TrustTier::Anonymous, 32), 16);
+        assert_eq!(shares.ceiling(TrustTier::Unverified, 32), 8);
+        assert_eq!(shares.ceiling(TrustTier::Unverified, 1), 1);
+    }
+}
@@ -0,0 +1,152 @@
+use ipnet::IpNet;
+use std::env;
+use std::net::IpAddr;
+
+/// Loopback and private ranges, where load balancers normally sit
+const DEFAULT_TRUSTED_PROXIES: &[&str] = &[
+    "127.0.0.0/8",
+    "10.0.0.0/8",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    "::1/128",
+    "fc00::/7",
+];
+
+/// Proxies whose forwarded-IP headers are believed
+/// Headers from any other peer are ignored, so direct clients can't spoof
+/// their address to dodge bans or poison someone else's reputation
+#[derive(Debug, Clone)]
+pub struct TrustedProxies {
+    networks: Vec<IpNet>,
+}
+
+impl TrustedProxies {
+    pub fn new(networks: Vec<IpNet>) -> Self {
+        Self { networks }
+    }
+
+    /// Read TRUSTED_PROXY_CIDRS (comma-separated), defaulting to loopback and private
+    /// ranges when unset; an empty value trusts no proxy
+    pub fn from_env() -> Self {
+        match env::var("TRUSTED_PROXY_CIDRS") {
+            Ok(value) => Self::parse(&value),
+            Err(_) => Self::parse(&DEFAULT_TRUSTED_PROXIES.join(",")),
+        }
+    }
+
+    /// Bare addresses are accepted as single-host networks; invalid entries are skipped
+    pub fn parse(value: &str) -> Self {
+        let networks = value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .filter_map(|entry| {
+                let parsed = entry
+                    .parse::<IpNet>()
+                    .ok()
+                    .or_else(|| entry.parse::<IpAddr>().ok().map(IpNet::from));
+                if parsed.is_none() {
+                    tracing::warn!(cidr = %entry, "ignoring invalid trusted proxy CIDR");
+                }
+                parsed
+            })
+            .collect();
+        Self::new(networks)
+    }
+
+    pub fn is_trusted(&self, ip: IpAddr) -> bool {
+        let ip = canonical(ip);
+        self.networks.iter().any(|net| net.contains(&ip))
+    }
+
+    /// Work out the client address for a connection from `peer`
+    /// Forwarded headers only count when the peer is a trusted proxy; within
+    /// X-Forwarded-For the rightmost hop that isn't a trusted proxy is the client,
+    /// since everything left of it was supplied by that (untrusted) client
+    pub fn client_ip(
+        &self,
+        peer: IpAddr,
+        cf_connecting_ip: Option<&str>,
+        forwarded_for: Option<&str>,
+    ) -> IpAddr {
+        let peer = canonical(peer);
+        if !self.is_trusted(peer) {
+            return peer;
+        }
+
+        if let Some(ip) = cf_connecting_ip.and_then(|v| v.trim().parse::<IpAddr>().ok()) {
+            return canonical(ip);
+        }
+
+        let mut client = peer;
+        if let Some(forwarded) = forwarded_for {
+            for hop in forwarded.rsplit(',') {
+                let Ok(hop) = hop.trim().parse::<IpAddr>() else {
+                    // A garbled hop can't be attributed; stop at the last one we could
+                    break;
+                };
+                client = canonical(hop);
+                if !self.is_trusted(client) {
+                    break;
+                }
+            }
+        }
+        client
+    }
+}
+
+/// IPv4-mapped IPv6 addresses compare as plain IPv4
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_untrusted_peer_headers_ignored() {
+        let proxies = TrustedProxies::parse("10.0.0.0/8");
+        let client = proxies.client_ip(ip("203.0.113.5"), Some("1.2.3.4"), Some("1.2.3.4"));
+        assert_eq!(client, ip("203.0.113.5"));
+    }
+
+    #[test]
+    fn test_rightmost_untrusted_hop() {
+        let proxies = TrustedProxies::parse("10.0.0.0/8, 192.0.2.10");
+        // The client prepended a spoofed address; the proxies appended the real one
+        let client = proxies.client_ip(
+            ip("10.0.0.2"),
+            None,
+            Some("6.6.6.6, 198.51.100.7, 192.0.2.10"),
+        );
+        assert_eq!(client, ip("198.51.100.7"));
+    }
+
+    #[test]
+    fn test_all_hops_trusted_uses_leftmost() {
+        let proxies = TrustedProxies::parse("10.0.0.0/8");
+        let client = proxies.client_ip(ip("10.0.0.2"), None, Some("10.1.1.1, 10.2.2.2"));
+        assert_eq!(client, ip("10.1.1.1"));
+    }
+
+    #[test]
+    fn test_cloudflare_header_from_trusted_peer() {
+        let proxies = TrustedProxies::parse("::ffff:10.0.0.0/104, 10.0.0.0/8");
+        let client = proxies.client_ip(ip("::ffff:10.0.0.2"), Some("198.51.100.7"), None);
+        assert_eq!(client, ip("198.51.100.7"));
+    }
+
+    #[test]
+    fn test_empty_config_trusts_nothing() {
+        let proxies = TrustedProxies::parse("");
+        assert!(!proxies.is_trusted(ip("127.0.0.1")));
+    }
+}
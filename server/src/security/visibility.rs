@@ -0,0 +1,110 @@
+use crate::security::ip_reputation::{RiskLevel, VisibilityMode};
+use crate::security::trust_tier::TrustTier;
+
+/// Who gets to see an accepted post
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryPlan {
+    /// Stored in the feed and broadcast to every connection
+    Broadcast,
+    /// Shadow-throttled: not stored, delivered live only to the poster's own
+    /// connections, so from where the poster sits it looks published
+    PosterOnly,
+    /// Hard shadowban: not stored or delivered, the poster is just told it went through
+    Suppress,
+}
+
+/// Decides how a post is delivered from everything known about its poster
+/// Pure so the interplay of shadowbans, IP risk and trust can be tested exhaustively
+#[derive(Debug, Clone, Copy)]
+pub struct VisibilityPolicy {
+    /// The poster's composite key, or their fingerprint after reports, is shadowbanned
+    pub shadowbanned: bool,
+    /// IP risk level, already raised by header heuristics
+    pub risk_level: RiskLevel,
+    pub trust_tier: TrustTier,
+}
+
+impl DeliveryPlan {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryPlan::Broadcast => "broadcast",
+            DeliveryPlan::PosterOnly => "poster_only",
+            DeliveryPlan::Suppress => "suppress",
+        }
+    }
+}
+
+impl VisibilityPolicy {
+    pub fn delivery_plan(&self) -> DeliveryPlan {
+        if self.shadowbanned {
+            return DeliveryPlan::Suppress;
+        }
+
+        match (self.risk_level.visibility_mode(), self.trust_tier) {
+            (VisibilityMode::Banned, _) => DeliveryPlan::Suppress,
+            // Throttled posters with scripted-looking identities don't even get the echo
+            (VisibilityMode::Throttled, TrustTier::Unverified) => DeliveryPlan::Suppress,
+            (VisibilityMode::Throttled, _) => DeliveryPlan::PosterOnly,
+            (VisibilityMode::Normal, TrustTier::Unverified) => DeliveryPlan::PosterOnly,
+            (VisibilityMode::Normal, _) => DeliveryPlan::Broadcast,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVELS: [RiskLevel; 4] = [RiskLevel::Level0, RiskLevel::Level1, RiskLevel::Level2, RiskLevel::Level3];
+    const TIERS: [TrustTier; 3] = [TrustTier::Unverified, TrustTier::Anonymous, TrustTier::Session];
+
+    fn plan(shadowbanned: bool, risk_level: RiskLevel, trust_tier: TrustTier) -> DeliveryPlan {
+        VisibilityPolicy { shadowbanned, risk_level, trust_tier }.delivery_plan()
+    }
+
+    #[test]
+    fn test_shadowban_always_suppresses() {
+        for level in LEVELS {
+            for tier in TIERS {
+                assert_eq!(plan(true, level, tier), DeliveryPlan::Suppress, "{:?} {:?}", level, tier);
+            }
+        }
+    }
+
+    #[test]
+    fn test_banned_risk_level_suppresses() {
+        for tier in TIERS {
+            assert_eq!(plan(false, RiskLevel::Level3, tier), DeliveryPlan::Suppress);
+        }
+    }
+
+    #[test]
+    fn test_throttled_risk_level() {
+        assert_eq!(plan(false, RiskLevel::Level2, TrustTier::Unverified), DeliveryPlan::Suppress);
+        assert_eq!(plan(false, RiskLevel::Level2, TrustTier::Anonymous), DeliveryPlan::PosterOnly);
+        assert_eq!(plan(false, RiskLevel::Level2, TrustTier::Session), DeliveryPlan::PosterOnly);
+    }
+
+    #[test]
+    fn test_normal_risk_levels() {
+        for level in [RiskLevel::Level0, RiskLevel::Level1] {
+            assert_eq!(plan(false, level, TrustTier::Unverified), DeliveryPlan::PosterOnly);
+            assert_eq!(plan(false, level, TrustTier::Anonymous), DeliveryPlan::Broadcast);
+            assert_eq!(plan(false, level, TrustTier::Session), DeliveryPlan::Broadcast);
+        }
+    }
+
+    #[test]
+    fn test_higher_risk_never_widens_delivery() {
+        let rank = |plan: DeliveryPlan| match plan {
+            DeliveryPlan::Broadcast => 2,
+            DeliveryPlan::PosterOnly => 1,
+            DeliveryPlan::Suppress => 0,
+        };
+        for tier in TIERS {
+            for pair in LEVELS.windows(2) {
+                assert!(rank(plan(false, pair[1], tier)) <= rank(plan(false, pair[0], tier)));
+            }
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Extended profanity list with semantic variations and common typos
+const PROFANITY_WORDS: &[&str] = &[
+    // Original offensive words
+    "damn", "hell", "crap", "ass", "bitch", "bastard", "piss", "fuck", "shit",
+    "asshole", "dick", "cock", "pussy", "whore", "slut", "cunt",
+    // Semantic variations and euphemisms
+    "fk", "f*k", "f***", "fu*k", "fck", "fcuk",
+    "sh*t", "s*it", "sh1t", "shyt", "sheit",
+    "b*tch", "bit*h", "b!tch", "biatch", "btch",
+    "a**", "a$s", "azz", "arse",
+    "h*ll", "hel", "h3ll",
+    "d@mn", "damn", "dammit", "damnit",
+    "c*ck", "c0ck", "c**k", "cawk",
+    "pu$$y", "p*ssy", "puss1", "kitty", // some are context-dependent
+    "wh0re", "wh*re", "hoar",
+    "sl*t", "slyt", "sloot",
+    "c*nt", "cunt", "cnt", // might catch false positives
+    // Indian Hinglish variations with typos
+    "bc", "b.c", "b c", "bhd",
+    "mf", "m.f", "m f", "mofo",
+    // Extended Hinglish (case-insensitive handled by regex)
+    "lodu", "lod", "loda", "lodu",
+    "chutiya", "chut", "chutya", "chutiye",
+    "gaandu", "gandu", "gaand",
+    "harami", "haram", "haramkhor",
+    "madarchod", "madarc", "maadarc",
+    "behenchod", "bewakoof", "bevkoof",
+    "randi", "rand", "randiya",
+    "ullu", "ull",
+    "saali", "sali",
+    "teri", "tere",
+];
+
+/// Mild words that can be masked instead of blocking the whole message
+const MILD_PROFANITY_WORDS: &[&str] = &[
+    "damn", "dammit", "damnit", "hell", "crap", "piss", "arse", "ass", "bloody",
+    "ullu", "bewakoof", "bevkoof",
+];
+
+/// Hinglish patterns not covered by the word list
+const HINGLISH_PATTERNS: &[&str] = &[
+    r"(?i)\b(bc|bhosdike|lodu|chutiya|gaandu|gandu|harami|besharam)\b",
+    r"(?i)\b(madarchod|mdarc|behenchod|bevkuf|chakka)\b",
+    r"(?i)\b(randi|teri|terepa|saali|ullu|chakli)\b",
+];
+
+/// Known scam and link-shortener domains
+const SCAM_DOMAINS: &[&str] = &[
+    "t.me",
+    "telegram.me",
+    "telegram.org",
+    "bit.ly",
+    "tinyurl.com",
+    "goo.gl",
+    "rebrand.ly",
+    "ow.ly",
+    "lnk.co",
+    "short.link",
+    "bitly.com",
+    "adf.ly",
+    "j.mp",
+    "clickbank.net",
+];
+
+/// Words that mark a message as being about rentals
+const RENTAL_KEYWORDS: &[&str] = &[
+    "room", "rooms", "flat", "apartment", "bhk", "bh", "studio", "rent",
+    "rented", "rental", "lease", "property", "location", "area", "locality",
+    "available", "looking", "wanted", "accommodation", "lodging", "tenant",
+    "landlord", "owner", "deposit", "advance", "monthly", "furnished",
+    "unfurnished", "sharing", "pg", "hostel", "shared", "attached", "bathroom",
+    "kitchen", "parking", "vegetarian", "non-veg", "pets", "furnishing",
+];
+
+static BUILT_IN: Lazy<Arc<Wordlists>> = Lazy::new(|| Arc::new(Wordlists::from_extra(WordlistFile::default())));
+
+/// Entries added on top of the built-in lists
+/// `MODERATION_WORDLISTS_FILE` names a JSON file of this shape, e.g.
+/// `{"profanity": ["newword"], "scam_domains": ["scam.example"]}`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WordlistFile {
+    #[serde(default)]
+    profanity: Vec<String>,
+    #[serde(default)]
+    mild_profanity: Vec<String>,
+    /// Regular expressions, matched case-sensitively unless they say `(?i)`
+    #[serde(default)]
+    hinglish_patterns: Vec<String>,
+    #[serde(default)]
+    scam_domains: Vec<String>,
+    #[serde(default)]
+    rental_keywords: Vec<String>,
+}
+
+/// Words and patterns moderation and the content filter match against
+/// Built in, plus whatever the wordlist file adds; reloaded with the runtime
+/// config so moderators can add a word without a deploy
+#[derive(Debug)]
+pub struct Wordlists {
+    pub profanity: HashSet<String>,
+    /// Maskable rather than blocked when masking is configured
+    pub mild_profanity: HashSet<String>,
+    pub hinglish_patterns: Vec<Regex>,
+    pub scam_domains: Vec<String>,
+    pub rental_keywords: Vec<String>,
+}
+
+impl Wordlists {
+    /// The lists compiled into the binary
+    pub fn built_in() -> Arc<Self> {
+        BUILT_IN.clone()
+    }
+
+    /// Built-in lists extended by `MODERATION_WORDLISTS_FILE`, if set
+    /// An unreadable or invalid file falls back to the built-in lists
+    pub fn from_env() -> Arc<Self> {
+        let Some(path) = std::env::var("MODERATION_WORDLISTS_FILE").ok().filter(|p| !p.is_empty()) else {
+            return Self::built_in();
+        };
+        let json = match std::fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(e) => {
+                tracing::warn!(path = %path, error = %e, "failed to read MODERATION_WORDLISTS_FILE, using built-in lists");
+                return Self::built_in();
+            }
+        };
+        match serde_json::from_str(&json) {
+            Ok(extra) => Arc::new(Self::from_extra(extra)),
+            Err(e) => {
+                tracing::warn!(path = %path, error = %e, "invalid wordlists, using built-in lists");
+                Self::built_in()
+            }
+        }
+    }
+
+    fn from_extra(extra: WordlistFile) -> Self {
+        let words = |built_in: &[&str], extra: Vec<String>| -> HashSet<String> {
+            built_in
+                .iter()
+                .map(|w| w.to_string())
+                .chain(extra.into_iter().map(|w| w.trim().to_lowercase()))
+                .filter(|w| !w.is_empty())
+                .collect()
+        };
+        let list = |built_in: &[&str], extra: Vec<String>| -> Vec<String> {
+            let mut list: Vec<String> = built_in.iter().map(|w| w.to_string()).collect();
+            for entry in extra {
+                let entry = entry.trim().to_lowercase();
+                if !entry.is_empty() && !list.contains(&entry) {
+                    list.push(entry);
+                }
+            }
+            list
+        };
+
+        let hinglish_patterns = HINGLISH_PATTERNS
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .chain(extra.hinglish_patterns)
+            .filter_map(|pattern| match Regex::new(&pattern) {
+                Ok(re) => Some(re),
+                Err(e) => {
+                    tracing::warn!(pattern = %pattern, error = %e, "skipping invalid wordlist pattern");
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            profanity: words(PROFANITY_WORDS, extra.profanity),
+            mild_profanity: words(MILD_PROFANITY_WORDS, extra.mild_profanity),
+            hinglish_patterns,
+            scam_domains: list(SCAM_DOMAINS, extra.scam_domains),
+            rental_keywords: list(RENTAL_KEYWORDS, extra.rental_keywords),
+        }
+    }
+
+    /// Entries across every list, for the reload summary
+    pub fn len(&self) -> usize {
+        self.profanity.len()
+            + self.mild_profanity.len()
+            + self.hinglish_patterns.len()
+            + self.scam_domains.len()
+            + self.rental_keywords.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_entries_extend_the_built_in_lists() {
+        let extra: WordlistFile = serde_json::from_str(
+            r#"{"profanity": [" NewWord "], "scam_domains": ["scam.example", "bit.ly"], "hinglish_patterns": ["(?i)\\bnaya\\b", "("]}"#,
+        )
+        .unwrap();
+        let lists = Wordlists::from_extra(extra);
+
+        assert!(lists.profanity.contains("newword"));
+        assert!(lists.profanity.contains("fuck"));
+        assert_eq!(lists.scam_domains.len(), SCAM_DOMAINS.len() + 1);
+        // The invalid pattern is skipped, the rest still load
+        assert_eq!(lists.hinglish_patterns.len(), HINGLISH_PATTERNS.len() + 1);
+        assert!(serde_json::from_str::<WordlistFile>(r#"{"swear_words": []}"#).is_err());
+    }
+}
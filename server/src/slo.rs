@@ -0,0 +1,311 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const DEFAULT_WINDOW_SECONDS: u64 = 300;
+/// Per-series sample cap, so a hot route can't grow the window without bound
+const MAX_SAMPLES: usize = 2048;
+const DEFAULT_ERROR_RATE: f64 = 0.01;
+const DEFAULT_P95_LATENCY_MS: u64 = 500;
+const DEFAULT_REDIS_AVAILABILITY: f64 = 0.999;
+const DEFAULT_MODERATION_FAILURE_RATE: f64 = 0.05;
+
+/// SLO targets from `SLO_*`
+#[derive(Clone, Debug, Serialize)]
+pub struct SloTargets {
+    pub error_rate: f64,
+    pub p95_latency_ms: u64,
+    pub redis_availability: f64,
+    pub moderation_failure_rate: f64,
+}
+
+impl SloTargets {
+    fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        }
+        Self {
+            error_rate: var("SLO_ERROR_RATE", DEFAULT_ERROR_RATE),
+            p95_latency_ms: var("SLO_P95_LATENCY_MS", DEFAULT_P95_LATENCY_MS),
+            redis_availability: var("SLO_REDIS_AVAILABILITY", DEFAULT_REDIS_AVAILABILITY),
+            moderation_failure_rate: var("SLO_MODERATION_FAILURE_RATE", DEFAULT_MODERATION_FAILURE_RATE),
+        }
+    }
+}
+
+/// Time-bounded window of samples
+struct Window<T> {
+    samples: VecDeque<(Instant, T)>,
+}
+
+impl<T> Default for Window<T> {
+    fn default() -> Self {
+        Self { samples: VecDeque::new() }
+    }
+}
+
+impl<T: Copy> Window<T> {
+    fn record(&mut self, now: Instant, sample: T) {
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((now, sample));
+    }
+
+    fn prune(&mut self, now: Instant, span: Duration) {
+        while self.samples.front().is_some_and(|(at, _)| now.duration_since(*at) > span) {
+            self.samples.pop_front();
+        }
+    }
+
+    fn values(&self) -> impl Iterator<Item = T> + '_ {
+        self.samples.iter().map(|(_, sample)| *sample)
+    }
+}
+
+#[derive(Default)]
+struct Windows {
+    /// (latency, server error) per "METHOD /matched/path"
+    routes: HashMap<String, Window<(Duration, bool)>>,
+    /// Redis health-check outcomes
+    redis: Window<bool>,
+    /// Moderation provider call outcomes
+    moderation: Window<bool>,
+}
+
+/// Rolling self-monitoring of request errors, latency and dependency health
+/// against configured SLO targets, served at `/admin/slo`
+/// Everything is held in memory for the last `SLO_WINDOW_SECONDS`, per instance
+#[derive(Clone)]
+pub struct SloTracker {
+    window: Duration,
+    targets: SloTargets,
+    windows: Arc<Mutex<Windows>>,
+}
+
+impl SloTracker {
+    pub fn from_env() -> Self {
+        let window = std::env::var("SLO_WINDOW_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_WINDOW_SECONDS)
+            .max(1);
+        Self::new(Duration::from_secs(window), SloTargets::from_env())
+    }
+
+    pub fn new(window: Duration, targets: SloTargets) -> Self {
+        Self { window, targets, windows: Arc::new(Mutex::new(Windows::default())) }
+    }
+
+    pub fn record_request(&self, route: String, latency: Duration, server_error: bool) {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+        let series = windows.routes.entry(route).or_default();
+        series.prune(now, self.window);
+        series.record(now, (latency, server_error));
+    }
+
+    pub fn record_redis_check(&self, ok: bool) {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+        windows.redis.prune(now, self.window);
+        windows.redis.record(now, ok);
+    }
+
+    pub fn record_moderation_call(&self, ok: bool) {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+        windows.moderation.prune(now, self.window);
+        windows.moderation.record(now, ok);
+    }
+
+    /// Current window measured against the targets
+    pub fn report(&self) -> SloReport {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+        let targets = &self.targets;
+        let p95_target = Duration::from_millis(targets.p95_latency_ms);
+
+        windows.routes.retain(|_, series| {
+            series.prune(now, self.window);
+            !series.samples.is_empty()
+        });
+        let mut routes: Vec<RouteSlo> = windows.routes
+            .iter()
+            .map(|(route, series)| {
+                let requests = series.samples.len();
+                let errors = series.values().filter(|(_, error)| *error).count();
+                let p95 = p95(series.values().map(|(latency, _)| latency).collect());
+                let error_rate = errors as f64 / requests as f64;
+                RouteSlo {
+                    route: route.clone(),
+                    requests,
+                    error_rate,
+                    p95_latency_ms: p95.as_secs_f64() * 1000.0,
+                    ok: error_rate <= targets.error_rate && p95 <= p95_target,
+                }
+            })
+            .collect();
+        routes.sort_by(|a, b| a.route.cmp(&b.route));
+
+        let requests: usize = routes.iter().map(|route| route.requests).sum();
+        let errors: f64 = routes.iter().map(|route| route.error_rate * route.requests as f64).sum();
+        let error_rate = if requests == 0 { 0.0 } else { errors / requests as f64 };
+
+        windows.redis.prune(now, self.window);
+        windows.moderation.prune(now, self.window);
+        let redis = Ratio::of(windows.redis.values());
+        let moderation = Ratio::of(windows.moderation.values());
+        // No data yet counts as meeting the target
+        let redis_availability = redis.map_or(1.0, |r| r.success_rate());
+        let moderation_failure_rate = moderation.map_or(0.0, |r| 1.0 - r.success_rate());
+
+        let error_rate_ok = error_rate <= targets.error_rate;
+        let redis_ok = redis_availability >= targets.redis_availability;
+        let moderation_ok = moderation_failure_rate <= targets.moderation_failure_rate;
+
+        SloReport {
+            ok: error_rate_ok && redis_ok && moderation_ok && routes.iter().all(|route| route.ok),
+            window_seconds: self.window.as_secs(),
+            targets: targets.clone(),
+            requests,
+            error_rate,
+            error_rate_ok,
+            routes,
+            redis_availability,
+            redis_checks: redis.map_or(0, |r| r.total),
+            redis_ok,
+            moderation_failure_rate,
+            moderation_calls: moderation.map_or(0, |r| r.total),
+            moderation_ok,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Ratio {
+    successes: usize,
+    total: usize,
+}
+
+impl Ratio {
+    fn of(outcomes: impl Iterator<Item = bool>) -> Option<Self> {
+        let (successes, total) = outcomes.fold((0, 0), |(s, t), ok| (s + ok as usize, t + 1));
+        (total > 0).then_some(Self { successes, total })
+    }
+
+    fn success_rate(&self) -> f64 {
+        self.successes as f64 / self.total as f64
+    }
+}
+
+fn p95(mut latencies: Vec<Duration>) -> Duration {
+    if latencies.is_empty() {
+        return Duration::ZERO;
+    }
+    latencies.sort_unstable();
+    latencies[(latencies.len() * 95).div_ceil(100) - 1]
+}
+
+#[derive(Debug, Serialize)]
+pub struct RouteSlo {
+    pub route: String,
+    pub requests: usize,
+    pub error_rate: f64,
+    pub p95_latency_ms: f64,
+    pub ok: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SloReport {
+    /// Every objective below is met
+    pub ok: bool,
+    pub window_seconds: u64,
+    pub targets: SloTargets,
+    pub requests: usize,
+    pub error_rate: f64,
+    pub error_rate_ok: bool,
+    pub routes: Vec<RouteSlo>,
+    pub redis_availability: f64,
+    pub redis_checks: usize,
+    pub redis_ok: bool,
+    pub moderation_failure_rate: f64,
+    pub moderation_calls: usize,
+    pub moderation_ok: bool,
+}
+
+/// Route layer recording each request's latency and whether it failed server-side
+pub async fn slo_middleware(State(slo): State<SloTracker>, req: Request, next: Next) -> Response {
+    let route = format!(
+        "{} {}",
+        req.method(),
+        req.extensions().get::<MatchedPath>().map_or("unmatched", |path| path.as_str())
+    );
+    let started = Instant::now();
+    let response = next.run(req).await;
+    slo.record_request(route, started.elapsed(), response.status().is_server_error());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> SloTracker {
+        SloTracker::new(
+            Duration::from_secs(60),
+            SloTargets {
+                error_rate: 0.1,
+                p95_latency_ms: 100,
+                redis_availability: 0.99,
+                moderation_failure_rate: 0.2,
+            },
+        )
+    }
+
+    #[test]
+    fn test_empty_window_meets_targets() {
+        let report = tracker().report();
+        assert!(report.ok);
+        assert_eq!(report.requests, 0);
+    }
+
+    #[test]
+    fn test_route_objectives() {
+        let slo = tracker();
+        for i in 0..20 {
+            slo.record_request("GET /messages".to_string(), Duration::from_millis(10), false);
+            // One slow failing request in twenty on the posting route
+            slo.record_request("POST /messages".to_string(), Duration::from_millis(if i == 0 { 900 } else { 20 }), i < 5);
+        }
+
+        let report = slo.report();
+        assert_eq!(report.requests, 40);
+        assert!((report.error_rate - 0.125).abs() < 1e-9);
+        assert!(!report.error_rate_ok);
+        let posting = report.routes.iter().find(|r| r.route == "POST /messages").unwrap();
+        assert!(!posting.ok);
+        assert!(report.routes.iter().find(|r| r.route == "GET /messages").unwrap().ok);
+    }
+
+    #[test]
+    fn test_dependency_objectives() {
+        let slo = tracker();
+        for i in 0..10 {
+            slo.record_redis_check(i != 0);
+            slo.record_moderation_call(i % 10 != 0);
+        }
+
+        let report = slo.report();
+        assert!((report.redis_availability - 0.9).abs() < 1e-9);
+        assert!(!report.redis_ok);
+        assert!((report.moderation_failure_rate - 0.1).abs() < 1e-9);
+        assert!(report.moderation_ok);
+    }
+}
@@ -1,69 +1,255 @@
-use crate::models::ChatMessage;
+use crate::models::{ChatMessage, ListingEvent};
 use crate::redis_client::RedisClient;
+use crate::security::rate_limiter::RateLimitConfig;
+use crate::security::report_guard::ReportPolicy;
 use crate::security::{
     CompositeKeyGenerator,
     RateLimiter,
     ShadowbanManager,
-    ContentFilter,
     IpReputationManager,
     BurstProfiler,
-    GovernorRateLimiter,
     ModerationService,
+    CityPolicyStore,
+    AuditLog,
+    SessionManager,
+    HotspotTracker,
+    RevealGraph,
+    ContextWindow,
+    ReviewQueue,
+    ReportGuard,
+    TrustedProxies,
+    FingerprintRegistry,
+    CorrectionTokens,
+    CitySurgeDetector,
+    CaptchaVerifier,
+    GeoIp,
 };
-use crate::scaling::{RedisBroadcastService, MetricsTracker};
+use crate::admin::AdminConfig;
+use crate::pagination::CursorSigner;
+use crate::listing_stats::ListingStatsTracker;
+use crate::pins::PinnedListings;
+use crate::cities::{CityLaunches, CityRegistry};
+use crate::translation::Translator;
+use crate::stats::StatsService;
+use crate::moderation_dataset::ModerationDataset;
+use crate::load_shedding::LoadShedder;
+use crate::slo::SloTracker;
+use crate::config::ConfigHandle;
+use crate::recorder::RequestRecorder;
+use crate::consent::ConsentStore;
+use crate::dependencies::DependencyTracker;
+use crate::ws_backpressure::WsBackpressure;
+use crate::concurrency::ConcurrencyLimit;
+use crate::ws_compression::WsCompression;
+use crate::ws_handover::WsHandover;
+use crate::poster_limits::PosterLimits;
+use crate::cache::{KeyedRefreshCache, RefreshCache, SnapshotCache};
+use crate::keys;
+use crate::search::SearchIndex;
+use crate::moderation_queue::ModerationQueue;
+use crate::scaling::{RedisBroadcastService, MetricsTracker, PubSubWatchdog, OutboxEntry};
 use anyhow::Result;
 use std::env;
+use std::time::Duration;
 
-const MESSAGES_KEY: &str = "messages";
-const MESSAGE_KEY_PREFIX: &str = "message:";
-const MESSAGE_TTL: u64 = 172800; // 48 hours in seconds
-const PUBSUB_CHANNEL: &str = "chat:messages";
+pub const MESSAGE_TTL: u64 = 172800; // 48 hours in seconds
+const INDEX_BATCH_SIZE: isize = 500;
+/// COUNT hint for SCAN steps when iterating message keys
+const SCAN_BATCH_SIZE: usize = 500;
+/// How long a loaded feed is served from memory (override with FEED_CACHE_MAX_AGE_MS)
+/// Local writes invalidate it at once; other instances' writes show up within this window
+const DEFAULT_FEED_CACHE_MAX_AGE_MS: u64 = 2000;
+/// How long public stats are served from memory (override with STATS_CACHE_MAX_AGE_MS)
+/// Stats endpoints aren't rate limited, so polling them must not reach Redis every time
+const DEFAULT_STATS_CACHE_MAX_AGE_MS: u64 = 2000;
+/// Registered cities whose view counts are cached at once (one entry per city per day)
+const CITY_VIEWS_CACHE_CAPACITY: usize = 1000;
 
 #[derive(Clone)]
 pub struct AppState {
     pub redis: RedisClient,
     pub key_generator: CompositeKeyGenerator,
     pub rate_limiter: RateLimiter,
-    pub governor_limiter: GovernorRateLimiter,
+    /// Rate limit windows and quotas, from the environment at startup
+    pub rate_limits: RateLimitConfig,
+    pub hotspots: HotspotTracker,
     pub shadowban_manager: ShadowbanManager,
-    pub content_filter: ContentFilter,
+    pub sessions: SessionManager,
     pub ip_reputation: IpReputationManager,
     pub burst_profiler: BurstProfiler,
     pub broadcast: RedisBroadcastService,
     pub metrics: MetricsTracker,
-    pub moderation_service: ModerationService,
+    pub pubsub_watchdog: PubSubWatchdog,
+    /// Reloadable moderation, escalation and quota settings
+    pub config: ConfigHandle,
+    pub cursor_signer: CursorSigner,
+    pub audit_log: AuditLog,
+    pub reveal_graph: RevealGraph,
+    pub listing_stats: ListingStatsTracker,
+    pub pins: PinnedListings,
+    pub cities: CityLaunches,
+    pub city_registry: CityRegistry,
+    pub translator: Translator,
+    pub context_window: ContextWindow,
+    pub review_queue: ReviewQueue,
+    pub report_guard: ReportGuard,
+    pub trusted_proxies: TrustedProxies,
+    pub geoip: GeoIp,
+    pub fingerprints: FingerprintRegistry,
+    pub stats: StatsService,
+    pub moderation_dataset: ModerationDataset,
+    pub load_shedder: LoadShedder,
+    pub slo: SloTracker,
+    pub city_surges: CitySurgeDetector,
+    pub recorder: RequestRecorder,
+    pub consents: ConsentStore,
+    pub dependencies: DependencyTracker,
+    pub ws_backpressure: WsBackpressure,
+    /// Ceiling on posts in flight, over HTTP and WebSockets alike
+    pub posting_limit: ConcurrencyLimit,
+    pub ws_compression: WsCompression,
+    pub ws_handover: WsHandover,
+    pub poster_limits: PosterLimits,
+    /// Every stored message, as last loaded by `get_messages`
+    pub feed_cache: SnapshotCache<Vec<ChatMessage>>,
+    /// Today's (unique visitors, message count)
+    pub daily_stats_cache: RefreshCache<(u64, u64)>,
+    /// Today's views by city
+    pub city_views_cache: KeyedRefreshCache<String, u64>,
+    pub corrections: CorrectionTokens,
+    pub search: SearchIndex,
+    pub moderation_queue: ModerationQueue,
+    pub captcha: CaptchaVerifier,
+    pub admin: AdminConfig,
+}
+
+/// Where the next index page starts after `batch`: at its last score, skipping the
+/// members with that score already read (several listings can share a second)
+fn next_page_start(batch: &[(String, f64)], previous: (f64, isize)) -> (f64, isize) {
+    let Some((_, last)) = batch.last() else {
+        return previous;
+    };
+    let tied = batch.iter().rev().take_while(|(_, score)| score == last).count() as isize;
+    if *last == previous.0 {
+        (previous.0, previous.1 + tied)
+    } else {
+        (*last, tied)
+    }
 }
 
 impl AppState {
     /// Create a new AppState with Redis connection
     pub async fn new(redis_url: &str, server_secret: String) -> Result<Self> {
         let redis = RedisClient::new(redis_url).await?;
+        let cursor_signer = CursorSigner::new(&server_secret);
+        let admin = AdminConfig::from_env(&server_secret);
+        let sessions = SessionManager::new(redis.clone(), &server_secret);
+        let moderation_dataset = ModerationDataset::new(redis.clone(), &server_secret);
+        let recorder = RequestRecorder::from_env(redis.clone(), &server_secret);
+        let consents = ConsentStore::from_env(redis.clone());
+        let load_shedder = LoadShedder::from_env(redis.latency());
+        let slo = SloTracker::from_env();
+        let dependencies = DependencyTracker::default();
+        let poster_limits = PosterLimits::new(redis.clone());
+        let feed_cache_max_age = env::var("FEED_CACHE_MAX_AGE_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_FEED_CACHE_MAX_AGE_MS);
+        let feed_cache = SnapshotCache::new(Duration::from_millis(feed_cache_max_age));
+        let stats_cache_max_age = Duration::from_millis(
+            env::var("STATS_CACHE_MAX_AGE_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_STATS_CACHE_MAX_AGE_MS),
+        );
+        let daily_stats_cache = RefreshCache::new(stats_cache_max_age);
+        let city_views_cache = KeyedRefreshCache::new(CITY_VIEWS_CACHE_CAPACITY, stats_cache_max_age);
+        let corrections = CorrectionTokens::new(redis.clone());
+        let captcha = CaptchaVerifier::from_env(redis.clone());
+        let search = SearchIndex::new(redis.clone());
+        let moderation_queue = ModerationQueue::new(redis.clone());
+        let city_surges = CitySurgeDetector::from_env(redis.clone());
         let key_generator = CompositeKeyGenerator::new(server_secret);
-        let rate_limiter = RateLimiter::new(redis.clone());
-        let governor_limiter = GovernorRateLimiter::new();
-        let shadowban_manager = ShadowbanManager::new(redis.clone());
-        let content_filter = ContentFilter::new();
+        let rate_limits = RateLimitConfig::from_env();
+        let rate_limiter = RateLimiter::new(redis.clone(), rate_limits);
+        let hotspots = HotspotTracker::new();
+        let shadowban_manager = ShadowbanManager::new(redis.clone(), sessions.clone());
         let ip_reputation = IpReputationManager::new(redis.clone());
         let burst_profiler = BurstProfiler::new(redis.clone());
         let broadcast = RedisBroadcastService::new(redis.clone());
         let metrics = MetricsTracker::new();
+        let pubsub_watchdog = PubSubWatchdog::new();
+        let audit_log = AuditLog::new(redis.clone());
+        let reveal_graph = RevealGraph::new(redis.clone());
+        let listing_stats = ListingStatsTracker::new(redis.clone());
+        let pins = PinnedListings::new(redis.clone());
+        let cities = CityLaunches::new(redis.clone());
+        let city_registry = CityRegistry::new(redis.clone());
+        let translator = Translator::from_env(redis.clone()).with_dependencies(dependencies.clone());
+        let context_window = ContextWindow::new(redis.clone());
+        let review_queue = ReviewQueue::new(redis.clone());
+        let report_guard = ReportGuard::new(redis.clone()).with_policy(ReportPolicy::from_env(admin.enabled()));
+        let fingerprints = FingerprintRegistry::new(redis.clone());
+        let stats = StatsService::new(redis.clone(), metrics.clone());
         
         // Initialize moderation service with optional OpenAI API key
         let openai_api_key = env::var("OPENAI_API_KEY").ok();
-        let moderation_service = ModerationService::new(openai_api_key);
+        let moderation_service = ModerationService::new(openai_api_key)
+            .with_city_policies(CityPolicyStore::new(redis.clone()).with_surges(city_surges.clone()))
+            .with_slo(slo.clone())
+            .with_dependencies(dependencies.clone())
+            .with_verdict_cache(redis.clone());
+        let config = ConfigHandle::new(moderation_service);
         
         Ok(Self {
             redis,
             key_generator,
             rate_limiter,
-            governor_limiter,
+            rate_limits,
+            hotspots,
             shadowban_manager,
-            content_filter,
+            sessions,
             ip_reputation,
             burst_profiler,
             broadcast,
             metrics,
-            moderation_service,
+            pubsub_watchdog,
+            config,
+            cursor_signer,
+            audit_log,
+            reveal_graph,
+            listing_stats,
+            pins,
+            cities,
+            city_registry,
+            translator,
+            context_window,
+            review_queue,
+            report_guard,
+            trusted_proxies: TrustedProxies::from_env(),
+            geoip: GeoIp::from_env(),
+            fingerprints,
+            stats,
+            moderation_dataset,
+            load_shedder,
+            slo,
+            city_surges,
+            recorder,
+            consents,
+            dependencies,
+            ws_backpressure: WsBackpressure::from_env(),
+            posting_limit: ConcurrencyLimit::from_env("posting", 32, 2000),
+            ws_compression: WsCompression::from_env(),
+            ws_handover: WsHandover::from_env(),
+            poster_limits,
+            feed_cache,
+            daily_stats_cache,
+            city_views_cache,
+            corrections,
+            search,
+            moderation_queue,
+            captcha,
+            admin,
         })
     }
 
@@ -71,102 +257,331 @@ impl AppState {
     pub async fn add_message(&self, message: ChatMessage) -> Result<()> {
         let message_json = serde_json::to_string(&message)?;
         
-        // Store individual message with TTL
-        let message_key = format!("{}{}", MESSAGE_KEY_PREFIX, message.id);
-        self.redis.set_ex(&message_key, &message_json, MESSAGE_TTL).await?;
-        
-        // Add message ID to the sorted set (using timestamp as score)
-        let timestamp = message.timestamp as f64;
-        self.redis.zadd(MESSAGES_KEY, timestamp, &message.id).await?;
-        
-        // Set TTL on the sorted set to auto-cleanup
-        self.redis.expire(MESSAGES_KEY, MESSAGE_TTL as i64).await?;
+        // Store individual message with TTL, add its ID to the sorted set (timestamp
+        // as score) and record the pending broadcast in one transaction, so a message
+        // is never stored unindexed or stored without ever being broadcast.
+        // The index itself never expires - stale members are pruned by the cleanup job
+        // The search index is written alongside it
+        let message_key = keys::message(&message.id);
+        let outbox = OutboxEntry::new(&message_json);
+        let mut transaction = self.redis.transaction();
+        transaction
+            .set_ex(&message_key, &message_json, MESSAGE_TTL)
+            .zadd(keys::MESSAGES, message.timestamp as f64, &message.id);
+        SearchIndex::enqueue(&message, &mut transaction);
+        outbox.enqueue(&mut transaction).execute().await?;
+        self.feed_cache.invalidate();
         
         // Broadcast message to all server instances via Redis Pub/Sub
-        self.broadcast.broadcast_message(&message_json).await?;
+        self.broadcast.relay(&outbox).await?;
         
-        // Update metrics
-        self.metrics.increment_messages().await;
+        // Count it once, however many instances or retries publish it
+        let city_label = match message.location.as_deref() {
+            Some(city) => Some(self.city_registry.directory().await.metric_label(city)),
+            None => None,
+        };
+        if let Err(e) = self.stats.record_message(&message.id, city_label.as_deref()).await {
+            tracing::error!("{}", e);
+        }
         
         Ok(())
     }
 
-    /// Get all messages from Redis (most recent first)
-    pub async fn get_messages(&self) -> Vec<ChatMessage> {
-        // Get all message IDs from sorted set (most recent first)
-        let message_ids: Vec<String> = match self.redis
-            .keys(&format!("{}*", MESSAGE_KEY_PREFIX))
-            .await
-        {
-            Ok(keys) => keys,
-            Err(e) => {
-                eprintln!("Failed to get message keys: {}", e);
-                return Vec::new();
-            }
-        };
+    /// Store an edited message and re-broadcast it, keeping its remaining TTL
+    pub async fn update_message(&self, message: &ChatMessage) -> Result<()> {
+        let message_json = serde_json::to_string(message)?;
+        self.replace_message(message, &message_json, &message_json).await
+    }
 
-        if message_ids.is_empty() {
-            return Vec::new();
+    /// Store the poster's edit, keeping its remaining TTL, and broadcast it as a
+    /// `message_updated` event so clients replace the listing they already show
+    pub async fn edit_message(&self, message: &ChatMessage) -> Result<()> {
+        let message_json = serde_json::to_string(message)?;
+        let event_json = serde_json::to_string(&ListingEvent::MessageUpdated { message: message.clone() })?;
+        self.replace_message(message, &message_json, &event_json).await
+    }
+
+    /// Overwrite a stored message and broadcast `payload` in the same transaction
+    async fn replace_message(&self, message: &ChatMessage, message_json: &str, payload: &str) -> Result<()> {
+        let message_key = keys::message(&message.id);
+        let outbox = OutboxEntry::new(payload);
+        let mut transaction = self.redis.transaction();
+        transaction.set_keepttl(&message_key, message_json);
+        SearchIndex::enqueue(message, &mut transaction);
+        outbox.enqueue(&mut transaction).execute().await?;
+        self.feed_cache.invalidate();
+        self.broadcast.relay(&outbox).await?;
+        Ok(())
+    }
+
+    /// Take down a listing that was already broadcast: delete it like
+    /// `delete_message` and broadcast a `message_deleted` event in the same
+    /// transaction so every connected client drops it
+    pub async fn retract_message(&self, message: &ChatMessage) -> Result<()> {
+        let event_json = serde_json::to_string(&ListingEvent::MessageDeleted {
+            message_id: message.id.clone(),
+            location: message.location.clone(),
+        })?;
+        let outbox = OutboxEntry::new(&event_json);
+        let mut transaction = self.redis.transaction();
+        transaction
+            .del(&keys::message(&message.id))
+            .zrem(keys::MESSAGES, &message.id);
+        SearchIndex::dequeue(message, &mut transaction);
+        outbox.enqueue(&mut transaction).execute().await?;
+        self.feed_cache.invalidate();
+        self.broadcast.relay(&outbox).await?;
+        Ok(())
+    }
+
+    /// Store a message with a fresh TTL and re-broadcast it, moving `expires_at` to match
+    pub async fn renew_message(&self, message: &mut ChatMessage) -> Result<()> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        message.expires_at = Some(now + MESSAGE_TTL);
+        let message_json = serde_json::to_string(message)?;
+        let message_key = keys::message(&message.id);
+        let outbox = OutboxEntry::new(&message_json);
+        let mut transaction = self.redis.transaction();
+        transaction.set_ex(&message_key, &message_json, MESSAGE_TTL);
+        SearchIndex::enqueue(message, &mut transaction);
+        outbox.enqueue(&mut transaction).execute().await?;
+        self.feed_cache.invalidate();
+        self.broadcast.relay(&outbox).await?;
+        Ok(())
+    }
+
+    /// Seconds until a stored message expires (None if it no longer exists)
+    pub async fn message_ttl(&self, id: &str) -> Result<Option<u64>> {
+        let ttl = self.redis.ttl(&keys::message(id)).await?;
+        Ok(u64::try_from(ttl).ok())
+    }
+
+    /// IDs of indexed messages posted at or before `timestamp`
+    pub async fn message_ids_posted_before(&self, timestamp: u64) -> Result<Vec<String>> {
+        Ok(self.redis.zrangebyscore(keys::MESSAGES, 0.0, timestamp as f64).await?)
+    }
+
+    /// Get all messages, from the feed cache while it is fresh
+    pub async fn get_messages(&self) -> Vec<ChatMessage> {
+        if let Some(messages) = self.feed_cache.get() {
+            metrics::counter!("feed_cache_hits_total", 1);
+            return messages.as_ref().clone();
         }
+        metrics::counter!("feed_cache_misses_total", 1);
+        self.feed_cache.store(self.load_messages().await).as_ref().clone()
+    }
+
+    /// Load the feed into memory ahead of the first request; returns how many messages it holds
+    pub async fn preload_messages(&self) -> usize {
+        self.feed_cache.store(self.load_messages().await).len()
+    }
 
-        // Get all messages
+    /// Get all messages from Redis (oldest first)
+    /// Walks the `messages` index in score order and fetches bodies a page at a time
+    /// with MGET, so the feed comes back already sorted; index entries whose message
+    /// has expired are skipped until the cleanup job prunes them
+    async fn load_messages(&self) -> Vec<ChatMessage> {
         let mut messages = Vec::new();
-        for key in message_ids {
-            if let Ok(Some(json)) = self.redis.get(&key).await {
-                if let Ok(msg) = serde_json::from_str::<ChatMessage>(&json) {
+        let mut start = (f64::NEG_INFINITY, 0);
+        loop {
+            let batch = match self.redis
+                .zrangebyscore_withscores_page(keys::MESSAGES, start.0, f64::INFINITY, start.1, INDEX_BATCH_SIZE)
+                .await
+            {
+                Ok(batch) => batch,
+                Err(e) => {
+                    tracing::error!("Failed to read message index: {}", e);
+                    return Vec::new();
+                }
+            };
+            if batch.is_empty() {
+                break;
+            }
+
+            let message_keys: Vec<String> = batch.iter().map(|(id, _)| keys::message(id)).collect();
+            let key_refs: Vec<&str> = message_keys.iter().map(String::as_str).collect();
+            let values = match self.redis.mget(&key_refs).await {
+                Ok(values) => values,
+                Err(e) => {
+                    tracing::error!("Failed to get messages: {}", e);
+                    return Vec::new();
+                }
+            };
+            for json in values.into_iter().flatten() {
+                if let Ok(mut msg) = serde_json::from_str::<ChatMessage>(&json) {
+                    self.fill_expiry(&mut msg).await;
                     messages.push(msg);
                 }
             }
+
+            if batch.len() < INDEX_BATCH_SIZE as usize {
+                break;
+            }
+            start = next_page_start(&batch, start);
         }
 
-        // Sort by timestamp (oldest first - chronological order)
-        messages.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-        
         messages
     }
 
     /// Get a specific message by ID
     pub async fn get_message_by_id(&self, id: &str) -> Option<ChatMessage> {
-        let message_key = format!("{}{}", MESSAGE_KEY_PREFIX, id);
+        let message_key = keys::message(id);
         
-        match self.redis.get(&message_key).await {
-            Ok(Some(json)) => {
-                serde_json::from_str(&json).ok()
+        let mut message: ChatMessage = match self.redis.get(&message_key).await {
+            Ok(Some(json)) => serde_json::from_str(&json).ok()?,
+            _ => return None,
+        };
+        self.fill_expiry(&mut message).await;
+        Some(message)
+    }
+
+    /// Derive `expires_at` from the stored TTL for listings saved before it was tracked
+    async fn fill_expiry(&self, message: &mut ChatMessage) {
+        if message.expires_at.is_some() {
+            return;
+        }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        message.expires_at = Some(match self.message_ttl(&message.id).await {
+            Ok(Some(ttl)) => now + ttl,
+            _ => message.timestamp + MESSAGE_TTL,
+        });
+    }
+
+    /// Pinned listings for a city (marked `pinned`), unpinning any that have expired
+    pub async fn pinned_messages(&self, city: &str) -> Result<Vec<ChatMessage>> {
+        let mut messages = Vec::new();
+        for id in self.pins.list(city).await? {
+            match self.get_message_by_id(&id).await {
+                Some(message) => messages.push(ChatMessage { pinned: true, ..message }),
+                None => {
+                    self.pins.unpin(city, &id).await?;
+                }
             }
-            _ => None
         }
+        Ok(messages)
     }
 
     /// Delete a specific message by ID
     pub async fn delete_message(&self, id: &str) -> Result<()> {
-        let message_key = format!("{}{}", MESSAGE_KEY_PREFIX, id);
-        
-        // Delete the message from Redis
-        self.redis.del(&message_key).await?;
-        
-        // Remove from sorted set
-        self.redis.zrem(MESSAGES_KEY, id).await?;
+        let message_key = keys::message(id);
+        // Loaded first for the search terms it's indexed under
+        let message = self.get_message_by_id(id).await;
+
+        // Delete the message and remove it from the sorted set and search index together
+        let mut transaction = self.redis.transaction();
+        transaction.del(&message_key).zrem(keys::MESSAGES, id);
+        if let Some(message) = &message {
+            SearchIndex::dequeue(message, &mut transaction);
+        }
+        transaction.execute().await?;
+        self.feed_cache.invalidate();
         
         Ok(())
     }
 
-    /// Clean up old messages (older than TTL)
-    #[allow(dead_code)]
-    pub async fn cleanup_old_messages(&self) -> Result<()> {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)?
-            .as_secs() as f64;
-        
-        let cutoff = now - MESSAGE_TTL as f64;
-        
-        // Remove old message IDs from sorted set
-        self.redis.zrembyscore(MESSAGES_KEY, 0.0, cutoff).await?;
-        
-        Ok(())
+    /// Clean up the message index
+    /// Prunes IDs whose message key has already expired or been deleted.
+    /// Age alone is not enough - renewed listings outlive MESSAGE_TTL from posting
+    /// Returns the number of index entries removed
+    pub async fn cleanup_old_messages(&self) -> Result<usize> {
+        self.prune_dangling_index_entries().await
+    }
+
+    /// Reconcile the sorted-set index with the stored message keys
+    /// Adds stored messages missing from the index and drops index entries
+    /// without a stored message. Stored messages are re-indexed for search too,
+    /// rescoring entries an older version wrote. Intended to run once at startup.
+    pub async fn reconcile_message_index(&self) -> Result<(usize, usize)> {
+        let message_keys = self.redis
+            .scan_match(&format!("{}*", keys::MESSAGE_PREFIX), SCAN_BATCH_SIZE)
+            .await?;
+
+        let mut added = 0;
+        for chunk in message_keys.chunks(INDEX_BATCH_SIZE as usize) {
+            let keys: Vec<&str> = chunk.iter().map(String::as_str).collect();
+            let values = self.redis.mget(&keys).await?;
+
+            let mut search_writes = self.redis.pipeline();
+            let mut reindexed = false;
+            for json in values.into_iter().flatten() {
+                let Ok(mut msg) = serde_json::from_str::<ChatMessage>(&json) else {
+                    continue;
+                };
+                // ZADD returns the number of new members - only count genuinely missing ones
+                if self.redis.zadd(keys::MESSAGES, msg.timestamp as f64, &msg.id).await? > 0 {
+                    added += 1;
+                }
+                self.fill_expiry(&mut msg).await;
+                SearchIndex::enqueue(&msg, &mut search_writes);
+                reindexed = true;
+            }
+            if reindexed {
+                search_writes.execute().await?;
+            }
+        }
+
+        let removed = self.prune_dangling_index_entries().await?;
+
+        Ok((added, removed))
+    }
+
+    /// Remove index members whose `message:{id}` key no longer exists
+    async fn prune_dangling_index_entries(&self) -> Result<usize> {
+        let mut removed = 0;
+        let mut start: isize = 0;
+
+        loop {
+            let ids = self.redis
+                .zrange(keys::MESSAGES, start, start + INDEX_BATCH_SIZE - 1)
+                .await?;
+            if ids.is_empty() {
+                break;
+            }
+
+            let keys: Vec<String> = ids
+                .iter()
+                .map(|id| keys::message(id))
+                .collect();
+            let key_refs: Vec<&str> = keys.iter().map(String::as_str).collect();
+            let values = self.redis.mget(&key_refs).await?;
+
+            let mut removed_in_batch: isize = 0;
+            for (id, value) in ids.iter().zip(values) {
+                if value.is_none() {
+                    removed_in_batch += self.redis.zrem(keys::MESSAGES, id).await? as isize;
+                }
+            }
+
+            removed += removed_in_batch as usize;
+            // Removed members shift the remaining ranks down
+            start += INDEX_BATCH_SIZE - removed_in_batch;
+        }
+
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(scores: &[f64]) -> Vec<(String, f64)> {
+        scores.iter().enumerate().map(|(i, score)| (i.to_string(), *score)).collect()
     }
 
-    /// Get the Redis pub/sub channel name
-    pub fn get_pubsub_channel(&self) -> &str {
-        PUBSUB_CHANNEL
+    #[test]
+    fn test_index_pages_resume_after_tied_scores() {
+        let start = (f64::NEG_INFINITY, 0);
+        assert_eq!(next_page_start(&page(&[1.0, 2.0, 3.0]), start), (3.0, 1));
+        assert_eq!(next_page_start(&page(&[1.0, 3.0, 3.0]), start), (3.0, 2));
+        // A page made up entirely of one second's posts moves further along that second
+        assert_eq!(next_page_start(&page(&[3.0, 3.0]), (3.0, 2)), (3.0, 4));
+        assert_eq!(next_page_start(&page(&[3.0, 4.0]), (3.0, 2)), (4.0, 1));
     }
 }
@@ -0,0 +1,283 @@
+use crate::redis_client::RedisClient;
+use crate::keys;
+use crate::scaling::MetricsTracker;
+use crate::state::MESSAGE_TTL;
+use anyhow::{Result, anyhow};
+use once_cell::sync::Lazy;
+use redis::Script;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// How long visitor stats are kept in Redis
+const STATS_TTL_SECONDS: i64 = 604800; // 7 days
+/// How long an event id is remembered; a retry after this counts again
+const EVENT_TTL_SECONDS: i64 = 604800; // 7 days
+/// Buffered members beyond this are dropped rather than growing without bound
+/// (only reachable if Redis is unreachable for many flush intervals)
+const MAX_PENDING_MEMBERS: usize = 100_000;
+
+/// Claim an event id, then bump every counter for it in the same atomic step
+/// KEYS: the event key, then the counters; ARGV: the event TTL, then one TTL per
+/// counter (0 keeps the counter forever). Returns the new totals, or nil if the
+/// event was already counted
+static RECORD_EVENT: Lazy<Script> = Lazy::new(|| Script::new(r"
+if not redis.call('SET', KEYS[1], '1', 'NX', 'EX', ARGV[1]) then
+    return false
+end
+local totals = {}
+for i = 2, #KEYS do
+    totals[i - 1] = redis.call('INCR', KEYS[i])
+    local ttl = tonumber(ARGV[i])
+    if ttl > 0 then
+        redis.call('EXPIRE', KEYS[i], ttl)
+    end
+end
+return totals
+"));
+
+/// Add buffered members to their sets and bump each set's counter by the number
+/// that were new, all in one atomic step so two instances flushing the same
+/// visitor can't both count them
+/// KEYS: each set, followed by its counter if it has one; ARGV: the TTL, then per
+/// set `<has counter 0|1> <member count> <members...>`
+static FLUSH_SETS: Lazy<Script> = Lazy::new(|| Script::new(r"
+local ttl = ARGV[1]
+local arg = 2
+local key = 1
+local added_total = 0
+while key <= #KEYS do
+    local set = KEYS[key]
+    local has_counter = ARGV[arg] == '1'
+    local count = tonumber(ARGV[arg + 1])
+    arg = arg + 2
+    local added = 0
+    for i = arg, arg + count - 1 do
+        added = added + redis.call('SADD', set, ARGV[i])
+    end
+    arg = arg + count
+    redis.call('EXPIRE', set, ttl)
+    key = key + 1
+    if has_counter then
+        if added > 0 then
+            redis.call('INCRBY', KEYS[key], added)
+            redis.call('EXPIRE', KEYS[key], ttl)
+        end
+        key = key + 1
+    end
+    added_total = added_total + added
+end
+return added_total
+"));
+
+/// Cluster-wide event totals kept in Redis
+#[derive(Debug, Serialize)]
+pub struct LifetimeTotals {
+    pub messages_sent: u64,
+    pub contact_reveals: u64,
+}
+
+/// Unique-member set awaiting a flush, with an optional counter bumped once per new member
+#[derive(Default)]
+struct PendingSet {
+    members: HashSet<String>,
+    counter_key: Option<String>,
+}
+
+#[derive(Default)]
+struct PendingStats {
+    sets: HashMap<String, PendingSet>,
+    members: usize,
+}
+
+impl PendingStats {
+    /// KEYS and ARGV for `FLUSH_SETS`
+    fn script_args(self) -> (Vec<String>, Vec<String>) {
+        let mut keys = Vec::with_capacity(self.sets.len() * 2);
+        let mut args = Vec::with_capacity(1 + self.sets.len() * 2 + self.members);
+        args.push(STATS_TTL_SECONDS.to_string());
+        for (key, set) in self.sets {
+            keys.push(key);
+            args.push(if set.counter_key.is_some() { "1" } else { "0" }.to_string());
+            args.push(set.members.len().to_string());
+            args.extend(set.members);
+            keys.extend(set.counter_key);
+        }
+        (keys, args)
+    }
+}
+
+/// Every stats mutation goes through here so several instances can't double-count
+/// Event counters (messages, reveals) are keyed by an idempotent event id and
+/// bumped atomically in Redis, which is the only source of truth for totals.
+/// Visitor stats are buffered in memory and flushed in a single script, so the
+/// read path never waits on stats writes
+#[derive(Clone)]
+pub struct StatsService {
+    redis: RedisClient,
+    metrics: MetricsTracker,
+    pending: Arc<Mutex<PendingStats>>,
+}
+
+impl StatsService {
+    pub fn new(redis: RedisClient, metrics: MetricsTracker) -> Self {
+        Self {
+            redis,
+            metrics,
+            pending: Arc::new(Mutex::new(PendingStats::default())),
+        }
+    }
+
+    /// Count a published listing toward today's and the lifetime message totals
+    /// Publishing the same message id again (a retry, a relaunch) isn't counted twice
+    pub async fn record_message(&self, message_id: &str, city: Option<&str>) -> Result<()> {
+        let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        let totals = self
+            .record_event(
+                &format!("message:{}", message_id),
+                &[
+                    (keys::message_count(&today), STATS_TTL_SECONDS),
+                    (keys::METRICS_MESSAGES_SENT.to_string(), 0),
+                ],
+            )
+            .await
+            .map_err(|e| anyhow!("Failed to record message stats: {}", e))?;
+
+        if totals.is_some() {
+            self.metrics.record_message(city);
+        }
+        Ok(())
+    }
+
+    /// Count a phone number shown to a viewer, site-wide and on the listing
+    /// Each viewer counts once per listing, however often they reveal it
+    pub async fn record_contact_reveal(&self, viewer: &str, message_id: &str) -> Result<()> {
+        let totals = self
+            .record_event(
+                &format!("reveal:{}:{}", viewer, message_id),
+                &[
+                    (keys::METRICS_CONTACT_REVEALS.to_string(), 0),
+                    (keys::listing_reveals(message_id), MESSAGE_TTL as i64),
+                ],
+            )
+            .await
+            .map_err(|e| anyhow!("Failed to record contact reveal: {}", e))?;
+
+        if totals.is_some() {
+            self.metrics.record_contact_reveal();
+        }
+        Ok(())
+    }
+
+    /// Messages and contact reveals counted across the cluster since the counters were created
+    pub async fn lifetime_totals(&self) -> Result<LifetimeTotals> {
+        let totals = self.redis
+            .mget(&[keys::METRICS_MESSAGES_SENT, keys::METRICS_CONTACT_REVEALS])
+            .await
+            .map_err(|e| anyhow!("Failed to read lifetime totals: {}", e))?;
+        let count = |index: usize| {
+            totals.get(index).cloned().flatten().and_then(|v| v.parse().ok()).unwrap_or(0)
+        };
+        Ok(LifetimeTotals { messages_sent: count(0), contact_reveals: count(1) })
+    }
+
+    /// Bump each counter once for an event id; None if the event was already counted
+    async fn record_event(&self, event_id: &str, counters: &[(String, i64)]) -> redis::RedisResult<Option<Vec<i64>>> {
+        let mut script_keys = Vec::with_capacity(counters.len() + 1);
+        let mut args = Vec::with_capacity(counters.len() + 1);
+        script_keys.push(keys::stats_event(event_id));
+        args.push(EVENT_TTL_SECONDS.to_string());
+        for (key, ttl) in counters {
+            script_keys.push(key.clone());
+            args.push(ttl.to_string());
+        }
+
+        let totals: Option<Vec<i64>> = self.redis.run_script(&RECORD_EVENT, &script_keys, &args).await?;
+        if totals.is_none() {
+            metrics::counter!("stats_duplicate_events_total", 1);
+        }
+        Ok(totals)
+    }
+
+    /// Count a unique daily visitor for a city feed
+    pub fn record_city_visitor(&self, city: &str, fingerprint: &str) {
+        let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        self.add(
+            keys::city_visitors(city, &today),
+            fingerprint,
+            Some(keys::city_views(city, &today)),
+        );
+    }
+
+    /// Count a unique daily visitor to the site
+    pub fn record_visitor(&self, fingerprint: &str) {
+        let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        self.add(keys::unique_visitors(&today), fingerprint, None);
+    }
+
+    fn add(&self, set_key: String, member: &str, counter_key: Option<String>) {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.members >= MAX_PENDING_MEMBERS {
+            metrics::counter!("stats_buffer_dropped_total", 1);
+            return;
+        }
+
+        let set = pending.sets.entry(set_key).or_default();
+        set.counter_key = counter_key;
+        if set.members.insert(member.to_string()) {
+            pending.members += 1;
+        }
+    }
+
+    /// Write every visitor buffered so far; returns the number of members flushed
+    /// On failure the batch is dropped - these are best-effort analytics
+    pub async fn flush(&self) -> Result<usize> {
+        let batch = std::mem::take(&mut *self.pending.lock().unwrap());
+        if batch.sets.is_empty() {
+            return Ok(0);
+        }
+
+        let members = batch.members;
+        let (script_keys, args) = batch.script_args();
+        let _added: i64 = self.redis
+            .run_script(&FLUSH_SETS, &script_keys, &args)
+            .await
+            .map_err(|e| anyhow!("Failed to flush visitor stats: {}", e))?;
+
+        metrics::counter!("stats_buffer_flushed_total", members as u64);
+        Ok(members)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flush_args_pair_each_set_with_its_counter() {
+        let mut pending = PendingStats::default();
+        pending.sets.insert(
+            "visitors".to_string(),
+            PendingSet {
+                members: HashSet::from(["a".to_string()]),
+                counter_key: None,
+            },
+        );
+        let (keys, args) = pending.script_args();
+        assert_eq!(keys, vec!["visitors"]);
+        assert_eq!(args, vec![STATS_TTL_SECONDS.to_string(), "0".to_string(), "1".to_string(), "a".to_string()]);
+
+        let mut pending = PendingStats::default();
+        pending.sets.insert(
+            "city".to_string(),
+            PendingSet {
+                members: HashSet::from(["a".to_string(), "b".to_string()]),
+                counter_key: Some("views".to_string()),
+            },
+        );
+        let (keys, args) = pending.script_args();
+        assert_eq!(keys, vec!["city", "views"]);
+        assert_eq!(&args[1..3], ["1", "2"]);
+        assert_eq!(args.len(), 5);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::dependencies::{self, DependencyTracker};
+use crate::redis_client::RedisClient;
+use crate::keys;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// How long a translation stays cached (listings are reposted with the same text)
+const TRANSLATION_CACHE_TTL: u64 = 604800; // 7 days
+
+/// Something that can translate listing text into a target language
+#[async_trait]
+pub trait TranslationProvider: Send + Sync {
+    /// Short provider name reported alongside translations
+    fn name(&self) -> &'static str;
+
+    /// Translate `text` into `target` (ISO 639-1 code), detecting the source language
+    async fn translate(&self, text: &str, target: &str) -> Result<String>;
+}
+
+/// LibreTranslate-compatible HTTP API (self-hosted or hosted)
+pub struct LibreTranslateProvider {
+    url: String,
+    api_key: Option<String>,
+    http_client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct LibreTranslateResponse {
+    #[serde(rename = "translatedText")]
+    translated_text: String,
+}
+
+impl LibreTranslateProvider {
+    pub fn new(url: String, api_key: Option<String>) -> Self {
+        Self {
+            url,
+            api_key,
+            http_client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for LibreTranslateProvider {
+    fn name(&self) -> &'static str {
+        "libretranslate"
+    }
+
+    async fn translate(&self, text: &str, target: &str) -> Result<String> {
+        let response = self.http_client
+            .post(format!("{}/translate", self.url.trim_end_matches('/')))
+            .json(&serde_json::json!({
+                "q": text,
+                "source": "auto",
+                "target": target,
+                "format": "text",
+                "api_key": self.api_key,
+            }))
+            .timeout(std::time::Duration::from_secs(5))
+            .send()
+            .await
+            .map_err(|e| anyhow!("Failed to call translation API: {}", e))?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!("Translation API returned {}", response.status()));
+        }
+
+        let body: LibreTranslateResponse = response
+            .json()
+            .await
+            .map_err(|e| anyhow!("Failed to parse translation response: {}", e))?;
+        Ok(body.translated_text)
+    }
+}
+
+/// A translated rendering of a listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Translation {
+    pub language: String,
+    pub text: String,
+    pub provider: String,
+}
+
+/// Translates listings through the configured provider, caching results in Redis
+/// by content hash so popular listings are only translated once per language
+#[derive(Clone)]
+pub struct Translator {
+    redis: RedisClient,
+    provider: Option<Arc<dyn TranslationProvider>>,
+    /// Records provider call outcomes for /health
+    dependencies: Option<DependencyTracker>,
+}
+
+impl Translator {
+    pub fn new(redis: RedisClient, provider: Option<Arc<dyn TranslationProvider>>) -> Self {
+        Self { redis, provider, dependencies: None }
+    }
+
+    pub fn with_dependencies(mut self, dependencies: DependencyTracker) -> Self {
+        self.dependencies = Some(dependencies);
+        self
+    }
+
+    /// Use LibreTranslate when TRANSLATION_API_URL is set; translation is off otherwise
+    pub fn from_env(redis: RedisClient) -> Self {
+        let provider = std::env::var("TRANSLATION_API_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .map(|url| {
+                let api_key = std::env::var("TRANSLATION_API_KEY").ok();
+                Arc::new(LibreTranslateProvider::new(url, api_key)) as Arc<dyn TranslationProvider>
+            });
+        Self::new(redis, provider)
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.provider.is_some()
+    }
+
+    /// Translate text into `target`, serving from cache when possible
+    pub async fn translate(&self, text: &str, target: &str) -> Result<Translation> {
+        let provider = self.provider.as_ref().ok_or_else(|| anyhow!("Translation is not configured"))?;
+        let key = cache_key(text, target);
+
+        if let Ok(Some(cached)) = self.redis.get(&key).await {
+            if let Ok(translation) = serde_json::from_str(&cached) {
+                metrics::counter!("translation_cache_hits_total", 1);
+                return Ok(translation);
+            }
+        }
+
+        let started = std::time::Instant::now();
+        let result = provider.translate(text, target).await;
+        if let Some(dependencies) = &self.dependencies {
+            let outcome = result.as_ref().map(|_| ()).map_err(|e| e.to_string());
+            dependencies.record(dependencies::TRANSLATION_PROVIDER, started.elapsed(), outcome);
+        }
+        let translation = Translation {
+            language: target.to_string(),
+            text: result?,
+            provider: provider.name().to_string(),
+        };
+        metrics::counter!("translations_total", 1, "provider" => provider.name());
+
+        if let Ok(json) = serde_json::to_string(&translation) {
+            if let Err(e) = self.redis.set_ex(&key, &json, TRANSLATION_CACHE_TTL).await {
+                tracing::error!("Failed to cache translation: {}", e);
+            }
+        }
+
+        Ok(translation)
+    }
+}
+
+/// Whether `code` looks like an ISO 639-1 language code
+pub fn is_valid_language_code(code: &str) -> bool {
+    (2..=3).contains(&code.len()) && code.chars().all(|c| c.is_ascii_lowercase())
+}
+
+fn cache_key(text: &str, target: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(text.as_bytes());
+    keys::translation(target, &hex::encode(hasher.finalize()))
+}
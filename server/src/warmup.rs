@@ -0,0 +1,40 @@
+use std::time::{Duration, Instant};
+
+use crate::state::AppState;
+
+/// How long to wait for the first pub/sub heartbeat (override with WARMUP_PUBSUB_TIMEOUT_SECONDS)
+const DEFAULT_PUBSUB_TIMEOUT_SECONDS: u64 = 5;
+
+/// Get the instance ready before the listener accepts traffic
+/// Reconciles the message index, loads the feed into memory and waits for the
+/// broadcast subscription to carry a heartbeat, so the first requests after a
+/// deploy don't pay for any of it. Every step is best effort - a failed step is
+/// logged and the server starts anyway
+pub async fn run(state: &AppState) {
+    let started = Instant::now();
+
+    // Reconcile the message index with stored messages
+    match state.reconcile_message_index().await {
+        Ok((added, removed)) => tracing::info!(added, removed, "message index reconciled"),
+        Err(e) => tracing::error!("Failed to reconcile message index: {}", e),
+    }
+
+    // Runs after reconciliation so the feed reflects the repaired index
+    let loaded = state.preload_messages().await;
+    tracing::info!(loaded, "preloaded feed cache");
+
+    // The watchdog subscribes as soon as background jobs start
+    let timeout = std::env::var("WARMUP_PUBSUB_TIMEOUT_SECONDS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_PUBSUB_TIMEOUT_SECONDS);
+    if state.pubsub_watchdog.wait_for_round_trip(Duration::from_secs(timeout)).await {
+        tracing::info!(round_trip_ms = state.pubsub_watchdog.last_lag_ms(), "pub/sub subscription confirmed");
+    } else {
+        tracing::warn!(timeout_secs = timeout, "no pub/sub heartbeat, starting anyway");
+    }
+
+    let elapsed = started.elapsed();
+    metrics::gauge!("warmup_duration_seconds", elapsed.as_secs_f64());
+    tracing::info!(elapsed_ms = elapsed.as_millis() as u64, "warm-up finished");
+}
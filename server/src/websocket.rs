@@ -1,85 +1,259 @@
-use axum::extract::ws::{Message, WebSocket};
-use crate::{models::ChatMessage, state::AppState};
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
+use axum::http::StatusCode;
+use crate::{
+    fanout::{FanoutFrame, Replay, StreamPosition},
+    models::{PostMessageRequest, WsClientFrame, WsCloseReason, WsCommand, WsErrorCode, WsResponseFrame, WsServerEvent},
+    deadline::{request_timeout_from_env, Deadline, DeadlineExceeded},
+    posting::{PostRejection, PostingService},
+    recorder::{Decisions, RecordedPost},
+    scaling::MetricsTracker,
+    security::middleware::{check_write_burst, SecurityContext},
+    security::trust_tier::TrustTier,
+    state::AppState,
+    ws_backpressure::{OutboundQueue, PushError, SlowClientPolicy},
+    ws_compression::DEFLATE_PROTOCOL,
+};
 use futures::{sink::SinkExt, stream::StreamExt};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::task::JoinSet;
+use tracing::Instrument;
+
+/// Invalid command frames tolerated on one connection before it is closed
+const MAX_INVALID_FRAMES: u32 = 20;
+/// How long a closing connection gets to flush its close frame
+const CLOSE_FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+/// Longest city name a client can subscribe to
+const MAX_CITY_LENGTH: usize = 64;
+/// Posts one connection may have in flight at once
+const MAX_PENDING_POSTS: usize = 4;
+
+/// Why a forwarding session ended
+enum ForwardEnd {
+    /// The client socket is gone - stop entirely
+    ClientClosed,
+    /// The client isn't reading fast enough - close it
+    SlowClient,
+    /// The instance's fan-out stopped - nothing more will arrive
+    FanoutClosed,
+}
+
+/// The producers' end of a connection's outbound queue
+/// What happens when the client falls behind is up to `WsBackpressure`
+#[derive(Clone)]
+struct Outbound {
+    queue: Arc<OutboundQueue<Message>>,
+    policy: SlowClientPolicy,
+    metrics: MetricsTracker,
+}
+
+impl Outbound {
+    /// Queue one frame for the client's writer
+    async fn send(&self, frame: Message) -> Result<(), ForwardEnd> {
+        match self.queue.push(frame).await {
+            Ok(0) => Ok(()),
+            Ok(dropped) => {
+                self.metrics.record_dropped_frames(dropped as u64, "buffer");
+                Ok(())
+            }
+            Err(PushError::SlowClient) => Err(ForwardEnd::SlowClient),
+            Err(PushError::Closed) => Err(ForwardEnd::ClientClosed),
+        }
+    }
+
+    /// Whether to carry on after the fan-out skipped `missed` frames this client hadn't read yet
+    fn lagged(&self, missed: u64) -> bool {
+        match self.policy {
+            SlowClientPolicy::DropOldest => {
+                self.metrics.record_dropped_frames(missed, "fanout");
+                true
+            }
+            SlowClientPolicy::Disconnect => false,
+        }
+    }
+}
+
+/// A request to close the connection, optionally preceded by a final event
+struct Closing {
+    reason: WsCloseReason,
+    notice: Option<WsServerEvent>,
+}
+
+impl Closing {
+    fn new(reason: WsCloseReason) -> Self {
+        Self { reason, notice: None }
+    }
+}
+
+fn close_frame(reason: WsCloseReason) -> Message {
+    Message::Close(Some(CloseFrame {
+        code: reason.code(),
+        reason: reason.as_str().into(),
+    }))
+}
+
+/// `ctx` is the upgrade request's security context; a connection with a session
+/// also receives activity on its poster's listings, and may post over the socket
+/// `resume_from` is the `stream_id` of the last listing a reconnecting client saw;
+/// everything broadcast since is replayed before live frames
+pub async fn handle_websocket(socket: WebSocket, state: AppState, ctx: SecurityContext, resume_from: Option<String>) {
+    let actor = ctx.session.as_ref().map(|claims| claims.key.clone());
 
-pub async fn handle_websocket(socket: WebSocket, state: AppState) {
     // Increment active connections metric
     state.metrics.increment_connections().await;
-    
+    let _open = state.ws_handover.open();
+    let mut shutdown = state.ws_handover.shutdown_signal();
+    let handover = state.ws_handover.clone();
+
+    // Set only if the client offered (and the server accepted) the deflate subprotocol
+    let compression = socket
+        .protocol()
+        .is_some_and(|protocol| protocol == DEFLATE_PROTOCOL)
+        .then(|| state.ws_compression.clone());
+    metrics::counter!("websocket_connections_total", 1, "compressed" => compression.is_some().to_string());
+
     let (mut sender, mut receiver) = socket.split();
-    
-    // Get Redis pub/sub connection
-    let client = state.redis.get_client();
-    let conn_result = client.get_async_connection().await;
-    
-    let pubsub = match conn_result {
-        Ok(conn) => conn.into_pubsub(),
-        Err(e) => {
-            eprintln!("Failed to create Redis connection for WebSocket: {}", e);
-            state.metrics.decrement_connections().await;
-            return;
-        }
+
+    // All outbound frames (broadcasts and command responses) go through one writer
+    let outbound = Outbound {
+        queue: Arc::new(state.ws_backpressure.queue()),
+        policy: state.ws_backpressure.policy,
+        metrics: state.metrics.clone(),
+    };
+    let out_queue = outbound.queue.clone();
+    let outbound_queue = outbound.queue.clone();
+
+    // Subscribed before the tasks start so nothing relayed after the upgrade is missed
+    let frames = state.broadcast.subscribe_local();
+    // Read after subscribing, so the replay and the live frames overlap rather than leave a gap
+    let replay = match resume_from {
+        Some(last_id) => Some(replay_missed(&state, &last_id).await),
+        None => None,
     };
 
-    // Subscribe to the Redis pub/sub channel
-    let channel = state.get_pubsub_channel().to_string();
-    
-    // Clone metrics for the send task
+    // Clone metrics for the cleanup after the tasks end
     let metrics = state.metrics.clone();
-    
-    // Task 1: Send messages to this client (Redis pub/sub receiver)
-    let mut send_task = tokio::spawn(async move {
-        let mut pubsub = pubsub;
-        
-        if let Err(e) = pubsub.subscribe(&channel).await {
-            eprintln!("Failed to subscribe to Redis channel: {}", e);
-            return;
-        }
-        
-        let mut pubsub_stream = pubsub.on_message();
-        
-        while let Some(msg) = pubsub_stream.next().await {
-            let payload: String = match msg.get_payload() {
-                Ok(p) => p,
-                Err(e) => {
-                    eprintln!("Failed to get payload from Redis message: {}", e);
-                    continue;
+
+    // Any task can ask for the connection to be closed with a reason; the first request wins
+    let (close_tx, mut close_rx) = mpsc::channel::<Closing>(1);
+
+    // City the client subscribed to, if any; listings elsewhere aren't forwarded
+    let (city_tx, city_rx) = watch::channel::<Option<String>>(None);
+
+    // Every task logs under the connection's span
+    // Task 1: Write queued frames to this client until it is closed
+    // A close request jumps the queue, so even a client too slow to drain it hears why
+    let mut write_task = tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                biased;
+                closing = close_rx.recv() => {
+                    if let Some(Closing { reason, notice }) = closing {
+                        metrics::counter!("websocket_server_closes_total", 1, "reason" => reason.as_str());
+                        let notice = notice.and_then(|notice| serde_json::to_string(&notice).ok());
+                        if let Some(json) = notice {
+                            let frame = match &compression {
+                                Some(compression) => compression.encode(json),
+                                None => Message::Text(json),
+                            };
+                            let _ = sender.send(frame).await;
+                        }
+                        let _ = sender.send(close_frame(reason)).await;
+                    }
+                    break;
                 }
-            };
-            
-            // Parse the message
-            match serde_json::from_str::<ChatMessage>(&payload) {
-                Ok(message) => {
-                    // Strip phone number for privacy - only available via API
-                    let broadcast_message = ChatMessage {
-                        phone: None,
-                        ..message
+                frame = out_queue.pop() => {
+                    let Some(frame) = frame else {
+                        break;
                     };
-                    
-                    match serde_json::to_string(&broadcast_message) {
-                        Ok(json) => {
-                            if sender.send(Message::Text(json)).await.is_err() {
-                                break;
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to serialize message: {}", e);
-                        }
+                    let frame = match (&compression, frame) {
+                        (Some(compression), Message::Text(text)) => compression.encode(text),
+                        (_, frame) => frame,
+                    };
+                    if sender.send(frame).await.is_err() {
+                        break;
                     }
                 }
-                Err(e) => {
-                    eprintln!("Failed to parse message from Redis: {}", e);
+            }
+        }
+    }.in_current_span());
+
+    // On shutdown, send the client away with a jittered reconnect hint so a
+    // deploy's clients don't all land on the next instance at once
+    let shutdown_close = close_tx.clone();
+    let shutdown_task = tokio::spawn(async move {
+        if shutdown.wait_for(|shutting_down| *shutting_down).await.is_err() {
+            return;
+        }
+        let _ = shutdown_close.try_send(Closing {
+            reason: WsCloseReason::ServerShutdown,
+            notice: Some(WsServerEvent::Shutdown { reconnect_after_ms: handover.reconnect_after_ms() }),
+        });
+    }.in_current_span());
+
+    // Task 2: Forward broadcasts and this poster's own events from the instance's fan-out
+    let broadcast_tx = outbound.clone();
+    let slow_close = close_tx.clone();
+    let mut send_task = tokio::spawn(async move {
+        let resumed_at = match replay {
+            Some(replay) => match forward_replay(replay, &broadcast_tx, &city_rx).await {
+                Ok(position) => position,
+                Err(end) => {
+                    if matches!(end, ForwardEnd::SlowClient) {
+                        let _ = slow_close.try_send(Closing::new(WsCloseReason::SlowClient));
+                    }
+                    return;
                 }
+            },
+            None => None,
+        };
+        match forward_messages(frames, &broadcast_tx, actor.as_deref(), &city_rx, resumed_at).await {
+            ForwardEnd::ClientClosed => {}
+            ForwardEnd::SlowClient => {
+                let _ = slow_close.try_send(Closing::new(WsCloseReason::SlowClient));
+            }
+            ForwardEnd::FanoutClosed => {
+                tracing::error!("WebSocket fan-out closed, dropping connection");
             }
         }
-    });
-    
-    // Task 2: Receive messages from this client (not implemented yet)
+    }.in_current_span());
+
+    // Task 3: Receive commands from this client and answer each with an ack or error frame
+    // Posts run on tasks of their own so a slow moderation call doesn't hold up other frames
     let mut recv_task = tokio::spawn(async move {
+        let mut invalid_frames = 0;
+        let mut posts = JoinSet::new();
         while let Some(Ok(msg)) = receiver.next().await {
             match msg {
-                Message::Text(_text) => {
-                    // Future: Handle incoming messages from client
+                Message::Text(text) => {
+                    let response = match handle_client_frame(&text, &state, &city_tx).await {
+                        Handled::Respond(response) => response,
+                        Handled::Post { id, request } => {
+                            while posts.try_join_next().is_some() {}
+                            if posts.len() >= MAX_PENDING_POSTS {
+                                WsResponseFrame::error(id, WsErrorCode::RateLimited, "Too many posts in flight")
+                            } else {
+                                let (state, ctx) = (state.clone(), ctx.clone());
+                                let (outbound, close_tx) = (outbound.clone(), close_tx.clone());
+                                posts.spawn(async move {
+                                    let response = post_listing(&state, &ctx, id, *request).await;
+                                    respond(&outbound, &close_tx, &response).await;
+                                }.in_current_span());
+                                continue;
+                            }
+                        }
+                    };
+                    if matches!(response, WsResponseFrame::Error { code: WsErrorCode::InvalidFrame, .. }) {
+                        invalid_frames += 1;
+                        if invalid_frames > MAX_INVALID_FRAMES {
+                            let _ = close_tx.try_send(Closing::new(WsCloseReason::PolicyViolation));
+                            break;
+                        }
+                    }
+                    if !respond(&outbound, &close_tx, &response).await {
+                        break;
+                    }
                 }
                 Message::Close(_) => {
                     break;
@@ -87,18 +261,305 @@ pub async fn handle_websocket(socket: WebSocket, state: AppState) {
                 _ => {}
             }
         }
-    });
-    
-    // Wait for either task to complete (which means the connection is closed)
+        // A client that hung up still gets its posts finished (each is bounded by
+        // its deadline); if another task ends the connection they're dropped instead
+        while posts.join_next().await.is_some() {}
+    }.in_current_span());
+
+    // Wait for any task to complete (which means the connection is closing)
     tokio::select! {
-        _ = &mut send_task => {
-            recv_task.abort();
-        },
-        _ = &mut recv_task => {
-            send_task.abort();
-        },
+        _ = &mut write_task => {},
+        _ = &mut send_task => {},
+        _ = &mut recv_task => {},
+    }
+    send_task.abort();
+    recv_task.abort();
+    shutdown_task.abort();
+    // With every other task gone the writer sends any pending close frame and stops
+    outbound_queue.close();
+    if tokio::time::timeout(CLOSE_FLUSH_TIMEOUT, &mut write_task).await.is_err() {
+        write_task.abort();
     }
-    
+
     // Decrement active connections metric when disconnected
     metrics.decrement_connections().await;
 }
+
+/// Queue a response frame for the client
+/// Returns false once the connection should stop reading
+async fn respond(outbound: &Outbound, close_tx: &mpsc::Sender<Closing>, response: &WsResponseFrame) -> bool {
+    let Ok(json) = serde_json::to_string(response) else {
+        return true;
+    };
+    match outbound.send(Message::Text(json)).await {
+        Ok(()) => true,
+        Err(ForwardEnd::SlowClient) => {
+            let _ = close_tx.try_send(Closing::new(WsCloseReason::SlowClient));
+            false
+        }
+        Err(_) => false,
+    }
+}
+
+/// What the receive loop does with a client frame
+enum Handled {
+    /// Answer straight away
+    Respond(WsResponseFrame),
+    /// Post a listing off the receive loop, answering when it's done
+    Post { id: Option<String>, request: Box<PostMessageRequest> },
+}
+
+/// Parse and execute a single client command frame
+async fn handle_client_frame(text: &str, state: &AppState, city: &watch::Sender<Option<String>>) -> Handled {
+    let frame = match serde_json::from_str::<WsClientFrame>(text) {
+        Ok(frame) => frame,
+        Err(e) => {
+            // Still echo the correlation id if the frame was at least valid JSON
+            let id = serde_json::from_str::<serde_json::Value>(text)
+                .ok()
+                .and_then(|v| v.get("id").and_then(|id| id.as_str()).map(str::to_string));
+            return Handled::Respond(WsResponseFrame::error(id, WsErrorCode::InvalidFrame, format!("Invalid command: {}", e)));
+        }
+    };
+
+    let response = match frame.command {
+        WsCommand::Ping => WsResponseFrame::ack(frame.id),
+        WsCommand::Subscribe { city: name } => {
+            let name = name.trim();
+            if name.is_empty() || name.chars().count() > MAX_CITY_LENGTH {
+                return Handled::Respond(WsResponseFrame::error(frame.id, WsErrorCode::InvalidFrame, "Invalid city".to_string()));
+            }
+            metrics::counter!("websocket_city_subscriptions_total", 1);
+            city.send_replace(Some(state.city_registry.normalize(name).await));
+            WsResponseFrame::ack(frame.id)
+        }
+        WsCommand::Unsubscribe => {
+            city.send_replace(None);
+            WsResponseFrame::ack(frame.id)
+        }
+        WsCommand::Post(request) => return Handled::Post { id: frame.id, request },
+    };
+    Handled::Respond(response)
+}
+
+/// Run a listing posted over the socket through the same pipeline as `POST /messages`,
+/// behind the same controls as the HTTP route: burst protection, the posting
+/// concurrency ceiling, the request deadline and the request recorder
+/// The security context dates from the upgrade, so the IP block check the HTTP
+/// middleware makes on every request is repeated here, and a session that has
+/// expired since no longer counts (the client refreshes it and reconnects)
+async fn post_listing(
+    state: &AppState,
+    ctx: &SecurityContext,
+    id: Option<String>,
+    request: PostMessageRequest,
+) -> WsResponseFrame {
+    match state.rate_limiter.is_ip_blocked(&ctx.ip_address).await {
+        Ok(true) => {
+            return WsResponseFrame::error(
+                id,
+                WsErrorCode::RateLimited,
+                "IP address temporarily blocked due to excessive requests",
+            );
+        }
+        Err(e) => tracing::error!("Error checking IP block: {}", e),
+        Ok(false) => {}
+    }
+
+    let started = Instant::now();
+    let recording = state.recorder.enabled().then(|| (RecordedPost::of(&request), Decisions::default()));
+    let decisions = recording.as_ref().map(|(_, decisions)| decisions.clone());
+    let (status, response) = submit_post(state, ctx, id, request, decisions.as_ref()).await;
+    if let Some((post, decisions)) = recording {
+        state.recorder.record_socket_post(ctx, post, status.as_u16(), started, decisions);
+    }
+    response
+}
+
+/// The checks and posting of `post_listing`, with the status the HTTP route would have answered
+async fn submit_post(
+    state: &AppState,
+    ctx: &SecurityContext,
+    id: Option<String>,
+    request: PostMessageRequest,
+    decisions: Option<&Decisions>,
+) -> (StatusCode, WsResponseFrame) {
+    if let Err(rejection) = check_write_burst(state, ctx, "/messages", decisions).await {
+        return (StatusCode::TOO_MANY_REQUESTS, WsResponseFrame::error(id, WsErrorCode::RateLimited, rejection.message()));
+    }
+
+    let now = chrono::Utc::now().timestamp() as u64;
+    let ctx = SecurityContext {
+        session: ctx.session.clone().filter(|claims| claims.exp > now),
+        ..ctx.clone()
+    };
+
+    // Waiting for a posting slot counts against the deadline, as it does over HTTP
+    let deadline = Deadline::after(request_timeout_from_env());
+    let posted = deadline.run(deadline.scope(async {
+        let _permit = state.posting_limit.acquire(TrustTier::of(&ctx)).await?;
+        let service = PostingService::new(state)
+            .recording(decisions.cloned())
+            .with_deadline(Some(deadline));
+        Some(service.submit(request, &ctx).await)
+    })).await;
+    let result = match posted {
+        Ok(Some(result)) => result,
+        Ok(None) => {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                WsResponseFrame::error(id, WsErrorCode::Unavailable, "Server busy, please retry shortly"),
+            );
+        }
+        Err(DeadlineExceeded) => {
+            metrics::counter!("requests_timed_out_total", 1);
+            Err(PostRejection::TimedOut)
+        }
+    };
+
+    let kind = match &result {
+        Ok(outcome) => outcome.kind(),
+        Err(rejection) => rejection.kind(),
+    };
+    metrics::counter!("websocket_posts_total", 1, "outcome" => kind);
+    match result {
+        Ok(outcome) => (StatusCode::OK, WsResponseFrame::Posted { id, message: Box::new(outcome.into_message()) }),
+        Err(rejection) => (
+            rejection.status(),
+            WsResponseFrame::Rejected { id, code: rejection.kind(), body: rejection.body() },
+        ),
+    }
+}
+
+/// Whether a listing in `location` goes to a client subscribed to `city` (every city if none)
+fn wanted_by(city: Option<&str>, location: Option<&str>) -> bool {
+    city.is_none_or(|city| location == Some(city))
+}
+
+/// What a client reconnecting from `last_id` missed; a malformed id or a failed
+/// read is treated like a gap, so the client refetches instead of silently missing listings
+async fn replay_missed(state: &AppState, last_id: &str) -> Replay {
+    let Some(last_id) = StreamPosition::parse(last_id) else {
+        metrics::counter!("websocket_resumes_total", 1, "outcome" => "invalid");
+        return Replay::Gap;
+    };
+    let replay = state.broadcast.replay_since(last_id).await.unwrap_or_else(|e| {
+        tracing::error!("Failed to replay the broadcast stream: {}", e);
+        Replay::Gap
+    });
+    let outcome = match &replay {
+        Replay::Missed { .. } => "replayed",
+        Replay::Gap => "gap",
+    };
+    metrics::counter!("websocket_resumes_total", 1, "outcome" => outcome);
+    replay
+}
+
+/// Send a resuming client what it missed, or tell it to refetch the feed
+/// Returns the stream position live frames must be past to not repeat the replay
+async fn forward_replay(
+    replay: Replay,
+    sender: &Outbound,
+    city: &watch::Receiver<Option<String>>,
+) -> Result<Option<StreamPosition>, ForwardEnd> {
+    match replay {
+        Replay::Missed { frames, position } => {
+            for frame in frames {
+                let FanoutFrame::Listing { city: location, json, .. } = frame else {
+                    continue;
+                };
+                if wanted_by(city.borrow().as_deref(), location.as_deref()) {
+                    sender.send(Message::Text(json)).await?;
+                }
+            }
+            Ok(Some(position))
+        }
+        Replay::Gap => {
+            if let Ok(json) = serde_json::to_string(&WsServerEvent::ResyncRequired) {
+                sender.send(Message::Text(json)).await?;
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// Forward fanned-out frames meant for this client until either side goes away
+/// `actor` is the composite key whose own events this connection receives;
+/// listings at or before `resumed_at` were already sent by the replay
+async fn forward_messages(
+    mut frames: broadcast::Receiver<Arc<FanoutFrame>>,
+    sender: &Outbound,
+    actor: Option<&str>,
+    city: &watch::Receiver<Option<String>>,
+    resumed_at: Option<StreamPosition>,
+) -> ForwardEnd {
+    loop {
+        let frame = match frames.recv().await {
+            Ok(frame) => frame,
+            // Frames were dropped before this client got them - it can't keep up
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                if sender.lagged(missed) {
+                    continue;
+                }
+                return ForwardEnd::SlowClient;
+            }
+            Err(broadcast::error::RecvError::Closed) => return ForwardEnd::FanoutClosed,
+        };
+
+        let text = match &*frame {
+            FanoutFrame::Listing { position, city: location, json } => {
+                if resumed_at.is_some_and(|resumed_at| *position <= resumed_at) {
+                    continue;
+                }
+                if !wanted_by(city.borrow().as_deref(), location.as_deref()) {
+                    continue;
+                }
+                json.clone()
+            }
+            FanoutFrame::Actor { composite_key, payload } => {
+                if actor != Some(composite_key.as_str()) {
+                    continue;
+                }
+                payload.clone()
+            }
+        };
+        if let Err(end) = sender.send(Message::Text(text)).await {
+            return end;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_city_subscription_filters_listings() {
+        let frame: WsClientFrame = serde_json::from_str(r#"{"type":"subscribe","city":"Pune","id":"1"}"#).unwrap();
+        assert!(matches!(frame.command, WsCommand::Subscribe { city } if city == "Pune"));
+
+        assert!(wanted_by(Some("Pune"), Some("Pune")));
+        assert!(!wanted_by(Some("Pune"), Some("Mumbai")));
+        assert!(!wanted_by(Some("Pune"), None));
+        assert!(wanted_by(None, Some("Mumbai")));
+    }
+
+    #[test]
+    fn test_post_frame_carries_the_listing() {
+        let frame: WsClientFrame = serde_json::from_str(
+            r#"{"type":"post","id":"7","browser_id":"b","message":"2BHK in Baner","message_type":"offered","website":""}"#,
+        ).unwrap();
+        assert_eq!(frame.id.as_deref(), Some("7"));
+        assert!(matches!(frame.command, WsCommand::Post(request) if request.message == "2BHK in Baner"));
+
+        let rejected = WsResponseFrame::Rejected {
+            id: frame.id,
+            code: "cooldown",
+            body: serde_json::json!({"error": "Rate limit exceeded"}),
+        };
+        assert_eq!(
+            serde_json::to_value(&rejected).unwrap(),
+            serde_json::json!({"type": "rejected", "id": "7", "code": "cooldown", "error": "Rate limit exceeded"}),
+        );
+    }
+}
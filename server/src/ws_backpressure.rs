@@ -0,0 +1,204 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+const DEFAULT_BUFFER: usize = 64;
+const DEFAULT_SLOW_CLIENT_TIMEOUT_MS: u64 = 5000;
+
+/// What a connection does once its client stops draining the outbound buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlowClientPolicy {
+    /// Make room by dropping the oldest queued frame; the client stays connected
+    /// and misses what was dropped
+    DropOldest,
+    /// Wait up to the slow-client timeout for room, then close the connection;
+    /// the client reconnects and resumes from the last listing it saw
+    Disconnect,
+}
+
+impl SlowClientPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "drop_oldest" | "drop-oldest" => Some(SlowClientPolicy::DropOldest),
+            "disconnect" => Some(SlowClientPolicy::Disconnect),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SlowClientPolicy::DropOldest => "drop_oldest",
+            SlowClientPolicy::Disconnect => "disconnect",
+        }
+    }
+}
+
+/// Outbound buffering per WebSocket connection, from `WS_OUTBOUND_BUFFER`,
+/// `WS_SLOW_CLIENT_POLICY` and `WS_SLOW_CLIENT_TIMEOUT_MS`
+#[derive(Clone, Copy, Debug)]
+pub struct WsBackpressure {
+    pub policy: SlowClientPolicy,
+    /// Frames queued for one client before the policy applies
+    pub buffer: usize,
+    /// How long a producer waits for room under `Disconnect`
+    pub slow_client_timeout: Duration,
+}
+
+impl Default for WsBackpressure {
+    fn default() -> Self {
+        Self {
+            policy: SlowClientPolicy::Disconnect,
+            buffer: DEFAULT_BUFFER,
+            slow_client_timeout: Duration::from_millis(DEFAULT_SLOW_CLIENT_TIMEOUT_MS),
+        }
+    }
+}
+
+impl WsBackpressure {
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok();
+        let defaults = Self::default();
+        Self {
+            policy: var("WS_SLOW_CLIENT_POLICY")
+                .and_then(|v| SlowClientPolicy::parse(&v))
+                .unwrap_or(defaults.policy),
+            buffer: var("WS_OUTBOUND_BUFFER")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.buffer)
+                .max(1),
+            slow_client_timeout: var("WS_SLOW_CLIENT_TIMEOUT_MS")
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.slow_client_timeout),
+        }
+    }
+
+    pub fn queue<T>(&self) -> OutboundQueue<T> {
+        OutboundQueue {
+            inner: Mutex::new(QueueState { frames: VecDeque::with_capacity(self.buffer), closed: false }),
+            readable: Notify::new(),
+            writable: Notify::new(),
+            config: *self,
+        }
+    }
+}
+
+/// Why a frame couldn't be queued
+#[derive(Debug, PartialEq, Eq)]
+pub enum PushError {
+    /// No room opened up within the slow-client timeout (`Disconnect` only)
+    SlowClient,
+    /// The connection is closing
+    Closed,
+}
+
+struct QueueState<T> {
+    frames: VecDeque<T>,
+    closed: bool,
+}
+
+/// Bounded queue between a connection's producers (fan-out forwarding, command
+/// responses) and its socket writer
+/// Unlike a bounded channel it can evict its oldest frame, so under
+/// `DropOldest` producers never wait on a slow socket
+pub struct OutboundQueue<T> {
+    inner: Mutex<QueueState<T>>,
+    readable: Notify,
+    writable: Notify,
+    config: WsBackpressure,
+}
+
+impl<T> OutboundQueue<T> {
+    /// Queue a frame for the writer, returning how many queued frames were dropped to fit it
+    pub async fn push(&self, frame: T) -> Result<usize, PushError> {
+        let deadline = tokio::time::Instant::now() + self.config.slow_client_timeout;
+        let mut frame = Some(frame);
+        loop {
+            // Registered before the check so a pop or close in between still wakes us
+            let writable = self.writable.notified();
+            tokio::pin!(writable);
+            writable.as_mut().enable();
+            {
+                let mut state = self.inner.lock().unwrap();
+                if state.closed {
+                    return Err(PushError::Closed);
+                }
+                let full = state.frames.len() >= self.config.buffer;
+                if !full || self.config.policy == SlowClientPolicy::DropOldest {
+                    let dropped = if full { state.frames.pop_front().map_or(0, |_| 1) } else { 0 };
+                    state.frames.extend(frame.take());
+                    drop(state);
+                    self.readable.notify_one();
+                    return Ok(dropped);
+                }
+            }
+            if tokio::time::timeout_at(deadline, writable).await.is_err() {
+                return Err(PushError::SlowClient);
+            }
+        }
+    }
+
+    /// The oldest queued frame, waiting for one; `None` once closed and drained
+    pub async fn pop(&self) -> Option<T> {
+        loop {
+            let readable = self.readable.notified();
+            tokio::pin!(readable);
+            readable.as_mut().enable();
+            {
+                let mut state = self.inner.lock().unwrap();
+                if let Some(frame) = state.frames.pop_front() {
+                    drop(state);
+                    self.writable.notify_one();
+                    return Some(frame);
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+            readable.await;
+        }
+    }
+
+    /// Refuse further frames; the writer still drains what was queued
+    pub fn close(&self) {
+        self.inner.lock().unwrap().closed = true;
+        self.readable.notify_waiters();
+        self.writable.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(policy: SlowClientPolicy) -> WsBackpressure {
+        WsBackpressure { policy, buffer: 2, slow_client_timeout: Duration::from_millis(20) }
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_keeps_newest_frames() {
+        let queue = config(SlowClientPolicy::DropOldest).queue();
+        assert_eq!(queue.push(1).await, Ok(0));
+        assert_eq!(queue.push(2).await, Ok(0));
+        assert_eq!(queue.push(3).await, Ok(1));
+        queue.close();
+        assert_eq!(queue.pop().await, Some(2));
+        assert_eq!(queue.pop().await, Some(3));
+        assert_eq!(queue.pop().await, None);
+        assert_eq!(queue.push(4).await, Err(PushError::Closed));
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_waits_for_room_then_gives_up() {
+        let queue = config(SlowClientPolicy::Disconnect).queue();
+        queue.push(1).await.unwrap();
+        queue.push(2).await.unwrap();
+        assert_eq!(queue.push(3).await, Err(PushError::SlowClient));
+
+        // A frame written while a producer waits lets it through
+        let (pushed, popped) = tokio::join!(queue.push(3), queue.pop());
+        assert_eq!((pushed, popped), (Ok(0), Some(1)));
+        assert_eq!(SlowClientPolicy::parse("drop-oldest"), Some(SlowClientPolicy::DropOldest));
+    }
+}
@@ -0,0 +1,102 @@
+use axum::extract::ws::Message;
+use flate2::{write::DeflateEncoder, Compression};
+use std::io::Write;
+
+/// WebSocket subprotocol a client offers to receive deflated frames
+/// Browsers can't negotiate permessage-deflate through this server's WebSocket
+/// stack, so compression is negotiated as a subprotocol instead: text frames
+/// over the threshold are sent as binary frames holding raw DEFLATE data
+/// (no zlib header, no context shared between frames), which clients inflate
+/// with `DecompressionStream("deflate-raw")`
+pub const DEFLATE_PROTOCOL: &str = "krib.deflate";
+
+const DEFAULT_MIN_BYTES: usize = 256;
+const DEFAULT_MAX_BYTES: usize = 65536;
+const DEFAULT_LEVEL: u32 = 6;
+
+/// Frame compression settings, from `WS_COMPRESSION*`
+/// Each frame is compressed on its own, so memory is bounded by the largest
+/// frame compressed (`WS_COMPRESSION_MAX_BYTES`) rather than held per connection
+#[derive(Clone, Debug)]
+pub struct WsCompression {
+    pub enabled: bool,
+    /// Smaller frames aren't worth the CPU and header overhead
+    min_bytes: usize,
+    /// Larger frames are sent as-is to cap the compressor's working memory
+    max_bytes: usize,
+    level: Compression,
+}
+
+impl WsCompression {
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok();
+        Self {
+            enabled: var("WS_COMPRESSION").is_none_or(|v| v != "false" && v != "0"),
+            min_bytes: var("WS_COMPRESSION_MIN_BYTES")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_MIN_BYTES),
+            max_bytes: var("WS_COMPRESSION_MAX_BYTES")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_MAX_BYTES),
+            level: Compression::new(
+                var("WS_COMPRESSION_LEVEL")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(DEFAULT_LEVEL)
+                    .min(9),
+            ),
+        }
+    }
+
+    /// Compress a text frame for a client that negotiated `DEFLATE_PROTOCOL`
+    /// Falls back to the original text when compression doesn't pay off
+    pub fn encode(&self, text: String) -> Message {
+        if !(self.min_bytes..=self.max_bytes).contains(&text.len()) {
+            return Message::Text(text);
+        }
+
+        let mut encoder = DeflateEncoder::new(Vec::with_capacity(text.len() / 2), self.level);
+        let compressed = match encoder.write_all(text.as_bytes()).and_then(|_| encoder.finish()) {
+            Ok(compressed) if compressed.len() < text.len() => compressed,
+            _ => return Message::Text(text),
+        };
+
+        metrics::counter!("websocket_compression_bytes_total", text.len() as u64, "stage" => "raw");
+        metrics::counter!("websocket_compression_bytes_total", compressed.len() as u64, "stage" => "compressed");
+        metrics::histogram!("websocket_compression_ratio", compressed.len() as f64 / text.len() as f64);
+        Message::Binary(compressed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::DeflateDecoder;
+    use std::io::Read;
+
+    fn config() -> WsCompression {
+        WsCompression {
+            enabled: true,
+            min_bytes: DEFAULT_MIN_BYTES,
+            max_bytes: DEFAULT_MAX_BYTES,
+            level: Compression::new(DEFAULT_LEVEL),
+        }
+    }
+
+    #[test]
+    fn test_large_frames_roundtrip() {
+        let text = r#"{"id":"m1","message":"2BHK flat near Baner, fully furnished, available now"}"#.repeat(10);
+        let Message::Binary(compressed) = config().encode(text.clone()) else {
+            panic!("expected a compressed frame");
+        };
+        assert!(compressed.len() < text.len());
+
+        let mut inflated = String::new();
+        DeflateDecoder::new(compressed.as_slice()).read_to_string(&mut inflated).unwrap();
+        assert_eq!(inflated, text);
+    }
+
+    #[test]
+    fn test_small_frames_stay_text() {
+        assert!(matches!(config().encode(r#"{"type":"ack"}"#.to_string()), Message::Text(_)));
+    }
+}
@@ -0,0 +1,139 @@
+use governor::{clock::DefaultClock, state::{InMemoryState, NotKeyed}, Quota, RateLimiter};
+use rand::Rng;
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+const DEFAULT_RAMP_SECONDS: u64 = 60;
+const DEFAULT_RAMP_UPGRADES_PER_SECOND: u32 = 50;
+const DEFAULT_RECONNECT_SPREAD_SECONDS: u64 = 30;
+/// Clients never get a hint shorter than this, so the old instance has time to stop
+const MIN_RECONNECT_AFTER_MS: u64 = 1000;
+
+/// Smooths WebSocket reconnect storms during rolling deploys, from `WS_RAMP_*` and
+/// `WS_RECONNECT_SPREAD_SECONDS`
+/// An instance shutting down tells each client to come back after a random delay
+/// spread over a window, and a freshly started instance caps how many upgrades it
+/// accepts per second until its ramp-up period is over
+#[derive(Clone)]
+pub struct WsHandover {
+    started: Instant,
+    ramp: Duration,
+    /// Upgrade quota while ramping up
+    ramp_limiter: Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>,
+    reconnect_spread_ms: u64,
+    shutdown: Arc<watch::Sender<bool>>,
+    open: Arc<AtomicUsize>,
+}
+
+/// Held by a connection for as long as it is open
+pub struct OpenConnection(Arc<AtomicUsize>);
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl WsHandover {
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok();
+        Self::new(
+            Duration::from_secs(
+                var("WS_RAMP_SECONDS")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(DEFAULT_RAMP_SECONDS),
+            ),
+            var("WS_RAMP_UPGRADES_PER_SECOND")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_RAMP_UPGRADES_PER_SECOND),
+            Duration::from_secs(
+                var("WS_RECONNECT_SPREAD_SECONDS")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(DEFAULT_RECONNECT_SPREAD_SECONDS),
+            ),
+        )
+    }
+
+    fn new(ramp: Duration, ramp_upgrades_per_second: u32, reconnect_spread: Duration) -> Self {
+        let per_second = NonZeroU32::new(ramp_upgrades_per_second).unwrap_or(NonZeroU32::MIN);
+        Self {
+            started: Instant::now(),
+            ramp,
+            ramp_limiter: Arc::new(RateLimiter::direct(Quota::per_second(per_second))),
+            reconnect_spread_ms: reconnect_spread.as_millis() as u64,
+            shutdown: Arc::new(watch::channel(false).0),
+            open: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Whether a WebSocket upgrade may go ahead now
+    /// Only throttled during the ramp-up period after startup
+    pub fn admit(&self) -> bool {
+        self.started.elapsed() >= self.ramp || self.ramp_limiter.check().is_ok()
+    }
+
+    /// Count a connection as open until the returned guard is dropped
+    pub fn open(&self) -> OpenConnection {
+        self.open.fetch_add(1, Ordering::Relaxed);
+        OpenConnection(self.open.clone())
+    }
+
+    /// Fires once the instance starts shutting down
+    pub fn shutdown_signal(&self) -> watch::Receiver<bool> {
+        self.shutdown.subscribe()
+    }
+
+    /// Tell every connection to send its client away
+    pub fn begin_shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    /// Whether shutdown has begun and the instance is draining
+    pub fn is_shutting_down(&self) -> bool {
+        *self.shutdown.borrow()
+    }
+
+    /// Wait for connections to finish closing, up to `timeout`
+    pub async fn drain(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        while self.open.load(Ordering::Relaxed) > 0 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// A jittered delay before a client should reconnect
+    pub fn reconnect_after_ms(&self) -> u64 {
+        jittered(self.reconnect_spread_ms, rand::thread_rng().gen())
+    }
+}
+
+/// Spread `sample` (uniform in [0, 1)) over the reconnect window
+fn jittered(spread_ms: u64, sample: f64) -> u64 {
+    MIN_RECONNECT_AFTER_MS + (spread_ms as f64 * sample) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconnect_hint_stays_within_window() {
+        assert_eq!(jittered(30000, 0.0), MIN_RECONNECT_AFTER_MS);
+        assert!(jittered(30000, 0.999) < MIN_RECONNECT_AFTER_MS + 30000);
+        assert_eq!(jittered(0, 0.5), MIN_RECONNECT_AFTER_MS);
+    }
+
+    #[test]
+    fn test_upgrades_throttled_only_while_ramping() {
+        let handover = WsHandover::new(Duration::from_secs(60), 2, Duration::from_secs(30));
+        assert!(handover.admit());
+        assert!(handover.admit());
+        assert!(!handover.admit());
+
+        let ramped = WsHandover { ramp: Duration::ZERO, ..handover };
+        assert!(ramped.admit());
+    }
+}
@@ -0,0 +1,248 @@
+//! Harness for the end-to-end scenarios: a throwaway `redis-server` and the real
+//! server binary on free ports, driven over HTTP and WebSocket the way a browser would
+//! Scenarios are `#[ignore]`d so a plain `cargo test` doesn't need Redis; run them
+//! with `cargo test -- --ignored`, pointing `REDIS_SERVER` at the binary if it
+//! isn't on `PATH`. They fail rather than pass when Redis can't be started
+
+use futures::StreamExt;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+/// A WebSocket subscribes to the fan-out after the upgrade; give it this long before posting
+const SUBSCRIBE_SETTLE: Duration = Duration::from_millis(500);
+/// How long a scenario waits for a broadcast it expects (or makes sure one never comes)
+pub const BROADCAST_TIMEOUT: Duration = Duration::from_secs(3);
+/// Consent version posters accept to share a phone number
+pub const PRIVACY_POLICY_VERSION: &str = "e2e";
+
+/// One Redis and one server instance, both killed on drop
+pub struct TestServer {
+    /// Redis, then the server once it's spawned
+    processes: Vec<Child>,
+    dir: PathBuf,
+    pub base_url: String,
+    pub ws_url: String,
+}
+
+impl TestServer {
+    /// Start Redis and the server
+    /// Panics when Redis can't be started, so a scenario never passes without running
+    pub async fn start() -> Self {
+        let redis_port = free_port();
+        let redis_bin = std::env::var("REDIS_SERVER").unwrap_or_else(|_| "redis-server".to_string());
+        let redis = Command::new(&redis_bin)
+            .args(["--port", &redis_port.to_string(), "--bind", "127.0.0.1", "--save", "", "--appendonly", "no"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap_or_else(|e| panic!("can't start {} ({}); set REDIS_SERVER to its path", redis_bin, e));
+
+        // The server loads `.env` from its working directory; keep a developer's out of it
+        let port = free_port();
+        let dir = std::env::temp_dir().join(format!("krib-e2e-{}", port));
+        std::fs::create_dir_all(&dir).expect("create scenario directory");
+
+        let mut test_server = Self {
+            processes: vec![redis],
+            dir,
+            base_url: format!("http://127.0.0.1:{}", port),
+            ws_url: format!("ws://127.0.0.1:{}/ws", port),
+        };
+        wait_for_port(redis_port).await;
+
+        let server = Command::new(env!("CARGO_BIN_EXE_kirb-server"))
+            .env_clear()
+            .env("PATH", std::env::var("PATH").unwrap_or_default())
+            .env("REDIS_URL", format!("redis://127.0.0.1:{}", redis_port))
+            .env("SERVER_SECRET", "e2e-secret-0123456789abcdef0123456789abcdef")
+            .env("ALLOWED_ORIGIN", "http://localhost:5173")
+            .env("PORT", port.to_string())
+            .env("RUST_LOG", "warn")
+            .env("PRIVACY_POLICY_VERSION", PRIVACY_POLICY_VERSION)
+            // Every scenario connects right after startup
+            .env("WS_RAMP_SECONDS", "0")
+            .current_dir(&test_server.dir)
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("spawn kirb-server");
+        test_server.processes.push(server);
+
+        test_server.wait_until_healthy().await;
+        test_server
+    }
+
+    async fn wait_until_healthy(&mut self) {
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        let health = format!("{}/health", self.base_url);
+        loop {
+            if let Ok(response) = reqwest::get(&health).await {
+                if response.status().is_success() {
+                    return;
+                }
+            }
+            if let Some(Ok(Some(status))) = self.processes.last_mut().map(Child::try_wait) {
+                panic!("kirb-server exited during startup: {}", status);
+            }
+            assert!(Instant::now() < deadline, "kirb-server not healthy after {:?}", STARTUP_TIMEOUT);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// A new browser with its own fingerprint, connecting from `ip`
+    pub fn visitor(&self, ip: &str) -> Visitor {
+        Visitor {
+            base_url: self.base_url.clone(),
+            http: reqwest::Client::new(),
+            fingerprint: uuid::Uuid::new_v4().simple().to_string(),
+            ip: ip.to_string(),
+            session: None,
+        }
+    }
+
+    /// A live feed subscriber
+    pub async fn feed(&self) -> Feed {
+        let (socket, _) = connect_async(self.ws_url.as_str()).await.expect("connect to /ws");
+        tokio::time::sleep(SUBSCRIBE_SETTLE).await;
+        Feed { socket }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        for process in self.processes.iter_mut().rev() {
+            let _ = process.kill();
+            let _ = process.wait();
+        }
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A browser talking to the API, identified by fingerprint and client IP
+/// The IP is passed as `Cf-Connecting-Ip`, which the server believes from loopback
+pub struct Visitor {
+    base_url: String,
+    http: reqwest::Client,
+    pub fingerprint: String,
+    pub ip: String,
+    session: Option<String>,
+}
+
+/// Status and JSON body of a response
+pub struct Reply {
+    pub status: StatusCode,
+    pub body: Value,
+}
+
+impl Visitor {
+    /// The same browser seen from another address
+    pub fn moved_to(&self, ip: &str) -> Visitor {
+        Visitor {
+            base_url: self.base_url.clone(),
+            http: self.http.clone(),
+            fingerprint: self.fingerprint.clone(),
+            ip: ip.to_string(),
+            session: None,
+        }
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self.http
+            .request(method, format!("{}{}", self.base_url, path))
+            .header("X-Browser-Fingerprint", &self.fingerprint)
+            .header("Cf-Connecting-Ip", &self.ip)
+            // Headers a real browser sends, so the scripted-client heuristics stay quiet
+            .header("User-Agent", "Mozilla/5.0 (X11; Linux x86_64) krib-e2e")
+            .header("Accept", "application/json")
+            .header("Accept-Language", "en-IN,en;q=0.9");
+        match &self.session {
+            Some(token) => request.header("X-Session-Token", token),
+            None => request,
+        }
+    }
+
+    async fn send(request: reqwest::RequestBuilder) -> Reply {
+        let response = request.send().await.expect("request failed");
+        let status = response.status();
+        let body = response.json().await.unwrap_or(Value::Null);
+        Reply { status, body }
+    }
+
+    pub async fn get(&self, path: &str) -> Reply {
+        Self::send(self.request(reqwest::Method::GET, path)).await
+    }
+
+    /// Register the fingerprint and keep the access token; posting needs one
+    pub async fn start_session(&mut self) {
+        let reply = Self::send(self.request(reqwest::Method::POST, "/api/session")).await;
+        assert_eq!(reply.status, StatusCode::OK, "session: {}", reply.body);
+        self.session = reply.body["access_token"].as_str().map(str::to_string);
+    }
+
+    pub async fn post_listing(&self, text: &str) -> Reply {
+        self.post_with(json!({
+            "browser_id": self.fingerprint,
+            "message": text,
+            "message_type": "offered",
+        }))
+        .await
+    }
+
+    pub async fn post_with(&self, body: Value) -> Reply {
+        Self::send(self.request(reqwest::Method::POST, "/messages").json(&body)).await
+    }
+
+    pub async fn report(&self, message_id: &str, poster_fingerprint: &str) -> Reply {
+        let body = json!({
+            "message_id": message_id,
+            "reported_browser_id": poster_fingerprint,
+        });
+        Self::send(self.request(reqwest::Method::POST, "/api/report").json(&body)).await
+    }
+}
+
+/// A WebSocket receiving the live feed
+pub struct Feed {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl Feed {
+    /// The next listing broadcast within `timeout`, skipping other frames
+    pub async fn next_listing(&mut self, timeout: Duration) -> Option<Value> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let frame = tokio::time::timeout_at(deadline, self.socket.next()).await.ok()??;
+            let Ok(Message::Text(text)) = frame else {
+                continue;
+            };
+            let Ok(value) = serde_json::from_str::<Value>(&text) else {
+                continue;
+            };
+            // Command acks and server events carry a `type`; listings don't
+            if value.get("type").is_none() && value.get("id").is_some() {
+                return Some(value);
+            }
+        }
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .expect("find a free port")
+}
+
+async fn wait_for_port(port: u16) {
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    while TcpStream::connect(("127.0.0.1", port)).await.is_err() {
+        assert!(Instant::now() < deadline, "redis-server not listening after {:?}", STARTUP_TIMEOUT);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
@@ -0,0 +1,123 @@
+//! End-to-end scenarios for the posting and security pipeline, against the real
+//! router and a throwaway Redis (see `common`)
+//! Run with `cargo test --test scenarios -- --ignored`
+
+mod common;
+
+use common::{TestServer, BROADCAST_TIMEOUT, PRIVACY_POLICY_VERSION};
+use reqwest::StatusCode;
+use serde_json::json;
+
+fn listing_text(n: usize) -> String {
+    format!("Furnished 2BHK flat for rent near Baner, {} minutes from the IT park, rent 18000", 5 + n)
+}
+
+#[tokio::test]
+#[ignore = "needs redis-server; run with --ignored"]
+async fn test_post_is_broadcast_without_phone() {
+    let server = TestServer::start().await;
+    let mut feed = server.feed().await;
+    let mut poster = server.visitor("203.0.113.10");
+    poster.start_session().await;
+
+    let reply = poster.post_with(json!({
+        "browser_id": poster.fingerprint,
+        "message": listing_text(1),
+        "message_type": "offered",
+        "phone": "9876543210",
+        "consent": PRIVACY_POLICY_VERSION,
+    }))
+    .await;
+    assert_eq!(reply.status, StatusCode::OK, "post: {}", reply.body);
+    let id = reply.body["id"].as_str().expect("posted listing id").to_string();
+
+    let listing = feed.next_listing(BROADCAST_TIMEOUT).await.expect("listing broadcast");
+    assert_eq!(listing["id"], id.as_str());
+    assert_eq!(listing["message"], listing_text(1).as_str());
+    assert!(listing["phone"].is_null());
+
+    let stored = poster.get(&format!("/messages/{}", id)).await;
+    assert_eq!(stored.status, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "needs redis-server; run with --ignored"]
+async fn test_shadowbanned_poster_is_silently_dropped() {
+    let server = TestServer::start().await;
+    let mut feed = server.feed().await;
+    let mut bot = server.visitor("203.0.113.20");
+    bot.start_session().await;
+
+    // Filling in the honeypot field shadowbans the poster for good
+    let reply = bot.post_with(json!({
+        "browser_id": bot.fingerprint,
+        "message": listing_text(2),
+        "message_type": "offered",
+        "website": "http://spam.example",
+    }))
+    .await;
+    assert_eq!(reply.status, StatusCode::FORBIDDEN);
+
+    // Later posts look accepted but are neither stored nor broadcast
+    let reply = bot.post_listing(&listing_text(3)).await;
+    assert_eq!(reply.status, StatusCode::OK, "post: {}", reply.body);
+    let id = reply.body["id"].as_str().expect("pretend listing id").to_string();
+
+    assert!(feed.next_listing(BROADCAST_TIMEOUT).await.is_none());
+    assert_eq!(bot.get(&format!("/messages/{}", id)).await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs redis-server; run with --ignored"]
+async fn test_three_reports_ban_the_poster() {
+    let server = TestServer::start().await;
+    let poster = server.visitor("198.51.100.10");
+
+    // One listing from each of three addresses, each reported once by a different
+    // user on a different network, so no listing looks brigaded
+    let mut listings = Vec::new();
+    for (n, ip) in ["198.51.100.10", "198.51.101.10", "198.51.102.10"].into_iter().enumerate() {
+        let mut poster = poster.moved_to(ip);
+        poster.start_session().await;
+        let reply = poster.post_listing(&listing_text(10 + n)).await;
+        assert_eq!(reply.status, StatusCode::OK, "post: {}", reply.body);
+        listings.push(reply.body["id"].as_str().expect("posted listing id").to_string());
+    }
+
+    for (n, id) in listings.iter().enumerate() {
+        let reporter = server.visitor(&format!("192.0.2.{}", 10 + n));
+        let reply = reporter.report(id, &poster.fingerprint).await;
+        assert_eq!(reply.status, StatusCode::OK, "report: {}", reply.body);
+        assert_eq!(reply.body["reports_on_ip"], n + 1);
+    }
+
+    // The ban follows the fingerprint to a fresh address
+    let mut feed = server.feed().await;
+    let mut poster = poster.moved_to("198.51.103.10");
+    poster.start_session().await;
+    let reply = poster.post_listing(&listing_text(13)).await;
+    assert_eq!(reply.status, StatusCode::OK, "post: {}", reply.body);
+    assert!(feed.next_listing(BROADCAST_TIMEOUT).await.is_none());
+}
+
+#[tokio::test]
+#[ignore = "needs redis-server; run with --ignored"]
+async fn test_second_post_is_limited_until_reset() {
+    let server = TestServer::start().await;
+    let mut poster = server.visitor("203.0.113.30");
+    poster.start_session().await;
+
+    let reply = poster.post_listing(&listing_text(20)).await;
+    assert_eq!(reply.status, StatusCode::OK, "post: {}", reply.body);
+
+    let reply = poster.post_listing(&listing_text(21)).await;
+    assert_eq!(reply.status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(reply.body["error"], "rate_limit_exceeded");
+
+    // A clean address waits out the 60 second post window
+    let now = chrono::Utc::now().timestamp() as u64;
+    let retry_after_seconds = reply.body["retry_after_seconds"].as_u64().expect("retry_after_seconds");
+    let retry_after = reply.body["retry_after"].as_u64().expect("retry_after");
+    assert!((55..=60).contains(&retry_after_seconds), "retry_after_seconds: {}", retry_after_seconds);
+    assert!(retry_after.abs_diff(now + retry_after_seconds) <= 2, "retry_after: {}", retry_after);
+}